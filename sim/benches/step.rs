@@ -0,0 +1,31 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use powder_keg_sim::{scenario, ScanOrder, GRID_HEIGHT, GRID_WIDTH};
+use rand::prelude::*;
+
+fn step(c: &mut Criterion) {
+    for (group_name, scan) in [
+        ("step", ScanOrder::Shuffled),
+        ("step-bottom-up", ScanOrder::BottomUp),
+    ] {
+        let mut group = c.benchmark_group(group_name);
+        for scenario in scenario::ALL {
+            let grid = (scenario.build)(GRID_WIDTH, GRID_HEIGHT);
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut tick = 0;
+            group.bench_function(scenario.name, |b| {
+                b.iter_batched_ref(
+                    || grid.clone(),
+                    |grid| {
+                        grid.step_with(scan, tick, &mut rng);
+                        tick += 1;
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, step);
+criterion_main!(benches);
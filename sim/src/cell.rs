@@ -0,0 +1,1636 @@
+#[cfg(feature = "bevy")]
+use bevy_reflect::Reflect;
+use glam::Vec2;
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const DATA_SAND: CellData = CellData {
+    name: "Sand",
+    material: Material::Powder,
+    density: 1600.0,
+    flammable: false,
+    lifespan: None,
+    color: [194, 178, 128],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_STONE: CellData = CellData {
+    name: "Stone",
+    material: Material::Solid,
+    density: 2600.0,
+    flammable: false,
+    lifespan: None,
+    color: [83, 86, 91],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_WOOD: CellData = CellData {
+    name: "Wood",
+    material: Material::Solid,
+    density: 700.0,
+    flammable: true,
+    lifespan: None,
+    color: [164, 116, 73],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_WATER: CellData = CellData {
+    name: "Water",
+    material: Material::Liquid,
+    density: 1000.0,
+    flammable: false,
+    lifespan: None,
+    color: [30, 144, 255],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.55,
+    adhesion: false,
+};
+
+const DATA_OIL: CellData = CellData {
+    name: "Oil",
+    material: Material::Liquid,
+    density: 900.0,
+    flammable: true,
+    lifespan: None,
+    color: [59, 49, 49],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.55,
+    adhesion: false,
+};
+
+const DATA_ACID: CellData = CellData {
+    name: "Acid",
+    material: Material::Acid,
+    density: 1200.0,
+    flammable: false,
+    lifespan: None,
+    color: [176, 191, 26],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_OXYGEN: CellData = CellData {
+    name: "Oxygen",
+    material: Material::Gas,
+    density: 1.43,
+    flammable: true,
+    lifespan: None,
+    color: [187, 198, 213],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_FIRE: CellData = CellData {
+    name: "Fire",
+    material: Material::Fire,
+    density: 0.3,
+    flammable: false,
+    lifespan: Some(Lifespan::new(12, 30).fueled()),
+    color: [226, 88, 34],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// A gust that dies down after a while, blowing nearby flames away from itself and out when they
+/// are small
+const DATA_WIND: CellData = CellData {
+    name: "Wind",
+    material: Material::Wind,
+    density: AIR_DENSITY,
+    flammable: false,
+    lifespan: Some(Lifespan::new(40, 60)),
+    color: [255, 255, 255],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_BEDROCK: CellData = CellData {
+    name: "Bedrock",
+    material: Material::Rigid,
+    density: 3000.0,
+    flammable: false,
+    lifespan: None,
+    color: [52, 50, 58],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_METAL: CellData = CellData {
+    name: "Metal",
+    material: Material::Rigid,
+    density: 7800.0,
+    flammable: false,
+    lifespan: None,
+    color: [150, 156, 166],
+    conductive: true,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Never moves, burns, dissolves or gets displaced, for framing experiments
+const DATA_BARRIER: CellData = CellData {
+    name: "Barrier",
+    material: Material::Rigid,
+    density: 3000.0,
+    flammable: false,
+    lifespan: None,
+    color: [96, 48, 112],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Tints the water it touches with its own color
+const DATA_RED_DYE: CellData = CellData {
+    name: "Red Dye",
+    material: Material::Powder,
+    density: 1500.0,
+    flammable: false,
+    lifespan: None,
+    color: [204, 32, 48],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_YELLOW_DYE: CellData = CellData {
+    name: "Yellow Dye",
+    material: Material::Powder,
+    density: 1500.0,
+    flammable: false,
+    lifespan: None,
+    color: [240, 200, 24],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_BLUE_DYE: CellData = CellData {
+    name: "Blue Dye",
+    material: Material::Powder,
+    density: 1500.0,
+    flammable: false,
+    lifespan: None,
+    color: [40, 64, 208],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Lathers the water it touches into foam while the water is moving
+const DATA_SOAP: CellData = CellData {
+    name: "Soap",
+    material: Material::Powder,
+    density: 1100.0,
+    flammable: false,
+    lifespan: None,
+    color: [232, 214, 228],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// The lightest liquid, floating on all the others until its bubbles pop
+const DATA_FOAM: CellData = CellData {
+    name: "Foam",
+    material: Material::Liquid,
+    density: 100.0,
+    flammable: false,
+    lifespan: Some(Lifespan::new(300, 900)),
+    color: [238, 244, 250],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.55,
+    adhesion: false,
+};
+
+/// A thick, sticky liquid that hardly flows, hangs on walls and holds powders in place
+const DATA_GEL: CellData = CellData {
+    name: "Gel",
+    material: Material::Liquid,
+    density: 1400.0,
+    flammable: false,
+    lifespan: None,
+    color: [96, 200, 120],
+    conductive: false,
+    viscosity: 0.95,
+    spread: 0.55,
+    adhesion: true,
+};
+
+/// A liquid metal, sinking under every other liquid
+const DATA_MERCURY: CellData = CellData {
+    name: "Mercury",
+    material: Material::Liquid,
+    density: 13546.0,
+    flammable: false,
+    lifespan: None,
+    color: [188, 194, 204],
+    conductive: true,
+    viscosity: 0.0,
+    spread: 0.55,
+    adhesion: false,
+};
+
+/// The lightest gas, rising above all the others
+const DATA_HYDROGEN: CellData = CellData {
+    name: "Hydrogen",
+    material: Material::Gas,
+    density: 0.09,
+    flammable: true,
+    lifespan: None,
+    color: [214, 226, 240],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_STEAM: CellData = CellData {
+    name: "Steam",
+    material: Material::Gas,
+    density: 0.6,
+    flammable: false,
+    lifespan: None,
+    color: [226, 228, 232],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Heavier than air, so it pools in hollows and hurts whatever breathes it
+const DATA_TOXIC_GAS: CellData = CellData {
+    name: "Toxic Gas",
+    material: Material::Gas,
+    density: 3.2,
+    flammable: false,
+    lifespan: None,
+    color: [150, 190, 60],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Burns white hot once lit, hot enough to set thermite alight
+const DATA_METAL_POWDER: CellData = CellData {
+    name: "Metal Powder",
+    material: Material::Powder,
+    density: 4000.0,
+    flammable: true,
+    lifespan: None,
+    color: [176, 178, 186],
+    conductive: true,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Only catches from a flame far hotter than fire, then burns into molten metal
+const DATA_THERMITE: CellData = CellData {
+    name: "Thermite",
+    material: Material::Powder,
+    density: 3000.0,
+    flammable: true,
+    lifespan: None,
+    color: [138, 72, 54],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// What metal powder and thermite burn into. It burns through whatever it rests on and sets
+/// its neighbours alight, until it cools into metal.
+const DATA_MOLTEN_METAL: CellData = CellData {
+    name: "Molten Metal",
+    material: Material::Liquid,
+    density: 7000.0,
+    flammable: false,
+    lifespan: Some(Lifespan::new(200, 400)),
+    color: [255, 176, 64],
+    conductive: true,
+    viscosity: 0.5,
+    spread: 0.55,
+    adhesion: false,
+};
+
+/// Pulls the cells that can move towards it, and swallows the ones that reach it
+const DATA_GRAVITY_WELL: CellData = CellData {
+    name: "Gravity Well",
+    material: Material::Rigid,
+    density: 10000.0,
+    flammable: false,
+    lifespan: None,
+    color: [46, 16, 72],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Pushes the cells that can move away from it
+const DATA_REPULSOR: CellData = CellData {
+    name: "Repulsor",
+    material: Material::Rigid,
+    density: 10000.0,
+    flammable: false,
+    lifespan: None,
+    color: [120, 196, 255],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Carries what moves into it out of the other portal of its pair, which shares its tint
+const DATA_PORTAL: CellData = CellData {
+    name: "Portal",
+    material: Material::Rigid,
+    density: 10000.0,
+    flammable: false,
+    lifespan: None,
+    color: [168, 84, 224],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Carries what rests on it one cell to the left each tick
+const DATA_CONVEYOR_LEFT: CellData = CellData {
+    name: "Conveyor Left",
+    material: Material::Rigid,
+    density: 7800.0,
+    flammable: false,
+    lifespan: None,
+    color: [96, 100, 110],
+    conductive: true,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Carries what rests on it one cell to the right each tick
+const DATA_CONVEYOR_RIGHT: CellData = CellData {
+    name: "Conveyor Right",
+    material: Material::Rigid,
+    density: 7800.0,
+    flammable: false,
+    lifespan: None,
+    color: [110, 100, 96],
+    conductive: true,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Warms what is next to it: boils water, melts ice and lights what catches from a hot stove
+const DATA_HEATER: CellData = CellData {
+    name: "Heater",
+    material: Material::Rigid,
+    density: 7800.0,
+    flammable: false,
+    lifespan: None,
+    color: [200, 72, 48],
+    conductive: true,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Chills what is next to it: freezes water, condenses gases and sets molten metal
+const DATA_COOLER: CellData = CellData {
+    name: "Cooler",
+    material: Material::Rigid,
+    density: 7800.0,
+    flammable: false,
+    lifespan: None,
+    color: [64, 140, 210],
+    conductive: true,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Frozen water, which holds its shape until something warms it
+const DATA_ICE: CellData = CellData {
+    name: "Ice",
+    material: Material::Rigid,
+    density: 917.0,
+    flammable: false,
+    lifespan: None,
+    color: [196, 228, 246],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Laid in lines to light something later: it burns along itself at a steady pace, and only
+/// lights what else is next to it where it ends
+const DATA_FUSE: CellData = CellData {
+    name: "Fuse",
+    material: Material::Rigid,
+    density: 900.0,
+    flammable: true,
+    lifespan: None,
+    color: [92, 74, 52],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// The lit part of a fuse, which always burns for the same number of ticks
+const DATA_BURNING_FUSE: CellData = CellData {
+    name: "Burning Fuse",
+    material: Material::Rigid,
+    density: 900.0,
+    flammable: false,
+    lifespan: Some(Lifespan::new(6, 6)),
+    color: [255, 204, 96],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Launches when lit, and bursts into sparks at the top of its climb
+const DATA_FIREWORK: CellData = CellData {
+    name: "Firework",
+    material: Material::Rigid,
+    density: 1000.0,
+    flammable: true,
+    lifespan: None,
+    color: [200, 40, 60],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// A lit firework, climbing until it is blocked or its fuel runs out
+const DATA_ROCKET: CellData = CellData {
+    name: "Rocket",
+    material: Material::Rigid,
+    density: 1000.0,
+    flammable: false,
+    lifespan: Some(Lifespan::new(12, 24)),
+    color: [255, 150, 60],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// A brief flame thrown out by a bursting firework, tinted with the color of the burst
+const DATA_SPARK: CellData = CellData {
+    name: "Spark",
+    material: Material::Fire,
+    density: 0.3,
+    flammable: false,
+    lifespan: Some(Lifespan::new(12, 30).fueled()),
+    color: [255, 230, 160],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Glowing rock falling from the sky, which blasts a crater where it lands
+const DATA_METEOR: CellData = CellData {
+    name: "Meteor",
+    material: Material::Rigid,
+    density: 3000.0,
+    flammable: false,
+    lifespan: None,
+    color: [255, 120, 50],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Annihilates with whatever it touches, leaving nothing of either
+const DATA_ANTIMATTER: CellData = CellData {
+    name: "Antimatter",
+    material: Material::Powder,
+    density: 1500.0,
+    flammable: false,
+    lifespan: None,
+    color: [200, 110, 255],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Grows over damp wood, which it slowly rots into dust
+const DATA_MOLD: CellData = CellData {
+    name: "Mold",
+    material: Material::Rigid,
+    density: 700.0,
+    flammable: true,
+    lifespan: Some(Lifespan::new(400, 800)),
+    color: [110, 140, 70],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// What rotten wood crumbles into
+const DATA_DUST: CellData = CellData {
+    name: "Dust",
+    material: Material::Powder,
+    density: 400.0,
+    flammable: false,
+    lifespan: None,
+    color: [150, 140, 110],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Strands of a rope, which hang together as a soft body
+const DATA_ROPE: CellData = CellData {
+    name: "Rope",
+    material: Material::Rigid,
+    density: 500.0,
+    flammable: true,
+    lifespan: None,
+    color: [170, 130, 80],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Threads of a sheet of cloth, which hang together as a soft body
+const DATA_CLOTH: CellData = CellData {
+    name: "Cloth",
+    material: Material::Rigid,
+    density: 400.0,
+    flammable: true,
+    lifespan: None,
+    color: [190, 70, 80],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// An oily liquid that nothing sets alight, but that detonates when something lands on it hard
+const DATA_NITROGLYCERIN: CellData = CellData {
+    name: "Nitroglycerin",
+    material: Material::Liquid,
+    density: 1600.0,
+    flammable: false,
+    lifespan: None,
+    color: [226, 214, 150],
+    conductive: false,
+    viscosity: 0.3,
+    spread: 0.55,
+    adhesion: false,
+};
+
+/// Blows what is in front of it along the way it faces, and fans flames like wind
+const DATA_FAN: CellData = CellData {
+    name: "Fan",
+    material: Material::Rigid,
+    density: 7800.0,
+    flammable: false,
+    lifespan: None,
+    color: [150, 164, 176],
+    conductive: true,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Wood built into the scenes, held in place so roofs and canopies can overhang until they burn.
+/// It isn't in the palette.
+const DATA_TIMBER: CellData = CellData {
+    name: "Timber",
+    material: Material::Rigid,
+    density: 700.0,
+    flammable: true,
+    lifespan: None,
+    color: [140, 98, 60],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Given off by acid as it dissolves things. It rises, and condenses back into acid on the
+/// ceilings it gathers under.
+const DATA_ACID_GAS: CellData = CellData {
+    name: "Acid Gas",
+    material: Material::Gas,
+    density: 0.9,
+    flammable: false,
+    lifespan: None,
+    color: [206, 222, 120],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// What burning wood smolders into: it glows in place, licking flames and setting its neighbours
+/// alight, until it crumbles to ash
+const DATA_EMBER: CellData = CellData {
+    name: "Ember",
+    material: Material::Rigid,
+    density: 700.0,
+    flammable: false,
+    lifespan: Some(Lifespan::new(150, 300)),
+    color: [255, 110, 30],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+const DATA_ASH: CellData = CellData {
+    name: "Ash",
+    material: Material::Powder,
+    density: 600.0,
+    flammable: false,
+    lifespan: None,
+    color: [150, 146, 140],
+    conductive: false,
+    viscosity: 0.0,
+    spread: 0.1,
+    adhesion: false,
+};
+
+/// Density of the air filling empty positions, which gases lighter than it rise through and
+/// heavier ones sink through
+pub const AIR_DENSITY: f32 = 1.2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+pub enum Material {
+    Powder,
+    /// Falls like a block without spreading out
+    Solid,
+    /// Stays where it was placed, so it can overhang empty space
+    Rigid,
+    Liquid,
+    Acid,
+    Gas,
+    Fire,
+    Wind,
+}
+
+/// How many ticks a cell of a kind that ages lives. Each cell gets its own life within the
+/// range, so cells placed together don't all disappear on the same tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lifespan {
+    pub min: u16,
+    pub max: u16,
+    /// Whether the life starts over every tick there is something next to the cell that catches
+    /// from it, and runs out [`STARVED_BURN`](crate::STARVED_BURN) times as fast while there isn't
+    pub fueled: bool,
+}
+
+impl Lifespan {
+    pub const fn new(min: u16, max: u16) -> Self {
+        Self {
+            min,
+            max,
+            fueled: false,
+        }
+    }
+
+    /// The same lifespan, kept up by fuel
+    pub const fn fueled(self) -> Self {
+        Self {
+            fueled: true,
+            ..self
+        }
+    }
+
+    /// A life for a new cell
+    pub fn roll(&self, rng: &mut impl Rng) -> u16 {
+        rng.gen_range(self.min..=self.max)
+    }
+}
+
+/// Cells a [`ContactRule`] matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Material(Material),
+    Flammable,
+    Nonflammable,
+}
+
+impl Class {
+    pub fn contains(self, cell: Cell) -> bool {
+        match self {
+            Class::Material(material) => cell.material() == material,
+            Class::Flammable => cell.flammable(),
+            Class::Nonflammable => !cell.flammable(),
+        }
+    }
+}
+
+/// How a cell comes into contact with what destroys it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Contact {
+    /// A cell of the class lands on it from above, and takes its place
+    LandedOn(Class),
+    /// It lands on a cell of the class
+    LandsOn(Class),
+    /// It stands in the wind with no more than one cell of its material next to it
+    Blown,
+}
+
+/// A cell destroyed by coming into contact with something
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactRule {
+    pub destroyed: Class,
+    pub contact: Contact,
+    /// Chance of the contact destroying the cell each tick it happens
+    pub chance: f32,
+}
+
+/// Every way cells are destroyed by contact, the first rule that matches applying
+pub const CONTACT_RULES: [ContactRule; 3] = [
+    // What burns is burnt up falling into flames
+    ContactRule {
+        destroyed: Class::Flammable,
+        contact: Contact::LandsOn(Class::Material(Material::Fire)),
+        chance: 1.0,
+    },
+    // Water, or anything else that doesn't burn, puts out the flames it falls on
+    ContactRule {
+        destroyed: Class::Material(Material::Fire),
+        contact: Contact::LandedOn(Class::Nonflammable),
+        chance: 1.0,
+    },
+    // Wind blows out small flames
+    ContactRule {
+        destroyed: Class::Material(Material::Fire),
+        contact: Contact::Blown,
+        chance: 0.3,
+    },
+];
+
+impl ContactRule {
+    /// The rule for a cell landing on another, if any, with whether it destroys the one below
+    pub fn landing(cell: Cell, below: Cell) -> Option<(ContactRule, bool)> {
+        CONTACT_RULES
+            .into_iter()
+            .find_map(|rule| match rule.contact {
+                Contact::LandsOn(class)
+                    if rule.destroyed.contains(cell) && class.contains(below) =>
+                {
+                    Some((rule, false))
+                }
+                Contact::LandedOn(class)
+                    if rule.destroyed.contains(below) && class.contains(cell) =>
+                {
+                    Some((rule, true))
+                }
+                _ => None,
+            })
+    }
+
+    /// The rule for a cell blown by the wind, if any
+    pub fn blown(cell: Cell) -> Option<ContactRule> {
+        CONTACT_RULES
+            .into_iter()
+            .find(|rule| rule.contact == Contact::Blown && rule.destroyed.contains(cell))
+    }
+
+    /// Whether the contact destroys the cell this time
+    pub fn happens(&self, rng: &mut impl Rng) -> bool {
+        self.chance >= 1.0 || rng.gen::<f32>() < self.chance
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CellData {
+    pub name: &'static str,
+    pub material: Material,
+    /// In kilograms per cubic meter. Cells sink under liquids and gases lighter than them.
+    pub density: f32,
+    pub flammable: bool,
+    pub lifespan: Option<Lifespan>,
+    pub color: [u8; 3],
+    /// Whether the cell carries electricity
+    pub conductive: bool,
+    /// Chance from 0 to 1 that the cell holds still instead of sliding or spreading in a tick
+    pub viscosity: f32,
+    /// Chance each tick that a flame next to the cell spreads to it, once it burns hot enough to
+    /// light it
+    pub spread: f32,
+    /// Whether the cell clings to the walls it touches and doesn't slide or spread off the edge
+    /// of what it rests on
+    pub adhesion: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+pub enum CellId {
+    Sand,
+    Stone,
+    Wood,
+    Water,
+    Oil,
+    Acid,
+    Oxygen,
+    Fire,
+    Wind,
+    Bedrock,
+    Metal,
+    Barrier,
+    RedDye,
+    YellowDye,
+    BlueDye,
+    Soap,
+    Foam,
+    Gel,
+    Mercury,
+    Hydrogen,
+    Steam,
+    ToxicGas,
+    Ember,
+    Ash,
+    AcidGas,
+    MetalPowder,
+    Thermite,
+    MoltenMetal,
+    GravityWell,
+    Repulsor,
+    Portal,
+    ConveyorLeft,
+    ConveyorRight,
+    Heater,
+    Cooler,
+    Ice,
+    Fuse,
+    BurningFuse,
+    Firework,
+    Rocket,
+    Spark,
+    Meteor,
+    Antimatter,
+    Mold,
+    Dust,
+    Rope,
+    Cloth,
+    Nitroglycerin,
+    Fan,
+    Timber,
+}
+
+impl CellId {
+    pub const ALL: [CellId; 50] = [
+        CellId::Sand,
+        CellId::Stone,
+        CellId::Wood,
+        CellId::Water,
+        CellId::Oil,
+        CellId::Acid,
+        CellId::Oxygen,
+        CellId::Fire,
+        CellId::Wind,
+        CellId::Bedrock,
+        CellId::Metal,
+        CellId::Barrier,
+        CellId::RedDye,
+        CellId::YellowDye,
+        CellId::BlueDye,
+        CellId::Soap,
+        CellId::Foam,
+        CellId::Gel,
+        CellId::Mercury,
+        CellId::Hydrogen,
+        CellId::Steam,
+        CellId::ToxicGas,
+        CellId::Ember,
+        CellId::Ash,
+        CellId::AcidGas,
+        CellId::MetalPowder,
+        CellId::Thermite,
+        CellId::MoltenMetal,
+        CellId::GravityWell,
+        CellId::Repulsor,
+        CellId::Portal,
+        CellId::ConveyorLeft,
+        CellId::ConveyorRight,
+        CellId::Heater,
+        CellId::Cooler,
+        CellId::Ice,
+        CellId::Fuse,
+        CellId::BurningFuse,
+        CellId::Firework,
+        CellId::Rocket,
+        CellId::Spark,
+        CellId::Meteor,
+        CellId::Antimatter,
+        CellId::Mold,
+        CellId::Dust,
+        CellId::Rope,
+        CellId::Cloth,
+        CellId::Nitroglycerin,
+        CellId::Fan,
+        CellId::Timber,
+    ];
+
+    pub const fn data(&self) -> CellData {
+        match self {
+            CellId::Sand => DATA_SAND,
+            CellId::Stone => DATA_STONE,
+            CellId::Wood => DATA_WOOD,
+            CellId::Water => DATA_WATER,
+            CellId::Oil => DATA_OIL,
+            CellId::Acid => DATA_ACID,
+            CellId::Oxygen => DATA_OXYGEN,
+            CellId::Fire => DATA_FIRE,
+            CellId::Wind => DATA_WIND,
+            CellId::Bedrock => DATA_BEDROCK,
+            CellId::Metal => DATA_METAL,
+            CellId::Barrier => DATA_BARRIER,
+            CellId::RedDye => DATA_RED_DYE,
+            CellId::YellowDye => DATA_YELLOW_DYE,
+            CellId::BlueDye => DATA_BLUE_DYE,
+            CellId::Soap => DATA_SOAP,
+            CellId::Foam => DATA_FOAM,
+            CellId::Gel => DATA_GEL,
+            CellId::Mercury => DATA_MERCURY,
+            CellId::Hydrogen => DATA_HYDROGEN,
+            CellId::Steam => DATA_STEAM,
+            CellId::ToxicGas => DATA_TOXIC_GAS,
+            CellId::Ember => DATA_EMBER,
+            CellId::Ash => DATA_ASH,
+            CellId::AcidGas => DATA_ACID_GAS,
+            CellId::MetalPowder => DATA_METAL_POWDER,
+            CellId::Thermite => DATA_THERMITE,
+            CellId::MoltenMetal => DATA_MOLTEN_METAL,
+            CellId::GravityWell => DATA_GRAVITY_WELL,
+            CellId::Repulsor => DATA_REPULSOR,
+            CellId::Portal => DATA_PORTAL,
+            CellId::ConveyorLeft => DATA_CONVEYOR_LEFT,
+            CellId::ConveyorRight => DATA_CONVEYOR_RIGHT,
+            CellId::Heater => DATA_HEATER,
+            CellId::Cooler => DATA_COOLER,
+            CellId::Ice => DATA_ICE,
+            CellId::Fuse => DATA_FUSE,
+            CellId::BurningFuse => DATA_BURNING_FUSE,
+            CellId::Firework => DATA_FIREWORK,
+            CellId::Rocket => DATA_ROCKET,
+            CellId::Spark => DATA_SPARK,
+            CellId::Meteor => DATA_METEOR,
+            CellId::Antimatter => DATA_ANTIMATTER,
+            CellId::Mold => DATA_MOLD,
+            CellId::Dust => DATA_DUST,
+            CellId::Rope => DATA_ROPE,
+            CellId::Cloth => DATA_CLOTH,
+            CellId::Nitroglycerin => DATA_NITROGLYCERIN,
+            CellId::Fan => DATA_FAN,
+            CellId::Timber => DATA_TIMBER,
+        }
+    }
+
+    /// Whether cells of the kind face a way, kept in their payload, which painting them sets to
+    /// the way the brush is dragged
+    pub const fn oriented(self) -> bool {
+        matches!(self, CellId::Fan)
+    }
+
+    /// Looks up a cell kind by its name, ignoring case
+    pub fn from_name(name: &str) -> Option<CellId> {
+        Self::ALL
+            .into_iter()
+            .find(|id| id.data().name.eq_ignore_ascii_case(name))
+    }
+
+    /// Fingerprint of every kind of cell and its data, which differs between builds whose cells
+    /// behave differently, so a recorded simulation can tell whether it would play out the same
+    pub fn registry_hash() -> u64 {
+        Self::ALL
+            .into_iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, id| {
+                fnv1a(hash, format!("{id:?}{:?}", id.data()).bytes())
+            })
+    }
+}
+
+/// Folds bytes into an FNV-1a hash, which unlike the standard library's hasher is the same on
+/// every build
+pub(crate) fn fnv1a(hash: u64, bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(hash, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+pub struct Cell {
+    pub id: CellId,
+    /// The ticks left to live of a cell whose kind ages, or none when it was just placed and gets
+    /// a life from the [`Lifespan`] of its kind the first tick it is simulated
+    pub life: Option<u16>,
+    /// Color the cell took on from dye, drawn instead of the color of its kind. Portals keep the
+    /// color of their pair here.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub tint: Option<[u8; 3]>,
+    /// A payload whose meaning is up to the kind of the cell, like how far it has burnt, the
+    /// color of a dye, which way it faces or a charge it carries. Fresh cells start at zero, and
+    /// the payload moves with the cell but is lost when it turns into another kind.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_zero"))]
+    pub aux: u16,
+}
+
+#[cfg(feature = "serde")]
+fn is_zero(aux: &u16) -> bool {
+    *aux == 0
+}
+
+/// The way a cell of an [oriented](CellId::oriented) kind faces, in grid coordinates where y
+/// grows downwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+pub enum Facing {
+    #[default]
+    Right,
+    Down,
+    Left,
+    Up,
+}
+
+impl Facing {
+    /// Every way, each a quarter turn clockwise from the one before, in the order they are
+    /// numbered in the payload
+    pub const ALL: [Facing; 4] = [Facing::Right, Facing::Down, Facing::Left, Facing::Up];
+
+    /// The way closest to a direction, favouring the sides on diagonals, or none for no direction
+    pub fn of(direction: Vec2) -> Option<Self> {
+        if direction == Vec2::ZERO {
+            None
+        } else if direction.x.abs() >= direction.y.abs() {
+            Some(if direction.x > 0.0 {
+                Facing::Right
+            } else {
+                Facing::Left
+            })
+        } else if direction.y > 0.0 {
+            Some(Facing::Down)
+        } else {
+            Some(Facing::Up)
+        }
+    }
+
+    /// The step from a cell to the one in front of it
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            Facing::Right => (1, 0),
+            Facing::Down => (0, 1),
+            Facing::Left => (-1, 0),
+            Facing::Up => (0, -1),
+        }
+    }
+
+    /// The way a quarter turn clockwise from this one
+    pub fn clockwise(self) -> Self {
+        Self::ALL[(self as usize + 1) % 4]
+    }
+
+    /// The way seen in a mirror standing upright, which swaps left and right
+    pub fn flip_x(self) -> Self {
+        match self {
+            Facing::Right => Facing::Left,
+            Facing::Left => Facing::Right,
+            facing => facing,
+        }
+    }
+
+    /// The way seen in a mirror lying flat, which swaps up and down
+    pub fn flip_y(self) -> Self {
+        match self {
+            Facing::Down => Facing::Up,
+            Facing::Up => Facing::Down,
+            facing => facing,
+        }
+    }
+}
+
+impl Cell {
+    /// A fresh cell of the kind, with no life, tint or payload yet
+    pub fn new(id: CellId) -> Self {
+        Self {
+            id,
+            life: None,
+            tint: None,
+            aux: 0,
+        }
+    }
+
+    /// The way the cell faces, for kinds that are oriented
+    pub fn facing(&self) -> Option<Facing> {
+        self.id
+            .oriented()
+            .then(|| Facing::ALL[self.aux as usize % Facing::ALL.len()])
+    }
+
+    /// The cell facing another way, unchanged if its kind isn't oriented
+    pub fn facing_to(self, facing: Facing) -> Self {
+        if self.id.oriented() {
+            Cell {
+                aux: facing as u16,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    pub fn material(&self) -> Material {
+        self.id.data().material
+    }
+
+    pub fn flammable(&self) -> bool {
+        self.id.data().flammable
+    }
+
+    pub fn lifespan(&self) -> Option<Lifespan> {
+        self.id.data().lifespan
+    }
+
+    pub fn color(&self) -> [u8; 3] {
+        self.id.data().color
+    }
+
+    pub fn conductive(&self) -> bool {
+        self.id.data().conductive
+    }
+
+    pub fn viscosity(&self) -> f32 {
+        self.id.data().viscosity
+    }
+
+    pub fn adheres(&self) -> bool {
+        self.id.data().adhesion
+    }
+
+    /// The color the cell tints water with, if it is a dye
+    pub fn dye(&self) -> Option<[u8; 3]> {
+        matches!(
+            self.id,
+            CellId::RedDye | CellId::YellowDye | CellId::BlueDye
+        )
+        .then(|| self.color())
+    }
+
+    /// Whether dye can tint the cell
+    pub fn dyeable(&self) -> bool {
+        self.id == CellId::Water
+    }
+
+    /// What the cell turns into when it catches fire. Wood smolders before it burns away.
+    pub fn burns_into(&self) -> CellId {
+        match self.id {
+            CellId::Wood | CellId::Timber => CellId::Ember,
+            CellId::MetalPowder | CellId::Thermite => CellId::MoltenMetal,
+            CellId::Fuse => CellId::BurningFuse,
+            CellId::Firework => CellId::Rocket,
+            _ => CellId::Fire,
+        }
+    }
+
+    /// How hot the cell burns in degrees Celsius, if it sets fire to the cells next to it
+    pub fn flame_temperature(&self) -> Option<u16> {
+        match self.id {
+            CellId::Fire | CellId::Spark => Some(1000),
+            CellId::Ember => Some(800),
+            CellId::MoltenMetal => Some(2500),
+            CellId::Meteor => Some(1200),
+            CellId::Heater => Some(400),
+            CellId::BurningFuse => Some(1000),
+            _ => None,
+        }
+    }
+
+    /// How hot a flame has to be to set the cell alight in degrees Celsius, if it is flammable
+    pub fn ignition_temperature(&self) -> Option<u16> {
+        if !self.flammable() {
+            return None;
+        }
+        match self.id {
+            CellId::MetalPowder => Some(700),
+            CellId::Thermite => Some(1500),
+            _ => Some(300),
+        }
+    }
+
+    /// Whether the flame is hot enough to set the cell alight
+    pub fn catches_from(&self, flame: Cell) -> bool {
+        match (self.ignition_temperature(), flame.flame_temperature()) {
+            (Some(ignition), Some(flame)) => flame >= ignition,
+            _ => false,
+        }
+    }
+
+    /// Whether the cell sets fire to the flammable cells next to it
+    pub fn ignites(&self) -> bool {
+        self.flame_temperature().is_some()
+    }
+
+    /// What the cell leaves behind when its life runs out
+    pub fn remains(&self) -> Option<CellId> {
+        match self.id {
+            CellId::Ember => Some(CellId::Ash),
+            CellId::MoltenMetal => Some(CellId::Metal),
+            CellId::BurningFuse => Some(CellId::Ash),
+            CellId::Mold => Some(CellId::Dust),
+            _ => None,
+        }
+    }
+
+    /// What the cell turns into when it condenses under a ceiling, if it is a gas that does
+    pub fn condenses_into(&self) -> Option<CellId> {
+        match self.id {
+            CellId::AcidGas => Some(CellId::Acid),
+            _ => None,
+        }
+    }
+
+    /// Which way the cell moves the cells around it, one step at a time: towards it when
+    /// positive, away from it when negative
+    pub fn pull(&self) -> Option<isize> {
+        match self.id {
+            CellId::GravityWell => Some(1),
+            CellId::Repulsor => Some(-1),
+            _ => None,
+        }
+    }
+
+    /// What the cell turns into when a heater warms it
+    pub fn heats_into(&self) -> Option<CellId> {
+        match self.id {
+            CellId::Ice => Some(CellId::Water),
+            CellId::Water => Some(CellId::Steam),
+            CellId::Acid => Some(CellId::AcidGas),
+            _ => None,
+        }
+    }
+
+    /// What the cell turns into when a cooler chills it
+    pub fn cools_into(&self) -> Option<CellId> {
+        match self.id {
+            CellId::Water => Some(CellId::Ice),
+            CellId::Steam => Some(CellId::Water),
+            CellId::MoltenMetal => Some(CellId::Metal),
+            _ => self.condenses_into(),
+        }
+    }
+
+    /// Which way the cell carries what rests on it, if it is a conveyor: left when negative
+    pub fn conveys(&self) -> Option<isize> {
+        match self.id {
+            CellId::ConveyorLeft => Some(-1),
+            CellId::ConveyorRight => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Whether the cell moves into a portal it is next to in the direction, carried on by how it
+    /// moves: falling cells go down, rising gases and fire up, liquids and gases sideways
+    pub fn enters_portal(&self, (dx, dy): (isize, isize)) -> bool {
+        match (dx, dy) {
+            (0, 1) => self.falls(),
+            (0, -1) => {
+                self.material() == Material::Fire
+                    || self.material() == Material::Gas && self.density() < AIR_DENSITY
+            }
+            _ => self.is_fluid() || self.material() == Material::Acid,
+        }
+    }
+
+    /// Whether nothing in the simulation can destroy or displace this cell
+    pub fn indestructible(&self) -> bool {
+        self.id == CellId::Barrier
+    }
+
+    pub fn falls(&self) -> bool {
+        match self.material() {
+            Material::Powder | Material::Solid | Material::Liquid | Material::Acid => true,
+            Material::Rigid | Material::Gas | Material::Fire | Material::Wind => false,
+        }
+    }
+
+    /// How far the cell throws the liquid it falls into. Until cells keep a velocity, this only
+    /// depends on what falls: solids hit harder than powder.
+    pub fn impact(&self) -> usize {
+        match self.material() {
+            Material::Solid => 2,
+            Material::Powder => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn slides(&self) -> bool {
+        match self.material() {
+            Material::Powder | Material::Liquid | Material::Acid => true,
+            Material::Solid | Material::Rigid | Material::Gas | Material::Fire | Material::Wind => {
+                false
+            }
+        }
+    }
+
+    pub fn density(&self) -> f32 {
+        self.id.data().density
+    }
+
+    /// Whether the cell moves down into the position, displacing what is there
+    pub fn sinks_under(&self, other: Option<Cell>) -> bool {
+        let Some(other) = other else {
+            return true;
+        };
+        let moves = self.falls() || self.material() == Material::Gas;
+        // Sticky liquids hold powders suspended
+        let held = self.material() == Material::Powder && other.adheres();
+        moves
+            && other.is_fluid()
+            && !other.indestructible()
+            && !held
+            && self.density() > other.density()
+    }
+
+    /// Whether bodies living on the grid collide with this cell
+    pub fn is_solid(&self) -> bool {
+        matches!(
+            self.material(),
+            Material::Powder | Material::Solid | Material::Rigid
+        )
+    }
+
+    /// Whether the cell keeps its shape, so sticky cells can cling to it
+    pub fn is_wall(&self) -> bool {
+        matches!(self.material(), Material::Solid | Material::Rigid)
+    }
+
+    /// Whether the cell, flowing over the other, can pick it up and carry it along
+    pub fn carries(&self, other: Cell) -> bool {
+        self.material() == Material::Liquid
+            && self.viscosity() == 0.0
+            && other.material() == Material::Powder
+            && other.density() < self.density() * 2.0
+    }
+
+    /// Whether the cell works its way down into the powder it rests on, being heavier than it
+    pub fn settles_into(&self, other: Cell) -> bool {
+        matches!(self.material(), Material::Solid | Material::Powder)
+            && other.material() == Material::Powder
+            && self.density() > other.density()
+    }
+
+    /// Whether the cell rises through liquids heavier than it, though it keeps its shape
+    pub fn floats(&self) -> bool {
+        matches!(self.id, CellId::Wood | CellId::Ice)
+    }
+
+    /// Whether gas packed into a sealed pocket bursts through the cell walling it in
+    pub fn gives_way(&self) -> bool {
+        self.id == CellId::Wood
+    }
+
+    /// Whether other cells can sink into this one, given they are denser
+    pub fn is_fluid(&self) -> bool {
+        matches!(self.material(), Material::Liquid | Material::Gas)
+    }
+
+    pub fn is_liquid(&self) -> bool {
+        matches!(self.material(), Material::Liquid | Material::Acid)
+    }
+
+    /// Whether bodies living on the grid are hurt by touching this cell
+    pub fn harms(&self) -> bool {
+        self.ignites()
+            || self.material() == Material::Acid
+            || matches!(self.id, CellId::ToxicGas | CellId::AcidGas)
+    }
+
+    pub fn dissolves(&self, other: Option<Cell>) -> bool {
+        if other.is_some_and(|c| c.indestructible()) {
+            return false;
+        }
+        match (self.material(), other.map(|c| c.material())) {
+            (Material::Acid, None) => false,
+            (Material::Acid, Some(Material::Acid)) => false,
+            (Material::Acid, Some(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The color halfway between two colors
+pub fn blend(a: [u8; 3], b: [u8; 3]) -> [u8; 3] {
+    std::array::from_fn(|i| ((a[i] as u16 + b[i] as u16) / 2) as u8)
+}
+
+/// Ticks a cell has to keep still before it falls asleep, see [`crate::Grid::is_asleep`]
+pub const SLEEP_TICKS: u16 = 8;
+
+/// Indexed by packed kind, whether the second byte of a tile holds a life, and whether the cell
+/// can fall asleep. Kinds that age change every tick, and gas moves at random, so it may keep
+/// still for a while without being settled. Heaters and coolers never settle either, since they
+/// keep working on their neighbours.
+const AGES: [bool; CellId::ALL.len() + 1] = kind_table(false);
+const SLEEPS: [bool; CellId::ALL.len() + 1] = kind_table(true);
+
+const fn kind_table(sleeps: bool) -> [bool; CellId::ALL.len() + 1] {
+    let mut table = [false; CellId::ALL.len() + 1];
+    let mut i = 0;
+    while i < CellId::ALL.len() {
+        let data = CellId::ALL[i].data();
+        table[i + 1] = if sleeps {
+            data.lifespan.is_none()
+                && !matches!(data.material, Material::Gas)
+                && !matches!(CellId::ALL[i], CellId::Heater | CellId::Cooler)
+        } else {
+            data.lifespan.is_some()
+        };
+        i += 1;
+    }
+    table
+}
+
+/// A tile of the grid packed into four bytes. One holds the kind, with [`PackedCell::EMPTY`] for
+/// no cell, in its low bits and whether the cell has a tint or a payload in its high bits. Tints
+/// and payloads are rare, so the grid keeps them apart from the tiles. Another holds the tile's
+/// flags and under them how many ticks in a row the cell has fallen straight down, up to
+/// [`PackedCell::MAX_FALL`]. The last two hold the remaining life of a cell whose kind ages, zero
+/// until it gets one, and for the others how many ticks in a row it has kept still, up to
+/// [`SLEEP_TICKS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "bevy", derive(Reflect))]
+pub(crate) struct PackedCell {
+    kind: u8,
+    flags: u8,
+    life: u16,
+}
+
+const _: () = assert!(std::mem::size_of::<PackedCell>() == 4);
+const _: () = assert!(CellId::ALL.len() < PackedCell::KIND as usize);
+
+impl PackedCell {
+    pub const EMPTY: u8 = 0;
+    /// Bits of the kind byte that hold the kind
+    const KIND: u8 = 0b0011_1111;
+    /// Set in the kind byte when the cell has a tint
+    const TINT: u8 = 0b0100_0000;
+    /// Set in the kind byte when the cell has a payload
+    const PAYLOAD: u8 = 0b1000_0000;
+    /// Arrived at the tile during the last tick, and hasn't kept still since
+    pub const MOVING: u8 = 0b0000_0001;
+    /// Left out of the simulation, see [`crate::Grid::set_frozen`]
+    pub const FROZEN: u8 = 0b0000_0010;
+    /// Written with a different cell since the changes were last taken
+    pub const CHANGED: u8 = 0b0000_0100;
+    /// Bits of the flags byte left for the fall counter
+    const FALL_SHIFT: u8 = 3;
+    pub const MAX_FALL: u8 = u8::MAX >> Self::FALL_SHIFT;
+
+    /// Packs an awake cell with no flags set. Only kinds with a lifespan keep a life.
+    pub fn pack(cell: Option<Cell>) -> Self {
+        match cell {
+            None => Self::default(),
+            Some(cell) => Self {
+                kind: (cell.id as u8 + 1)
+                    | if cell.tint.is_some() { Self::TINT } else { 0 }
+                    | if cell.aux != 0 { Self::PAYLOAD } else { 0 },
+                flags: 0,
+                life: cell.lifespan().map_or(0, |_| cell.life.unwrap_or(0)),
+            },
+        }
+    }
+
+    /// The cell in the tile, with no tint or payload. The grid puts back those it keeps.
+    pub fn unpack(self) -> Option<Cell> {
+        match self.kind() {
+            Self::EMPTY => None,
+            kind => {
+                let id = CellId::ALL[kind as usize - 1];
+                Some(Cell {
+                    life: id.data().lifespan.and((self.life > 0).then_some(self.life)),
+                    ..Cell::new(id)
+                })
+            }
+        }
+    }
+
+    fn kind(self) -> u8 {
+        self.kind & Self::KIND
+    }
+
+    pub fn has_tint(self) -> bool {
+        self.kind & Self::TINT != 0
+    }
+
+    pub fn has_payload(self) -> bool {
+        self.kind & Self::PAYLOAD != 0
+    }
+
+    /// Whether both tiles hold the same cell, whatever their flags and stillness. Their tints and
+    /// payloads are up to the grid to compare.
+    pub fn same_cell(self, other: PackedCell) -> bool {
+        self.kind == other.kind && (!AGES[self.kind() as usize] || self.life == other.life)
+    }
+
+    pub fn is_powder(self) -> bool {
+        self.unpack()
+            .is_some_and(|cell| cell.material() == Material::Powder)
+    }
+
+    pub fn is_asleep(self) -> bool {
+        SLEEPS[self.kind() as usize] && self.life >= SLEEP_TICKS
+    }
+
+    /// Counts one more tick the cell kept still
+    pub fn rest(&mut self) {
+        if SLEEPS[self.kind() as usize] && self.life < SLEEP_TICKS {
+            self.life += 1;
+        }
+    }
+
+    pub fn wake(&mut self) {
+        if SLEEPS[self.kind() as usize] {
+            self.life = 0;
+        }
+    }
+
+    /// This tile's flags with another cell
+    pub fn with_cell(self, cell: PackedCell) -> Self {
+        Self {
+            kind: cell.kind,
+            flags: self.flags,
+            life: cell.life,
+        }
+    }
+
+    /// How many ticks in a row the cell has fallen straight down
+    pub fn fall(self) -> u8 {
+        self.flags >> Self::FALL_SHIFT
+    }
+
+    pub fn set_fall(&mut self, fall: u8) {
+        let flags = self.flags & !(Self::MAX_FALL << Self::FALL_SHIFT);
+        self.flags = flags | fall.min(Self::MAX_FALL) << Self::FALL_SHIFT;
+    }
+
+    pub fn has(self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    pub fn set_flag(&mut self, flag: u8, on: bool) {
+        if on {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! The weather of the whole world, acting on the cells open to the air rather than through the
+//! reactions between neighbours
+//!
+//! A [`Climate`] is applied after each tick like the text rules are. Below freezing, the surface
+//! of shallow water freezes over; above it, ice open to the air melts. Above the dew point, water
+//! open to the air evaporates, and below it steam condenses back into water wherever it is. The
+//! further the air is from those temperatures, the sooner. Whatever the temperature, steam that
+//! gathers thickly enough under the top of the world rains back down, closing the water cycle.
+
+use crate::cell::{Cell, CellId};
+use crate::Grid;
+use rand::prelude::*;
+
+/// Temperature water freezes and ice melts at, in degrees Celsius
+pub const FREEZING: f32 = 0.0;
+
+/// Temperature above which water open to the air evaporates and below which steam condenses, in
+/// degrees Celsius. Air this warm leaves water and steam as they are.
+pub const DEW_POINT: f32 = 20.0;
+
+/// Chance per tick of a water surface freezing, for each degree below freezing
+const FREEZE_RATE: f32 = 0.0004;
+
+/// Chance per tick of ice open to the air melting, for each degree above freezing
+const MELT_RATE: f32 = 0.0002;
+
+/// Chance per tick of water open to the air evaporating, for each degree above the dew point
+const EVAPORATION_RATE: f32 = 0.0001;
+
+/// Chance per tick of a cell of steam condensing, for each degree below the dew point
+const CONDENSATION_RATE: f32 = 0.00005;
+
+/// Rows under the top of the world that clouds gather in
+const CLOUD_DEPTH: usize = 8;
+
+/// Columns of the cloud layer whose steam is counted together
+const CLOUD_WIDTH: usize = 16;
+
+/// Share of the cloud layer over some columns that has to be steam for it to rain there
+const RAIN_DENSITY: f32 = 0.4;
+
+/// Chance per tick of a cell of steam in a raining cloud turning into a drop of water
+const RAIN_CHANCE: f32 = 0.01;
+
+/// Deepest water that freezes over, in cells
+const SHALLOW_DEPTH: usize = 4;
+
+/// The air the world is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    /// Temperature of the air, in degrees Celsius
+    pub temperature: f32,
+}
+
+impl Climate {
+    /// Freezes, melts, evaporates or condenses the cells as the temperature says. Returns how
+    /// many cells changed.
+    pub fn apply(&self, grid: &mut Grid, rng: &mut impl Rng) -> usize {
+        let mut changed = 0;
+        let mut turn = |grid: &mut Grid, x: usize, y: usize, id: CellId| {
+            grid.set(x, y, Some(Cell::new(id)));
+            changed += 1;
+        };
+
+        for x in 0..grid.width() {
+            // Only the top of each column meets the air
+            let Some(y) = (0..grid.height()).find(|&y| grid.get(x, y).is_some()) else {
+                continue;
+            };
+            if grid.is_frozen(x, y) {
+                continue;
+            }
+            if let Some((id, chance)) = self.weathering(grid, x, y) {
+                if rng.gen::<f32>() < chance {
+                    turn(grid, x, y, id);
+                }
+            }
+        }
+
+        // Clouds thick enough rain, a few drops at a time
+        let cloud_depth = CLOUD_DEPTH.min(grid.height());
+        for left in (0..grid.width()).step_by(CLOUD_WIDTH) {
+            let columns = left..(left + CLOUD_WIDTH).min(grid.width());
+            let cloud: Vec<(usize, usize)> = columns
+                .flat_map(|x| (0..cloud_depth).map(move |y| (x, y)))
+                .filter(|&(x, y)| grid.get(x, y).is_some_and(|cell| cell.id == CellId::Steam))
+                .collect();
+            let area = (CLOUD_WIDTH.min(grid.width() - left) * cloud_depth) as f32;
+            if (cloud.len() as f32) < area * RAIN_DENSITY {
+                continue;
+            }
+            for (x, y) in cloud {
+                if !grid.is_frozen(x, y) && rng.gen::<f32>() < RAIN_CHANCE {
+                    turn(grid, x, y, CellId::Water);
+                }
+            }
+        }
+
+        let condensation = (DEW_POINT - self.temperature) * CONDENSATION_RATE;
+        if condensation > 0.0 {
+            for (x, y, cell) in grid.clone().iter() {
+                let steam = cell.is_some_and(|cell| cell.id == CellId::Steam);
+                if steam && !grid.is_frozen(x, y) && rng.gen::<f32>() < condensation {
+                    turn(grid, x, y, CellId::Water);
+                }
+            }
+        }
+        changed
+    }
+
+    /// What the cell at the top of a column turns into in this air, with the chance per tick
+    fn weathering(&self, grid: &Grid, x: usize, y: usize) -> Option<(CellId, f32)> {
+        let temperature = self.temperature;
+        match grid.get(x, y)?.id {
+            CellId::Water if temperature < FREEZING => (depth(grid, x, y) <= SHALLOW_DEPTH)
+                .then_some((CellId::Ice, (FREEZING - temperature) * FREEZE_RATE)),
+            CellId::Water if temperature > DEW_POINT => {
+                Some((CellId::Steam, (temperature - DEW_POINT) * EVAPORATION_RATE))
+            }
+            CellId::Ice if temperature > FREEZING => {
+                Some((CellId::Water, (temperature - FREEZING) * MELT_RATE))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How many cells of water are stacked from the position down
+fn depth(grid: &Grid, x: usize, y: usize) -> usize {
+    (y..grid.height())
+        .take_while(|&y| grid.get(x, y).is_some_and(|cell| cell.id == CellId::Water))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pond two cells deep next to one ten cells deep, with a block of ice on the bank
+    fn ponds() -> Grid {
+        let mut grid = Grid::new(6, 12);
+        for x in 0..6 {
+            grid.spawn(x, 11, CellId::Stone);
+        }
+        for y in 9..11 {
+            grid.spawn(0, y, CellId::Water);
+        }
+        for y in 1..11 {
+            grid.spawn(1, y, CellId::Water);
+        }
+        grid.spawn(4, 10, CellId::Ice);
+        grid
+    }
+
+    fn cold_ticks(temperature: f32, ticks: usize) -> Grid {
+        let mut grid = ponds();
+        let mut rng = StdRng::seed_from_u64(4);
+        let climate = Climate { temperature };
+        for _ in 0..ticks {
+            climate.apply(&mut grid, &mut rng);
+        }
+        grid
+    }
+
+    #[test]
+    fn cold_freezes_the_surface_of_shallow_water() {
+        let grid = cold_ticks(-20.0, 2000);
+        assert_eq!(grid.get(0, 9).unwrap().id, CellId::Ice);
+        assert_eq!(grid.get(0, 10).unwrap().id, CellId::Water);
+        // Deep water keeps moving under the cold
+        assert_eq!(grid.get(1, 1).unwrap().id, CellId::Water);
+        assert_eq!(grid.get(4, 10).unwrap().id, CellId::Ice);
+    }
+
+    #[test]
+    fn warmth_melts_ice_open_to_the_air() {
+        let grid = cold_ticks(15.0, 2000);
+        assert_eq!(grid.get(4, 10).unwrap().id, CellId::Water);
+        // Exactly freezing leaves everything as it is
+        let grid = cold_ticks(FREEZING, 2000);
+        assert!(grid.iter().eq(ponds().iter()));
+    }
+
+    #[test]
+    fn heat_evaporates_water_and_cold_condenses_steam() {
+        let grid = cold_ticks(50.0, 2000);
+        assert_eq!(grid.get(1, 1).unwrap().id, CellId::Steam);
+        // Only the surface meets the air
+        assert_eq!(grid.get(1, 2).unwrap().id, CellId::Water);
+
+        let mut grid = Grid::new(4, 4);
+        grid.spawn(2, 0, CellId::Steam);
+        grid.spawn(2, 3, CellId::Steam);
+        let mut rng = StdRng::seed_from_u64(4);
+        let climate = Climate { temperature: 0.0 };
+        for _ in 0..2000 {
+            climate.apply(&mut grid, &mut rng);
+        }
+        assert_eq!(grid.census().count(CellId::Water), 2);
+    }
+
+    #[test]
+    fn thick_clouds_rain() {
+        let mut grid = Grid::new(32, 40);
+        // A thick cloud over the left half and a few wisps over the right
+        for x in 0..16 {
+            for y in 0..6 {
+                grid.spawn(x, y, CellId::Steam);
+            }
+        }
+        for x in (16..32).step_by(4) {
+            grid.spawn(x, 0, CellId::Steam);
+        }
+        let mut rng = StdRng::seed_from_u64(4);
+        // Air at the dew point, so only rain turns steam into water
+        let climate = Climate {
+            temperature: DEW_POINT,
+        };
+        let changed: usize = (0..10).map(|_| climate.apply(&mut grid, &mut rng)).sum();
+        assert!(changed > 0);
+        assert_eq!(grid.census().count(CellId::Water), changed);
+        assert!(grid
+            .iter()
+            .filter(|&(_, _, cell)| cell.is_some_and(|cell| cell.id == CellId::Water))
+            .all(|(x, _, _)| x < 16));
+    }
+}
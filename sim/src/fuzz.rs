@@ -0,0 +1,89 @@
+//! An entry point for fuzzers: any bytes make a world, which is stepped for a few ticks. A
+//! `cargo fuzz` target only has to call [`fuzz_step`] with the data it is given.
+//!
+//! The first bytes give the size of the grid, how many ticks to step it, the scan order, a region
+//! kept frozen and the seed. Every position then takes two bytes: the kind of cell, if any, and
+//! its life, with a tint for the odd ones. Bytes running out leave the rest of the grid empty.
+
+use crate::cell::{Cell, CellId};
+use crate::{Grid, ScanOrder};
+use rand::prelude::*;
+
+/// Widest and tallest grid the bytes can ask for, small enough to step fast and large enough for
+/// every rule to have room
+const MAX_SIZE: usize = 48;
+
+/// Most ticks the bytes can ask for
+const MAX_TICKS: u64 = 8;
+
+/// Reads bytes one at a time, zeros once they run out
+struct Bytes<'a>(std::slice::Iter<'a, u8>);
+
+impl Bytes<'_> {
+    fn next(&mut self) -> u8 {
+        self.0.next().copied().unwrap_or(0)
+    }
+
+    fn below(&mut self, max: usize) -> usize {
+        self.next() as usize % max
+    }
+}
+
+/// Steps the world the bytes describe for the ticks they say, and panics if the grid comes out
+/// of it another size or with positions it can't read
+pub fn fuzz_step(data: &[u8]) {
+    let mut bytes = Bytes(data.iter());
+    let width = 1 + bytes.below(MAX_SIZE);
+    let height = 1 + bytes.below(MAX_SIZE);
+    let ticks = 1 + bytes.next() as u64 % MAX_TICKS;
+    let scan = if bytes.next() % 2 == 0 {
+        ScanOrder::Shuffled
+    } else {
+        ScanOrder::BottomUp
+    };
+    let frozen = (bytes.below(width + 1), bytes.below(height + 1));
+    let seed = u64::from_le_bytes(std::array::from_fn(|_| bytes.next()));
+
+    let mut grid = Grid::new(width, height);
+    grid.set_frozen(0..frozen.0, 0..frozen.1, true);
+    for y in 0..height {
+        for x in 0..width {
+            let (kind, life) = (bytes.next(), bytes.next());
+            // Half of the values leave the position empty
+            let Some(&id) = CellId::ALL.get(kind as usize % (CellId::ALL.len() * 2)) else {
+                continue;
+            };
+            grid.set(
+                x,
+                y,
+                Some(Cell {
+                    life: (life > 0).then(|| life as u16 - 1),
+                    tint: (life % 2 == 1).then_some([kind, life, kind ^ life]),
+                    ..Cell::new(id)
+                }),
+            );
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for tick in 0..ticks {
+        grid.step_with(scan, tick, &mut rng);
+        assert_eq!((grid.width(), grid.height()), (width, height));
+        assert_eq!(grid.iter().count(), width * height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_with_no_neighbours_step() {
+        // A grid of a single position, for each kind of cell in it
+        for kind in 0..CellId::ALL.len() as u8 {
+            let mut bytes = vec![0; 14];
+            bytes.extend([kind, 0]);
+            fuzz_step(&bytes);
+        }
+    }
+}
@@ -0,0 +1,3672 @@
+//! The falling-sand simulation: cell kinds, the grid they live on and the rules stepping it, with
+//! no dependency on a renderer or game engine. The `bevy` feature adds the derives the game needs
+//! to keep a [`Grid`] as a reflected resource, and the `serde` feature implements
+//! `Serialize`/`Deserialize` for the grid and cell types. The `scripting` feature adds element
+//! behaviours written in Rhai, see the `script` module, and the `mods` feature runs mods compiled
+//! to WebAssembly, see the `wasm` module. Elements can also be prototyped as text rules, see the
+//! `rules` module, and the weather of the whole world is in the `climate` module. Fuzzers step
+//! worlds made of arbitrary bytes through the `fuzz` module.
+
+pub mod cell;
+pub mod climate;
+pub mod fuzz;
+pub mod life;
+pub mod rle;
+pub mod rules;
+pub mod scenario;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "mods")]
+pub mod wasm;
+
+use crate::cell::{blend, Cell, CellId, ContactRule, Facing, Material, PackedCell, AIR_DENSITY};
+#[cfg(feature = "bevy")]
+use bevy_ecs::{reflect::ReflectResource, resource::Resource};
+#[cfg(feature = "bevy")]
+use bevy_reflect::Reflect;
+use glam::{IVec2, Vec2};
+use rand::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+pub const GRID_WIDTH: usize = 320;
+
+pub const GRID_HEIGHT: usize = 180;
+
+/// Most tiles a grid read from outside the game may have, a 4096x4096 world, so a few bytes
+/// claiming a huge size can't make it allocate gigabytes
+pub const MAX_TILES: usize = 4096 * 4096;
+
+/// Side of the square pieces an unbounded world is stored in, which tile the default grid
+pub const CHUNK_SIZE: usize = 20;
+
+/// Ticks of life a fire with no fuel next to it loses each tick
+pub const STARVED_BURN: u16 = 4;
+/// Chance each tick that an ember sets fire to one of its neighbours, or licks a flame into an
+/// empty one
+const EMBER_FLARE: f32 = 0.05;
+/// Chance each tick that a heater or cooler changes one of its neighbours
+const TEMPER_CHANCE: f32 = 0.1;
+/// Chance that acid gives off a puff of acid gas when it dissolves something
+const ACID_FUMES: f32 = 0.5;
+/// Chance each tick that molten metal burns through the cell it rests on
+const MELT_CHANCE: f32 = 0.1;
+/// Furthest a gravity well or repulsor reaches, in cells
+const WELL_RADIUS: isize = 8;
+/// Chance each tick that mold tries to spread to a random neighbour
+const MOLD_GROWTH: f32 = 0.05;
+/// Cells a meteor falls each tick
+const METEOR_SPEED: usize = 3;
+/// Radius of the clump of meteor dropped at once, in cells
+const METEOR_SIZE: isize = 2;
+/// How far a landing meteor's blast reaches, in cells
+const METEOR_BLAST: isize = 6;
+/// Chance that a blasted position is left burning
+const BLAST_FLAMES: f32 = 0.3;
+/// Cells a lit firework climbs each tick
+const ROCKET_SPEED: usize = 2;
+/// How far from a bursting firework its sparks reach, in cells
+const BURST_RADIUS: isize = 4;
+/// The colors fireworks burst in
+const FIREWORK_COLORS: [[u8; 3]; 6] = [
+    [255, 70, 70],
+    [255, 200, 60],
+    [90, 230, 110],
+    [80, 170, 255],
+    [210, 110, 255],
+    [255, 255, 255],
+];
+/// The colors telling pairs of portals apart, and so how many pairs there can be
+pub const PORTAL_COLORS: [[u8; 3]; 8] = [
+    [168, 84, 224],
+    [255, 140, 40],
+    [60, 200, 230],
+    [250, 90, 160],
+    [120, 220, 90],
+    [250, 220, 70],
+    [90, 110, 250],
+    [240, 240, 240],
+];
+/// Chance each tick that a gas which condenses does so under a ceiling
+const CONDENSE_CHANCE: f32 = 0.02;
+/// Furthest a cell of wind blows on flames, in cells
+const WIND_REACH: usize = 3;
+/// Furthest in front of a fan it blows cells, in cells
+const FAN_REACH: isize = 6;
+/// How much likelier fuel downwind of a flame is to catch, and how much less likely fuel upwind is
+const DOWNWIND_SPREAD: f32 = 2.0;
+const UPWIND_SPREAD: f32 = 0.25;
+/// Furthest along the surface of a slick that a flame on it reaches, in cells
+const SLICK_REACH: usize = 3;
+/// Chance each tick of a flame lighting each cell of slick within reach
+const SLICK_SPREAD: f32 = 0.3;
+/// Ticks in a row a cell has to fall for landing on nitroglycerin to set it off
+const DETONATION_FALL: u8 = 6;
+/// Radius of the blast of a cell of nitroglycerin going off
+const NITRO_BLAST: isize = 5;
+/// Most positions a sealed pocket of gas spans. Bigger ones count as open air.
+const MAX_POCKET: usize = 2048;
+/// Share of a sealed pocket that has to be gas for it to burst its weak walls
+const BURST_PRESSURE: f32 = 0.7;
+/// Chance each tick of each weak wall of a pocket over pressure giving way
+const BURST_CHANCE: f32 = 0.2;
+/// Chance each tick of a cell that floats rising through the heavier liquid above it
+const BOB_CHANCE: f32 = 0.3;
+/// Most cells of a piece of floating cells that can float. Bigger pieces stay put.
+const RAFT_SIZE: usize = 64;
+/// Chance each tick of a cell working its way down into the lighter powder it rests on
+const SETTLE_CHANCE: f32 = 0.05;
+/// Chance of a liquid flowing aside over powder it carries picking a cell of it up
+const EROSION_CHANCE: f32 = 0.05;
+
+/// How a tick visits the positions of the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScanOrder {
+    /// Every position once, in an order shuffled anew each tick
+    #[default]
+    Shuffled,
+    /// Row by row from the bottom up, sweeping the rows left to right on even ticks and right to
+    /// left on odd ones. Needs no allocation and walks memory in order, but lets the direction
+    /// show in how piles spread.
+    BottomUp,
+}
+
+/// A cell found by a collision query against the grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub x: usize,
+    pub y: usize,
+    pub cell: Cell,
+    /// Where the query touched the cell, in cell coordinates
+    pub point: Vec2,
+    /// Outward normal of the cell face a ray entered through, zero for overlaps and rays starting
+    /// inside the cell
+    pub normal: Vec2,
+}
+
+/// An axis-aligned box in cell coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+}
+
+#[cfg(feature = "bevy")]
+impl From<bevy_math::Rect> for Aabb {
+    fn from(rect: bevy_math::Rect) -> Self {
+        Self::new(rect.min, rect.max)
+    }
+}
+
+/// The cell data of the simulation, independent of any rendering or input handling
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "bevy", derive(Resource, Reflect), reflect(Resource))]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    /// Each cell with whether it changed since the last [`Grid::take_changes`], whether it is
+    /// left out of the simulation, so nothing there updates and nothing moves in or out, and how
+    /// long it has kept still
+    tiles: Vec<PackedCell>,
+    /// The tints of the cells that have one, by tile. Few cells do, so the tiles only mark them.
+    tints: HashMap<usize, [u8; 3]>,
+    /// The payloads of the cells that have one, by tile, kept apart like the tints
+    payloads: HashMap<usize, u16>,
+    /// Chance of flames spreading to each kind of cell, by kind
+    spread: [f32; CellId::ALL.len()],
+}
+
+impl PartialEq for Grid {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && (0..self.tiles.len()).all(|index| self.same_tile(other, index))
+    }
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self::new(GRID_WIDTH, GRID_HEIGHT)
+    }
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            tiles: vec![PackedCell::default(); width * height],
+            tints: HashMap::new(),
+            payloads: HashMap::new(),
+            spread: CellId::ALL.map(|id| id.data().spread),
+        }
+    }
+
+    /// Chance each tick that a flame spreads to a cell of the kind next to it, the one in its
+    /// [`CellData`](cell::CellData) unless tuned
+    pub fn spread_chance(&self, id: CellId) -> f32 {
+        self.spread[id as usize]
+    }
+
+    /// Tunes the chance of flames spreading to a kind of cell, for this grid only
+    pub fn set_spread_chance(&mut self, id: CellId, chance: f32) {
+        self.spread[id as usize] = chance;
+    }
+
+    /// [`CellId::registry_hash`] folded together with the spread chances of this grid, which
+    /// change how it plays out just like the data of its cells
+    pub fn registry_hash(&self) -> u64 {
+        self.spread
+            .iter()
+            .fold(CellId::registry_hash(), |hash, chance| {
+                cell::fnv1a(hash, chance.to_bits().to_le_bytes())
+            })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn in_bounds(&self, x: isize, y: isize) -> bool {
+        x >= 0 && (x as usize) < self.width && y >= 0 && (y as usize) < self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Cell> {
+        self.cell(self.index(x, y))
+    }
+
+    fn cell(&self, index: usize) -> Option<Cell> {
+        let tile = self.tiles[index];
+        let cell = tile.unpack()?;
+        Some(Cell {
+            tint: tile.has_tint().then(|| self.tints[&index]),
+            aux: if tile.has_payload() {
+                self.payloads[&index]
+            } else {
+                0
+            },
+            ..cell
+        })
+    }
+
+    /// Whether the tile holds the same cell in both grids, whatever its flags and stillness
+    fn same_tile(&self, other: &Grid, index: usize) -> bool {
+        let tile = self.tiles[index];
+        tile.same_cell(other.tiles[index])
+            && (!tile.has_tint() || self.tints.get(&index) == other.tints.get(&index))
+            && (!tile.has_payload() || self.payloads.get(&index) == other.payloads.get(&index))
+    }
+
+    /// Bounds-checked lookup, reading positions outside the grid as empty
+    pub fn cell_at(&self, x: isize, y: isize) -> Option<Cell> {
+        if self.in_bounds(x, y) {
+            self.get(x as usize, y as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Whether a body can't move into the position, either because it holds a solid cell or
+    /// because it lies outside the grid
+    pub fn is_blocked(&self, x: isize, y: isize) -> bool {
+        !self.in_bounds(x, y)
+            || self
+                .get(x as usize, y as usize)
+                .is_some_and(|c| c.is_solid())
+    }
+
+    /// Whether any position overlapped by the box, given in cell coordinates, is blocked
+    pub fn aabb_blocked(&self, aabb: impl Into<Aabb>) -> bool {
+        aabb_positions(aabb.into()).any(|(x, y)| self.is_blocked(x, y))
+    }
+
+    /// The first cell overlapped by the box, given in cell coordinates, that matches the filter
+    pub fn overlaps_aabb(
+        &self,
+        aabb: impl Into<Aabb>,
+        filter: impl Fn(Cell) -> bool,
+    ) -> Option<Hit> {
+        aabb_positions(aabb.into()).find_map(|(x, y)| {
+            let cell = self.cell_at(x, y).filter(|&c| filter(c))?;
+            Some(Hit {
+                x: x as usize,
+                y: y as usize,
+                cell,
+                point: Vec2::new(x as f32 + 0.5, y as f32 + 0.5),
+                normal: Vec2::ZERO,
+            })
+        })
+    }
+
+    /// The first solid or liquid cell on the segment between two points in cell coordinates
+    pub fn raycast(&self, from: Vec2, to: Vec2) -> Option<Hit> {
+        self.raycast_with(from, to, |c| c.is_solid() || c.is_liquid())
+    }
+
+    /// The first cell on the segment between two points in cell coordinates that matches the
+    /// filter, visiting every cell the segment passes through in order
+    pub fn raycast_with(&self, from: Vec2, to: Vec2, filter: impl Fn(Cell) -> bool) -> Option<Hit> {
+        if self.tiles.is_empty() {
+            return None;
+        }
+        let delta = to - from;
+        let size = Vec2::new(self.width as f32, self.height as f32);
+
+        // Clip the segment to the grid, so the walk below starts at the first cell inside it and
+        // stops at the last one
+        let (mut t, mut t_exit) = (0.0, 1.0);
+        let mut normal = Vec2::ZERO;
+        for axis in 0..2 {
+            let (p, d) = (from[axis], delta[axis]);
+            if d == 0.0 {
+                if p < 0.0 || p >= size[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = (-p / d, (size[axis] - p) / d);
+            let (near, far) = if d > 0.0 { (t0, t1) } else { (t1, t0) };
+            if near > t {
+                t = near;
+                normal = Vec2::ZERO;
+                normal[axis] = -d.signum();
+            }
+            t_exit = f32::min(t_exit, far);
+        }
+        if t > t_exit {
+            return None;
+        }
+
+        let start = from + delta * t;
+        let mut position = start
+            .floor()
+            .as_ivec2()
+            .clamp(IVec2::ZERO, size.as_ivec2() - IVec2::ONE);
+        let step = IVec2::new(
+            if delta.x < 0.0 { -1 } else { 1 },
+            if delta.y < 0.0 { -1 } else { 1 },
+        );
+
+        // Ray parameter needed to cross one cell along each axis, and to reach the next boundary
+        let t_delta = Vec2::new(1.0 / delta.x.abs(), 1.0 / delta.y.abs());
+        let boundary = |p: f32, cell: i32, step: i32, t_delta: f32| {
+            if t_delta.is_infinite() {
+                f32::INFINITY
+            } else if step > 0 {
+                t + (cell as f32 + 1.0 - p) * t_delta
+            } else {
+                t + (p - cell as f32) * t_delta
+            }
+        };
+        let mut t_max = Vec2::new(
+            boundary(start.x, position.x, step.x, t_delta.x),
+            boundary(start.y, position.y, step.y, t_delta.y),
+        );
+
+        while t <= t_exit {
+            if let Some(cell) = self
+                .cell_at(position.x as isize, position.y as isize)
+                .filter(|&c| filter(c))
+            {
+                return Some(Hit {
+                    x: position.x as usize,
+                    y: position.y as usize,
+                    cell,
+                    point: from + delta * t,
+                    normal,
+                });
+            }
+
+            if t_max.x < t_max.y {
+                t = t_max.x;
+                t_max.x += t_delta.x;
+                position.x += step.x;
+                normal = Vec2::new(-step.x as f32, 0.0);
+            } else {
+                t = t_max.y;
+                t_max.y += t_delta.y;
+                position.y += step.y;
+                normal = Vec2::new(0.0, -step.y as f32);
+            }
+        }
+        None
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, cell: Option<Cell>) {
+        let index = self.index(x, y);
+        let tile = &mut self.tiles[index];
+        let (tint, aux) = cell.map_or((None, 0), |cell| (cell.tint, cell.aux));
+        let cell = PackedCell::pack(cell);
+        let same_tint = !tile.has_tint() || self.tints.get(&index) == tint.as_ref();
+        let same_payload = !tile.has_payload() || self.payloads.get(&index) == Some(&aux);
+        if !tile.same_cell(cell) || !same_tint || !same_payload {
+            if let Some(tint) = tint {
+                self.tints.insert(index, tint);
+            } else if tile.has_tint() {
+                self.tints.remove(&index);
+            }
+            if cell.has_payload() {
+                self.payloads.insert(index, aux);
+            } else if tile.has_payload() {
+                self.payloads.remove(&index);
+            }
+            *tile = tile.with_cell(cell);
+            tile.set_fall(0);
+            tile.set_flag(PackedCell::CHANGED, true);
+            self.wake_around(x..x + 1, y..y + 1);
+        }
+    }
+
+    /// Places a fresh cell of the given kind if the position is empty
+    pub fn spawn(&mut self, x: usize, y: usize, id: CellId) -> bool {
+        if self.get(x, y).is_some() {
+            return false;
+        }
+        self.set(x, y, Some(Cell::new(id)));
+        true
+    }
+
+    /// Places a portal if the position is empty. It pairs up with the portal waiting for one, or
+    /// else waits for the next portal placed, taking a color no other pair has.
+    pub fn place_portal(&mut self, x: usize, y: usize) -> bool {
+        if self.get(x, y).is_some() {
+            return false;
+        }
+        let mut counts = BTreeMap::new();
+        for (_, _, cell) in self.iter() {
+            if let Some(tint) = cell.filter(|c| c.id == CellId::Portal).and_then(|c| c.tint) {
+                *counts.entry(tint).or_insert(0) += 1;
+            }
+        }
+        let waiting = PORTAL_COLORS
+            .into_iter()
+            .find(|tint| counts.get(tint) == Some(&1));
+        let unused = PORTAL_COLORS
+            .into_iter()
+            .find(|tint| !counts.contains_key(tint));
+        let Some(tint) = waiting.or(unused) else {
+            return false;
+        };
+        self.set(
+            x,
+            y,
+            Some(Cell {
+                tint: Some(tint),
+                ..Cell::new(CellId::Portal)
+            }),
+        );
+        true
+    }
+
+    /// The portals whose tint only one other portal shares, each with the other
+    pub fn portal_pairs(&self) -> Vec<((usize, usize), (usize, usize))> {
+        let mut ends: BTreeMap<[u8; 3], Vec<(usize, usize)>> = BTreeMap::new();
+        for (x, y, cell) in self.iter() {
+            if let Some(tint) = cell.filter(|c| c.id == CellId::Portal).and_then(|c| c.tint) {
+                ends.entry(tint).or_default().push((x, y));
+            }
+        }
+        ends.into_values()
+            .filter_map(|ends| match ends[..] {
+                [a, b] => Some((a, b)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Places a fresh cell of the given kind over whatever is at the position, unless a cell of
+    /// that kind is already there
+    pub fn replace(&mut self, x: usize, y: usize, id: CellId) -> bool {
+        if self.get(x, y).is_some_and(|cell| cell.id == id) {
+            return false;
+        }
+        self.set(x, y, Some(Cell::new(id)));
+        true
+    }
+
+    /// Turns the cell at the position to face a way, if its kind is [oriented](CellId::oriented)
+    pub fn face(&mut self, x: usize, y: usize, facing: Facing) {
+        if let Some(cell) = self.get(x, y).filter(|cell| cell.id.oriented()) {
+            self.set(x, y, Some(cell.facing_to(facing)));
+        }
+    }
+
+    /// Whether the cell has kept still for [`cell::SLEEP_TICKS`] and is skipped by the simulation until
+    /// something next to it changes
+    pub fn is_asleep(&self, x: usize, y: usize) -> bool {
+        self.tiles[self.index(x, y)].is_asleep()
+    }
+
+    /// Wakes the cells within the ranges and the ones next to them
+    fn wake_around(&mut self, xs: Range<usize>, ys: Range<usize>) {
+        for x in xs.start.saturating_sub(1)..xs.end.saturating_add(1).min(self.width) {
+            for y in ys.start.saturating_sub(1)..ys.end.saturating_add(1).min(self.height) {
+                let index = self.index(x, y);
+                self.tiles[index].wake();
+            }
+        }
+    }
+
+    /// How many ticks in a row the cell at the position has fallen straight down, counting up to
+    /// 31. It starts again from nothing once the cell keeps still or moves any other way.
+    pub fn fall(&self, x: usize, y: usize) -> u8 {
+        self.tiles[self.index(x, y)].fall()
+    }
+
+    pub fn is_frozen(&self, x: usize, y: usize) -> bool {
+        self.tiles[self.index(x, y)].has(PackedCell::FROZEN)
+    }
+
+    /// Freezes or thaws the positions within the ranges, clamped to the grid
+    pub fn set_frozen(&mut self, xs: Range<usize>, ys: Range<usize>, frozen: bool) {
+        for x in xs.start..xs.end.min(self.width) {
+            for y in ys.start..ys.end.min(self.height) {
+                let index = self.index(x, y);
+                self.tiles[index].set_flag(PackedCell::FROZEN, frozen);
+            }
+        }
+        // Cells that kept still while frozen may be free to move now
+        self.wake_around(xs, ys);
+    }
+
+    pub fn thaw_all(&mut self) {
+        for tile in &mut self.tiles {
+            tile.set_flag(PackedCell::FROZEN, false);
+            tile.wake();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for x in 0..self.width {
+            for y in 0..self.height {
+                self.set(x, y, None);
+            }
+        }
+    }
+
+    /// Positions whose cell changed since the last call, column by column. A cell that changed
+    /// and changed back within that time is still reported.
+    pub fn take_changes(&mut self) -> Vec<(usize, usize)> {
+        let height = self.height;
+        self.tiles
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, tile)| {
+                let changed = tile.has(PackedCell::CHANGED);
+                tile.set_flag(PackedCell::CHANGED, false);
+                changed.then_some((i / height, i % height))
+            })
+            .collect()
+    }
+
+    /// Moves every cell and frozen mark by `(dx, dy)` tiles. Whatever crosses the edges is lost,
+    /// and the tiles left behind are empty.
+    pub fn shift(&mut self, dx: isize, dy: isize) {
+        let before = self.clone();
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let (from_x, from_y) = (x as isize - dx, y as isize - dy);
+                let (cell, frozen) = if before.in_bounds(from_x, from_y) {
+                    let (from_x, from_y) = (from_x as usize, from_y as usize);
+                    (before.get(from_x, from_y), before.is_frozen(from_x, from_y))
+                } else {
+                    (None, false)
+                };
+                self.set(x, y, cell);
+                let index = self.index(x, y);
+                self.tiles[index].set_flag(PackedCell::FROZEN, frozen);
+            }
+        }
+    }
+
+    /// The cells within the ranges, clamped to the grid, as a grid of their own
+    pub fn region(&self, xs: Range<usize>, ys: Range<usize>) -> Grid {
+        let (xs, ys) = (
+            xs.start.min(self.width)..xs.end.min(self.width),
+            ys.start.min(self.height)..ys.end.min(self.height),
+        );
+        let mut region = Grid::new(xs.len(), ys.len());
+        region.spread = self.spread;
+        for x in xs.clone() {
+            for y in ys.clone() {
+                region.set(x - xs.start, y - ys.start, self.get(x, y));
+            }
+        }
+        region
+    }
+
+    /// Copies the cells of `other` with its top left corner at `(x, y)`, leaving out what doesn't
+    /// fit
+    pub fn paste(&mut self, x: usize, y: usize, other: &Grid) {
+        for (ox, oy, cell) in other.iter() {
+            if x + ox < self.width && y + oy < self.height {
+                self.set(x + ox, y + oy, cell);
+            }
+        }
+    }
+
+    /// Copies the cells of `other` with its top left corner at `(x, y)`, which may lie outside the
+    /// grid, over this one. The positions `other` leaves empty keep what they hold, and what
+    /// doesn't fit is left out.
+    pub fn paste_over(&mut self, x: isize, y: isize, other: &Grid) {
+        for (ox, oy, cell) in other.iter() {
+            let (x, y) = (x + ox as isize, y + oy as isize);
+            if let Some(cell) = cell.filter(|_| self.in_bounds(x, y)) {
+                self.set(x as usize, y as usize, Some(cell));
+            }
+        }
+    }
+
+    /// The grid turned a quarter turn clockwise, with the cells that face a way turned along
+    pub fn rotated(&self) -> Grid {
+        let mut rotated = Grid::new(self.height, self.width);
+        rotated.spread = self.spread;
+        for (x, y, cell) in self.iter() {
+            let cell = cell.map(|c| c.facing().map_or(c, |f| c.facing_to(f.clockwise())));
+            rotated.set(self.height - 1 - y, x, cell);
+        }
+        rotated
+    }
+
+    /// The grid mirrored left to right, with the cells that face a way mirrored along
+    pub fn mirrored(&self) -> Grid {
+        let mut mirrored = Grid::new(self.width, self.height);
+        mirrored.spread = self.spread;
+        for (x, y, cell) in self.iter() {
+            let cell = cell.map(|c| c.facing().map_or(c, |f| c.facing_to(f.flip_x())));
+            mirrored.set(self.width - 1 - x, y, cell);
+        }
+        mirrored
+    }
+
+    /// The cells of `other` at the positions where it differs from this grid, column by column.
+    /// Setting them turns this grid into `other`, which must be the same size.
+    pub fn diff(&self, other: &Grid) -> Vec<(usize, usize, Option<Cell>)> {
+        assert_eq!((self.width, self.height), (other.width, other.height));
+        self.iter()
+            .zip(other.iter())
+            .filter(|&((_, _, cell), (_, _, theirs))| cell != theirs)
+            .map(|(_, theirs)| theirs)
+            .collect()
+    }
+
+    /// Iterates over every position with its cell, column by column
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, Option<Cell>)> + '_ {
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(|(i, _)| (i / self.height, i % self.height, self.cell(i)))
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        debug_assert!(x < self.width && y < self.height);
+        x * self.height + y
+    }
+
+    /// Advances the simulation by a single tick
+    pub fn step(&mut self, rng: &mut impl Rng) -> TickStats {
+        self.step_with(ScanOrder::Shuffled, 0, rng)
+    }
+
+    /// Advances the simulation by a single tick, the `tick`th, visiting the positions as `scan`
+    /// says
+    pub fn step_with(&mut self, scan: ScanOrder, tick: u64, rng: &mut impl Rng) -> TickStats {
+        let order = self.scan_order(scan, tick, rng);
+        self.step_in_order(order, rng)
+    }
+
+    /// The order in which the `tick`th tick visits the positions of the grid
+    pub fn scan_order(
+        &self,
+        scan: ScanOrder,
+        tick: u64,
+        rng: &mut impl Rng,
+    ) -> Box<dyn Iterator<Item = (usize, usize)>> {
+        match scan {
+            ScanOrder::Shuffled => Box::new(self.update_order(rng).into_iter()),
+            ScanOrder::BottomUp => {
+                let (width, height) = (self.width, self.height);
+                let reversed = !tick.is_multiple_of(2);
+                Box::new((0..height).rev().flat_map(move |y| {
+                    (0..width).map(move |i| (if reversed { width - 1 - i } else { i }, y))
+                }))
+            }
+        }
+    }
+
+    /// The randomized order in which a tick visits the positions of the grid
+    pub fn update_order(&self, rng: &mut impl Rng) -> Vec<(usize, usize)> {
+        let mut coords: Vec<_> = (0..self.width)
+            .flat_map(|x| (0..self.height).map(move |y| (x, y)))
+            .collect();
+        coords.shuffle(rng);
+        coords
+    }
+
+    /// Advances the simulation by a single tick, visiting the positions in the given order
+    pub fn step_in_order(
+        &mut self,
+        order: impl IntoIterator<Item = (usize, usize)>,
+        rng: &mut impl Rng,
+    ) -> TickStats {
+        #[cfg(feature = "mass-check")]
+        let before = self.census();
+        let mut next = self.clone();
+        let mut stats = TickStats::default();
+
+        // A cell only moves while both ends of the move still hold what they held at the start of
+        // the tick, otherwise an earlier move would be cloned or overwritten
+        // Frozen positions count as touched, so no move can reach into them
+        let untouched = |next: &Grid, x: usize, y: usize| {
+            let index = self.index(x, y);
+            next.same_tile(self, index) && !self.tiles[index].has(PackedCell::FROZEN)
+        };
+
+        for (x, y) in order {
+            if !untouched(&next, x, y) || self.tiles[self.index(x, y)].is_asleep() {
+                continue;
+            }
+
+            if let Some(mut cell) = self.get(x, y) {
+                if let Some(lifespan) = cell.lifespan() {
+                    // Fire keeps burning while it has fuel next to it, and soon goes out without
+                    let fueled = lifespan.fueled
+                        && self
+                            .adjacent(x, y)
+                            .into_iter()
+                            .any(|(ax, ay)| self.get(ax, ay).is_some_and(|c| c.catches_from(cell)));
+                    let burn = if lifespan.fueled && !fueled {
+                        STARVED_BURN
+                    } else {
+                        1
+                    };
+                    let life = cell.life.get_or_insert_with(|| lifespan.roll(rng));
+                    if fueled {
+                        *life = lifespan.roll(rng);
+                    }
+                    *life = life.saturating_sub(burn);
+                    if *life == 0 {
+                        if cell.id == CellId::Rocket {
+                            self.burst(&mut next, x, y, &untouched, rng, &mut stats);
+                            continue;
+                        }
+                        // A fuse burns on into the rest of itself, and at its end into whatever
+                        // else is there
+                        if cell.id == CellId::BurningFuse {
+                            let neighbours = self.adjacent(x, y);
+                            let fuse: Vec<_> = neighbours
+                                .iter()
+                                .copied()
+                                .filter(|&(nx, ny)| {
+                                    self.get(nx, ny).is_some_and(|c| c.id == CellId::Fuse)
+                                })
+                                .collect();
+                            let lit = if fuse.is_empty() { neighbours } else { fuse };
+                            for (nx, ny) in lit {
+                                self.ignite(&mut next, nx, ny, cell, &untouched, &mut stats);
+                            }
+                        }
+                        let remains = cell.remains().map(Cell::new);
+                        next.set(x, y, remains);
+                        stats.destroyed.add(cell.id);
+                        if let Some(remains) = remains {
+                            stats.created.add(remains.id);
+                        }
+                        continue;
+                    }
+                    // Cells that age without moving, like foam, still get older
+                    next.set(x, y, Some(cell));
+                }
+
+                // Heaters and coolers change what is next to them, and heaters light it if it
+                // catches from them
+                if matches!(cell.id, CellId::Heater | CellId::Cooler) {
+                    if let Some(&(nx, ny)) = self.adjacent(x, y).choose(rng) {
+                        let other = self.get(nx, ny);
+                        if rng.gen::<f32>() < TEMPER_CHANCE && untouched(&next, nx, ny) {
+                            let changed = other.and_then(|other| match cell.id {
+                                CellId::Heater => other.heats_into(),
+                                _ => other.cools_into(),
+                            });
+                            if let (Some(other), Some(id)) = (other, changed) {
+                                next.set(nx, ny, Some(Cell::new(id)));
+                                stats.destroyed.add(other.id);
+                                stats.created.add(id);
+                                stats.reactions += 1;
+                            } else {
+                                self.ignite(&mut next, nx, ny, cell, &untouched, &mut stats);
+                            }
+                        }
+                        continue;
+                    }
+                }
+
+                // Lit fireworks climb until something blocks them, then burst
+                if cell.id == CellId::Rocket {
+                    let mut to = y;
+                    while y - to < ROCKET_SPEED
+                        && to > 0
+                        && self.get(x, to - 1).is_none()
+                        && untouched(&next, x, to - 1)
+                    {
+                        to -= 1;
+                    }
+                    if to == y {
+                        self.burst(&mut next, x, y, &untouched, rng, &mut stats);
+                    } else {
+                        next.set(x, y, None);
+                        next.set(x, to, Some(cell));
+                        stats.moved += 1;
+                    }
+                    continue;
+                }
+
+                // Mold creeps now and then onto wood next to it, as long as the wood is damp
+                if cell.id == CellId::Mold && rng.gen::<f32>() < MOLD_GROWTH {
+                    if let Some(&(wx, wy)) = self.adjacent(x, y).choose(rng) {
+                        let damp = |(wx, wy): (usize, usize)| {
+                            self.adjacent(wx, wy).into_iter().any(|(ax, ay)| {
+                                self.get(ax, ay).is_some_and(|c| c.id == CellId::Water)
+                            })
+                        };
+                        if self.get(wx, wy).is_some_and(|c| c.id == CellId::Wood)
+                            && untouched(&next, wx, wy)
+                            && damp((wx, wy))
+                        {
+                            next.set(wx, wy, Some(Cell::new(CellId::Mold)));
+                            stats.destroyed.add(CellId::Wood);
+                            stats.created.add(CellId::Mold);
+                            stats.reactions += 1;
+                        }
+                    }
+                }
+
+                // Antimatter annihilates with the first thing it touches
+                if cell.id == CellId::Antimatter {
+                    let touched = [(0, 1), (-1, 0), (1, 0), (0, -1)]
+                        .into_iter()
+                        .map(|(dx, dy)| (x as isize + dx, y as isize + dy))
+                        .filter(|&(nx, ny)| self.in_bounds(nx, ny))
+                        .map(|(nx, ny)| (nx as usize, ny as usize))
+                        .find_map(|(nx, ny)| {
+                            let other = self.get(nx, ny)?;
+                            (other.id != CellId::Antimatter
+                                && !other.indestructible()
+                                && !self.is_frozen(nx, ny)
+                                && untouched(&next, nx, ny))
+                            .then_some((nx, ny, other))
+                        });
+                    if let Some((nx, ny, other)) = touched {
+                        next.set(x, y, None);
+                        next.set(nx, ny, None);
+                        stats.destroyed.add(cell.id);
+                        stats.destroyed.add(other.id);
+                        stats.reactions += 1;
+                        stats.events.push(SimEvent::Annihilated { x, y });
+                        continue;
+                    }
+                }
+
+                // Meteors plunge until they hit something other than the rest of their clump,
+                // then explode
+                if cell.id == CellId::Meteor {
+                    let mut to = y;
+                    while to - y < METEOR_SPEED
+                        && to < self.height - 1
+                        && self.get(x, to + 1).is_none()
+                        && untouched(&next, x, to + 1)
+                    {
+                        to += 1;
+                    }
+                    let below = self.cell_at(x as isize, to as isize + 1);
+                    if to != y {
+                        next.set(x, y, None);
+                        next.set(x, to, Some(cell));
+                        stats.moved += 1;
+                    } else if below.is_none_or(|c| c.id != CellId::Meteor) {
+                        self.explode(&mut next, x, y, METEOR_BLAST, &untouched, rng, &mut stats);
+                        next.set(x, y, Some(Cell::new(CellId::Stone)));
+                        stats.destroyed.add(CellId::Meteor);
+                        stats.created.add(CellId::Stone);
+                    }
+                    continue;
+                }
+
+                // Embers smolder in place, now and then flaring up next to them
+                if cell.id == CellId::Ember {
+                    if let Some(&(nx, ny)) = self.adjacent(x, y).choose(rng) {
+                        if rng.gen::<f32>() < EMBER_FLARE && untouched(&next, nx, ny) {
+                            if self.get(nx, ny).is_none() {
+                                next.set(nx, ny, Some(Cell::new(CellId::Fire)));
+                                stats.created.add(CellId::Fire);
+                            } else {
+                                self.ignite(&mut next, nx, ny, cell, &untouched, &mut stats);
+                            }
+                        }
+                        continue;
+                    }
+                }
+
+                // Molten metal burns through what it rests on and sets its neighbours alight
+                if cell.id == CellId::MoltenMetal {
+                    let melts = |c: Cell| !c.indestructible() && c.id != cell.id;
+                    let below = self.cell_at(x as isize, y as isize + 1);
+                    if let Some(below) = below.filter(|&c| melts(c)) {
+                        if rng.gen::<f32>() < MELT_CHANCE && untouched(&next, x, y + 1) {
+                            next.set(x, y + 1, None);
+                            stats.destroyed.add(below.id);
+                            stats.reactions += 1;
+                        }
+                    }
+                    if let Some(&(nx, ny)) = self.adjacent(x, y).choose(rng) {
+                        self.ignite(&mut next, nx, ny, cell, &untouched, &mut stats);
+                    }
+                }
+
+                // Tint the water next to dye
+                if let Some(dye) = cell.dye() {
+                    for (nx, ny) in self.adjacent(x, y) {
+                        let Some(water) = self.get(nx, ny).filter(|c| c.dyeable()) else {
+                            continue;
+                        };
+                        if water.tint != Some(dye) && untouched(&next, nx, ny) {
+                            // Blending alone would stop one step short of the dye's color
+                            let tint = water.tint.map(|tint| blend(tint, dye));
+                            let tint = tint.filter(|&tint| Some(tint) != water.tint);
+                            next.set(
+                                nx,
+                                ny,
+                                Some(Cell {
+                                    tint: Some(tint.unwrap_or(dye)),
+                                    ..water
+                                }),
+                            );
+                        }
+                    }
+                }
+
+                // Lather moving water into foam
+                if cell.id == CellId::Soap {
+                    for (nx, ny) in self.neighbors(x, y) {
+                        let agitated = self.get(nx, ny).is_some_and(|c| c.id == CellId::Water)
+                            && self.tiles[self.index(nx, ny)].has(PackedCell::MOVING);
+                        if agitated && untouched(&next, nx, ny) && rng.gen::<f32>() < 0.5 {
+                            next.set(nx, ny, Some(Cell::new(CellId::Foam)));
+                            stats.destroyed.add(CellId::Water);
+                            stats.created.add(CellId::Foam);
+                            stats.reactions += 1;
+                        }
+                    }
+                }
+
+                // Blend the tints of dyed waters that meet
+                if let Some(tint) = cell.tint.filter(|_| cell.dyeable()) {
+                    let mixing: Vec<_> = self
+                        .adjacent(x, y)
+                        .into_iter()
+                        .filter(|&(nx, ny)| {
+                            self.get(nx, ny).is_some_and(|c| {
+                                c.dyeable() && c.tint.is_some_and(|other| other != tint)
+                            }) && untouched(&next, nx, ny)
+                        })
+                        .collect();
+                    if let Some(&(nx, ny)) = mixing.choose(rng) {
+                        let other = self.get(nx, ny).unwrap();
+                        let mixed = Some(blend(tint, other.tint.unwrap()));
+                        next.set(
+                            x,
+                            y,
+                            Some(Cell {
+                                tint: mixed,
+                                ..cell
+                            }),
+                        );
+                        next.set(
+                            nx,
+                            ny,
+                            Some(Cell {
+                                tint: mixed,
+                                ..other
+                            }),
+                        );
+                        continue;
+                    }
+                }
+
+                if y > 0 {
+                    let above = self.get(x, y - 1);
+
+                    // Float
+                    if let Some(above) = above
+                        .filter(|above| above.sinks_under(Some(cell)) && untouched(&next, x, y - 1))
+                    {
+                        stats.events.extend(self.splash(x, y, above));
+                        next.set(x, y, Some(above));
+                        next.set(x, y - 1, Some(cell));
+                        stats.moved += 1;
+                        continue;
+                    }
+
+                    // Bob up through heavier liquids, unless held in place
+                    let buoyed = above.filter(|above| {
+                        cell.floats()
+                            && above.material() == Material::Liquid
+                            && above.density() > cell.density()
+                            && untouched(&next, x, y - 1)
+                    });
+                    if let Some(above) = buoyed.filter(|_| !self.held(x, y)) {
+                        if rng.gen::<f32>() < BOB_CHANCE {
+                            next.set(x, y, Some(above));
+                            next.set(x, y - 1, Some(cell));
+                            stats.moved += 1;
+                            continue;
+                        }
+                        // Kept from falling asleep until it gets there
+                        let index = self.index(x, y);
+                        next.tiles[index].wake();
+                    }
+                }
+
+                // Landing hard on nitroglycerin sets it off
+                let detonates = cell.falls()
+                    && self.fall(x, y) >= DETONATION_FALL
+                    && self
+                        .cell_at(x as isize, y as isize + 1)
+                        .is_some_and(|c| c.id == CellId::Nitroglycerin)
+                    && untouched(&next, x, y + 1);
+                if detonates {
+                    self.explode(
+                        &mut next,
+                        x,
+                        y + 1,
+                        NITRO_BLAST,
+                        &untouched,
+                        rng,
+                        &mut stats,
+                    );
+                    next.set(x, y + 1, Some(Cell::new(CellId::Fire)));
+                    stats.destroyed.add(CellId::Nitroglycerin);
+                    stats.created.add(CellId::Fire);
+                    continue;
+                }
+
+                // Ride the conveyor the cell rests on
+                if let Some(dx) = self
+                    .cell_at(x as isize, y as isize + 1)
+                    .and_then(|c| c.conveys())
+                {
+                    let to = x as isize + dx;
+                    let rides = cell.falls()
+                        && self.in_bounds(to, y as isize)
+                        && self.get(to as usize, y).is_none()
+                        && untouched(&next, to as usize, y);
+                    if rides {
+                        next.set(x, y, None);
+                        next.set(to as usize, y, Some(cell));
+                        stats.moved += 1;
+                        continue;
+                    }
+                }
+
+                if y < self.height - 1 {
+                    // Thick cells only flow now and then, and sticky ones hang on to the walls
+                    // beside and above them and don't leave the wall they rest on
+                    let sluggish = cell.viscosity() > 0.0 && rng.gen::<f32>() < cell.viscosity();
+                    let clinging = cell.adheres() && self.clings(x, y);
+                    let on_wall = cell.adheres() && self.get(x, y + 1).is_some_and(|c| c.is_wall());
+
+                    if cell.falls() && !clinging && untouched(&next, x, y + 1) {
+                        // Fall
+                        if cell.sinks_under(self.get(x, y + 1))
+                            || cell.dissolves(self.get(x, y + 1))
+                        {
+                            if cell.dissolves(self.get(x, y + 1)) {
+                                let fumes =
+                                    stats.dissolve(cell, self.get(x, y + 1), (x, y + 1), rng);
+                                next.set(x, y, fumes);
+                                next.set(x, y + 1, None);
+                            } else {
+                                let below = self.get(x, y + 1);
+                                let splash = self.splash(x, y + 1, cell);
+                                next.set(x, y, below);
+                                next.set(x, y + 1, Some(cell));
+                                let index = self.index(x, y + 1);
+                                next.tiles[index].set_fall(self.fall(x, y).saturating_add(1));
+                                stats.moved += 1;
+
+                                // A cell that was already falling before it hit the
+                                // surface splashes
+                                let falling = self.tiles[self.index(x, y)].has(PackedCell::MOVING);
+                                if splash.is_some() && falling {
+                                    stats.moved +=
+                                        self.throw_liquid(&mut next, x, y, cell, &untouched, rng);
+                                }
+                                stats.events.extend(splash);
+                            }
+                            continue;
+                        } else {
+                            // Put out what it lands on, or burn up in it
+                            let below = self.get(x, y + 1);
+                            let contact = below.and_then(|below| ContactRule::landing(cell, below));
+                            if let Some((_, doused)) = contact.filter(|(rule, _)| rule.happens(rng))
+                            {
+                                let (destroyed, at) = if doused {
+                                    next.set(x, y + 1, Some(cell));
+                                    stats.moved += 1;
+                                    (below.unwrap(), y + 1)
+                                } else {
+                                    (cell, y)
+                                };
+                                next.set(x, y, None);
+                                stats.reactions += 1;
+                                stats.destroyed.add(destroyed.id);
+                                stats.events.push(SimEvent::Destroyed {
+                                    x,
+                                    y: at,
+                                    id: destroyed.id,
+                                });
+                                continue;
+                            }
+                            match below {
+                                // Dissolve in acid
+                                Some(c) if c.dissolves(Some(cell)) => {
+                                    let fumes = stats.dissolve(c, Some(cell), (x, y), rng);
+                                    next.set(x, y, fumes);
+                                    next.set(x, y + 1, None);
+                                    continue;
+                                }
+                                // Slowly work down into lighter powder, kept awake until it
+                                // reaches something it can't
+                                Some(c) if cell.settles_into(c) => {
+                                    if rng.gen::<f32>() < SETTLE_CHANCE {
+                                        next.set(x, y, Some(c));
+                                        next.set(x, y + 1, Some(cell));
+                                        stats.moved += 1;
+                                        continue;
+                                    }
+                                    let index = self.index(x, y);
+                                    next.tiles[index].wake();
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
+
+                    // Slide down slopes
+                    if cell.slides() && !clinging && !sluggish && !on_wall {
+                        let below_left = x > 0
+                            && (cell.sinks_under(self.get(x - 1, y + 1))
+                                || cell.dissolves(self.get(x - 1, y + 1)))
+                            && cell.sinks_under(self.get(x - 1, y))
+                            && untouched(&next, x - 1, y + 1);
+                        let below_right = x < self.width - 1
+                            && (cell.sinks_under(self.get(x + 1, y + 1))
+                                || cell.dissolves(self.get(x + 1, y + 1)))
+                            && cell.sinks_under(self.get(x + 1, y))
+                            && untouched(&next, x + 1, y + 1);
+
+                        let (below_left, below_right) = if below_left && below_right {
+                            if rng.gen() {
+                                (true, false)
+                            } else {
+                                (false, true)
+                            }
+                        } else {
+                            (below_left, below_right)
+                        };
+
+                        if below_left {
+                            if cell.dissolves(self.get(x - 1, y + 1)) {
+                                let fumes = stats.dissolve(
+                                    cell,
+                                    self.get(x - 1, y + 1),
+                                    (x - 1, y + 1),
+                                    rng,
+                                );
+                                next.set(x, y, fumes);
+                                next.set(x - 1, y + 1, None);
+                            } else {
+                                next.set(x, y, self.get(x - 1, y + 1));
+                                next.set(x - 1, y + 1, Some(cell));
+                                stats.moved += 1;
+                            }
+                            continue;
+                        }
+
+                        if below_right {
+                            if cell.dissolves(self.get(x + 1, y + 1)) {
+                                let fumes = stats.dissolve(
+                                    cell,
+                                    self.get(x + 1, y + 1),
+                                    (x + 1, y + 1),
+                                    rng,
+                                );
+                                next.set(x, y, fumes);
+                                next.set(x + 1, y + 1, None);
+                            } else {
+                                next.set(x, y, self.get(x + 1, y + 1));
+                                next.set(x + 1, y + 1, Some(cell));
+                                stats.moved += 1;
+                            }
+                            continue;
+                        }
+                    }
+
+                    // Blown out by the wind when on its own
+                    if let Some(rule) = ContactRule::blown(cell) {
+                        if self.wind_at(x, y) != IVec2::ZERO && rule.happens(rng) {
+                            let kin = self
+                                .adjacent(x, y)
+                                .into_iter()
+                                .filter(|&(ax, ay)| {
+                                    self.get(ax, ay)
+                                        .is_some_and(|c| c.material() == cell.material())
+                                })
+                                .count();
+                            if kin <= 1 {
+                                next.set(x, y, None);
+                                stats.destroyed.add(cell.id);
+                                continue;
+                            }
+                        }
+                    }
+
+                    match cell.material() {
+                        Material::Powder | Material::Solid | Material::Rigid => (),
+                        Material::Liquid | Material::Acid => {
+                            // Fill gaps
+
+                            let spreads = !clinging && !sluggish;
+                            let supported =
+                                |x: usize| !cell.adheres() || self.get(x, y + 1).is_some();
+                            let left = spreads
+                                && x > 0
+                                && !self.is_frozen(x - 1, y)
+                                && (cell.sinks_under(next.get(x - 1, y))
+                                    || cell.dissolves(next.get(x - 1, y)))
+                                && (y == 0 || cell.sinks_under(self.get(x - 1, y - 1)))
+                                && supported(x - 1);
+                            let right = spreads
+                                && x < self.width - 1
+                                && !self.is_frozen(x + 1, y)
+                                && (cell.sinks_under(next.get(x + 1, y))
+                                    || cell.dissolves(next.get(x + 1, y)))
+                                && (y == 0 || cell.sinks_under(self.get(x + 1, y - 1)))
+                                && supported(x + 1);
+
+                            let (left, right) = if left && right {
+                                if rng.gen() {
+                                    (true, false)
+                                } else {
+                                    (false, true)
+                                }
+                            } else {
+                                (left, right)
+                            };
+
+                            if left {
+                                if cell.dissolves(next.get(x - 1, y)) {
+                                    let fumes =
+                                        stats.dissolve(cell, next.get(x - 1, y), (x - 1, y), rng);
+                                    next.set(x, y, fumes);
+                                    next.set(x - 1, y, None);
+                                } else if !self.erode(&mut next, x, y, x - 1, &untouched, rng) {
+                                    next.set(x, y, next.get(x - 1, y));
+                                    next.set(x - 1, y, Some(cell));
+                                    stats.moved += 1;
+                                } else {
+                                    stats.moved += 2;
+                                }
+                                continue;
+                            }
+
+                            if right {
+                                if cell.dissolves(next.get(x + 1, y)) {
+                                    let fumes =
+                                        stats.dissolve(cell, next.get(x + 1, y), (x + 1, y), rng);
+                                    next.set(x, y, fumes);
+                                    next.set(x + 1, y, None);
+                                } else if !self.erode(&mut next, x, y, x + 1, &untouched, rng) {
+                                    next.set(x, y, next.get(x + 1, y));
+                                    next.set(x + 1, y, Some(cell));
+                                    stats.moved += 1;
+                                } else {
+                                    stats.moved += 2;
+                                }
+                                continue;
+                            }
+                        }
+                        Material::Gas => {
+                            // Condense under a ceiling
+                            let ceiling = y == 0 || self.get(x, y - 1).is_some_and(|c| c.is_wall());
+                            if let Some(id) = cell.condenses_into().filter(|_| ceiling) {
+                                if rng.gen::<f32>() < CONDENSE_CHANCE {
+                                    next.set(x, y, Some(Cell::new(id)));
+                                    stats.destroyed.add(cell.id);
+                                    stats.created.add(id);
+                                    stats.reactions += 1;
+                                    continue;
+                                }
+                            }
+
+                            // Disperse, drifting up when lighter than air and down when heavier
+
+                            let dx = rng.gen_range(-1..=1);
+                            let drift = (cell.density() - AIR_DENSITY).abs()
+                                / (cell.density() + AIR_DENSITY);
+                            let dy = if rng.gen::<f32>() < drift {
+                                if cell.density() < AIR_DENSITY {
+                                    -1
+                                } else {
+                                    1
+                                }
+                            } else {
+                                rng.gen_range(-1..=1)
+                            };
+
+                            let new_x =
+                                (x as isize + dx).clamp(0, self.width as isize - 1) as usize;
+                            let new_y =
+                                (y as isize + dy).clamp(0, self.height as isize - 1) as usize;
+
+                            if self.get(new_x, new_y).is_none()
+                                && next.get(new_x, new_y).is_none()
+                                && !self.is_frozen(new_x, new_y)
+                            {
+                                next.set(x, y, None);
+                                next.set(new_x, new_y, Some(cell));
+                                stats.moved += 1;
+                                continue;
+                            }
+                        }
+                        Material::Fire => {
+                            let wind = self.wind_at(x, y);
+
+                            // Spread flames, further downwind than against it
+
+                            let flammables: Vec<_> = self
+                                .adjacent(x, y)
+                                .into_iter()
+                                .filter(|&(nx, ny)| {
+                                    self.get(nx, ny).is_some_and(|c| c.catches_from(cell))
+                                        && untouched(&next, nx, ny)
+                                })
+                                .collect();
+
+                            for (nx, ny) in flammables {
+                                let open: Vec<_> = self
+                                    .adjacent(nx, ny)
+                                    .into_iter()
+                                    .filter(|&(ax, ay)| {
+                                        self.get(ax, ay).is_none()
+                                            && next.get(ax, ay).is_none()
+                                            && !self.is_frozen(ax, ay)
+                                    })
+                                    .collect();
+
+                                if let Some(&(ax, ay)) = open.choose(rng) {
+                                    next.set(ax, ay, Some(Cell::new(cell.id)));
+                                    stats.created.add(cell.id);
+                                }
+
+                                let fuel = self.get(nx, ny).unwrap();
+                                let chance = self.spread_chance(fuel.id);
+                                let towards =
+                                    IVec2::new(nx as i32 - x as i32, ny as i32 - y as i32)
+                                        .dot(wind);
+                                let chance = match towards.signum() {
+                                    1 => chance * DOWNWIND_SPREAD,
+                                    -1 => chance * UPWIND_SPREAD,
+                                    _ => chance,
+                                };
+
+                                if rng.gen::<f32>() < chance {
+                                    self.ignite(&mut next, nx, ny, cell, &untouched, &mut stats);
+                                }
+                            }
+
+                            // Run along the surface of slicks floating on heavier liquids, which
+                            // burn across the top with nothing else to catch below them
+                            for row in [y, y + 1] {
+                                for dir in [-1, 1] {
+                                    let run = (1..=SLICK_REACH as isize)
+                                        .map(|i| (x as isize + dir * i, row as isize))
+                                        .filter(|&(sx, sy)| {
+                                            self.in_bounds(sx, sy)
+                                                && self.floats_alight(
+                                                    sx as usize,
+                                                    sy as usize,
+                                                    cell,
+                                                )
+                                        });
+                                    for (sx, sy) in run {
+                                        if rng.gen::<f32>() < SLICK_SPREAD {
+                                            self.ignite(
+                                                &mut next,
+                                                sx as usize,
+                                                sy as usize,
+                                                cell,
+                                                &untouched,
+                                                &mut stats,
+                                            );
+                                        }
+                                    }
+                                }
+                            }
+
+                            // Flames burning on the surface of a liquid stay on it until the slick
+                            // around them is spent, rather than rising off and leaving it
+                            let afloat = self
+                                .cell_at(x as isize, y as isize + 1)
+                                .is_some_and(|under| under.material() == Material::Liquid);
+                            if afloat {
+                                continue;
+                            }
+
+                            // Rise, bent by the wind
+
+                            let dx = (rng.gen_range(-1..=1) + wind.x as isize).clamp(-1, 1);
+                            let dy = (rng.gen_range(-2..=0) + wind.y as isize).clamp(-2, 0);
+
+                            let new_x =
+                                (x as isize + dx).clamp(0, self.width as isize - 1) as usize;
+                            let new_y =
+                                (y as isize + dy).clamp(0, self.height as isize - 1) as usize;
+
+                            next.set(x, y, None);
+
+                            let rose = match self.get(new_x, new_y) {
+                                Some(c)
+                                    if c.catches_from(cell) && untouched(&next, new_x, new_y) =>
+                                {
+                                    stats.destroyed.add(c.id);
+                                    stats.reactions += 1;
+                                    stats.events.push(SimEvent::Ignited { x: new_x, y: new_y });
+                                    stats.events.push(SimEvent::Destroyed {
+                                        x: new_x,
+                                        y: new_y,
+                                        id: c.id,
+                                    });
+                                    // The flame spends itself on fuel that smolders instead
+                                    let id = c.burns_into();
+                                    if id == cell.id {
+                                        next.set(new_x, new_y, Some(cell));
+                                    } else {
+                                        next.set(new_x, new_y, Some(Cell::new(id)));
+                                        stats.created.add(id);
+                                    }
+                                    id == cell.id
+                                }
+                                Some(_) => false,
+                                None => {
+                                    let free = next.get(new_x, new_y).is_none()
+                                        && !self.is_frozen(new_x, new_y);
+                                    if free {
+                                        next.set(new_x, new_y, Some(cell));
+                                    }
+                                    free
+                                }
+                            };
+                            if rose {
+                                stats.moved += 1;
+                            } else {
+                                stats.destroyed.add(cell.id);
+                            }
+
+                            continue;
+                        }
+                        // Wind stays where it was placed until it fades, blowing on flames nearby
+                        Material::Wind => next.set(x, y, Some(cell)),
+                    }
+                }
+            }
+        }
+
+        self.attract(&mut next, rng, &mut stats);
+        self.blow(&mut next, rng, &mut stats);
+        self.teleport(&mut next, &mut stats);
+        self.burst_pockets(&mut next, &untouched, rng, &mut stats);
+
+        // Cells that kept still get closer to falling asleep, unless a change next to them woke
+        // them up again, and stop counting how long they fell. Powder that just stopped moving
+        // has landed.
+        for i in 0..next.tiles.len() {
+            let same = next.same_tile(self, i);
+            let (tile, before) = (&mut next.tiles[i], self.tiles[i]);
+            if same {
+                tile.rest();
+                tile.set_fall(0);
+                if before.has(PackedCell::MOVING) {
+                    tile.set_flag(PackedCell::MOVING, false);
+                    if tile.is_powder() {
+                        let (x, y) = (i / self.height, i % self.height);
+                        stats.events.push(SimEvent::Landed { x, y });
+                    }
+                }
+            } else {
+                tile.set_flag(PackedCell::MOVING, true);
+            }
+        }
+
+        *self = next;
+
+        #[cfg(feature = "mass-check")]
+        {
+            let changed = before.unexplained_changes(&self.census());
+            assert!(
+                changed.is_empty(),
+                "{changed:?} cells were created or destroyed without reacting"
+            );
+        }
+
+        stats
+    }
+
+    /// Moves the cells around each gravity well a step closer to it and those around each
+    /// repulsor a step away, more often the closer they are. Unlike the other rules this reaches
+    /// past the neighbours, so it runs over the whole tick's result. Cells that reach a gravity
+    /// well fall in.
+    fn attract(&self, next: &mut Grid, rng: &mut impl Rng, stats: &mut TickStats) {
+        let wells: Vec<_> = self
+            .iter()
+            .filter_map(|(x, y, cell)| Some((x, y, cell?)))
+            .filter(|(_, _, cell)| cell.pull().is_some())
+            .collect();
+        if wells.is_empty() {
+            return;
+        }
+
+        // Nearest first, so the cells closer in make way for the ones behind them
+        let mut offsets: Vec<(isize, isize)> = (-WELL_RADIUS..=WELL_RADIUS)
+            .flat_map(|dx| (-WELL_RADIUS..=WELL_RADIUS).map(move |dy| (dx, dy)))
+            .filter(|&(dx, dy)| (dx, dy) != (0, 0) && dx * dx + dy * dy <= WELL_RADIUS.pow(2))
+            .collect();
+        offsets.sort_by_key(|&(dx, dy)| dx * dx + dy * dy);
+        let movable = |c: Cell| (c.falls() || c.material() == Material::Gas) && !c.indestructible();
+
+        for (wx, wy, well) in wells {
+            if next.get(wx, wy) != Some(well) {
+                continue;
+            }
+            let pull = well.pull().unwrap();
+            // Repulsors push the furthest cells first, for the same reason
+            let order: Vec<_> = if pull > 0 {
+                offsets.clone()
+            } else {
+                offsets.iter().rev().copied().collect()
+            };
+
+            for (dx, dy) in order {
+                let (x, y) = (wx as isize + dx, wy as isize + dy);
+                if !next.in_bounds(x, y) || next.is_frozen(x as usize, y as usize) {
+                    continue;
+                }
+                let (x, y) = (x as usize, y as usize);
+                let Some(cell) = next.get(x, y).filter(|&c| movable(c)) else {
+                    continue;
+                };
+
+                if pull > 0 && dx.abs() <= 1 && dy.abs() <= 1 {
+                    next.set(x, y, None);
+                    stats.destroyed.add(cell.id);
+                    stats.reactions += 1;
+                    continue;
+                }
+
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                if rng.gen::<f32>() >= 1.0 - distance / (WELL_RADIUS + 1) as f32 {
+                    continue;
+                }
+                let to_x = x as isize - dx.signum() * pull;
+                let to_y = y as isize - dy.signum() * pull;
+                if !next.in_bounds(to_x, to_y) {
+                    continue;
+                }
+                let (to_x, to_y) = (to_x as usize, to_y as usize);
+                if next.get(to_x, to_y).is_none() && !next.is_frozen(to_x, to_y) {
+                    next.set(x, y, None);
+                    next.set(to_x, to_y, Some(cell));
+                    stats.moved += 1;
+                }
+            }
+        }
+    }
+
+    /// Moves the cells in front of each fan a step further along the way it faces, more often the
+    /// closer they are. The furthest go first so the ones behind have room, and the first cell
+    /// that can't be blown shelters those beyond it.
+    fn blow(&self, next: &mut Grid, rng: &mut impl Rng, stats: &mut TickStats) {
+        let fans: Vec<_> = self
+            .iter()
+            .filter_map(|(x, y, cell)| Some((x, y, cell?.facing()?)))
+            .collect();
+        let movable = |c: Cell| (c.falls() || c.material() == Material::Gas) && !c.indestructible();
+
+        for (fx, fy, facing) in fans {
+            if next.get(fx, fy).and_then(|c| c.facing()) != Some(facing) {
+                continue;
+            }
+            let (dx, dy) = facing.offset();
+            let reach = (1..=FAN_REACH)
+                .take_while(|&d| {
+                    let (x, y) = (fx as isize + dx * d, fy as isize + dy * d);
+                    next.in_bounds(x, y) && next.get(x as usize, y as usize).is_none_or(movable)
+                })
+                .last()
+                .unwrap_or(0);
+
+            for d in (1..=reach).rev() {
+                let (x, y) = (
+                    (fx as isize + dx * d) as usize,
+                    (fy as isize + dy * d) as usize,
+                );
+                let Some(cell) = next.get(x, y) else {
+                    continue;
+                };
+                if rng.gen::<f32>() >= 1.0 - d as f32 / (FAN_REACH + 1) as f32 {
+                    continue;
+                }
+                let (to_x, to_y) = (x as isize + dx, y as isize + dy);
+                if !next.in_bounds(to_x, to_y) {
+                    continue;
+                }
+                let (to_x, to_y) = (to_x as usize, to_y as usize);
+                let open = next.get(to_x, to_y).is_none()
+                    && !next.is_frozen(x, y)
+                    && !next.is_frozen(to_x, to_y);
+                if open {
+                    next.set(x, y, None);
+                    next.set(to_x, to_y, Some(cell));
+                    stats.moved += 1;
+                }
+            }
+        }
+    }
+
+    /// Carries the cells moving into a portal out of the other portal of its pair, on the side
+    /// they keep moving towards
+    fn teleport(&self, next: &mut Grid, stats: &mut TickStats) {
+        for (a, b) in self.portal_pairs() {
+            for ((px, py), (qx, qy)) in [(a, b), (b, a)] {
+                if next.get(px, py) != self.get(px, py) || next.get(qx, qy) != self.get(qx, qy) {
+                    continue;
+                }
+                for (dx, dy) in [(0, 1), (0, -1), (-1, 0), (1, 0)] {
+                    let from = (px as isize - dx, py as isize - dy);
+                    let to = (qx as isize + dx, qy as isize + dy);
+                    if !next.in_bounds(from.0, from.1) || !next.in_bounds(to.0, to.1) {
+                        continue;
+                    }
+                    let from = (from.0 as usize, from.1 as usize);
+                    let to = (to.0 as usize, to.1 as usize);
+                    let Some(cell) = next.get(from.0, from.1) else {
+                        continue;
+                    };
+                    let open = next.get(to.0, to.1).is_none()
+                        && !next.is_frozen(from.0, from.1)
+                        && !next.is_frozen(to.0, to.1);
+                    if open && cell.enters_portal((dx, dy)) {
+                        next.set(from.0, from.1, None);
+                        next.set(to.0, to.1, Some(cell));
+                        stats.moved += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lets the liquid at the position, about to flow aside to `to`, pick up the powder it runs
+    /// over and carry it there, sinking into the hole it leaves. Returns whether it did.
+    fn erode(
+        &self,
+        next: &mut Grid,
+        x: usize,
+        y: usize,
+        to: usize,
+        untouched: &impl Fn(&Grid, usize, usize) -> bool,
+        rng: &mut impl Rng,
+    ) -> bool {
+        let liquid = self.get(x, y);
+        let Some(bed) = self.cell_at(x as isize, y as isize + 1) else {
+            return false;
+        };
+        let carried = liquid.is_some_and(|liquid| liquid.carries(bed))
+            && untouched(next, x, y + 1)
+            && rng.gen::<f32>() < EROSION_CHANCE;
+        if carried {
+            next.set(x, y, next.get(to, y));
+            next.set(to, y, Some(bed));
+            next.set(x, y + 1, liquid);
+        }
+        carried
+    }
+
+    /// Whether the piece of cells of one kind joined side by side at the position is held in
+    /// place, by a rigid cell of another kind or by being more than [`RAFT_SIZE`] cells
+    fn held(&self, x: usize, y: usize) -> bool {
+        let Some(cell) = self.get(x, y) else {
+            return false;
+        };
+        let mut piece = vec![(x, y)];
+        let mut queue = vec![(x, y)];
+        while let Some((x, y)) = queue.pop() {
+            for (nx, ny) in self.adjacent(x, y) {
+                match self.get(nx, ny) {
+                    Some(c) if c.id == cell.id && !piece.contains(&(nx, ny)) => {
+                        piece.push((nx, ny));
+                        queue.push((nx, ny));
+                    }
+                    Some(c) if c.id != cell.id && c.material() == Material::Rigid => return true,
+                    _ => (),
+                }
+            }
+            if piece.len() > RAFT_SIZE {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Bursts the weak walls of the sealed pockets packed with gas, letting the gas out
+    fn burst_pockets(
+        &self,
+        next: &mut Grid,
+        untouched: &impl Fn(&Grid, usize, usize) -> bool,
+        rng: &mut impl Rng,
+        stats: &mut TickStats,
+    ) {
+        let mut visits = vec![Visit::Unseen; self.tiles.len()];
+        for (x, y, cell) in self.iter() {
+            let gas = cell.is_some_and(|c| c.material() == Material::Gas);
+            if !gas || visits[self.index(x, y)] != Visit::Unseen {
+                continue;
+            }
+            let Some(pocket) = self.pocket(x, y, &mut visits) else {
+                continue;
+            };
+            if (pocket.gas as f32) < pocket.area as f32 * BURST_PRESSURE {
+                continue;
+            }
+            for (wx, wy) in pocket.walls {
+                let Some(wall) = self.get(wx, wy).filter(|c| c.gives_way()) else {
+                    continue;
+                };
+                if untouched(next, wx, wy) && rng.gen::<f32>() < BURST_CHANCE {
+                    next.set(wx, wy, None);
+                    stats.destroyed.add(wall.id);
+                    stats.reactions += 1;
+                    stats.events.push(SimEvent::Burst {
+                        x: wx,
+                        y: wy,
+                        wall: wall.id,
+                    });
+                }
+            }
+        }
+    }
+
+    /// The pocket of empty and gas positions connected to the position, unless it reaches the
+    /// edge of the world or spans more than [`MAX_POCKET`] positions and so is open air. Marks
+    /// the positions visited on the way.
+    fn pocket(&self, x: usize, y: usize, visits: &mut [Visit]) -> Option<Pocket> {
+        let open = |cell: Option<Cell>| cell.is_none_or(|c| c.material() == Material::Gas);
+        let mut pocket = Pocket {
+            area: 0,
+            gas: 0,
+            walls: Vec::new(),
+        };
+        let mut visited = vec![(x, y)];
+        let mut queue = vec![(x, y)];
+        visits[self.index(x, y)] = Visit::Sealed;
+        let mut sealed = true;
+        while let Some((x, y)) = queue.pop() {
+            pocket.area += 1;
+            if self.get(x, y).is_some() {
+                pocket.gas += 1;
+            }
+            let edge = x == 0 || y == 0 || x == self.width - 1 || y == self.height - 1;
+            if edge || pocket.area > MAX_POCKET {
+                sealed = false;
+                break;
+            }
+            for (nx, ny) in self.adjacent(x, y) {
+                if !open(self.get(nx, ny)) {
+                    pocket.walls.push((nx, ny));
+                    continue;
+                }
+                match visits[self.index(nx, ny)] {
+                    Visit::Unseen => {
+                        visits[self.index(nx, ny)] = Visit::Sealed;
+                        visited.push((nx, ny));
+                        queue.push((nx, ny));
+                    }
+                    Visit::Open => sealed = false,
+                    Visit::Sealed => (),
+                }
+            }
+        }
+        if !sealed {
+            // What's left of the pocket joins open air too, so it isn't searched again
+            for (x, y) in visited {
+                visits[self.index(x, y)] = Visit::Open;
+            }
+            return None;
+        }
+        pocket.walls.sort_unstable();
+        pocket.walls.dedup();
+        Some(pocket)
+    }
+
+    /// The splash of a cell falling from above into the liquid at the position, unless it was
+    /// already sinking through it
+    fn splash(&self, x: usize, y: usize, falling: Cell) -> Option<SimEvent> {
+        let liquid = self.get(x, y).filter(|c| c.is_liquid())?;
+        let sinking = y > 1 && self.get(x, y - 2).is_some_and(|c| c.id == liquid.id);
+        (!sinking && !falling.is_liquid()).then_some(SimEvent::Splashed {
+            x,
+            y,
+            liquid: liquid.id,
+        })
+    }
+
+    /// Blasts away what is around the position, more surely the closer it is, leaving flames in
+    /// some of the space it clears. Only indestructible cells withstand it, and the position
+    /// itself is left to the caller.
+    #[allow(clippy::too_many_arguments)]
+    fn explode(
+        &self,
+        next: &mut Grid,
+        x: usize,
+        y: usize,
+        radius: isize,
+        untouched: &impl Fn(&Grid, usize, usize) -> bool,
+        rng: &mut impl Rng,
+        stats: &mut TickStats,
+    ) {
+        stats.reactions += 1;
+        stats.events.push(SimEvent::Exploded { x, y });
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                let reach = ((dx * dx + dy * dy) as f32).sqrt() / (radius + 1) as f32;
+                let (bx, by) = (x as isize + dx, y as isize + dy);
+                if reach >= 1.0 || !self.in_bounds(bx, by) {
+                    continue;
+                }
+                let (bx, by) = (bx as usize, by as usize);
+                let blasted = self.get(bx, by);
+                if (bx, by) == (x, y)
+                    || blasted.is_some_and(|c| c.indestructible())
+                    || !untouched(next, bx, by)
+                    || rng.gen::<f32>() < reach
+                {
+                    continue;
+                }
+                if let Some(blasted) = blasted {
+                    stats.destroyed.add(blasted.id);
+                    stats.events.push(SimEvent::Destroyed {
+                        x: bx,
+                        y: by,
+                        id: blasted.id,
+                    });
+                }
+                let flame = rng.gen::<f32>() < BLAST_FLAMES;
+                next.set(bx, by, flame.then_some(Cell::new(CellId::Fire)));
+                if flame {
+                    stats.created.add(CellId::Fire);
+                }
+            }
+        }
+    }
+
+    /// Drops a clump of meteor at the top of the grid over the column
+    pub fn drop_meteor(&mut self, x: usize) {
+        for dx in -METEOR_SIZE..=METEOR_SIZE {
+            for dy in -METEOR_SIZE..=METEOR_SIZE {
+                let (mx, my) = (x as isize + dx, METEOR_SIZE + dy);
+                if dx * dx + dy * dy <= METEOR_SIZE.pow(2) && self.in_bounds(mx, my) {
+                    self.spawn(mx as usize, my as usize, CellId::Meteor);
+                }
+            }
+        }
+    }
+
+    /// Bursts the lit firework at the position into a ball of sparks of one color. The sparks
+    /// further out burn out sooner, so the ball shrinks as it fades.
+    fn burst(
+        &self,
+        next: &mut Grid,
+        x: usize,
+        y: usize,
+        untouched: &impl Fn(&Grid, usize, usize) -> bool,
+        rng: &mut impl Rng,
+        stats: &mut TickStats,
+    ) {
+        let tint = *FIREWORK_COLORS.choose(rng).unwrap();
+        let lifespan = CellId::Spark.data().lifespan.unwrap();
+        stats.destroyed.add(CellId::Rocket);
+        stats.reactions += 1;
+        stats.events.push(SimEvent::Ignited { x, y });
+
+        for dx in -BURST_RADIUS..=BURST_RADIUS {
+            for dy in -BURST_RADIUS..=BURST_RADIUS {
+                let reach = ((dx * dx + dy * dy) as f32).sqrt() / (BURST_RADIUS + 1) as f32;
+                let (sx, sy) = (x as isize + dx, y as isize + dy);
+                if reach >= 1.0 || !self.in_bounds(sx, sy) {
+                    continue;
+                }
+                let (sx, sy) = (sx as usize, sy as usize);
+                // The firework itself always turns into a spark, and half the open space around
+                let center = (sx, sy) == (x, y);
+                let open = self.get(sx, sy).is_none() && untouched(next, sx, sy);
+                let sparks = center || open && rng.gen_bool(0.5);
+                if !sparks {
+                    continue;
+                }
+                let life = (lifespan.roll(rng) as f32 * (1.0 - reach)).max(1.0) as u16;
+                next.set(
+                    sx,
+                    sy,
+                    Some(Cell {
+                        life: Some(life),
+                        tint: Some(tint),
+                        ..Cell::new(CellId::Spark)
+                    }),
+                );
+                stats.created.add(CellId::Spark);
+            }
+        }
+    }
+
+    /// Which way the wind blows at the position, away from the cells of wind within reach and
+    /// along the fans facing it, as -1, 0 or 1 along each axis
+    fn wind_at(&self, x: usize, y: usize) -> IVec2 {
+        self.neighbors_within(x, y, WIND_REACH)
+            .into_iter()
+            .filter_map(|(wx, wy)| {
+                let cell = self.get(wx, wy)?;
+                let away = IVec2::new(x as i32 - wx as i32, y as i32 - wy as i32).signum();
+                if cell.id == CellId::Wind {
+                    return Some(away);
+                }
+                let (dx, dy) = cell.facing()?.offset();
+                // Only straight in front of the fan
+                let ahead = IVec2::new(dx as i32, dy as i32);
+                (away == ahead).then_some(ahead)
+            })
+            .sum::<IVec2>()
+            .signum()
+    }
+
+    /// Whether the cell at the position is a liquid the flame sets alight, floating on a heavier
+    /// liquid
+    fn floats_alight(&self, x: usize, y: usize, flame: Cell) -> bool {
+        let Some(fuel) = self.get(x, y) else {
+            return false;
+        };
+        fuel.material() == Material::Liquid
+            && fuel.catches_from(flame)
+            && self
+                .cell_at(x as isize, y as isize + 1)
+                .is_some_and(|under| {
+                    under.material() == Material::Liquid && under.density() > fuel.density()
+                })
+    }
+
+    /// Sets the cell at the position alight if the flame is hot enough to, turning it into what it
+    /// burns into. Returns whether it caught.
+    fn ignite(
+        &self,
+        next: &mut Grid,
+        x: usize,
+        y: usize,
+        flame: Cell,
+        untouched: &impl Fn(&Grid, usize, usize) -> bool,
+        stats: &mut TickStats,
+    ) -> bool {
+        let Some(fuel) = self.get(x, y).filter(|fuel| fuel.catches_from(flame)) else {
+            return false;
+        };
+        if !untouched(next, x, y) {
+            return false;
+        }
+        let id = fuel.burns_into();
+        next.set(x, y, Some(Cell::new(id)));
+        stats.destroyed.add(fuel.id);
+        stats.created.add(id);
+        stats.reactions += 1;
+        stats.events.push(SimEvent::Ignited { x, y });
+        stats.events.push(SimEvent::Destroyed { x, y, id: fuel.id });
+        true
+    }
+
+    /// Throws the liquid a falling cell displaced to (x, y) up and aside into empty positions, as
+    /// far as the cell's impact reaches. A heavy cell throws the liquid beside it as well. Returns
+    /// how many liquid cells were thrown.
+    fn throw_liquid(
+        &self,
+        next: &mut Grid,
+        x: usize,
+        y: usize,
+        falling: Cell,
+        untouched: &impl Fn(&Grid, usize, usize) -> bool,
+        rng: &mut impl Rng,
+    ) -> usize {
+        let reach = falling.impact();
+        let mut thrown = vec![(x, y)];
+        if reach > 1 {
+            thrown.extend(
+                [x.wrapping_sub(1), x + 1]
+                    .into_iter()
+                    .filter(|&sx| {
+                        sx < self.width
+                            && self.get(sx, y + 1).is_some_and(|c| c.is_liquid())
+                            && untouched(next, sx, y + 1)
+                    })
+                    .map(|sx| (sx, y + 1)),
+            );
+        }
+
+        let mut count = 0;
+        for (lx, ly) in thrown {
+            let targets: Vec<_> = self
+                .neighbors_within(lx, ly, reach)
+                .into_iter()
+                .filter(|&(tx, ty)| {
+                    ty <= ly && self.get(tx, ty).is_none() && untouched(next, tx, ty)
+                })
+                .collect();
+            if let Some(&(tx, ty)) = targets.choose(rng) {
+                next.set(tx, ty, next.get(lx, ly));
+                next.set(lx, ly, None);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Whether a wall is beside or above the position, for sticky cells to cling to
+    fn clings(&self, x: usize, y: usize) -> bool {
+        let wall = |x: isize, y: isize| self.cell_at(x, y).is_some_and(|c| c.is_wall());
+        let (x, y) = (x as isize, y as isize);
+        wall(x - 1, y) || wall(x + 1, y) || wall(x, y - 1)
+    }
+
+    /// Counts the cells of each kind
+    pub fn census(&self) -> Census {
+        let mut census = Census::default();
+        for cell in self.tiles.iter().filter_map(|tile| tile.unpack()) {
+            census.add(cell.id);
+        }
+        census
+    }
+
+    pub fn adjacent(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut ids = Vec::new();
+        if x > 0 {
+            ids.push((x - 1, y));
+        }
+        if x < self.width - 1 {
+            ids.push((x + 1, y));
+        }
+        if y > 0 {
+            ids.push((x, y - 1));
+        }
+        if y < self.height - 1 {
+            ids.push((x, y + 1));
+        }
+        ids
+    }
+
+    pub fn neighbors(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        self.neighbors_within(x, y, 1)
+    }
+
+    pub fn neighbors_within(&self, x: usize, y: usize, n: usize) -> Vec<(usize, usize)> {
+        let mut ids = Vec::new();
+        for dx in -(n as isize)..=n as isize {
+            for dy in -(n as isize)..=n as isize {
+                let nx = x as isize + dx;
+                let ny = y as isize + dy;
+
+                if !self.in_bounds(nx, ny) {
+                    continue;
+                }
+
+                let nx = nx as usize;
+                let ny = ny as usize;
+
+                if !(dx == 0 && dy == 0) {
+                    ids.push((nx, ny));
+                }
+            }
+        }
+        ids
+    }
+}
+
+// Integer positions of every cell the box touches, a cell at (x, y) covering [x, x + 1) × [y, y + 1)
+fn aabb_positions(aabb: Aabb) -> impl Iterator<Item = (isize, isize)> {
+    let (x0, x1) = (aabb.min.x.floor() as isize, aabb.max.x.ceil() as isize);
+    let (y0, y1) = (aabb.min.y.floor() as isize, aabb.max.y.ceil() as isize);
+    (x0..x1).flat_map(move |x| (y0..y1).map(move |y| (x, y)))
+}
+
+/// Something that happened at a position during a tick, for effects beyond the grid to follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::event::Event))]
+pub enum SimEvent {
+    /// Fire spread to a flammable cell
+    Ignited { x: usize, y: usize },
+    /// A cell fell into a liquid, where the liquid was
+    Splashed { x: usize, y: usize, liquid: CellId },
+    /// Powder came to rest after moving
+    Landed { x: usize, y: usize },
+    /// Antimatter annihilated with what it touched
+    Annihilated { x: usize, y: usize },
+    /// Something blew up, blasting a crater around where it was
+    Exploded { x: usize, y: usize },
+    /// A sealed pocket of gas burst through a weak wall that held it in
+    Burst { x: usize, y: usize, wall: CellId },
+    /// A cell was burned up, dissolved, put out or blasted away
+    Destroyed { x: usize, y: usize, id: CellId },
+}
+
+/// How far the search for sealed pockets of gas has got with a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    Unseen,
+    /// In a pocket closed off from the open air, as far as the search got
+    Sealed,
+    /// Open to the air or in a pocket too big to count as sealed
+    Open,
+}
+
+/// Empty and gas positions closed off from the open air, and the cells walling them in
+struct Pocket {
+    area: usize,
+    gas: usize,
+    walls: Vec<(usize, usize)>,
+}
+
+/// What happened on the grid during a tick
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TickStats {
+    /// Cells that moved to another position, not counting the ones they swapped places with
+    pub moved: usize,
+    /// Cells of each kind that appeared, like flames spreading
+    pub created: Census,
+    /// Cells of each kind that disappeared, by burning, dissolving or running out of life
+    pub destroyed: Census,
+    /// Fires lit or put out, cells dissolved by acid and water lathered into foam
+    pub reactions: usize,
+    /// Cells acid ate through, not counting the acid
+    pub dissolved: usize,
+    pub events: Vec<SimEvent>,
+}
+
+impl TickStats {
+    /// Counts acid dissolving the cell at the position, and returns the acid gas the reaction
+    /// gives off, if any
+    fn dissolve(
+        &mut self,
+        acid: Cell,
+        other: Option<Cell>,
+        (x, y): (usize, usize),
+        rng: &mut impl Rng,
+    ) -> Option<Cell> {
+        self.destroyed.add(acid.id);
+        if let Some(other) = other {
+            self.destroyed.add(other.id);
+            self.dissolved += 1;
+            self.events.push(SimEvent::Destroyed { x, y, id: other.id });
+        }
+        self.reactions += 1;
+        (rng.gen::<f32>() < ACID_FUMES).then(|| {
+            self.created.add(CellId::AcidGas);
+            Cell::new(CellId::AcidGas)
+        })
+    }
+}
+
+/// Number of cells of each kind on a grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Census([usize; CellId::ALL.len()]);
+
+// Arrays only derive a default up to 32 items
+impl Default for Census {
+    fn default() -> Self {
+        Self([0; CellId::ALL.len()])
+    }
+}
+
+impl Census {
+    pub fn count(&self, id: CellId) -> usize {
+        self.0[id as usize]
+    }
+
+    pub fn total(&self) -> usize {
+        self.0.iter().sum()
+    }
+
+    fn add(&mut self, id: CellId) {
+        self.0[id as usize] += 1;
+    }
+
+    /// The kinds whose count changed between this census and one taken a tick later, even though
+    /// nothing on the grid could have made them react
+    pub fn unexplained_changes(&self, after: &Census) -> Vec<CellId> {
+        CellId::ALL
+            .into_iter()
+            .filter(|&id| self.count(id) != after.count(id) && !self.may_react(id))
+            .collect()
+    }
+
+    /// Whether cells of a kind can appear or disappear during a tick starting from this census
+    fn may_react(&self, id: CellId) -> bool {
+        let data = id.data();
+        let present: Vec<Cell> = CellId::ALL
+            .into_iter()
+            .filter(|&id| self.count(id) > 0)
+            .map(Cell::new)
+            .collect();
+        let destroyers = [
+            CellId::Acid,
+            CellId::MoltenMetal,
+            CellId::GravityWell,
+            CellId::Heater,
+            CellId::Cooler,
+            CellId::Meteor,
+            CellId::Antimatter,
+            CellId::Nitroglycerin,
+        ];
+        data.lifespan.is_some()
+            || destroyers.into_iter().any(|id| self.count(id) > 0) && id != CellId::Barrier
+            || data.flammable && present.iter().any(|c| c.ignites())
+            || present.iter().any(|c| c.remains() == Some(id))
+            || matches!(id, CellId::Acid | CellId::AcidGas) && self.count(CellId::AcidGas) > 0
+            || id == CellId::Water && self.count(CellId::Soap) > 0
+            || id == CellId::Wood && self.count(CellId::Mold) > 0
+            || id == CellId::Wood && present.iter().any(|c| c.material() == Material::Gas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::SLEEP_TICKS;
+
+    fn cell(id: CellId) -> Option<Cell> {
+        Some(Cell::new(id))
+    }
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(7)
+    }
+
+    #[test]
+    fn sand_falls_one_cell_per_tick() {
+        let mut grid = Grid::new(3, 4);
+        grid.set(1, 0, cell(CellId::Sand));
+        grid.step(&mut rng());
+        assert_eq!(grid.get(1, 0), None);
+        assert_eq!(grid.get(1, 1), cell(CellId::Sand));
+    }
+
+    #[test]
+    fn stats_count_moves_and_reactions() {
+        let mut grid = Grid::new(2, 2);
+        grid.set(0, 0, cell(CellId::Sand));
+        grid.set(1, 0, cell(CellId::Acid));
+        grid.set(1, 1, cell(CellId::Stone));
+        let stats = grid.step(&mut rng());
+        assert_eq!(stats.moved, 1);
+        assert_eq!(stats.reactions, 1);
+        assert_eq!(stats.destroyed.count(CellId::Acid), 1);
+        assert_eq!(stats.destroyed.count(CellId::Stone), 1);
+        // At most the acid gas the reaction gives off
+        assert_eq!(stats.created.total(), stats.created.count(CellId::AcidGas));
+    }
+
+    #[test]
+    fn events_follow_what_cells_do() {
+        let mut grid = Grid::new(3, 4);
+        grid.set(0, 0, cell(CellId::Sand));
+        grid.set(1, 1, cell(CellId::Stone));
+        grid.set(1, 2, cell(CellId::Water));
+        grid.set(1, 3, cell(CellId::Bedrock));
+        let mut events = Vec::new();
+        for _ in 0..6 {
+            events.extend(grid.step(&mut rng()).events);
+        }
+        assert!(events.contains(&SimEvent::Landed { x: 0, y: 3 }));
+        assert!(events.contains(&SimEvent::Splashed {
+            x: 1,
+            y: 2,
+            liquid: CellId::Water
+        }));
+
+        let mut grid = Grid::new(1, 2);
+        grid.set(0, 0, cell(CellId::Fire));
+        grid.set(0, 1, cell(CellId::Wood));
+        let mut rng = rng();
+        let ignited = (0..50).any(|_| {
+            grid.set(0, 0, cell(CellId::Fire));
+            let events = grid.step(&mut rng).events;
+            events.contains(&SimEvent::Ignited { x: 0, y: 1 })
+                && events.contains(&SimEvent::Destroyed {
+                    x: 0,
+                    y: 1,
+                    id: CellId::Wood,
+                })
+        });
+        assert!(ignited);
+
+        let mut grid = Grid::new(1, 2);
+        grid.set(0, 0, cell(CellId::Acid));
+        grid.set(0, 1, cell(CellId::Stone));
+        let events = grid.step(&mut rng).events;
+        assert_eq!(
+            events,
+            vec![SimEvent::Destroyed {
+                x: 0,
+                y: 1,
+                id: CellId::Stone
+            }]
+        );
+    }
+
+    #[test]
+    fn falling_cells_splash_liquid_aside() {
+        for (id, thrown) in [(CellId::Sand, 1), (CellId::Stone, 3)] {
+            let mut grid = Grid::new(5, 8);
+            for x in 0..5 {
+                for y in 6..8 {
+                    grid.set(x, y, cell(CellId::Water));
+                }
+            }
+            grid.set(2, 0, cell(id));
+            let mut rng = rng();
+            while grid.step(&mut rng).events.is_empty() {}
+            // Instead of only swapping places with the faller, the water was thrown clear of it
+            assert_eq!(grid.get(2, 6), cell(id));
+            assert_ne!(grid.get(2, 5), cell(CellId::Water));
+            let above = (0..5)
+                .flat_map(|x| (0..6).map(move |y| (x, y)))
+                .filter(|&(x, y)| grid.get(x, y) == cell(CellId::Water))
+                .count();
+            assert_eq!(above, thrown);
+            assert_eq!(grid.census().count(CellId::Water), 10);
+        }
+    }
+
+    #[test]
+    fn dye_tints_water_and_dyed_waters_blend() {
+        let red = CellId::RedDye.data().color;
+        let blue = CellId::BlueDye.data().color;
+        let mut grid = Grid::new(4, 1);
+        grid.set(0, 0, cell(CellId::RedDye));
+        grid.set(1, 0, cell(CellId::Water));
+        grid.set(2, 0, cell(CellId::Water));
+        grid.set(3, 0, cell(CellId::BlueDye));
+        let mut rng = rng();
+        grid.step(&mut rng);
+        assert_eq!(grid.get(1, 0).unwrap().tint, Some(red));
+        assert_eq!(grid.get(2, 0).unwrap().tint, Some(blue));
+
+        // Away from the dye, the two waters settle on a color between them and keep it
+        grid.set(0, 0, None);
+        grid.set(3, 0, None);
+        for _ in 0..20 {
+            grid.step(&mut rng);
+        }
+        let tint = grid.get(1, 0).unwrap().tint.unwrap();
+        assert_eq!(grid.get(2, 0).unwrap().tint, Some(tint));
+        assert!((0..3).all(|i| tint[i].clamp(red[i].min(blue[i]), red[i].max(blue[i])) == tint[i]));
+        assert_ne!(tint, red);
+        assert_ne!(tint, blue);
+    }
+
+    #[test]
+    fn soap_lathers_moving_water_into_foam_that_floats_and_pops() {
+        let mut grid = Grid::new(3, 8);
+        for x in 0..3 {
+            grid.set(x, 7, cell(CellId::Soap));
+        }
+        // Pouring water keeps it moving around the soap
+        let mut rng = rng();
+        for _ in 0..12 {
+            grid.spawn(1, 0, CellId::Water);
+            grid.step(&mut rng);
+        }
+        for _ in 0..20 {
+            grid.step(&mut rng);
+        }
+        assert!(grid.census().count(CellId::Foam) > 0);
+        // Once the water settles, the foam sits on top of it
+        for x in 0..3 {
+            let column: Vec<_> = (0..8).filter_map(|y| grid.get(x, y)).collect();
+            assert!(column
+                .windows(2)
+                .all(|pair| pair[0].id == CellId::Foam || pair[1].id != CellId::Foam));
+        }
+
+        for _ in 0..CellId::Foam.data().lifespan.unwrap().max {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.census().count(CellId::Foam), 0);
+    }
+
+    #[test]
+    fn gel_clings_to_walls_and_holds_powder() {
+        // Gel on a ledge, against a wall and under a ceiling, and a pool with sand in and on it
+        let mut grid = Grid::new(7, 8);
+        grid.set(1, 3, cell(CellId::Bedrock));
+        grid.set(1, 2, cell(CellId::Gel));
+        grid.set(5, 1, cell(CellId::Bedrock));
+        grid.set(4, 1, cell(CellId::Gel));
+        grid.set(5, 2, cell(CellId::Gel));
+        for x in 0..7 {
+            grid.set(x, 6, cell(CellId::Gel));
+            grid.set(x, 7, cell(CellId::Gel));
+        }
+        grid.set(3, 6, cell(CellId::Sand));
+        grid.set(5, 5, cell(CellId::Sand));
+        let before = grid.clone();
+        let mut rng = rng();
+        for _ in 0..50 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid, before);
+
+        // Water flows right off the same ledge
+        let mut grid = Grid::new(3, 8);
+        grid.set(1, 3, cell(CellId::Bedrock));
+        grid.set(1, 2, cell(CellId::Water));
+        for _ in 0..10 {
+            grid.step(&mut rng);
+        }
+        assert_ne!(grid.get(1, 2), cell(CellId::Water));
+    }
+
+    #[test]
+    fn liquids_layer_by_density() {
+        let layers = [
+            CellId::Mercury,
+            CellId::Gel,
+            CellId::Water,
+            CellId::Oil,
+            CellId::Foam,
+        ];
+        let mut grid = Grid::new(1, layers.len());
+        for (y, &id) in layers.iter().enumerate() {
+            grid.set(0, y, cell(id));
+        }
+        let mut rng = rng();
+        for _ in 0..20 {
+            grid.step(&mut rng);
+        }
+        for (y, &id) in layers.iter().rev().enumerate() {
+            assert_eq!(grid.get(0, y).map(|c| c.id), Some(id));
+        }
+    }
+
+    #[test]
+    fn density_decides_what_sinks() {
+        let sand = cell(CellId::Sand).unwrap();
+        let stone = cell(CellId::Stone).unwrap();
+        assert!(sand.sinks_under(cell(CellId::Water)));
+        assert!(sand.sinks_under(cell(CellId::Oxygen)));
+        assert!(!sand.sinks_under(cell(CellId::Mercury)));
+        assert!(!stone.sinks_under(cell(CellId::Mercury)));
+        assert!(cell(CellId::Mercury)
+            .unwrap()
+            .sinks_under(cell(CellId::Water)));
+        // Only liquids and gases make way
+        assert!(!stone.sinks_under(cell(CellId::Sand)));
+        assert!(!cell(CellId::Metal)
+            .unwrap()
+            .sinks_under(cell(CellId::Water)));
+    }
+
+    #[test]
+    fn gases_layer_by_density() {
+        // Mixed gases in a sealed box sort themselves, the lightest on top
+        let layers = [
+            CellId::Hydrogen,
+            CellId::Steam,
+            CellId::Oxygen,
+            CellId::ToxicGas,
+        ];
+        let mut grid = Grid::new(3, layers.len() + 1);
+        for x in 0..3 {
+            for (y, &id) in layers.iter().rev().enumerate() {
+                grid.set(x, y, cell(id));
+            }
+            grid.set(x, layers.len(), cell(CellId::Bedrock));
+        }
+        let mut rng = rng();
+        for _ in 0..200 {
+            grid.step(&mut rng);
+        }
+        for x in 0..3 {
+            for (y, &id) in layers.iter().enumerate() {
+                assert_eq!(grid.get(x, y).map(|c| c.id), Some(id));
+            }
+        }
+    }
+
+    #[test]
+    fn each_cell_gets_its_own_life() {
+        let mut grid = Grid::new(20, 2);
+        for x in 0..20 {
+            grid.spawn(x, 0, CellId::Wind);
+        }
+        grid.step(&mut rng());
+        let lifespan = CellId::Wind.data().lifespan.unwrap();
+        let lives: Vec<_> = (0..20)
+            .map(|x| grid.get(x, 0).unwrap().life.unwrap())
+            .collect();
+        assert!(lives
+            .iter()
+            .all(|&life| (lifespan.min - 1..lifespan.max).contains(&life)));
+        assert!(lives.iter().any(|&life| life != lives[0]));
+    }
+
+    #[test]
+    fn fire_lasts_only_while_it_has_fuel() {
+        let lifespan = CellId::Fire.data().lifespan.unwrap();
+        let mut rng = rng();
+
+        // On the bottom row, where flames neither rise nor spread
+        let mut grid = Grid::new(1, 1);
+        grid.spawn(0, 0, CellId::Fire);
+        for _ in 0..lifespan.max.div_ceil(STARVED_BURN) {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(0, 0), None);
+
+        // Frozen wood feeds the fire without ever catching
+        let mut grid = Grid::new(2, 1);
+        grid.spawn(0, 0, CellId::Fire);
+        grid.spawn(1, 0, CellId::Wood);
+        grid.set_frozen(1..2, 0..1, true);
+        for _ in 0..lifespan.max * 2 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(0, 0).map(|c| c.id), Some(CellId::Fire));
+    }
+
+    #[test]
+    fn wind_blows_away_from_itself() {
+        let mut grid = Grid::new(10, 10);
+        assert_eq!(grid.wind_at(5, 5), IVec2::ZERO);
+        grid.spawn(3, 5, CellId::Wind);
+        assert_eq!(grid.wind_at(5, 5), IVec2::new(1, 0));
+        grid.spawn(3, 2, CellId::Wind);
+        assert_eq!(grid.wind_at(5, 5), IVec2::new(1, 1));
+        // Out of reach
+        assert_eq!(grid.wind_at(9, 5), IVec2::ZERO);
+    }
+
+    #[test]
+    fn wind_bends_and_snuffs_flames() {
+        let (mut bent, mut snuffed, mut calm_snuffed) = (0, 0, 0);
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut grid = Grid::new(20, 20);
+            grid.spawn(10, 10, CellId::Fire);
+            let mut calm = grid.clone();
+            grid.spawn(8, 10, CellId::Wind);
+            grid.step(&mut rng);
+            calm.step(&mut StdRng::seed_from_u64(seed));
+
+            let flames: Vec<_> = grid
+                .iter()
+                .filter(|(_, _, c)| c.is_some_and(|c| c.id == CellId::Fire))
+                .map(|(x, _, _)| x)
+                .collect();
+            // Never blown back towards the wind
+            assert!(flames.iter().all(|&x| x >= 10));
+            bent += flames.iter().filter(|&&x| x > 10).count();
+            snuffed += flames.is_empty() as usize;
+            calm_snuffed += (calm.census().count(CellId::Fire) == 0) as usize;
+        }
+        assert!(bent > 0);
+        assert!(snuffed > calm_snuffed);
+    }
+
+    #[test]
+    fn fire_spreads_downwind() {
+        let (mut downwind, mut upwind) = (0, 0);
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut grid = Grid::new(30, 20);
+            for x in 0..30 {
+                grid.spawn(x, 10, CellId::Wood);
+                grid.spawn(x, 11, CellId::Stone);
+            }
+            grid.replace(15, 10, CellId::Fire);
+            grid.spawn(13, 8, CellId::Wind);
+            grid.step(&mut rng);
+            let burnt = |x| grid.get(x, 10).is_none_or(|c| c.id != CellId::Wood) as usize;
+            downwind += burnt(16);
+            upwind += burnt(14);
+        }
+        assert!(
+            downwind > upwind * 2,
+            "{downwind} downwind, {upwind} upwind"
+        );
+    }
+
+    #[test]
+    fn water_puts_out_flames_and_oil_burns_up_in_them() {
+        for (falling, left) in [(CellId::Water, CellId::Water), (CellId::Oil, CellId::Fire)] {
+            let mut grid = Grid::new(5, 5);
+            grid.spawn(2, 3, falling);
+            grid.spawn(2, 4, CellId::Fire);
+            let stats = grid.step_in_order([(2, 3)], &mut StdRng::seed_from_u64(1));
+            assert_eq!(stats.reactions, 1);
+            assert_eq!(grid.get(2, 3), None);
+            assert_eq!(grid.get(2, 4).map(|c| c.id), Some(left));
+        }
+        let (sand, water) = (cell(CellId::Sand).unwrap(), cell(CellId::Water).unwrap());
+        assert!(ContactRule::landing(sand, water).is_none());
+    }
+
+    #[test]
+    fn spread_chances_can_be_tuned() {
+        // Wood burnt next to a flame in a tick, over many worlds
+        let burnt = |chance: f32| {
+            (0..100)
+                .map(|seed| {
+                    let mut grid = Grid::new(30, 20);
+                    grid.set_spread_chance(CellId::Wood, chance);
+                    for x in 0..30 {
+                        grid.spawn(x, 10, CellId::Wood);
+                    }
+                    grid.replace(15, 10, CellId::Fire);
+                    grid.step(&mut StdRng::seed_from_u64(seed));
+                    30 - grid.census().count(CellId::Wood)
+                })
+                .sum::<usize>()
+        };
+        let built_in = burnt(CellId::Wood.data().spread);
+        assert!(burnt(0.0) < built_in);
+        assert!(burnt(1.0) > built_in);
+    }
+
+    #[test]
+    fn tuned_chances_change_the_registry_hash() {
+        let mut grid = Grid::new(1, 1);
+        let built_in = grid.registry_hash();
+        assert_eq!(Grid::new(2, 2).registry_hash(), built_in);
+        grid.set_spread_chance(CellId::Wood, 1.0);
+        assert_ne!(grid.registry_hash(), built_in);
+    }
+
+    #[test]
+    fn fire_runs_along_oil_slicks() {
+        // A slick of oil one cell thick on a long, shallow pool, lit at one end
+        let mut grid = Grid::new(40, 8);
+        for x in 0..40 {
+            grid.spawn(x, 7, CellId::Stone);
+            grid.spawn(x, 6, CellId::Water);
+            grid.spawn(x, 5, CellId::Oil);
+        }
+        grid.set(0, 5, cell(CellId::Fire));
+        let mut rng = rng();
+        for _ in 0..60 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.census().count(CellId::Oil), 0);
+        assert_eq!(grid.census().count(CellId::Water), 40);
+
+        // Oil on the ground is a puddle that burns from cell to cell
+        let mut grid = Grid::new(2, 3);
+        grid.spawn(0, 2, CellId::Stone);
+        grid.spawn(0, 1, CellId::Oil);
+        grid.spawn(1, 2, CellId::Water);
+        grid.spawn(1, 1, CellId::Oil);
+        let flame = cell(CellId::Fire).unwrap();
+        assert!(!grid.floats_alight(0, 1, flame));
+        assert!(grid.floats_alight(1, 1, flame));
+    }
+
+    #[test]
+    fn cells_count_how_long_they_fall() {
+        let mut grid = Grid::new(3, 20);
+        grid.spawn(1, 0, CellId::Sand);
+        let mut rng = rng();
+        for _ in 0..5 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.fall(1, 5), 5);
+        for _ in 0..20 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(1, 19).unwrap().id, CellId::Sand);
+        assert_eq!(grid.fall(1, 19), 0);
+    }
+
+    #[test]
+    fn nitroglycerin_goes_off_when_something_lands_on_it_hard() {
+        let pool = || {
+            let mut grid = Grid::new(30, 30);
+            for y in 25..30 {
+                grid.spawn(9, y, CellId::Metal);
+                grid.spawn(20, y, CellId::Metal);
+            }
+            for x in 10..20 {
+                grid.spawn(x, 29, CellId::Metal);
+                grid.spawn(x, 28, CellId::Nitroglycerin);
+                grid.spawn(x, 27, CellId::Nitroglycerin);
+            }
+            grid
+        };
+        let settle = |grid: &mut Grid| {
+            let mut rng = rng();
+            let mut stats = TickStats::default();
+            for _ in 0..40 {
+                stats.events.extend(grid.step(&mut rng).events);
+            }
+            stats
+                .events
+                .iter()
+                .any(|event| matches!(event, SimEvent::Exploded { .. }))
+        };
+
+        // Set down gently, a stone keeps it still
+        let mut grid = pool();
+        grid.spawn(15, 26, CellId::Stone);
+        assert!(!settle(&mut grid));
+        assert_eq!(grid.census().count(CellId::Nitroglycerin), 20);
+        // Flames don't light it
+        grid.spawn(14, 26, CellId::Fire);
+        assert!(!settle(&mut grid));
+        assert_eq!(grid.census().count(CellId::Nitroglycerin), 20);
+
+        // Dropped from high above, the stone sets it off
+        let mut grid = pool();
+        grid.spawn(15, 2, CellId::Stone);
+        assert!(settle(&mut grid));
+        assert!(grid.census().count(CellId::Nitroglycerin) < 20);
+    }
+
+    /// A box of the wall's kind six cells wide inside, with the share of it given filled with gas
+    fn gas_box(wall: CellId, filled: usize) -> Grid {
+        let mut grid = Grid::new(20, 20);
+        for i in 5..13 {
+            for (x, y) in [(i, 5), (i, 12), (5, i), (12, i)] {
+                grid.spawn(x, y, wall);
+            }
+        }
+        for (x, y) in (6..12)
+            .flat_map(|y| (6..12).map(move |x| (x, y)))
+            .take(filled)
+        {
+            grid.spawn(x, y, CellId::Hydrogen);
+        }
+        grid
+    }
+
+    #[test]
+    fn gas_packed_into_a_wooden_box_bursts_it() {
+        let mut grid = gas_box(CellId::Wood, 36);
+        let mut rng = rng();
+        let burst = (0..20)
+            .flat_map(|_| grid.step(&mut rng).events)
+            .any(|event| {
+                matches!(
+                    event,
+                    SimEvent::Burst {
+                        wall: CellId::Wood,
+                        ..
+                    }
+                )
+            });
+        assert!(burst);
+        assert!(grid.census().count(CellId::Wood) < 28);
+    }
+
+    #[test]
+    fn loose_gas_and_strong_walls_hold() {
+        for mut grid in [gas_box(CellId::Wood, 12), gas_box(CellId::Stone, 36)] {
+            let walls = grid.census().total() - grid.census().count(CellId::Hydrogen);
+            let mut rng = rng();
+            for _ in 0..50 {
+                grid.step(&mut rng);
+            }
+            assert_eq!(
+                grid.census().total() - grid.census().count(CellId::Hydrogen),
+                walls
+            );
+        }
+        // Gas out in the open presses on nothing
+        let mut grid = Grid::new(10, 10);
+        for x in 0..10 {
+            grid.spawn(x, 9, CellId::Wood);
+            grid.spawn(x, 8, CellId::Hydrogen);
+        }
+        grid.step(&mut rng());
+        assert_eq!(grid.census().count(CellId::Wood), 10);
+    }
+
+    #[test]
+    fn wood_smolders_into_embers_then_ash() {
+        let mut rng = rng();
+        // A plank on a stone floor, lit in the middle
+        let mut grid = Grid::new(7, 4);
+        for x in 0..7 {
+            grid.spawn(x, 3, CellId::Stone);
+        }
+        for x in 2..5 {
+            grid.spawn(x, 2, CellId::Wood);
+        }
+        grid.replace(3, 2, CellId::Fire);
+
+        let mut smoldered = false;
+        for _ in 0..3000 {
+            grid.step(&mut rng);
+            smoldered |= grid.census().count(CellId::Ember) > 0;
+        }
+        let census = grid.census();
+        assert!(smoldered);
+        assert_eq!(census.count(CellId::Wood), 0);
+        assert_eq!(census.count(CellId::Ember), 0);
+        assert_eq!(census.count(CellId::Ash), 2);
+    }
+
+    #[test]
+    fn only_burning_metal_is_hot_enough_to_light_thermite() {
+        let mut rng = rng();
+        let mut grid = Grid::new(5, 4);
+        for x in 0..5 {
+            grid.spawn(x, 3, CellId::Stone);
+            grid.spawn(x, 2, CellId::Thermite);
+        }
+        grid.spawn(2, 1, CellId::Fire);
+        for _ in 0..100 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.census().count(CellId::Thermite), 5);
+
+        // Metal powder catches from fire, and its molten metal lights the thermite, which burns
+        // through the stone under it
+        grid.spawn(2, 1, CellId::MetalPowder);
+        grid.spawn(2, 0, CellId::Fire);
+        for _ in 0..1000 {
+            grid.step(&mut rng);
+        }
+        let census = grid.census();
+        assert_eq!(census.count(CellId::Thermite), 0);
+        assert!(census.count(CellId::Stone) < 5);
+        assert!(census.count(CellId::Metal) > 0);
+    }
+
+    #[test]
+    fn gravity_wells_swallow_what_they_pull_in() {
+        let mut rng = rng();
+        let mut grid = Grid::new(12, 1);
+        grid.spawn(0, 0, CellId::GravityWell);
+        for x in 4..8 {
+            grid.spawn(x, 0, CellId::Sand);
+        }
+        for _ in 0..200 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.census().count(CellId::Sand), 0);
+        assert_eq!(grid.census().count(CellId::GravityWell), 1);
+    }
+
+    #[test]
+    fn repulsors_push_cells_away() {
+        let mut rng = rng();
+        let mut grid = Grid::new(12, 1);
+        grid.spawn(0, 0, CellId::Repulsor);
+        grid.spawn(2, 0, CellId::Sand);
+        for _ in 0..200 {
+            grid.step(&mut rng);
+        }
+        let (x, _, _) = grid
+            .iter()
+            .find(|(_, _, c)| *c == cell(CellId::Sand))
+            .unwrap();
+        assert!(x > WELL_RADIUS as usize);
+    }
+
+    #[test]
+    fn fans_blow_along_the_way_they_face() {
+        let mut rng = rng();
+        // A wall in front of the second fan shelters what is behind it
+        let mut grids = [Grid::new(12, 1), Grid::new(12, 1)];
+        for grid in &mut grids {
+            grid.spawn(0, 0, CellId::Fan);
+            grid.face(0, 0, Facing::Right);
+            grid.spawn(2, 0, CellId::Sand);
+        }
+        grids[1].spawn(1, 0, CellId::Bedrock);
+        assert_eq!(grids[0].wind_at(3, 0), IVec2::new(1, 0));
+
+        for _ in 0..200 {
+            for grid in &mut grids {
+                grid.step(&mut rng);
+            }
+        }
+        let [grid, sheltered] = grids;
+        assert_eq!(grid.get(FAN_REACH as usize + 1, 0), cell(CellId::Sand));
+        assert_eq!(sheltered.get(2, 0), cell(CellId::Sand));
+        assert_eq!(grid.get(0, 0).unwrap().facing(), Some(Facing::Right));
+    }
+
+    #[test]
+    fn portals_pair_up_in_the_order_placed() {
+        let mut grid = Grid::new(4, 1);
+        for x in 0..4 {
+            assert!(grid.place_portal(x, 0));
+        }
+        assert!(!grid.place_portal(0, 0));
+        let tint = |x| grid.get(x, 0).unwrap().tint;
+        assert_eq!(tint(0), tint(1));
+        assert_eq!(tint(2), tint(3));
+        assert_ne!(tint(0), tint(2));
+        assert_eq!(grid.portal_pairs().len(), 2);
+    }
+
+    #[test]
+    fn cells_fall_through_portals_and_out_of_their_pair() {
+        let mut rng = rng();
+        let mut grid = Grid::new(5, 10);
+        grid.place_portal(1, 3);
+        grid.place_portal(3, 6);
+        grid.spawn(1, 0, CellId::Sand);
+        for _ in 0..20 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(3, 9), cell(CellId::Sand));
+        assert_eq!(grid.census().count(CellId::Sand), 1);
+    }
+
+    #[test]
+    fn conveyors_carry_what_rests_on_them() {
+        let mut rng = rng();
+        let mut grid = Grid::new(8, 3);
+        for x in 0..4 {
+            grid.spawn(x, 2, CellId::ConveyorRight);
+            grid.spawn(x + 4, 2, CellId::ConveyorLeft);
+        }
+        grid.spawn(0, 1, CellId::Stone);
+        grid.spawn(7, 1, CellId::Stone);
+        for _ in 0..3 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(3, 1), cell(CellId::Stone));
+        assert_eq!(grid.get(4, 1), cell(CellId::Stone));
+
+        // Meeting in the middle, both stay put
+        for _ in 0..3 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(3, 1), cell(CellId::Stone));
+        assert_eq!(grid.get(4, 1), cell(CellId::Stone));
+    }
+
+    #[test]
+    fn heaters_boil_water_and_coolers_freeze_it() {
+        let mut rng = rng();
+        for (machine, product) in [
+            (CellId::Heater, CellId::Steam),
+            (CellId::Cooler, CellId::Ice),
+        ] {
+            let mut grid = Grid::new(3, 2);
+            grid.spawn(1, 1, machine);
+            grid.spawn(0, 1, CellId::Water);
+            grid.spawn(2, 1, CellId::Water);
+            for _ in 0..200 {
+                grid.step(&mut rng);
+            }
+            assert!(grid.census().count(product) > 0);
+        }
+    }
+
+    #[test]
+    fn fuses_burn_along_themselves_and_light_what_they_lead_to() {
+        let lifespan = CellId::BurningFuse.data().lifespan.unwrap();
+        let mut rng = rng();
+        // Oil under the fuse up to its end, and wood on stone past it
+        let mut grid = Grid::new(8, 2);
+        for x in 0..6 {
+            grid.spawn(x, 0, CellId::Fuse);
+        }
+        for x in 0..5 {
+            grid.spawn(x, 1, CellId::Oil);
+        }
+        grid.spawn(6, 0, CellId::Wood);
+        grid.spawn(6, 1, CellId::Stone);
+        grid.replace(0, 0, CellId::BurningFuse);
+
+        for _ in 0..lifespan.max * 5 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(5, 0).map(|c| c.id), Some(CellId::BurningFuse));
+        assert_eq!(grid.get(6, 0).map(|c| c.id), Some(CellId::Wood));
+
+        for _ in 0..lifespan.max {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(6, 0).map(|c| c.id), Some(CellId::Ember));
+        assert_eq!(grid.census().count(CellId::Oil), 5);
+    }
+
+    #[test]
+    fn lit_fireworks_climb_and_burst_into_colored_sparks() {
+        let mut rng = rng();
+        let mut grid = Grid::new(11, 40);
+        // Lit
+        grid.spawn(5, 38, cell(CellId::Firework).unwrap().burns_into());
+
+        let mut sparks = Vec::new();
+        for _ in 0..100 {
+            grid.step(&mut rng);
+            sparks = grid
+                .iter()
+                .filter_map(|(_, y, c)| Some((y, c?)))
+                .filter(|(_, c)| c.id == CellId::Spark)
+                .collect();
+            if !sparks.is_empty() {
+                break;
+            }
+        }
+        assert!(sparks.len() > 1);
+        assert!(sparks.iter().all(|&(y, _)| y < 30));
+        assert!(sparks.iter().all(|&(_, c)| c.tint == sparks[0].1.tint));
+    }
+
+    #[test]
+    fn meteors_blast_a_crater_where_they_land() {
+        let mut rng = rng();
+        let mut grid = Grid::new(21, 40);
+        for x in 0..21 {
+            for y in 30..40 {
+                grid.spawn(x, y, CellId::Sand);
+            }
+        }
+        grid.drop_meteor(10);
+        let meteors = grid.census().count(CellId::Meteor);
+        assert!(meteors > 1);
+
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            events.extend(grid.step(&mut rng).events);
+        }
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, SimEvent::Exploded { .. })));
+        let census = grid.census();
+        assert_eq!(census.count(CellId::Meteor), 0);
+        assert!(census.count(CellId::Stone) > 0);
+        assert!(census.count(CellId::Sand) < 21 * 10 - 10);
+    }
+
+    #[test]
+    fn antimatter_annihilates_anything_but_barriers() {
+        let mut rng = rng();
+        let mut grid = Grid::new(3, 4);
+        grid.spawn(1, 0, CellId::Antimatter);
+        grid.spawn(1, 1, CellId::Antimatter);
+        grid.spawn(1, 3, CellId::Stone);
+        grid.spawn(0, 3, CellId::Barrier);
+        grid.spawn(2, 3, CellId::Antimatter);
+
+        let mut events = Vec::new();
+        for _ in 0..10 {
+            events.extend(grid.step(&mut rng).events);
+        }
+        let census = grid.census();
+        assert_eq!(census.count(CellId::Stone), 0);
+        assert_eq!(census.count(CellId::Barrier), 1);
+        assert_eq!(census.count(CellId::Antimatter), 2);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, SimEvent::Annihilated { .. })));
+    }
+
+    #[test]
+    fn mold_spreads_over_damp_wood_and_rots_it_into_dust() {
+        let mut rng = rng();
+        // Only the left column of wood stands next to water
+        let mut grid = Grid::new(4, 12);
+        for y in 0..12 {
+            grid.spawn(0, y, CellId::Wood);
+            grid.spawn(1, y, CellId::Water);
+            grid.spawn(2, y, CellId::Barrier);
+            grid.spawn(3, y, CellId::Wood);
+        }
+        grid.set(0, 0, cell(CellId::Mold));
+        grid.set(3, 0, cell(CellId::Mold));
+
+        for _ in 0..200 {
+            grid.step(&mut rng);
+        }
+        let molded = |grid: &Grid, x: usize| {
+            (0..12)
+                .filter(|&y| grid.get(x, y).is_some_and(|c| c.id != CellId::Wood))
+                .count()
+        };
+        assert!(molded(&grid, 0) > 1);
+        assert_eq!(molded(&grid, 3), 1);
+
+        // Mold lives up to 800 ticks, and the last of it may only have grown in late
+        for _ in 0..2000 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.census().count(CellId::Mold), 0);
+        assert!(grid.census().count(CellId::Dust) > 1);
+    }
+
+    #[test]
+    fn mold_alone_in_a_single_cell_world_has_nowhere_to_spread() {
+        let mut rng = rng();
+        let mut grid = Grid::new(1, 1);
+        grid.spawn(0, 0, CellId::Mold);
+        for _ in 0..200 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.census().count(CellId::Wood), 0);
+    }
+
+    #[test]
+    fn replace_overwrites_other_kinds() {
+        let mut grid = Grid::new(2, 1);
+        grid.set(0, 0, cell(CellId::Water));
+        assert!(!grid.spawn(0, 0, CellId::Acid));
+        assert!(grid.replace(0, 0, CellId::Acid));
+        assert_eq!(grid.get(0, 0), cell(CellId::Acid));
+        assert!(!grid.replace(0, 0, CellId::Acid));
+        assert!(grid.replace(1, 0, CellId::Acid));
+    }
+
+    #[test]
+    fn bottom_up_scans_alternate_direction() {
+        let grid = Grid::new(3, 2);
+        let order: Vec<_> = grid
+            .scan_order(ScanOrder::BottomUp, 0, &mut rng())
+            .collect();
+        assert_eq!(order, [(0, 1), (1, 1), (2, 1), (0, 0), (1, 0), (2, 0)]);
+        let order: Vec<_> = grid
+            .scan_order(ScanOrder::BottomUp, 1, &mut rng())
+            .collect();
+        assert_eq!(order, [(2, 1), (1, 1), (0, 1), (2, 0), (1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn both_scan_orders_settle_sand_the_same() {
+        for scan in [ScanOrder::Shuffled, ScanOrder::BottomUp] {
+            let mut grid = Grid::new(4, 6);
+            for x in 0..4 {
+                grid.set(x, 0, cell(CellId::Sand));
+            }
+            for tick in 0..10 {
+                grid.step_with(scan, tick, &mut rng());
+            }
+            for x in 0..4 {
+                assert_eq!(grid.get(x, 5), cell(CellId::Sand), "{scan:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn settled_cells_sleep_until_a_neighbor_changes() {
+        let mut grid = Grid::new(1, 3);
+        grid.set(0, 1, cell(CellId::Sand));
+        grid.set(0, 2, cell(CellId::Stone));
+        for _ in 0..SLEEP_TICKS {
+            assert!(!grid.is_asleep(0, 1));
+            grid.step(&mut rng());
+        }
+        assert!(grid.is_asleep(0, 1));
+
+        grid.set(0, 2, None);
+        assert!(!grid.is_asleep(0, 1));
+        grid.step(&mut rng());
+        assert_eq!(grid.get(0, 2), cell(CellId::Sand));
+    }
+
+    #[test]
+    fn thawing_wakes_cells() {
+        let mut grid = Grid::new(1, 3);
+        grid.set(0, 0, cell(CellId::Sand));
+        grid.set_frozen(0..1, 0..1, true);
+        for _ in 0..SLEEP_TICKS * 2 {
+            grid.step(&mut rng());
+        }
+        grid.thaw_all();
+        grid.step(&mut rng());
+        assert_eq!(grid.get(0, 1), cell(CellId::Sand));
+    }
+
+    #[test]
+    fn cells_survive_packing() {
+        let mut grid = Grid::new(CellId::ALL.len(), 2);
+        for (x, id) in CellId::ALL.into_iter().enumerate() {
+            grid.set(x, 0, cell(id));
+            grid.set_frozen(x..x + 1, 0..1, true);
+        }
+        for (x, id) in CellId::ALL.into_iter().enumerate() {
+            assert_eq!(grid.get(x, 0), cell(id));
+            assert_eq!(grid.get(x, 1), None);
+            assert!(grid.is_frozen(x, 0));
+        }
+        assert_eq!(grid.take_changes().len(), CellId::ALL.len());
+    }
+
+    #[test]
+    fn payloads_move_with_their_cells() {
+        let mut grid = Grid::new(1, 4);
+        let sand = Cell {
+            aux: 0xbeef,
+            ..cell(CellId::Sand).unwrap()
+        };
+        grid.set(0, 0, Some(sand));
+        for _ in 0..3 {
+            grid.step(&mut rng());
+        }
+        assert_eq!(grid.get(0, 3), Some(sand));
+        assert_eq!(grid.payloads.len(), 1);
+
+        // Grids whose cells only differ by their payloads differ
+        let before = grid.clone();
+        grid.set(0, 3, Some(Cell { aux: 1, ..sand }));
+        assert_ne!(grid, before);
+        grid.set(0, 3, cell(CellId::Sand));
+        assert!(grid.payloads.is_empty());
+    }
+
+    #[test]
+    fn applying_a_diff_gives_the_other_grid() {
+        let mut before = Grid::new(3, 3);
+        before.set(0, 0, cell(CellId::Sand));
+        before.set(2, 2, cell(CellId::Stone));
+        let mut after = before.clone();
+        after.set(0, 0, None);
+        after.set(1, 1, cell(CellId::Water));
+
+        let diff = before.diff(&after);
+        assert_eq!(diff, vec![(0, 0, None), (1, 1, cell(CellId::Water))]);
+        for (x, y, cell) in diff {
+            before.set(x, y, cell);
+        }
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn shift_moves_cells_and_drops_the_edges() {
+        let mut grid = Grid::new(3, 2);
+        grid.set(0, 0, cell(CellId::Sand));
+        grid.set(2, 1, cell(CellId::Stone));
+        grid.set_frozen(0..1, 0..1, true);
+        grid.shift(1, 1);
+        assert_eq!(grid.get(1, 1), cell(CellId::Sand));
+        assert!(grid.is_frozen(1, 1) && !grid.is_frozen(0, 0));
+        assert_eq!(grid.census().count(CellId::Stone), 0);
+    }
+
+    #[test]
+    fn pasting_a_region_copies_it_back() {
+        let mut grid = Grid::new(4, 4);
+        grid.set(1, 2, cell(CellId::Water));
+        grid.set(2, 3, cell(CellId::Wood));
+        let region = grid.region(1..3, 2..6);
+        assert_eq!((region.width(), region.height()), (2, 2));
+
+        let mut copy = Grid::new(4, 4);
+        copy.paste(1, 2, &region);
+        assert_eq!(copy, grid);
+    }
+
+    #[test]
+    fn regions_turn_and_mirror_with_what_faces_a_way() {
+        let mut grid = Grid::new(3, 2);
+        grid.set(0, 0, cell(CellId::Sand));
+        grid.set(2, 1, cell(CellId::Fan));
+        grid.face(2, 1, Facing::Up);
+
+        let rotated = grid.rotated();
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(rotated.get(1, 0), cell(CellId::Sand));
+        assert_eq!(rotated.get(0, 2).unwrap().facing(), Some(Facing::Right));
+        let mirrored = grid.mirrored();
+        assert_eq!(mirrored.get(2, 0), cell(CellId::Sand));
+        assert_eq!(mirrored.get(0, 1).unwrap().facing(), Some(Facing::Up));
+        assert_eq!(rotated.rotated().rotated().rotated(), grid);
+        assert_eq!(mirrored.mirrored(), grid);
+
+        // Pasting over leaves what the pasted grid has no cells for
+        let mut world = Grid::new(4, 4);
+        world.set(3, 3, cell(CellId::Stone));
+        world.set(1, 3, cell(CellId::Stone));
+        world.paste_over(1, 2, &grid);
+        assert_eq!(world.get(1, 2), cell(CellId::Sand));
+        assert_eq!(world.get(1, 3), cell(CellId::Stone));
+        assert_eq!(world.get(3, 3).unwrap().id, CellId::Fan);
+        world.paste_over(-2, -1, &grid);
+        assert_eq!(world.get(0, 0).unwrap().id, CellId::Fan);
+    }
+
+    #[test]
+    fn frozen_cells_stay_put() {
+        let mut grid = Grid::new(3, 4);
+        grid.set(1, 0, cell(CellId::Sand));
+        grid.set(1, 2, cell(CellId::Sand));
+        grid.set_frozen(0..3, 0..1, true);
+        for _ in 0..4 {
+            grid.step(&mut rng());
+        }
+        assert_eq!(grid.get(1, 0), cell(CellId::Sand));
+        assert_eq!(grid.get(1, 3), cell(CellId::Sand));
+
+        // Nothing falls into a frozen gap either, it slides off as if the gap were filled
+        grid.thaw_all();
+        grid.set_frozen(1..2, 1..2, true);
+        grid.step(&mut rng());
+        assert_eq!(grid.get(1, 1), None);
+        assert_eq!(grid.census().count(CellId::Sand), 2);
+    }
+
+    #[test]
+    fn barriers_hold_back_acid_and_fire() {
+        let mut grid = Grid::new(3, 3);
+        grid.set(1, 0, cell(CellId::Acid));
+        grid.set(1, 1, cell(CellId::Barrier));
+        grid.set(0, 2, cell(CellId::Fire));
+        for _ in 0..10 {
+            grid.step(&mut rng());
+        }
+        assert_eq!(grid.get(1, 1), cell(CellId::Barrier));
+        assert_eq!(grid.census().count(CellId::Acid), 1);
+    }
+
+    #[test]
+    fn rigid_cells_hang_in_the_air() {
+        let mut grid = Grid::new(1, 3);
+        grid.set(0, 0, cell(CellId::Metal));
+        grid.step(&mut rng());
+        assert_eq!(grid.get(0, 0), cell(CellId::Metal));
+    }
+
+    // A 10x5 grid with a stone at (5, 2)
+    fn wall() -> Grid {
+        let mut grid = Grid::new(10, 5);
+        grid.set(5, 2, cell(CellId::Stone));
+        grid
+    }
+
+    #[track_caller]
+    fn assert_hit(hit: Option<Hit>, position: (usize, usize), point: Vec2, normal: Vec2) {
+        let hit = hit.expect("expected a hit");
+        assert_eq!((hit.x, hit.y), position);
+        assert!(hit.point.abs_diff_eq(point, 1e-5), "hit at {}", hit.point);
+        assert_eq!(hit.normal, normal);
+    }
+
+    #[test]
+    fn raycast_along_each_axis() {
+        let grid = wall();
+        let (left, right) = (Vec2::new(0.5, 2.5), Vec2::new(9.5, 2.5));
+        assert_hit(
+            grid.raycast(left, right),
+            (5, 2),
+            Vec2::new(5.0, 2.5),
+            Vec2::NEG_X,
+        );
+        assert_hit(
+            grid.raycast(right, left),
+            (5, 2),
+            Vec2::new(6.0, 2.5),
+            Vec2::X,
+        );
+
+        let (top, bottom) = (Vec2::new(5.5, 0.5), Vec2::new(5.5, 4.5));
+        assert_hit(
+            grid.raycast(top, bottom),
+            (5, 2),
+            Vec2::new(5.5, 2.0),
+            Vec2::NEG_Y,
+        );
+        assert_hit(
+            grid.raycast(bottom, top),
+            (5, 2),
+            Vec2::new(5.5, 3.0),
+            Vec2::Y,
+        );
+    }
+
+    #[test]
+    fn raycast_stops_at_the_end_of_the_segment() {
+        let grid = wall();
+        assert_eq!(grid.raycast(Vec2::new(0.5, 2.5), Vec2::new(4.9, 2.5)), None);
+        assert_eq!(grid.raycast(Vec2::new(0.5, 0.5), Vec2::new(9.5, 0.5)), None);
+    }
+
+    #[test]
+    fn zero_length_raycast_checks_its_cell() {
+        let grid = wall();
+        let inside = Vec2::new(5.25, 2.75);
+        assert_hit(grid.raycast(inside, inside), (5, 2), inside, Vec2::ZERO);
+        assert_eq!(grid.raycast(Vec2::new(1.5, 1.5), Vec2::new(1.5, 1.5)), None);
+    }
+
+    #[test]
+    fn raycast_starting_inside_a_cell_has_no_normal() {
+        let grid = wall();
+        let from = Vec2::new(5.5, 2.5);
+        assert_hit(
+            grid.raycast(from, Vec2::new(0.5, 0.5)),
+            (5, 2),
+            from,
+            Vec2::ZERO,
+        );
+    }
+
+    #[test]
+    fn raycast_through_a_corner_steps_vertically_first() {
+        let mut grid = Grid::new(4, 4);
+        grid.set(1, 0, cell(CellId::Stone));
+        grid.set(0, 1, cell(CellId::Stone));
+        let hit = grid.raycast(Vec2::new(0.5, 0.5), Vec2::new(2.5, 2.5));
+        assert_hit(hit, (0, 1), Vec2::new(1.0, 1.0), Vec2::NEG_Y);
+    }
+
+    #[test]
+    fn raycast_is_clipped_to_the_grid() {
+        let mut grid = wall();
+        grid.set(0, 4, cell(CellId::Stone));
+
+        // Entering from outside reports the face of the grid edge
+        let hit = grid.raycast(Vec2::new(-20.0, 4.5), Vec2::new(3.0, 4.5));
+        assert_hit(hit, (0, 4), Vec2::new(0.0, 4.5), Vec2::NEG_X);
+        let hit = grid.raycast(Vec2::new(5.5, 40.0), Vec2::new(5.5, -40.0));
+        assert_hit(hit, (5, 2), Vec2::new(5.5, 3.0), Vec2::Y);
+
+        // Rays leaving the grid, or missing it entirely, return without walking the whole segment
+        assert_eq!(
+            grid.raycast(Vec2::new(1.5, 1.5), Vec2::new(-1e9, -1e9)),
+            None
+        );
+        assert_eq!(
+            grid.raycast(Vec2::new(-5.0, -1.0), Vec2::new(20.0, -1.0)),
+            None
+        );
+        assert_eq!(
+            grid.raycast(Vec2::new(-5.0, 5.0), Vec2::new(20.0, 5.0)),
+            None
+        );
+        assert_eq!(
+            grid.raycast(Vec2::new(11.0, -1.0), Vec2::new(14.0, 3.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn raycast_ignores_gases_and_fire() {
+        let mut grid = Grid::new(6, 1);
+        grid.set(1, 0, cell(CellId::Oxygen));
+        grid.set(2, 0, cell(CellId::Fire));
+        grid.set(3, 0, cell(CellId::Water));
+        let hit = grid.raycast(Vec2::new(0.5, 0.5), Vec2::new(5.5, 0.5));
+        assert_hit(hit, (3, 0), Vec2::new(3.0, 0.5), Vec2::NEG_X);
+        assert_eq!(hit.unwrap().cell, cell(CellId::Water).unwrap());
+    }
+
+    #[test]
+    fn take_changes_reports_written_positions_once() {
+        let mut grid = Grid::new(3, 4);
+        grid.set(1, 0, cell(CellId::Sand));
+        grid.set(2, 3, cell(CellId::Stone));
+        grid.take_changes();
+
+        grid.set(2, 3, cell(CellId::Stone));
+        assert_eq!(grid.take_changes(), vec![]);
+        grid.step(&mut rng());
+        assert_eq!(grid.take_changes(), vec![(1, 0), (1, 1)]);
+        assert_eq!(grid.take_changes(), vec![]);
+        grid.clear();
+        assert_eq!(grid.take_changes(), vec![(1, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn sand_sinks_through_water() {
+        let mut grid = Grid::new(1, 2);
+        grid.set(0, 0, cell(CellId::Sand));
+        grid.set(0, 1, cell(CellId::Water));
+        grid.step(&mut rng());
+        assert_eq!(grid.get(0, 0), cell(CellId::Water));
+        assert_eq!(grid.get(0, 1), cell(CellId::Sand));
+    }
+
+    #[test]
+    fn wood_bobs_up_to_the_surface_and_stone_sinks() {
+        let mut grid = Grid::new(5, 12);
+        for x in 0..5 {
+            for y in 2..12 {
+                grid.spawn(x, y, CellId::Water);
+            }
+        }
+        grid.set(1, 11, cell(CellId::Wood));
+        grid.set(3, 2, cell(CellId::Stone));
+        let mut rng = rng();
+        // Not all at once, but over several ticks
+        grid.step(&mut rng);
+        assert_ne!(grid.get(1, 1), cell(CellId::Wood));
+        for _ in 0..80 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.get(1, 2), cell(CellId::Wood));
+        assert_eq!(grid.get(3, 11), cell(CellId::Stone));
+    }
+
+    #[test]
+    fn wood_fixed_to_metal_stays_under_water() {
+        let mut grid = Grid::new(3, 10);
+        grid.set(1, 9, cell(CellId::Metal));
+        for y in 4..9 {
+            grid.set(1, y, cell(CellId::Wood));
+        }
+        for x in [0, 2] {
+            for y in 1..10 {
+                grid.spawn(x, y, CellId::Water);
+            }
+        }
+        grid.set(1, 3, cell(CellId::Water));
+        let mut rng = rng();
+        for _ in 0..50 {
+            grid.step(&mut rng);
+        }
+        assert!((4..9).all(|y| grid.get(1, y) == cell(CellId::Wood)));
+    }
+
+    #[test]
+    fn heavy_cells_sink_slowly_into_sand_and_light_ones_rest_on_it() {
+        let mut grid = Grid::new(5, 12);
+        for x in 0..5 {
+            for y in 4..12 {
+                grid.spawn(x, y, CellId::Sand);
+            }
+        }
+        grid.set(1, 3, cell(CellId::Stone));
+        grid.set(3, 3, cell(CellId::Ash));
+        let mut rng = rng();
+        for _ in 0..10 {
+            grid.step(&mut rng);
+        }
+        let depth = |grid: &Grid, id| {
+            grid.iter()
+                .find(|&(_, _, c)| c.is_some_and(|c| c.id == id))
+                .map(|(_, y, _)| y)
+        };
+        assert!(depth(&grid, CellId::Stone).unwrap() < 11);
+        for _ in 0..600 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(depth(&grid, CellId::Stone), Some(11));
+        assert_eq!(depth(&grid, CellId::Ash), Some(3));
+        assert_eq!(grid.census().count(CellId::Sand), 40);
+    }
+
+    #[test]
+    fn flowing_water_carries_sand_downstream() {
+        // A sand bed up to x = 10 and a stone floor past it, with water let loose at one end
+        let mut grid = Grid::new(30, 8);
+        for x in 0..30 {
+            let bed = if x < 10 { CellId::Sand } else { CellId::Stone };
+            grid.spawn(x, 7, bed);
+        }
+        for x in 0..4 {
+            for y in 1..7 {
+                grid.spawn(x, y, CellId::Water);
+            }
+        }
+        let mut rng = rng();
+        for _ in 0..200 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.census().count(CellId::Sand), 10);
+        assert_eq!(grid.census().count(CellId::Water), 24);
+        // Part of the bed was carved out and left further along
+        let sand: Vec<_> = grid
+            .iter()
+            .filter(|(_, _, c)| c.is_some_and(|c| c.id == CellId::Sand))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert!(sand.iter().any(|&(_, y)| y < 7));
+        assert!(sand.iter().map(|&(x, _)| x).sum::<usize>() > (0..10).sum());
+    }
+
+    #[test]
+    fn water_spreads_along_the_floor() {
+        let mut grid = Grid::new(3, 2);
+        for x in 0..3 {
+            grid.set(x, 1, cell(CellId::Stone));
+        }
+        grid.set(1, 0, cell(CellId::Water));
+        grid.step(&mut rng());
+        assert_eq!(grid.get(1, 0), None);
+        assert_eq!(grid.census().count(CellId::Water), 1);
+    }
+
+    #[test]
+    fn acid_dissolves_what_it_falls_on() {
+        let mut grid = Grid::new(1, 3);
+        grid.set(0, 1, cell(CellId::Acid));
+        grid.set(0, 2, cell(CellId::Stone));
+        assert_eq!(grid.step(&mut rng()).dissolved, 1);
+        // Leaving at most a puff of acid gas
+        assert!(grid
+            .iter()
+            .all(|(_, _, c)| c.is_none_or(|c| c.id == CellId::AcidGas)));
+    }
+
+    #[test]
+    fn acid_gas_rises_and_condenses_under_ceilings() {
+        let mut rng = rng();
+        let mut grid = Grid::new(8, 12);
+        for x in 0..8 {
+            grid.set(x, 0, cell(CellId::Stone));
+            grid.set(x, 10, cell(CellId::Acid));
+            grid.set(x, 11, cell(CellId::Stone));
+        }
+        grid.step(&mut rng);
+        assert!(grid.census().count(CellId::AcidGas) > 0);
+
+        let mut condensed = false;
+        for _ in 0..500 {
+            grid.step(&mut rng);
+            condensed |= (0..8).any(|x| grid.get(x, 1).is_some_and(|c| c.id == CellId::Acid));
+        }
+        assert!(condensed);
+    }
+
+    #[test]
+    fn short_lived_cells_expire() {
+        let mut grid = Grid::new(3, 3);
+        grid.set(1, 0, cell(CellId::Fire));
+        grid.set(1, 1, cell(CellId::Wind));
+        let mut rng = rng();
+        for _ in 0..CellId::Wind.data().lifespan.unwrap().max {
+            grid.step(&mut rng);
+        }
+        assert_eq!(grid.iter().filter(|(_, _, c)| c.is_some()).count(), 0);
+    }
+}
@@ -0,0 +1,150 @@
+//! Conway's Game of Life running on a layer of its own over the grid, which can deposit cells
+//! where Life cells are born and clear the grid where they die
+
+use crate::cell::CellId;
+use crate::Grid;
+#[cfg(feature = "bevy")]
+use bevy_ecs::resource::Resource;
+
+/// What a Life cell being born or dying does to the grid position under it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LifeEffect {
+    #[default]
+    Nothing,
+    /// Places a cell of the kind if the position is empty
+    Deposit(CellId),
+    /// Clears the position, unless what is there is indestructible
+    Destroy,
+}
+
+/// Live and dead positions with the size of the grid, evolving under the B3/S23 rules. Positions
+/// past the edges count as dead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(Resource))]
+pub struct LifeLayer {
+    width: usize,
+    height: usize,
+    alive: Vec<bool>,
+    pub on_birth: LifeEffect,
+    pub on_death: LifeEffect,
+}
+
+impl LifeLayer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            alive: vec![false; width * height],
+            on_birth: LifeEffect::Nothing,
+            on_death: LifeEffect::Nothing,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_alive(&self, x: usize, y: usize) -> bool {
+        self.alive[x * self.height + y]
+    }
+
+    pub fn set_alive(&mut self, x: usize, y: usize, alive: bool) {
+        self.alive[x * self.height + y] = alive;
+    }
+
+    /// Number of live cells
+    pub fn population(&self) -> usize {
+        self.alive.iter().filter(|&&alive| alive).count()
+    }
+
+    fn live_neighbours(&self, x: usize, y: usize) -> usize {
+        let columns = x.saturating_sub(1)..=(x + 1).min(self.width - 1);
+        let rows = y.saturating_sub(1)..=(y + 1).min(self.height - 1);
+        columns
+            .flat_map(|nx| rows.clone().map(move |ny| (nx, ny)))
+            .filter(|&(nx, ny)| (nx, ny) != (x, y) && self.is_alive(nx, ny))
+            .count()
+    }
+
+    /// Advances the layer by one generation, applying the birth and death effects to the grid
+    pub fn step(&mut self, grid: &mut Grid) {
+        let next: Vec<bool> = (0..self.width)
+            .flat_map(|x| (0..self.height).map(move |y| (x, y)))
+            .map(|(x, y)| {
+                matches!(
+                    (self.is_alive(x, y), self.live_neighbours(x, y)),
+                    (true, 2) | (_, 3)
+                )
+            })
+            .collect();
+
+        for (i, (&was, &is)) in self.alive.iter().zip(&next).enumerate() {
+            let effect = match (was, is) {
+                (false, true) => self.on_birth,
+                (true, false) => self.on_death,
+                _ => continue,
+            };
+            let (x, y) = (i / self.height, i % self.height);
+            if x >= grid.width() || y >= grid.height() {
+                continue;
+            }
+            match effect {
+                LifeEffect::Nothing => (),
+                LifeEffect::Deposit(id) => {
+                    grid.spawn(x, y, id);
+                }
+                LifeEffect::Destroy => {
+                    if grid.get(x, y).is_some_and(|cell| !cell.indestructible()) {
+                        grid.set(x, y, None);
+                    }
+                }
+            }
+        }
+        self.alive = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blinker() -> LifeLayer {
+        let mut life = LifeLayer::new(5, 5);
+        for x in 1..4 {
+            life.set_alive(x, 2, true);
+        }
+        life
+    }
+
+    #[test]
+    fn blinkers_blink() {
+        let mut life = blinker();
+        let mut grid = Grid::new(5, 5);
+        life.step(&mut grid);
+        assert!((1..4).all(|y| life.is_alive(2, y)));
+        assert_eq!(life.population(), 3);
+
+        life.step(&mut grid);
+        assert_eq!(life, blinker());
+    }
+
+    #[test]
+    fn births_deposit_and_deaths_destroy() {
+        let mut life = blinker();
+        life.on_birth = LifeEffect::Deposit(CellId::Sand);
+        life.on_death = LifeEffect::Destroy;
+        let mut grid = Grid::new(5, 5);
+        grid.spawn(1, 2, CellId::Stone);
+        grid.spawn(3, 2, CellId::Barrier);
+
+        life.step(&mut grid);
+        assert_eq!(grid.get(2, 1).map(|c| c.id), Some(CellId::Sand));
+        assert_eq!(grid.get(2, 3).map(|c| c.id), Some(CellId::Sand));
+        assert_eq!(grid.get(1, 2), None);
+        assert_eq!(grid.get(3, 2).map(|c| c.id), Some(CellId::Barrier));
+    }
+}
@@ -0,0 +1,39 @@
+/// Collapses consecutive equal values into `(count, value)` runs
+pub fn encode<T: Copy + PartialEq>(values: impl IntoIterator<Item = T>) -> Vec<(u32, T)> {
+    let mut runs: Vec<(u32, T)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((count, last)) if *last == value && *count < u32::MAX => *count += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs
+}
+
+/// Expands runs produced by [`encode`] back into a flat list of values
+pub fn decode<T: Copy>(runs: &[(u32, T)]) -> Vec<T> {
+    let len = runs.iter().map(|&(count, _)| count as usize).sum();
+    let mut values = Vec::with_capacity(len);
+    for &(count, value) in runs {
+        values.extend(std::iter::repeat_n(value, count as usize));
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_merges_equal_neighbours() {
+        assert_eq!(encode([1, 1, 2, 2, 2, 1]), vec![(2, 1), (3, 2), (1, 1)]);
+        assert!(encode(Vec::<u8>::new()).is_empty());
+    }
+
+    #[test]
+    fn decode_reverses_encode() {
+        let values = [None, None, Some(3), None, Some(3), Some(3), Some(3)];
+        assert_eq!(decode(&encode(values)), values);
+        assert!(decode::<u8>(&[]).is_empty());
+    }
+}
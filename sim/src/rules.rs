@@ -0,0 +1,274 @@
+//! Cellular automaton rules written as text, for prototyping elements without touching the
+//! built-in ones
+//!
+//! Each line is one rule, `<kind>: <conditions> => <kind> [<chance>%]`, turning cells of the first
+//! kind into the second where every condition holds, with the given chance per tick or always.
+//! Conditions are separated by commas and count the eight neighbours of a kind, as in `>=2 Water`,
+//! `<=1 Sand` or `=0 Fire`. `Empty` stands for an empty position on either side, so rules can
+//! also fill gaps or clear cells. Blank lines and lines starting with `#` are skipped.
+//!
+//! ```text
+//! # Sand next to plenty of water turns to stone now and then
+//! Sand: >=3 Water => Stone 2%
+//! # Fire with nothing around goes out at once
+//! Fire: =8 Empty => Empty
+//! ```
+
+use crate::cell::{Cell, CellId};
+use crate::Grid;
+use rand::prelude::*;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    /// Line of the source the error is on, counting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rule on line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compare {
+    AtLeast,
+    AtMost,
+    Exactly,
+}
+
+/// A number of neighbours of one kind, `None` for empty positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Condition {
+    kind: Option<CellId>,
+    compare: Compare,
+    count: usize,
+}
+
+impl Condition {
+    fn holds(&self, neighbours: &[Option<CellId>]) -> bool {
+        let count = neighbours.iter().filter(|&&n| n == self.kind).count();
+        match self.compare {
+            Compare::AtLeast => count >= self.count,
+            Compare::AtMost => count <= self.count,
+            Compare::Exactly => count == self.count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    from: Option<CellId>,
+    conditions: Vec<Condition>,
+    to: Option<CellId>,
+    chance: f32,
+}
+
+/// A compiled set of rules, tried in the order they were written
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+fn parse_kind(name: &str) -> Result<Option<CellId>, String> {
+    match name.trim() {
+        name if name.eq_ignore_ascii_case("empty") => Ok(None),
+        name => CellId::from_name(name)
+            .map(Some)
+            .ok_or_else(|| format!("no cell kind {name:?}")),
+    }
+}
+
+fn parse_condition(condition: &str) -> Result<Condition, String> {
+    let (compare, rest) = if let Some(rest) = condition.strip_prefix(">=") {
+        (Compare::AtLeast, rest)
+    } else if let Some(rest) = condition.strip_prefix("<=") {
+        (Compare::AtMost, rest)
+    } else if let Some(rest) = condition.strip_prefix('=') {
+        (Compare::Exactly, rest)
+    } else {
+        return Err(format!("expected >=, <= or = in {condition:?}"));
+    };
+    let (count, kind) = rest
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| format!("expected a count and a kind in {condition:?}"))?;
+    let count = count
+        .parse()
+        .ok()
+        .filter(|&count| count <= 8)
+        .ok_or_else(|| format!("expected a count of neighbours from 0 to 8, not {count:?}"))?;
+    Ok(Condition {
+        kind: parse_kind(kind)?,
+        compare,
+        count,
+    })
+}
+
+fn parse_rule(line: &str) -> Result<Rule, String> {
+    let (head, to) = line
+        .split_once("=>")
+        .ok_or("expected => between the conditions and the result")?;
+    let (from, conditions) = head
+        .split_once(':')
+        .ok_or("expected : after the kind the rule applies to")?;
+    let conditions = conditions
+        .split(',')
+        .map(str::trim)
+        .filter(|condition| !condition.is_empty())
+        .map(parse_condition)
+        .collect::<Result<_, _>>()?;
+
+    let to = to.trim();
+    let (to, chance) = match to.rsplit_once(char::is_whitespace) {
+        Some((kind, chance)) if chance.ends_with('%') => {
+            let chance = chance
+                .trim_end_matches('%')
+                .parse::<f32>()
+                .ok()
+                .filter(|chance| (0.0..=100.0).contains(chance))
+                .ok_or_else(|| format!("expected a chance from 0% to 100%, not {chance:?}"))?;
+            (kind, chance / 100.0)
+        }
+        _ => (to, 1.0),
+    };
+    Ok(Rule {
+        from: parse_kind(from)?,
+        conditions,
+        to: parse_kind(to)?,
+        chance,
+    })
+}
+
+impl Rules {
+    pub fn compile(source: &str) -> Result<Self, RuleError> {
+        let rules = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(line, rule)| parse_rule(rule).map_err(|message| RuleError { line, message }))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Applies the rules to every position at once, judging each by the grid as it was before any
+    /// of them changed it. Returns how many positions changed.
+    pub fn apply(&self, grid: &mut Grid, rng: &mut impl Rng) -> usize {
+        if self.rules.is_empty() {
+            return 0;
+        }
+        let before = grid.clone();
+        let mut changed = 0;
+        for (x, y, cell) in before.iter() {
+            if before.is_frozen(x, y) || cell.is_some_and(|c| c.indestructible()) {
+                continue;
+            }
+            let kind = cell.map(|c| c.id);
+            let mut rules = self
+                .rules
+                .iter()
+                .filter(|rule| rule.from == kind)
+                .peekable();
+            if rules.peek().is_none() {
+                continue;
+            }
+
+            let neighbours: Vec<Option<CellId>> = (-1..=1)
+                .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+                .filter(|&offset| offset != (0, 0))
+                .map(|(dx, dy)| (x as isize + dx, y as isize + dy))
+                .filter(|&(nx, ny)| before.in_bounds(nx, ny))
+                .map(|(nx, ny)| before.cell_at(nx, ny).map(|c| c.id))
+                .collect();
+            let rule = rules.find(|rule| {
+                rule.conditions
+                    .iter()
+                    .all(|condition| condition.holds(&neighbours))
+            });
+            if let Some(rule) = rule.filter(|rule| rng.gen::<f32>() < rule.chance) {
+                if kind != rule.to {
+                    let cell = rule.to.map(Cell::new);
+                    grid.set(x, y, cell);
+                    changed += 1;
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_are_parsed() {
+        let rules = Rules::compile(
+            "# A comment\n\nRed Dye: >=2 Water, =0 Empty => Stone 25%\nEmpty: => Sand\n",
+        )
+        .unwrap();
+        assert_eq!(
+            rules.rules,
+            vec![
+                Rule {
+                    from: Some(CellId::RedDye),
+                    conditions: vec![
+                        Condition {
+                            kind: Some(CellId::Water),
+                            compare: Compare::AtLeast,
+                            count: 2,
+                        },
+                        Condition {
+                            kind: None,
+                            compare: Compare::Exactly,
+                            count: 0,
+                        },
+                    ],
+                    to: Some(CellId::Stone),
+                    chance: 0.25,
+                },
+                Rule {
+                    from: None,
+                    conditions: vec![],
+                    to: Some(CellId::Sand),
+                    chance: 1.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = Rules::compile("Sand: >=1 Water => Stone\n\nSand => Stone").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(Rules::compile("Sand: >=9 Water => Stone").is_err());
+        assert!(Rules::compile("Sand: 2 Water => Stone").is_err());
+        assert!(Rules::compile("Sand: >=2 Lava => Stone").is_err());
+        assert!(Rules::compile("Sand: => Stone 200%").is_err());
+    }
+
+    #[test]
+    fn rules_count_neighbours_before_any_change() {
+        // Every sand cell with a sand neighbour turns to stone, including the last one checked
+        let rules = Rules::compile("Sand: >=1 Sand => Stone\nEmpty: >=3 Stone => Water").unwrap();
+        let mut grid = Grid::new(3, 3);
+        grid.spawn(0, 2, CellId::Sand);
+        grid.spawn(1, 2, CellId::Sand);
+        grid.spawn(2, 0, CellId::Sand);
+
+        let changed = rules.apply(&mut grid, &mut StdRng::seed_from_u64(0));
+        assert_eq!(changed, 2);
+        assert_eq!(grid.census().count(CellId::Stone), 2);
+        assert_eq!(grid.census().count(CellId::Sand), 1);
+        assert_eq!(grid.census().count(CellId::Water), 0);
+    }
+}
@@ -0,0 +1,68 @@
+//! Worst-case worlds for measuring how long a simulation step takes
+
+use crate::cell::CellId;
+use crate::Grid;
+use rand::prelude::*;
+
+/// A named world to benchmark the step function on
+pub struct Scenario {
+    pub name: &'static str,
+    pub build: fn(usize, usize) -> Grid,
+}
+
+pub const ALL: [Scenario; 3] = [
+    Scenario {
+        name: "water",
+        build: water,
+    },
+    Scenario {
+        name: "fire",
+        build: fire,
+    },
+    Scenario {
+        name: "mixed",
+        build: mixed,
+    },
+];
+
+/// Water filling the lower three quarters, so every cell keeps looking for a gap to flow into
+pub fn water(width: usize, height: usize) -> Grid {
+    let mut grid = Grid::new(width, height);
+    for x in 0..width {
+        for y in height / 4..height {
+            grid.spawn(x, y, CellId::Water);
+        }
+    }
+    grid
+}
+
+/// A forest of wood columns soaked in oil and lit from below, so flames spread everywhere
+pub fn fire(width: usize, height: usize) -> Grid {
+    let mut grid = Grid::new(width, height);
+    for x in 0..width {
+        let id = if x % 3 == 0 {
+            CellId::Wood
+        } else {
+            CellId::Oil
+        };
+        for y in height / 3..height - 1 {
+            grid.spawn(x, y, id);
+        }
+        grid.spawn(x, height - 1, CellId::Fire);
+    }
+    grid
+}
+
+/// Every kind of cell scattered at random over the whole grid
+pub fn mixed(width: usize, height: usize) -> Grid {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut grid = Grid::new(width, height);
+    for x in 0..width {
+        for y in 0..height {
+            if rng.gen::<f32>() < 0.7 {
+                grid.spawn(x, y, *CellId::ALL.choose(&mut rng).unwrap());
+            }
+        }
+    }
+    grid
+}
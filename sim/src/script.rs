@@ -0,0 +1,367 @@
+//! Element behaviours written in [Rhai](https://rhai.rs) scripts
+//!
+//! A script defines functions named after cell kinds, like `fn oxygen(cell)`, which run once per
+//! tick for every cell of that kind after the built-in rules. The `cell` argument is the only way
+//! a script can touch the grid:
+//!
+//! - `cell.x`, `cell.y` and `cell.life` read the position and remaining life of the cell
+//! - `cell.aux` reads the payload of the cell, and `cell.aux = n` writes it, keeping the lowest
+//!   16 bits
+//! - `cell.get(dx, dy)` names the kind of a neighbour, `""` for an empty position and `"Edge"`
+//!   outside the grid
+//! - `cell.is_empty(dx, dy)` checks for an empty position inside the grid
+//! - `cell.swap(dx, dy)` moves the cell, trading places with the neighbour, and returns whether
+//!   it could
+//! - `cell.set(dx, dy, name)` replaces a neighbour with a fresh cell, or clears it for `""`
+//! - `cell.chance(p)` is true with probability `p`, and `cell.random(min, max)` picks an integer
+//!   in `min..=max`
+//!
+//! Scripts can't reach anything outside the grid, and run with limits on how many operations a
+//! single call may take.
+
+use crate::cell::{Cell, CellId};
+use crate::Grid;
+use rand::prelude::*;
+use rhai::{Dynamic, Engine, EvalAltResult, ParseError, Scope, AST};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Operations a single behaviour call may run before it is aborted
+const MAX_OPERATIONS: u64 = 10_000;
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Parse(ParseError),
+    Run(Box<EvalAltResult>),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Parse(err) => write!(f, "could not parse script: {err}"),
+            ScriptError::Run(err) => write!(f, "script failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Compiled element behaviours
+pub struct Scripts {
+    engine: Engine,
+    ast: AST,
+    /// The kinds with a behaviour, with the name of the function implementing it
+    behaviours: Vec<(CellId, String)>,
+}
+
+// What the script API reads and writes while the behaviours run
+struct State {
+    grid: Grid,
+    rng: StdRng,
+    /// Positions holding a cell that already ran or was moved this tick
+    visited: Vec<bool>,
+}
+
+/// The handle a behaviour receives for the cell it runs on
+#[derive(Clone)]
+pub struct ScriptCell {
+    state: Arc<Mutex<State>>,
+    x: i64,
+    y: i64,
+}
+
+impl ScriptCell {
+    fn target(&self, dx: i64, dy: i64) -> (isize, isize) {
+        ((self.x + dx) as isize, (self.y + dy) as isize)
+    }
+
+    fn get(&mut self, dx: i64, dy: i64) -> String {
+        let state = self.state.lock().unwrap();
+        let (x, y) = self.target(dx, dy);
+        if !state.grid.in_bounds(x, y) {
+            return "Edge".into();
+        }
+        state
+            .grid
+            .cell_at(x, y)
+            .map_or("", |c| c.id.data().name)
+            .into()
+    }
+
+    fn is_empty(&mut self, dx: i64, dy: i64) -> bool {
+        let state = self.state.lock().unwrap();
+        let (x, y) = self.target(dx, dy);
+        state.grid.in_bounds(x, y) && state.grid.cell_at(x, y).is_none()
+    }
+
+    fn life(&mut self) -> i64 {
+        let state = self.state.lock().unwrap();
+        let cell = state.grid.get(self.x as usize, self.y as usize);
+        cell.and_then(|c| c.life).map_or(-1, i64::from)
+    }
+
+    fn aux(&mut self) -> i64 {
+        let state = self.state.lock().unwrap();
+        let cell = state.grid.get(self.x as usize, self.y as usize);
+        cell.map_or(0, |c| i64::from(c.aux))
+    }
+
+    fn set_aux(&mut self, aux: i64) {
+        let mut state = self.state.lock().unwrap();
+        let (x, y) = (self.x as usize, self.y as usize);
+        if let Some(cell) = state.grid.get(x, y) {
+            state.grid.set(
+                x,
+                y,
+                Some(Cell {
+                    aux: aux as u16,
+                    ..cell
+                }),
+            );
+        }
+    }
+
+    fn swap(&mut self, dx: i64, dy: i64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let (x, y) = self.target(dx, dy);
+        if !state.grid.in_bounds(x, y)
+            || (dx, dy) == (0, 0)
+            || state.grid.is_frozen(x as usize, y as usize)
+        {
+            return false;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let (from_x, from_y) = (self.x as usize, self.y as usize);
+        let (here, there) = (state.grid.get(from_x, from_y), state.grid.get(x, y));
+        state.grid.set(from_x, from_y, there);
+        state.grid.set(x, y, here);
+
+        let height = state.grid.height();
+        state.visited[x * height + y] = true;
+        state.visited[from_x * height + from_y] = there.is_some();
+        (self.x, self.y) = (x as i64, y as i64);
+        true
+    }
+
+    fn set(&mut self, dx: i64, dy: i64, name: &str) -> Result<(), Box<EvalAltResult>> {
+        let id = match name {
+            "" => None,
+            name => Some(CellId::from_name(name).ok_or_else(|| format!("no cell kind {name}"))?),
+        };
+        let mut state = self.state.lock().unwrap();
+        let (x, y) = self.target(dx, dy);
+        if state.grid.in_bounds(x, y) && !state.grid.is_frozen(x as usize, y as usize) {
+            let (x, y) = (x as usize, y as usize);
+            let cell = id.map(Cell::new);
+            state.grid.set(x, y, cell);
+            let height = state.grid.height();
+            state.visited[x * height + y] = true;
+        }
+        Ok(())
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        self.state.lock().unwrap().rng.gen::<f64>() < p
+    }
+
+    fn random(&mut self, min: i64, max: i64) -> i64 {
+        if min > max {
+            return min;
+        }
+        self.state.lock().unwrap().rng.gen_range(min..=max)
+    }
+}
+
+impl Scripts {
+    pub fn compile(source: &str) -> Result<Self, ScriptError> {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(16)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(256)
+            .set_max_array_size(1024)
+            .set_max_map_size(256);
+        engine
+            .register_type_with_name::<ScriptCell>("Cell")
+            .register_get("x", |cell: &mut ScriptCell| cell.x)
+            .register_get("y", |cell: &mut ScriptCell| cell.y)
+            .register_get("life", ScriptCell::life)
+            .register_get_set("aux", ScriptCell::aux, ScriptCell::set_aux)
+            .register_fn("get", ScriptCell::get)
+            .register_fn("is_empty", ScriptCell::is_empty)
+            .register_fn("swap", ScriptCell::swap)
+            .register_fn("set", ScriptCell::set)
+            .register_fn("chance", ScriptCell::chance)
+            .register_fn("random", ScriptCell::random);
+
+        let ast = engine.compile(source).map_err(ScriptError::Parse)?;
+        let behaviours = CellId::ALL
+            .into_iter()
+            .filter_map(|id| {
+                let name = id.data().name.to_lowercase();
+                ast.iter_functions()
+                    .any(|f| f.name == name && f.params.len() == 1)
+                    .then_some((id, name))
+            })
+            .collect();
+        Ok(Self {
+            engine,
+            ast,
+            behaviours,
+        })
+    }
+
+    /// The cell kinds the script gives a behaviour to
+    pub fn kinds(&self) -> impl Iterator<Item = CellId> + '_ {
+        self.behaviours.iter().map(|&(id, _)| id)
+    }
+
+    /// Runs the behaviours once for every scripted cell, in random order. The grid is left as the
+    /// behaviours that finished before an error made it.
+    pub fn apply(&self, grid: &mut Grid, rng: &mut impl Rng) -> Result<(), ScriptError> {
+        if self.behaviours.is_empty() {
+            return Ok(());
+        }
+
+        let mut cells: Vec<_> = grid
+            .iter()
+            .filter(|&(x, y, cell)| {
+                !grid.is_frozen(x, y) && cell.is_some_and(|c| self.function(c.id).is_some())
+            })
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        cells.shuffle(rng);
+
+        let state = Arc::new(Mutex::new(State {
+            visited: vec![false; grid.width() * grid.height()],
+            grid: std::mem::replace(grid, Grid::new(0, 0)),
+            rng: StdRng::seed_from_u64(rng.gen()),
+        }));
+        let mut scope = Scope::new();
+        let mut result = Ok(());
+        for (x, y) in cells {
+            let function = {
+                let mut state = state.lock().unwrap();
+                let height = state.grid.height();
+                if std::mem::replace(&mut state.visited[x * height + y], true) {
+                    continue;
+                }
+                match state.grid.get(x, y).and_then(|c| self.function(c.id)) {
+                    Some(function) => function,
+                    None => continue,
+                }
+            };
+
+            let cell = ScriptCell {
+                state: state.clone(),
+                x: x as i64,
+                y: y as i64,
+            };
+            // Whatever the behaviour returns is ignored
+            if let Err(err) =
+                self.engine
+                    .call_fn::<Dynamic>(&mut scope, &self.ast, function, (cell,))
+            {
+                result = Err(ScriptError::Run(err));
+                break;
+            }
+        }
+
+        *grid = Arc::into_inner(state)
+            .expect("script kept a handle to its cell")
+            .into_inner()
+            .unwrap()
+            .grid;
+        result
+    }
+
+    fn function(&self, id: CellId) -> Option<&str> {
+        self.behaviours
+            .iter()
+            .find(|&&(kind, _)| kind == id)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_with(cells: &[(usize, usize, CellId)]) -> Grid {
+        let mut grid = Grid::new(4, 4);
+        for &(x, y, id) in cells {
+            grid.spawn(x, y, id);
+        }
+        grid
+    }
+
+    #[test]
+    fn behaviours_are_found_by_kind_name() {
+        let scripts = Scripts::compile("fn oxygen(cell) {} fn helper() {} fn sand() {}").unwrap();
+        assert_eq!(scripts.kinds().collect::<Vec<_>>(), vec![CellId::Oxygen]);
+    }
+
+    #[test]
+    fn behaviours_move_cells() {
+        let scripts =
+            Scripts::compile("fn oxygen(cell) { if cell.is_empty(0, -1) { cell.swap(0, -1); } }")
+                .unwrap();
+        let mut grid = grid_with(&[(1, 3, CellId::Oxygen)]);
+        scripts
+            .apply(&mut grid, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+
+        // Moved once, not again from its new position
+        assert_eq!(grid.get(1, 2).map(|c| c.id), Some(CellId::Oxygen));
+        assert_eq!(grid.get(1, 3), None);
+    }
+
+    #[test]
+    fn behaviours_read_and_replace_neighbours() {
+        let source = r#"
+            fn acid(cell) {
+                if cell.get(0, 1) == "Stone" && cell.get(0, -1) == "" && cell.get(-5, 0) == "Edge" {
+                    cell.set(0, 1, "Sand");
+                    cell.set(0, 0, "");
+                }
+            }
+        "#;
+        let scripts = Scripts::compile(source).unwrap();
+        let mut grid = grid_with(&[(2, 1, CellId::Acid), (2, 2, CellId::Stone)]);
+        scripts
+            .apply(&mut grid, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_eq!(grid.get(2, 1), None);
+        assert_eq!(grid.get(2, 2).map(|c| c.id), Some(CellId::Sand));
+    }
+
+    #[test]
+    fn behaviours_keep_count_in_the_payload() {
+        let scripts = Scripts::compile("fn stone(cell) { cell.aux += 1; }").unwrap();
+        let mut grid = grid_with(&[(0, 3, CellId::Stone)]);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..3 {
+            scripts.apply(&mut grid, &mut rng).unwrap();
+        }
+        assert_eq!(grid.get(0, 3).map(|c| c.aux), Some(3));
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        let scripts = Scripts::compile("fn sand(cell) { loop {} }").unwrap();
+        let mut grid = grid_with(&[(0, 0, CellId::Sand)]);
+        let result = scripts.apply(&mut grid, &mut StdRng::seed_from_u64(0));
+        assert!(matches!(result, Err(ScriptError::Run(_))));
+        assert_eq!(grid.width(), 4);
+    }
+
+    #[test]
+    fn unknown_kinds_are_errors() {
+        let scripts = Scripts::compile(r#"fn sand(cell) { cell.set(1, 0, "Lava"); }"#).unwrap();
+        let mut grid = grid_with(&[(0, 0, CellId::Sand)]);
+        assert!(scripts
+            .apply(&mut grid, &mut StdRng::seed_from_u64(0))
+            .is_err());
+    }
+}
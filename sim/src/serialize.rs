@@ -0,0 +1,140 @@
+use crate::cell::Cell;
+use crate::{rle, Grid, MAX_TILES};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serialized form of [`Grid`], storing the cells as run-length encoded columns
+#[derive(Serialize, Deserialize)]
+struct GridData {
+    width: usize,
+    height: usize,
+    cells: Vec<(u32, Option<Cell>)>,
+}
+
+impl Serialize for Grid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GridData {
+            width: self.width,
+            height: self.height,
+            cells: rle::encode(self.iter().map(|(_, _, cell)| cell)),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Grid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let data = GridData::deserialize(deserializer)?;
+        let expected = data
+            .width
+            .checked_mul(data.height)
+            .filter(|&tiles| tiles <= MAX_TILES)
+            .ok_or_else(|| {
+                D::Error::custom(format!(
+                    "a {}x{} grid is too large",
+                    data.width, data.height
+                ))
+            })? as u64;
+        let len: u64 = data.cells.iter().map(|&(count, _)| count as u64).sum();
+        if len != expected {
+            return Err(D::Error::custom(format!(
+                "expected {expected} cells for a {}x{} grid, found {len}",
+                data.width, data.height
+            )));
+        }
+        if let Some(cell) = data
+            .cells
+            .iter()
+            .find_map(|&(_, cell)| cell.filter(|&c| !is_valid(c)))
+        {
+            return Err(D::Error::custom(format!("invalid cell {cell:?}")));
+        }
+
+        let mut grid = Grid::new(data.width, data.height);
+        for (i, cell) in rle::decode(&data.cells).into_iter().enumerate() {
+            grid.set(i / data.height, i % data.height, cell);
+        }
+        Ok(grid)
+    }
+}
+
+// Whether a deserialized cell has a life left that its kind allows
+fn is_valid(cell: Cell) -> bool {
+    match (cell.life, cell.lifespan()) {
+        (None, _) => true,
+        (Some(life), Some(lifespan)) => (1..=lifespan.max).contains(&life),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+
+    fn cell(id: CellId) -> Option<Cell> {
+        Some(Cell::new(id))
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let mut grid = Grid::new(5, 3);
+        grid.set(0, 2, cell(CellId::Stone));
+        grid.set(1, 2, cell(CellId::Stone));
+        grid.set(2, 0, cell(CellId::Water));
+        grid.set(4, 1, cell(CellId::Fire));
+        grid.set(
+            3,
+            2,
+            Some(Cell {
+                aux: 513,
+                ..cell(CellId::Metal).unwrap()
+            }),
+        );
+
+        let text = ron::to_string(&grid).unwrap();
+        assert_eq!(ron::from_str::<Grid>(&text).unwrap(), grid);
+    }
+
+    #[test]
+    fn deserialize_rejects_wrong_cell_count() {
+        let text = "(width: 2, height: 2, cells: [(3, None)])";
+        assert!(ron::from_str::<Grid>(text).is_err());
+    }
+
+    #[test]
+    fn deserialize_rejects_overflowing_size() {
+        let text = format!("(width: {}, height: 2, cells: [])", usize::MAX);
+        assert!(ron::from_str::<Grid>(&text).is_err());
+    }
+
+    #[test]
+    fn deserialize_rejects_huge_grids() {
+        let text = format!(
+            "(width: 65536, height: 65536, cells: [({}, None)])",
+            u32::MAX
+        );
+        assert!(ron::from_str::<Grid>(&text).is_err());
+    }
+
+    #[test]
+    fn deserialize_rejects_invalid_life() {
+        for life in ["Some(0)", "Some(31)"] {
+            let text =
+                format!("(width: 1, height: 1, cells: [(1, Some((id: Fire, life: {life})))])");
+            assert!(
+                ron::from_str::<Grid>(&text).is_err(),
+                "accepted fire with life {life}"
+            );
+        }
+        let text = "(width: 1, height: 1, cells: [(1, Some((id: Sand, life: Some(3))))])";
+        assert!(ron::from_str::<Grid>(text).is_err());
+    }
+
+    #[test]
+    fn deserialize_rejects_unknown_cells() {
+        let text = "(width: 1, height: 1, cells: [(1, Some((id: Lava, life: None)))])";
+        assert!(ron::from_str::<Grid>(text).is_err());
+    }
+}
@@ -0,0 +1,416 @@
+//! Mods compiled to WebAssembly
+//!
+//! A mod is a WebAssembly module exporting `api_version() -> i32`, which has to return
+//! [`API_VERSION`], and hooks named after the cell kinds they take over, like
+//! `acid(x: i32, y: i32)`. The hooks run once per tick for every cell of that kind after the
+//! built-in rules, and an optional `tick()` export runs once per tick before them. Mods reach the
+//! grid only through these imports from the `powder_keg` module, all relative to the cell the
+//! hook runs on:
+//!
+//! - `get(dx: i32, dy: i32) -> i32` is the kind of a neighbour, [`EMPTY`] or [`EDGE`]
+//! - `life() -> i32` is the remaining life of the cell, or -1 when it doesn't age
+//! - `aux() -> i32` is the payload of the cell, and `set_aux(aux: i32)` replaces it with the
+//!   lowest 16 bits of `aux`
+//! - `swap(dx: i32, dy: i32) -> i32` moves the cell, trading places with the neighbour, and
+//!   returns 1 if it could
+//! - `set(dx: i32, dy: i32, kind: i32)` puts a fresh cell of a kind at a neighbour, or clears it
+//!   for [`EMPTY`]
+//! - `random(n: i32) -> i32` picks an integer in `0..n`
+//!
+//! Kinds are numbered by their position in [`CellId::ALL`], which only ever grows at the end.
+//! Every hook call runs on a fixed amount of fuel, and memory is capped, so a broken mod fails
+//! instead of hanging the game.
+
+use crate::cell::{Cell, CellId};
+use crate::Grid;
+use rand::prelude::*;
+use std::fmt;
+use wasmi::{
+    Caller, Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+/// Version of the host API described in the module documentation. Changes that break existing
+/// mods bump it.
+pub const API_VERSION: i32 = 1;
+/// The kind number of an empty position
+pub const EMPTY: i32 = -1;
+/// The kind number of a position outside the grid
+pub const EDGE: i32 = -2;
+
+/// Instructions a single hook call may execute, roughly
+const FUEL: u64 = 100_000;
+/// Largest linear memory a mod may grow to, in bytes
+const MEMORY_LIMIT: usize = 16 << 20;
+
+#[derive(Debug)]
+pub enum ModError {
+    Load(wasmi::Error),
+    Version(Option<i32>),
+    Run(wasmi::Error),
+}
+
+impl fmt::Display for ModError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModError::Load(err) => write!(f, "could not load mod: {err}"),
+            ModError::Version(Some(version)) => write!(
+                f,
+                "mod targets API version {version}, but this game provides {API_VERSION}"
+            ),
+            ModError::Version(None) => write!(f, "mod does not export `api_version`"),
+            ModError::Run(err) => write!(f, "mod failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ModError {}
+
+// What the host API reads and writes while the hooks run
+struct Host {
+    grid: Grid,
+    rng: StdRng,
+    /// Positions holding a cell that already ran or was moved this tick
+    visited: Vec<bool>,
+    /// The cell the running hook belongs to
+    x: usize,
+    y: usize,
+    limits: StoreLimits,
+}
+
+impl Host {
+    fn target(&self, dx: i32, dy: i32) -> Option<(usize, usize)> {
+        let (x, y) = (self.x as isize + dx as isize, self.y as isize + dy as isize);
+        self.grid
+            .in_bounds(x, y)
+            .then_some((x as usize, y as usize))
+    }
+
+    fn visit(&mut self, x: usize, y: usize, visited: bool) {
+        self.visited[x * self.grid.height() + y] = visited;
+    }
+}
+
+fn kind_number(id: CellId) -> i32 {
+    CellId::ALL.iter().position(|&kind| kind == id).unwrap() as i32
+}
+
+fn host_api(engine: &Engine) -> Linker<Host> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap(
+            "powder_keg",
+            "get",
+            |caller: Caller<'_, Host>, dx: i32, dy: i32| {
+                let host = caller.data();
+                match host.target(dx, dy) {
+                    Some((x, y)) => host.grid.get(x, y).map_or(EMPTY, |c| kind_number(c.id)),
+                    None => EDGE,
+                }
+            },
+        )
+        .unwrap()
+        .func_wrap("powder_keg", "life", |caller: Caller<'_, Host>| {
+            let host = caller.data();
+            let cell = host.grid.get(host.x, host.y);
+            cell.and_then(|c| c.life).map_or(-1, i32::from)
+        })
+        .unwrap()
+        .func_wrap("powder_keg", "aux", |caller: Caller<'_, Host>| {
+            let host = caller.data();
+            host.grid
+                .get(host.x, host.y)
+                .map_or(0, |c| i32::from(c.aux))
+        })
+        .unwrap()
+        .func_wrap(
+            "powder_keg",
+            "set_aux",
+            |mut caller: Caller<'_, Host>, aux: i32| {
+                let host = caller.data_mut();
+                if let Some(cell) = host.grid.get(host.x, host.y) {
+                    let aux = aux as u16;
+                    host.grid.set(host.x, host.y, Some(Cell { aux, ..cell }));
+                }
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "powder_keg",
+            "swap",
+            |mut caller: Caller<'_, Host>, dx: i32, dy: i32| {
+                let host = caller.data_mut();
+                let Some((x, y)) = host
+                    .target(dx, dy)
+                    .filter(|&(x, y)| (dx, dy) != (0, 0) && !host.grid.is_frozen(x, y))
+                else {
+                    return 0;
+                };
+                let (here, there) = (host.grid.get(host.x, host.y), host.grid.get(x, y));
+                host.grid.set(host.x, host.y, there);
+                host.grid.set(x, y, here);
+                host.visit(x, y, true);
+                host.visit(host.x, host.y, there.is_some());
+                (host.x, host.y) = (x, y);
+                1
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "powder_keg",
+            "set",
+            |mut caller: Caller<'_, Host>, dx: i32, dy: i32, kind: i32| {
+                let cell = match kind {
+                    EMPTY => None,
+                    kind => {
+                        let id = *usize::try_from(kind)
+                            .ok()
+                            .and_then(|kind| CellId::ALL.get(kind))
+                            .ok_or_else(|| wasmi::Error::new(format!("no cell kind {kind}")))?;
+                        Some(Cell::new(id))
+                    }
+                };
+                let host = caller.data_mut();
+                if let Some((x, y)) = host
+                    .target(dx, dy)
+                    .filter(|&(x, y)| !host.grid.is_frozen(x, y))
+                {
+                    host.grid.set(x, y, cell);
+                    host.visit(x, y, true);
+                }
+                Ok(())
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "powder_keg",
+            "random",
+            |mut caller: Caller<'_, Host>, n: i32| {
+                if n <= 0 {
+                    return 0;
+                }
+                caller.data_mut().rng.gen_range(0..n)
+            },
+        )
+        .unwrap();
+    linker
+}
+
+/// A cell hook, called with the position of the cell
+type Hook = TypedFunc<(i32, i32), ()>;
+
+/// A loaded mod, with the state it keeps between ticks
+pub struct Mod {
+    store: Store<Host>,
+    tick: Option<TypedFunc<(), ()>>,
+    hooks: Vec<(CellId, Hook)>,
+}
+
+impl Mod {
+    /// Loads a mod from the bytes of a `.wasm` file
+    pub fn load(wasm: &[u8]) -> Result<Self, ModError> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).map_err(ModError::Load)?;
+
+        let host = Host {
+            grid: Grid::new(0, 0),
+            rng: StdRng::seed_from_u64(0),
+            visited: Vec::new(),
+            x: 0,
+            y: 0,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MEMORY_LIMIT)
+                .instances(1)
+                .build(),
+        };
+        let mut store = Store::new(&engine, host);
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(FUEL).map_err(ModError::Load)?;
+        let instance = host_api(&engine)
+            .instantiate_and_start(&mut store, &module)
+            .map_err(ModError::Load)?;
+
+        let version = instance
+            .get_typed_func::<(), i32>(&store, "api_version")
+            .ok()
+            .map(|f| f.call(&mut store, ()))
+            .transpose()
+            .map_err(ModError::Load)?;
+        if version != Some(API_VERSION) {
+            return Err(ModError::Version(version));
+        }
+
+        Ok(Self {
+            tick: instance.get_typed_func(&store, "tick").ok(),
+            hooks: hooks(&store, &instance),
+            store,
+        })
+    }
+
+    /// The cell kinds the mod has hooks for
+    pub fn kinds(&self) -> impl Iterator<Item = CellId> + '_ {
+        self.hooks.iter().map(|&(id, _)| id)
+    }
+
+    /// Runs the tick hook, then the cell hooks once for every cell they take over, in random
+    /// order. The grid is left as the hooks that finished before an error made it.
+    pub fn apply(&mut self, grid: &mut Grid, rng: &mut impl Rng) -> Result<(), ModError> {
+        let mut cells: Vec<_> = grid
+            .iter()
+            .filter(|&(x, y, cell)| {
+                !grid.is_frozen(x, y) && cell.is_some_and(|c| self.hook(c.id).is_some())
+            })
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        cells.shuffle(rng);
+
+        let host = self.store.data_mut();
+        host.visited = vec![false; grid.width() * grid.height()];
+        host.grid = std::mem::replace(grid, Grid::new(0, 0));
+        host.rng = StdRng::seed_from_u64(rng.gen());
+
+        let result = self.run(cells);
+        *grid = std::mem::replace(&mut self.store.data_mut().grid, Grid::new(0, 0));
+        result.map_err(ModError::Run)
+    }
+
+    fn run(&mut self, cells: Vec<(usize, usize)>) -> Result<(), wasmi::Error> {
+        if let Some(tick) = self.tick {
+            self.store.set_fuel(FUEL)?;
+            tick.call(&mut self.store, ())?;
+        }
+
+        for (x, y) in cells {
+            let host = self.store.data_mut();
+            let height = host.grid.height();
+            if std::mem::replace(&mut host.visited[x * height + y], true) {
+                continue;
+            }
+            let id = host.grid.get(x, y).map(|c| c.id);
+            (host.x, host.y) = (x, y);
+            let Some(hook) = id.and_then(|id| self.hook(id)) else {
+                continue;
+            };
+            self.store.set_fuel(FUEL)?;
+            hook.call(&mut self.store, (x as i32, y as i32))?;
+        }
+        Ok(())
+    }
+
+    fn hook(&self, id: CellId) -> Option<Hook> {
+        self.hooks
+            .iter()
+            .find(|&&(kind, _)| kind == id)
+            .map(|&(_, hook)| hook)
+    }
+}
+
+fn hooks(store: &Store<Host>, instance: &Instance) -> Vec<(CellId, Hook)> {
+    CellId::ALL
+        .into_iter()
+        .filter_map(|id| {
+            let name = id.data().name.to_lowercase();
+            let hook = instance.get_typed_func(store, &name).ok()?;
+            Some((id, hook))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(hooks: &str) -> Result<Mod, ModError> {
+        let source = format!(
+            r#"(module
+                (import "powder_keg" "get" (func $get (param i32 i32) (result i32)))
+                (import "powder_keg" "swap" (func $swap (param i32 i32) (result i32)))
+                (import "powder_keg" "set" (func $set (param i32 i32 i32)))
+                (func (export "api_version") (result i32) i32.const 1)
+                {hooks}
+            )"#
+        );
+        Mod::load(&wat::parse_str(source).unwrap())
+    }
+
+    fn apply(module: &mut Mod, grid: &mut Grid) -> Result<(), ModError> {
+        module.apply(grid, &mut StdRng::seed_from_u64(0))
+    }
+
+    #[test]
+    fn hooks_are_found_by_kind_name() {
+        let module = load(
+            r#"(func (export "oxygen") (param i32 i32))
+               (func (export "helper") (param i32 i32))
+               (func (export "sand"))"#,
+        )
+        .unwrap();
+        assert_eq!(module.kinds().collect::<Vec<_>>(), vec![CellId::Oxygen]);
+    }
+
+    #[test]
+    fn hooks_move_cells() {
+        // Rises by one if the position above is empty
+        let mut module = load(
+            r#"(func (export "oxygen") (param i32 i32)
+                 (if (i32.eq (call $get (i32.const 0) (i32.const -1)) (i32.const -1))
+                   (then (drop (call $swap (i32.const 0) (i32.const -1))))))"#,
+        )
+        .unwrap();
+        let mut grid = Grid::new(3, 4);
+        grid.spawn(1, 3, CellId::Oxygen);
+        apply(&mut module, &mut grid).unwrap();
+
+        // Moved once, not again from its new position
+        assert_eq!(grid.get(1, 2).map(|c| c.id), Some(CellId::Oxygen));
+        assert_eq!(grid.get(1, 3), None);
+    }
+
+    #[test]
+    fn hooks_replace_neighbours() {
+        let stone = kind_number(CellId::Stone);
+        let sand = kind_number(CellId::Sand);
+        let mut module = load(&format!(
+            r#"(func (export "acid") (param i32 i32)
+                 (if (i32.and
+                       (i32.eq (call $get (i32.const 0) (i32.const 1)) (i32.const {stone}))
+                       (i32.eq (call $get (i32.const -5) (i32.const 0)) (i32.const -2)))
+                   (then
+                     (call $set (i32.const 0) (i32.const 1) (i32.const {sand}))
+                     (call $set (i32.const 0) (i32.const 0) (i32.const -1)))))"#
+        ))
+        .unwrap();
+        let mut grid = Grid::new(4, 4);
+        grid.spawn(2, 1, CellId::Acid);
+        grid.spawn(2, 2, CellId::Stone);
+        apply(&mut module, &mut grid).unwrap();
+        assert_eq!(grid.get(2, 1), None);
+        assert_eq!(grid.get(2, 2).map(|c| c.id), Some(CellId::Sand));
+    }
+
+    #[test]
+    fn runaway_mods_run_out_of_fuel() {
+        let mut module =
+            load(r#"(func (export "sand") (param i32 i32) (loop $forever (br $forever)))"#)
+                .unwrap();
+        let mut grid = Grid::new(4, 4);
+        grid.spawn(0, 0, CellId::Sand);
+        assert!(matches!(
+            apply(&mut module, &mut grid),
+            Err(ModError::Run(_))
+        ));
+        assert_eq!(grid.census().count(CellId::Sand), 1);
+    }
+
+    #[test]
+    fn mods_for_other_api_versions_are_rejected() {
+        let wasm =
+            wat::parse_str(r#"(module (func (export "api_version") (result i32) i32.const 2))"#)
+                .unwrap();
+        assert!(matches!(Mod::load(&wasm), Err(ModError::Version(Some(2)))));
+        let wasm = wat::parse_str("(module)").unwrap();
+        assert!(matches!(Mod::load(&wasm), Err(ModError::Version(None))));
+    }
+}
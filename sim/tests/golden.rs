@@ -0,0 +1,162 @@
+//! Simulations run from the worlds in `tests/golden/*.start.txt` and compared to the states they
+//! are known to end in, `tests/golden/*.golden.txt`, so a change to how elements behave shows up
+//! here before players notice it. When the change is intended, run the tests with `BLESS=1` to
+//! write the new states, and review them in the diff.
+//!
+//! Worlds are drawn a character per cell, with spaces for empty positions. Only the kinds of the
+//! cells are compared, not how long they have left to live or their tints.
+
+use powder_keg_sim::cell::{Cell, CellId};
+use powder_keg_sim::{Grid, ScanOrder};
+use rand::prelude::*;
+use std::path::PathBuf;
+
+/// The character a kind of cell is drawn with
+fn symbol(id: CellId) -> char {
+    match id {
+        CellId::Sand => '.',
+        CellId::Stone => '#',
+        CellId::Wood => 'W',
+        CellId::Water => '~',
+        CellId::Oil => 'o',
+        CellId::Acid => 'a',
+        CellId::Oxygen => 'O',
+        CellId::Fire => '^',
+        CellId::Wind => '>',
+        CellId::Bedrock => '=',
+        CellId::Metal => 'M',
+        CellId::Barrier => '|',
+        CellId::RedDye => 'r',
+        CellId::YellowDye => 'y',
+        CellId::BlueDye => 'b',
+        CellId::Soap => 's',
+        CellId::Foam => 'f',
+        CellId::Gel => 'g',
+        CellId::Mercury => 'm',
+        CellId::Hydrogen => 'h',
+        CellId::Steam => '"',
+        CellId::ToxicGas => 't',
+        CellId::Ember => '*',
+        CellId::Ash => ',',
+        CellId::AcidGas => 'A',
+        CellId::MetalPowder => ':',
+        CellId::Thermite => 'T',
+        CellId::MoltenMetal => '&',
+        CellId::GravityWell => '@',
+        CellId::Repulsor => 'R',
+        CellId::Portal => 'P',
+        CellId::ConveyorLeft => '{',
+        CellId::ConveyorRight => '}',
+        CellId::Heater => 'H',
+        CellId::Cooler => 'C',
+        CellId::Ice => 'i',
+        CellId::Fuse => '-',
+        CellId::BurningFuse => '+',
+        CellId::Firework => 'F',
+        CellId::Rocket => '!',
+        CellId::Spark => '\'',
+        CellId::Meteor => 'X',
+        CellId::Antimatter => 'x',
+        CellId::Mold => '%',
+        CellId::Dust => '`',
+        CellId::Rope => 'L',
+        CellId::Cloth => 'c',
+        CellId::Nitroglycerin => 'n',
+        CellId::Fan => 'V',
+        CellId::Timber => 'w',
+    }
+}
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+}
+
+/// Reads a world drawn as text, as wide as its longest line
+fn parse(text: &str) -> Grid {
+    let lines: Vec<&str> = text.lines().collect();
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut grid = Grid::new(width, lines.len());
+    for (y, line) in lines.iter().enumerate() {
+        for (x, c) in line.chars().enumerate().filter(|&(_, c)| c != ' ') {
+            let id = CellId::ALL
+                .into_iter()
+                .find(|&id| symbol(id) == c)
+                .unwrap_or_else(|| panic!("no cell kind is drawn as {c:?}"));
+            grid.set(
+                x,
+                y,
+                Some(Cell {
+                    id,
+                    life: None,
+                    tint: None,
+                    aux: 0,
+                }),
+            );
+        }
+    }
+    grid
+}
+
+/// Draws a world as text, without the spaces ending each line
+fn draw(grid: &Grid) -> String {
+    (0..grid.height())
+        .map(|y| {
+            let line: String = (0..grid.width())
+                .map(|x| grid.get(x, y).map_or(' ', |cell| symbol(cell.id)))
+                .collect();
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+/// Steps the world named for some ticks and compares where it ends up to its golden state
+fn check(name: &str, ticks: u64) {
+    let start = std::fs::read_to_string(path(&format!("{name}.start.txt"))).unwrap();
+    let mut grid = parse(&start);
+    let mut rng = StdRng::seed_from_u64(7);
+    for tick in 0..ticks {
+        grid.step_with(ScanOrder::Shuffled, tick, &mut rng);
+    }
+    let state = draw(&grid);
+
+    let golden = path(&format!("{name}.golden.txt"));
+    if std::env::var_os("BLESS").is_some() {
+        std::fs::write(&golden, &state).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&golden).unwrap_or_default();
+    assert!(
+        state == expected,
+        "{name} ended up differently after {ticks} ticks. Run with BLESS=1 if that was \
+         intended.\n--- expected\n{expected}--- got\n{state}"
+    );
+}
+
+#[test]
+fn sand_pile_collapses() {
+    check("sand_pile", 80);
+}
+
+#[test]
+fn water_runs_into_a_u_tube() {
+    check("water_u_tube", 300);
+}
+
+#[test]
+fn oil_fire_burns_down() {
+    check("oil_fire", 120);
+}
+
+#[test]
+fn worlds_are_drawn_as_they_are_read() {
+    for name in ["sand_pile", "water_u_tube", "oil_fire"] {
+        let start = std::fs::read_to_string(path(&format!("{name}.start.txt"))).unwrap();
+        assert_eq!(draw(&parse(&start)), start);
+    }
+}
@@ -0,0 +1,132 @@
+use powder_keg_sim::cell::{Cell, CellId};
+use powder_keg_sim::fuzz::fuzz_step;
+use powder_keg_sim::Grid;
+use proptest::prelude::*;
+use rand::prelude::*;
+
+/// Kinds that neither burn, dissolve nor expire, so a tick can only move them around. None of
+/// them is heavier than sand unless it keeps still, or a liquid that could carry sand off.
+const INERT: [CellId; 4] = [
+    CellId::Sand,
+    CellId::Metal,
+    CellId::Bedrock,
+    CellId::Barrier,
+];
+
+/// Kinds that never react as long as there is no fire or acid around, nor gas to burst wood
+const NON_REACTING: [CellId; 8] = [
+    CellId::Sand,
+    CellId::Stone,
+    CellId::Wood,
+    CellId::Water,
+    CellId::Oil,
+    CellId::Bedrock,
+    CellId::Metal,
+    CellId::Barrier,
+];
+
+fn world(ids: &'static [CellId]) -> impl Strategy<Value = Grid> {
+    (2usize..24, 2usize..24).prop_flat_map(move |(width, height)| {
+        proptest::collection::vec(
+            proptest::option::weighted(0.6, proptest::sample::select(ids)),
+            width * height,
+        )
+        .prop_map(move |cells| {
+            let mut grid = Grid::new(width, height);
+            for (i, id) in cells.into_iter().enumerate() {
+                if let Some(id) = id {
+                    grid.set(
+                        i / height,
+                        i % height,
+                        Some(Cell {
+                            id,
+                            life: None,
+                            tint: None,
+                            aux: 0,
+                        }),
+                    );
+                }
+            }
+            grid
+        })
+    })
+}
+
+/// Number of sand cells above each row boundary, top to bottom
+fn sand_above(grid: &Grid) -> Vec<usize> {
+    (0..=grid.height())
+        .map(|boundary| {
+            grid.iter()
+                .filter(|&(_, y, cell)| y < boundary && cell.is_some_and(|c| c.id == CellId::Sand))
+                .count()
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn cells_never_duplicate_or_vanish(mut grid in world(&NON_REACTING), seed: u64, ticks in 1usize..8) {
+        let before = grid.census();
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..ticks {
+            grid.step(&mut rng);
+        }
+        prop_assert_eq!(grid.census(), before);
+    }
+
+    #[test]
+    fn sand_never_moves_upward(mut grid in world(&INERT), seed: u64) {
+        let before = sand_above(&grid);
+        grid.step(&mut StdRng::seed_from_u64(seed));
+        for (after, before) in sand_above(&grid).into_iter().zip(before) {
+            prop_assert!(after <= before);
+        }
+    }
+
+    #[test]
+    fn reactions_explain_every_change(mut grid in world(&CellId::ALL), seed: u64) {
+        let before = grid.census();
+        grid.step(&mut StdRng::seed_from_u64(seed));
+        prop_assert_eq!(before.unexplained_changes(&grid.census()), vec![]);
+    }
+
+    #[test]
+    fn barriers_never_change(mut grid in world(&CellId::ALL), seed: u64) {
+        let barriers = |grid: &Grid| -> Vec<_> {
+            grid.iter()
+                .filter(|&(_, _, cell)| cell.is_some_and(|c| c.id == CellId::Barrier))
+                .map(|(x, y, _)| (x, y))
+                .collect()
+        };
+        let before = barriers(&grid);
+        grid.step(&mut StdRng::seed_from_u64(seed));
+        prop_assert_eq!(barriers(&grid), before);
+    }
+
+    #[test]
+    fn stats_account_for_every_change(mut grid in world(&CellId::ALL), seed: u64) {
+        let before = grid.census();
+        let stats = grid.step(&mut StdRng::seed_from_u64(seed));
+        let after = grid.census();
+        for id in CellId::ALL {
+            prop_assert_eq!(
+                before.count(id) + stats.created.count(id),
+                after.count(id) + stats.destroyed.count(id),
+                "{:?}", id
+            );
+        }
+    }
+
+    #[test]
+    fn any_bytes_step_without_panicking(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        fuzz_step(&bytes);
+    }
+
+    #[test]
+    fn step_is_deterministic(grid in world(&CellId::ALL), seed: u64) {
+        let (mut a, mut b) = (grid.clone(), grid);
+        a.step(&mut StdRng::seed_from_u64(seed));
+        b.step(&mut StdRng::seed_from_u64(seed));
+        prop_assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::cell::{Cell, CellId};
+use crate::registry::CellRegistry;
+use serde::{Deserialize, Serialize};
+
+/// How much darker the hatched tiles of a material are drawn
+const HATCH_SHADE: f32 = 0.7;
+
+/// How far high contrast pushes colors away from grey
+const CONTRAST: f32 = 1.5;
+
+/// Colors from the Okabe-Ito palette, which stay apart under the common kinds of color blindness
+const ORANGE: [u8; 3] = [230, 159, 0];
+const SKY_BLUE: [u8; 3] = [86, 180, 233];
+const BLUISH_GREEN: [u8; 3] = [0, 158, 115];
+const YELLOW: [u8; 3] = [240, 228, 66];
+const BLUE: [u8; 3] = [0, 114, 178];
+const VERMILLION: [u8; 3] = [213, 94, 0];
+const REDDISH_PURPLE: [u8; 3] = [204, 121, 167];
+
+/// Colors replacing the ones in the cell registry, for the kinds that are hard to tell apart
+const RED_GREEN: &[(CellId, [u8; 3])] = &[
+    (CellId::Sand, [196, 186, 160]),
+    (CellId::Stone, [120, 120, 124]),
+    (CellId::Oil, [36, 20, 56]),
+    (CellId::Acid, YELLOW),
+    (CellId::Water, BLUE),
+    (CellId::Fire, VERMILLION),
+    (CellId::Gel, BLUISH_GREEN),
+    (CellId::ToxicGas, SKY_BLUE),
+    (CellId::Mold, REDDISH_PURPLE),
+    (CellId::Heater, ORANGE),
+];
+
+const BLUE_YELLOW: &[(CellId, [u8; 3])] = &[
+    (CellId::Sand, [196, 186, 160]),
+    (CellId::Stone, [120, 120, 124]),
+    (CellId::Oil, [20, 20, 20]),
+    (CellId::Acid, REDDISH_PURPLE),
+    (CellId::Water, BLUE),
+    (CellId::Ice, [230, 230, 230]),
+    (CellId::Gel, BLUISH_GREEN),
+    (CellId::Cooler, SKY_BLUE),
+    (CellId::Heater, VERMILLION),
+];
+
+const HIGH_CONTRAST: &[(CellId, [u8; 3])] = &[
+    (CellId::Stone, [160, 160, 160]),
+    (CellId::Oil, [0, 0, 0]),
+    (CellId::Acid, YELLOW),
+    (CellId::Water, BLUE),
+];
+
+/// A table of colors the cells are drawn with in place of the registry's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorScheme {
+    #[default]
+    Default,
+    /// For deuteranopia and protanopia
+    RedGreen,
+    /// For tritanopia
+    BlueYellow,
+    HighContrast,
+}
+
+impl ColorScheme {
+    pub const ALL: [ColorScheme; 4] = [
+        ColorScheme::Default,
+        ColorScheme::RedGreen,
+        ColorScheme::BlueYellow,
+        ColorScheme::HighContrast,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorScheme::Default => "Default colors",
+            ColorScheme::RedGreen => "Red-green safe",
+            ColorScheme::BlueYellow => "Blue-yellow safe",
+            ColorScheme::HighContrast => "High contrast",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&scheme| scheme == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn table(self) -> &'static [(CellId, [u8; 3])] {
+        match self {
+            ColorScheme::Default => &[],
+            ColorScheme::RedGreen => RED_GREEN,
+            ColorScheme::BlueYellow => BLUE_YELLOW,
+            ColorScheme::HighContrast => HIGH_CONTRAST,
+        }
+    }
+
+    /// The color a kind is drawn with under the scheme
+    pub fn color(self, registry: &CellRegistry, id: CellId) -> [u8; 3] {
+        let color = self
+            .table()
+            .iter()
+            .find(|&&(kind, _)| kind == id)
+            .map_or_else(|| registry.color(id), |&(_, color)| color);
+        self.adjust(color)
+    }
+
+    /// The color a cell is drawn with under the scheme. Tints are the player's choice, so they
+    /// are kept, only with more contrast.
+    pub fn cell_color(self, registry: &CellRegistry, cell: Cell) -> [u8; 3] {
+        match cell.tint {
+            Some(tint) => self.adjust(tint),
+            None => self.color(registry, cell.id),
+        }
+    }
+
+    fn adjust(self, color: [u8; 3]) -> [u8; 3] {
+        if self != ColorScheme::HighContrast {
+            return color;
+        }
+        color.map(|c| (128.0 + (c as f32 - 128.0) * CONTRAST).clamp(0.0, 255.0) as u8)
+    }
+}
+
+/// A pattern drawn over a material, so it can be told apart without relying on color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hatch {
+    Stripes,
+    Diagonal,
+    Checker,
+    Dots,
+}
+
+impl Hatch {
+    fn of(id: CellId) -> Option<Self> {
+        match id {
+            CellId::Oil | CellId::Mercury => Some(Hatch::Stripes),
+            CellId::Acid | CellId::Antimatter => Some(Hatch::Checker),
+            CellId::ToxicGas | CellId::AcidGas | CellId::Hydrogen => Some(Hatch::Dots),
+            CellId::Barrier | CellId::MoltenMetal | CellId::Thermite => Some(Hatch::Diagonal),
+            _ => None,
+        }
+    }
+
+    fn covers(self, x: usize, y: usize) -> bool {
+        match self {
+            Hatch::Stripes => y.is_multiple_of(2),
+            Hatch::Diagonal => (x + y).is_multiple_of(3),
+            Hatch::Checker => (x / 2 + y / 2).is_multiple_of(2),
+            Hatch::Dots => x.is_multiple_of(2) && y.is_multiple_of(2),
+        }
+    }
+}
+
+/// Shades the tiles of a kind's pattern that fall on the position
+pub fn hatch(id: CellId, x: usize, y: usize, color: [u8; 3]) -> [u8; 3] {
+    match Hatch::of(id) {
+        Some(hatch) if hatch.covers(x, y) => color.map(|c| (c as f32 * HATCH_SHADE) as u8),
+        _ => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemes_remap_confusable_kinds() {
+        let registry = CellRegistry::default();
+        assert_eq!(
+            ColorScheme::Default.color(&registry, CellId::Oil),
+            registry.color(CellId::Oil)
+        );
+        for scheme in [ColorScheme::RedGreen, ColorScheme::BlueYellow] {
+            assert_ne!(
+                scheme.color(&registry, CellId::Oil),
+                registry.color(CellId::Oil)
+            );
+            assert_ne!(
+                scheme.color(&registry, CellId::Acid),
+                scheme.color(&registry, CellId::Sand)
+            );
+        }
+        // High contrast pushes colors towards the extremes
+        let [r, ..] = ColorScheme::HighContrast.color(&registry, CellId::Sand);
+        assert!(r > registry.color(CellId::Sand)[0]);
+    }
+
+    #[test]
+    fn schemes_cycle_through_all() {
+        let mut scheme = ColorScheme::Default;
+        for _ in 0..ColorScheme::ALL.len() {
+            scheme = scheme.next();
+        }
+        assert_eq!(scheme, ColorScheme::Default);
+    }
+
+    #[test]
+    fn hatching_shades_part_of_a_material() {
+        let shaded = (0..4)
+            .flat_map(|x| (0..4).map(move |y| (x, y)))
+            .filter(|&(x, y)| hatch(CellId::Oil, x, y, [100; 3]) != [100; 3])
+            .count();
+        assert_eq!(shaded, 8);
+        assert_eq!(hatch(CellId::Sand, 0, 0, [100; 3]), [100; 3]);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::cell::CellId;
+use crate::settings::storage;
+use crate::sim::{Grid, SimEvent};
+use crate::stats::SimStats;
+use crate::InWorld;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Name the achievements are stored under
+const FILE: &str = "achievements";
+
+/// Cells acid has to eat through for [`Achievement::Dissolver`]
+const DISSOLVED_GOAL: u64 = 10_000;
+
+/// Cells of water stacked in one column for [`Achievement::WaterColumn`]
+const WATER_COLUMN_GOAL: usize = 100;
+
+/// How long a toast stays up, in seconds
+const TOAST_TIME: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Achievement {
+    FirstExplosion,
+    Dissolver,
+    WaterColumn,
+}
+
+impl Achievement {
+    pub fn title(self) -> &'static str {
+        match self {
+            Achievement::FirstExplosion => "Kaboom",
+            Achievement::Dissolver => "Solvent",
+            Achievement::WaterColumn => "Water Tower",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Achievement::FirstExplosion => "Set off your first explosion",
+            Achievement::Dissolver => "Dissolve 10,000 cells with acid",
+            Achievement::WaterColumn => "Stack a column of 100 water cells",
+        }
+    }
+}
+
+/// The achievements unlocked so far and the progress towards the others, kept in
+/// `achievements.ron` in the config directory, or in local storage on the web
+#[derive(Resource, Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Achievements {
+    pub unlocked: Vec<Achievement>,
+    /// Cells dissolved by acid over every session
+    pub dissolved: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum AchievementsError {
+    #[error("could not access the achievements: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the achievements: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not write the achievements: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+impl Achievements {
+    /// Reads the saved achievements, starting afresh when there are none
+    pub fn load() -> Result<Self, AchievementsError> {
+        match storage::read(FILE)? {
+            Some(contents) => Ok(ron::from_str(&contents)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), AchievementsError> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        storage::write(FILE, &contents)?;
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    fn unlock(&mut self, achievement: Achievement, unlocked: &mut Vec<Achievement>) {
+        if !self.is_unlocked(achievement) {
+            self.unlocked.push(achievement);
+            unlocked.push(achievement);
+        }
+    }
+
+    /// Counts what happened during a tick towards the achievements, returning the ones it
+    /// unlocked
+    fn record(
+        &mut self,
+        exploded: bool,
+        dissolved: usize,
+        water_column: usize,
+    ) -> Vec<Achievement> {
+        let mut unlocked = Vec::new();
+        if exploded {
+            self.unlock(Achievement::FirstExplosion, &mut unlocked);
+        }
+        self.dissolved += dissolved as u64;
+        if self.dissolved >= DISSOLVED_GOAL {
+            self.unlock(Achievement::Dissolver, &mut unlocked);
+        }
+        if water_column >= WATER_COLUMN_GOAL {
+            self.unlock(Achievement::WaterColumn, &mut unlocked);
+        }
+        unlocked
+    }
+}
+
+/// The tallest unbroken run of a kind of cell in any column
+fn tallest_column(grid: &Grid, id: CellId) -> usize {
+    (0..grid.width())
+        .map(|x| {
+            (0..grid.height())
+                .scan(0, |run, y| {
+                    *run = if grid.get(x, y).is_some_and(|c| c.id == id) {
+                        *run + 1
+                    } else {
+                        0
+                    };
+                    Some(*run)
+                })
+                .max()
+                .unwrap_or(0)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// A notice of an unlocked achievement, dismissed once its timer runs out
+#[derive(Component)]
+struct Toast(Timer);
+
+/// Where toasts stack up
+#[derive(Component)]
+struct Toasts;
+
+pub struct AchievementsPlugin;
+
+/// This plugin loads the [`Achievements`] at startup, counts the simulation events and stats of
+/// each tick towards them, and pops up a toast and saves them whenever one is unlocked
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        let achievements = Achievements::load().unwrap_or_else(|err| {
+            warn!("{err}, starting without achievements");
+            Achievements::default()
+        });
+        app.insert_resource(achievements)
+            .add_systems(OnEnter(InWorld), spawn_toasts)
+            .add_systems(
+                Update,
+                (
+                    track_achievements.run_if(resource_changed::<SimStats>),
+                    dismiss_toasts,
+                )
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(OnExit(InWorld), save_achievements);
+    }
+}
+
+fn spawn_toasts(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(InWorld),
+        Toasts,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            right: Val::Px(12.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+    ));
+}
+
+fn track_achievements(
+    mut commands: Commands,
+    mut ev_sim: EventReader<SimEvent>,
+    stats: Res<SimStats>,
+    grid: Res<Grid>,
+    mut achievements: ResMut<Achievements>,
+    q_toasts: Query<Entity, With<Toasts>>,
+) {
+    let exploded = ev_sim
+        .read()
+        .any(|event| matches!(event, SimEvent::Exploded { .. }));
+    // Scanning the grid is only worth it while there is something to find
+    let water_column = if achievements.is_unlocked(Achievement::WaterColumn) {
+        0
+    } else {
+        tallest_column(&grid, CellId::Water)
+    };
+    let unlocked = achievements.record(exploded, stats.tick.dissolved, water_column);
+    if unlocked.is_empty() {
+        return;
+    }
+
+    if let Err(err) = achievements.save() {
+        error!("{err}");
+    }
+    for achievement in unlocked {
+        info!("Achievement unlocked: {}", achievement.title());
+        for toasts in &q_toasts {
+            commands.entity(toasts).with_child((
+                Toast(Timer::from_seconds(TOAST_TIME, TimerMode::Once)),
+                Text::new(format!(
+                    "Achievement unlocked: {}\n{}",
+                    achievement.title(),
+                    achievement.description()
+                )),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                Node {
+                    padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.6)),
+                BorderRadius::all(Val::Px(4.0)),
+            ));
+        }
+    }
+}
+
+fn dismiss_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_toasts: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in &mut q_toasts {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Keeps the progress made since the last unlock
+fn save_achievements(achievements: Res<Achievements>) {
+    if let Err(err) = achievements.save() {
+        error!("{err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn achievements_unlock_once() {
+        let mut achievements = Achievements::default();
+        assert_eq!(achievements.record(false, 9_999, 99), vec![]);
+        assert_eq!(
+            achievements.record(true, 1, 0),
+            vec![Achievement::FirstExplosion, Achievement::Dissolver]
+        );
+        assert_eq!(
+            achievements.record(true, 5, 100),
+            vec![Achievement::WaterColumn]
+        );
+        assert_eq!(achievements.record(true, 5, 100), vec![]);
+        assert_eq!(achievements.dissolved, 10_010);
+    }
+
+    #[test]
+    fn columns_only_count_unbroken_runs() {
+        let mut grid = Grid::new(3, 10);
+        for y in 0..10 {
+            if y != 4 {
+                grid.spawn(1, y, CellId::Water);
+            }
+        }
+        for y in 2..6 {
+            grid.spawn(2, y, CellId::Water);
+        }
+        assert_eq!(tallest_column(&grid, CellId::Water), 5);
+        assert_eq!(tallest_column(&grid, CellId::Sand), 0);
+    }
+}
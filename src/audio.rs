@@ -1,11 +1,486 @@
+use crate::cell::{CellId, Material};
+use crate::grid::{tiles_to_world, Brush, MainCamera, Poured};
+use crate::input::Action;
+use crate::loading::{MusicAssets, SoundAssets};
+use crate::settings::Settings;
+use crate::sim::{Grid, SimEvent};
+use crate::stats::SimStats;
+use crate::{GameState, InWorld};
 use bevy::prelude::*;
 use bevy_kira_audio::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Shortest time between two pouring sounds, so a held brush doesn't play one every frame
+const POUR_INTERVAL: f32 = 0.09;
+/// Number of cells placed at once that plays a pouring sound at full volume
+const LOUD_POUR: f32 = 64.0;
+
+/// Shortest time between two sounds of cells destroyed the same way
+const DESTRUCTION_INTERVAL: f32 = 0.12;
+/// Number of cells destroyed the same way at once that plays their sound at full volume
+const LOUD_DESTRUCTION: f32 = 32.0;
+/// How far sounds are panned toward the side of the view they come from, from 0 for not at all
+/// to 1 for all the way
+const STEREO_WIDTH: f64 = 0.8;
+/// How much quieter sounds get for each width of the view they are away from it
+const FALLOFF: f64 = 3.0;
+/// The volume sounds far out of view fade to, so they stay audible
+const QUIETEST: f64 = 0.15;
+
+/// Seconds the music takes to swell to the activity of the simulation
+const SWELL_TIME: f32 = 1.0;
+/// Seconds the music takes to calm down once the simulation does, longer so drums outlast the
+/// explosion that brought them in
+const CALM_TIME: f32 = 5.0;
+
+/// How much each volume changes per click in the settings
+pub const VOLUME_STEP: f32 = 0.1;
+
+/// The channel for short sound effects
+#[derive(Resource)]
+pub struct Effects;
+
+/// The channel for background music
+#[derive(Resource)]
+pub struct Music;
+
+/// Which of the volumes in [`Volumes`] to change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeKind {
+    Master,
+    Effects,
+    Music,
+}
+
+impl VolumeKind {
+    pub const ALL: [VolumeKind; 3] = [VolumeKind::Master, VolumeKind::Effects, VolumeKind::Music];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            VolumeKind::Master => "Master",
+            VolumeKind::Effects => "Effects",
+            VolumeKind::Music => "Music",
+        }
+    }
+}
+
+/// Volumes between 0 and 1, the master volume scaling the others
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Volumes {
+    pub master: f32,
+    pub effects: f32,
+    pub music: f32,
+    pub muted: bool,
+}
+
+impl Default for Volumes {
+    fn default() -> Self {
+        Self {
+            master: 0.8,
+            effects: 1.0,
+            music: 0.6,
+            muted: false,
+        }
+    }
+}
+
+impl Volumes {
+    pub fn get(&self, kind: VolumeKind) -> f32 {
+        match kind {
+            VolumeKind::Master => self.master,
+            VolumeKind::Effects => self.effects,
+            VolumeKind::Music => self.music,
+        }
+    }
+
+    /// Steps a volume by tenths, between silent and full
+    pub fn change(&mut self, kind: VolumeKind, steps: f32) {
+        let volume = match kind {
+            VolumeKind::Master => &mut self.master,
+            VolumeKind::Effects => &mut self.effects,
+            VolumeKind::Music => &mut self.music,
+        };
+        *volume =
+            ((*volume + steps * VOLUME_STEP).clamp(0.0, 1.0) / VOLUME_STEP).round() * VOLUME_STEP;
+    }
+
+    /// The volume a channel actually plays at
+    pub fn output(&self, kind: VolumeKind) -> f32 {
+        if self.muted {
+            0.0
+        } else if kind == VolumeKind::Master {
+            self.master
+        } else {
+            self.master * self.get(kind)
+        }
+    }
+}
+
+/// The sound of a cell being destroyed, which follows its material
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Destruction {
+    /// Something flammable burning up
+    Crackle,
+    /// Something solid eaten away
+    Sizzle,
+    /// A liquid or gas, or a flame put out
+    Hiss,
+}
+
+impl Destruction {
+    const ALL: [Destruction; 3] = [Destruction::Crackle, Destruction::Sizzle, Destruction::Hiss];
+
+    fn of(id: CellId) -> Self {
+        let data = id.data();
+        if data.flammable {
+            return Destruction::Crackle;
+        }
+        match data.material {
+            Material::Liquid | Material::Gas | Material::Fire | Material::Wind => Destruction::Hiss,
+            Material::Powder | Material::Solid | Material::Rigid | Material::Acid => {
+                Destruction::Sizzle
+            }
+        }
+    }
+
+    fn sound(self, sounds: &SoundAssets) -> Handle<AudioSource> {
+        match self {
+            Destruction::Crackle => sounds.crackle.clone(),
+            Destruction::Sizzle => sounds.sizzle.clone(),
+            Destruction::Hiss => sounds.hiss.clone(),
+        }
+    }
+}
+
+/// A layer of the music. They all loop in time together, each fading in as the simulation gets
+/// busy enough for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stem {
+    /// The calm pad, always playing
+    Pad,
+    /// A bass line for a world on the move
+    Pulse,
+    /// Percussion for big fires and explosions
+    Drums,
+}
+
+impl Stem {
+    const ALL: [Stem; 3] = [Stem::Pad, Stem::Pulse, Stem::Drums];
+
+    /// Activity, as in [`SimStats::activity`], at which the stem starts fading in and at which
+    /// it plays at full volume
+    fn activity(self) -> (f32, f32) {
+        match self {
+            Stem::Pad => (0.0, 0.0),
+            Stem::Pulse => (200.0, 2000.0),
+            Stem::Drums => (4000.0, 16000.0),
+        }
+    }
+
+    /// Volume of the stem with the simulation this busy
+    fn volume(self, activity: f32) -> f64 {
+        let (start, full) = self.activity();
+        if activity >= full {
+            1.0
+        } else {
+            ((activity - start) / (full - start)).clamp(0.0, 1.0) as f64
+        }
+    }
+
+    fn sound(self, music: &MusicAssets) -> Handle<AudioSource> {
+        match self {
+            Stem::Pad => music.ambient.clone(),
+            Stem::Pulse => music.pulse.clone(),
+            Stem::Drums => music.drums.clone(),
+        }
+    }
+}
+
+/// The music playing, by stem, and the activity it follows, eased so the stems fade
+#[derive(Resource, Default)]
+struct Score {
+    stems: Vec<(Stem, Handle<AudioInstance>)>,
+    activity: f32,
+}
+
+/// How a sound made somewhere in the world is heard from the camera: panned toward the side it
+/// comes from, and quieter the further out of view it is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Emitter {
+    /// From 0 on the left to 1 on the right
+    pub panning: f64,
+    /// Scale of the volume the sound plays at
+    pub attenuation: f64,
+}
+
+impl Emitter {
+    /// A sound made at a position in world space, heard by a camera seeing `view`
+    pub fn at(position: Vec2, view: Rect) -> Self {
+        let width = view.width().max(f32::EPSILON);
+        let across = ((position.x - view.center().x) / (width / 2.0)).clamp(-1.0, 1.0);
+        let outside = position.distance(position.clamp(view.min, view.max)) / width;
+        Self {
+            panning: 0.5 + across as f64 * STEREO_WIDTH / 2.0,
+            attenuation: (1.0 / (1.0 + outside as f64 * FALLOFF)).max(QUIETEST),
+        }
+    }
+
+    /// Plays an effect from here at the volume it has up close
+    pub fn play(
+        self,
+        effects: &AudioChannel<Effects>,
+        sound: Handle<AudioSource>,
+        volume: f64,
+        rate: f64,
+    ) {
+        effects
+            .play(sound)
+            .with_volume(volume * self.attenuation)
+            .with_playback_rate(rate)
+            .with_panning(self.panning);
+    }
+}
+
+/// The part of the world the camera sees, in world space
+pub fn camera_view(projection: &Projection, transform: &Transform) -> Option<Rect> {
+    let Projection::Orthographic(projection) = projection else {
+        return None;
+    };
+    let offset = transform.translation.truncate();
+    Some(Rect::from_corners(
+        projection.area.min + offset,
+        projection.area.max + offset,
+    ))
+}
 
 pub struct InternalAudioPlugin;
 
-// This plugin is responsible to control the game audio
+// This plugin is responsible to control the game audio, at the volumes in the settings. Cells
+// destroyed crackle, sizzle or hiss as their material does, panned to where they were on screen
+// and fainter off it. The music is layered, a pulse and then drums fading in over the calm pad as
+// the simulation gets busier.
 impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(AudioPlugin);
+        app.add_plugins(AudioPlugin)
+            .add_audio_channel::<Effects>()
+            .add_audio_channel::<Music>()
+            .init_resource::<Score>()
+            .add_systems(OnExit(GameState::Loading), start_music)
+            .add_systems(
+                Update,
+                (play_pour_sounds, play_destruction_sounds).run_if(in_state(InWorld)),
+            )
+            .add_systems(Update, follow_activity)
+            .add_systems(
+                Update,
+                toggle_mute.run_if(not(in_state(GameState::Settings))),
+            )
+            .add_systems(Update, apply_volumes.run_if(resource_changed::<Settings>));
+    }
+}
+
+fn start_music(
+    music_assets: Res<MusicAssets>,
+    music: Res<AudioChannel<Music>>,
+    mut score: ResMut<Score>,
+) {
+    score.stems = Stem::ALL
+        .into_iter()
+        .map(|stem| {
+            let instance = music
+                .play(stem.sound(&music_assets))
+                .looped()
+                .with_volume(stem.volume(0.0))
+                .handle();
+            (stem, instance)
+        })
+        .collect();
+}
+
+// Out of a game the world is still, and the music calms down with it
+fn follow_activity(
+    time: Res<Time>,
+    stats: Res<SimStats>,
+    in_world: Option<Res<State<InWorld>>>,
+    mut score: ResMut<Score>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    let activity = if in_world.is_some() {
+        stats.activity() as f32
+    } else {
+        0.0
+    };
+    let ease = if activity > score.activity {
+        SWELL_TIME
+    } else {
+        CALM_TIME
+    };
+    score.activity += (activity - score.activity) * (time.delta_secs() / ease).min(1.0);
+    for (stem, instance) in &score.stems {
+        if let Some(instance) = instances.get_mut(instance) {
+            instance.set_volume(stem.volume(score.activity), AudioTween::default());
+        }
+    }
+}
+
+fn toggle_mute(keyboard_input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<Settings>) {
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::Mute)
+    {
+        settings.volumes.muted = !settings.volumes.muted;
+    }
+}
+
+fn apply_volumes(
+    settings: Res<Settings>,
+    audio: Res<Audio>,
+    effects: Res<AudioChannel<Effects>>,
+    music: Res<AudioChannel<Music>>,
+) {
+    let volumes = &settings.volumes;
+    audio.set_volume(volumes.output(VolumeKind::Master) as f64);
+    effects.set_volume(volumes.output(VolumeKind::Effects) as f64);
+    music.set_volume(volumes.output(VolumeKind::Music) as f64);
+}
+
+/// The sound for placing a kind of cell, with the playback rate that gives it its pitch
+fn pour_sound(sounds: &SoundAssets, id: CellId) -> (Handle<AudioSource>, f64) {
+    match id.data().material {
+        Material::Powder => (sounds.pour_sand.clone(), 1.0),
+        Material::Liquid => (sounds.pour_water.clone(), 1.0),
+        Material::Acid => (sounds.pour_water.clone(), 1.25),
+        Material::Solid | Material::Rigid => (sounds.place_stone.clone(), 1.0),
+        Material::Gas | Material::Wind => (sounds.pour_sand.clone(), 1.6),
+        Material::Fire => (sounds.pour_sand.clone(), 0.7),
+    }
+}
+
+fn play_pour_sounds(
+    time: Res<Time>,
+    mut ev_poured: EventReader<Poured>,
+    brush: Res<Brush>,
+    sounds: Res<SoundAssets>,
+    effects: Res<AudioChannel<Effects>>,
+    mut since_last: Local<f32>,
+) {
+    *since_last += time.delta_secs();
+    let Some(poured) = ev_poured.read().last() else {
+        return;
+    };
+    if *since_last < POUR_INTERVAL {
+        return;
+    }
+    *since_last = 0.0;
+
+    // Wider brushes sound deeper, and bigger pours louder
+    let (sound, rate) = pour_sound(&sounds, poured.id);
+    let rate = rate / (1.0 + brush.radius as f64 / 16.0) * thread_rng().gen_range(0.95..1.05);
+    let volume = 0.15 + 0.35 * (poured.count as f32 / LOUD_POUR).min(1.0);
+    effects
+        .play(sound)
+        .with_volume(volume as f64)
+        .with_playback_rate(rate);
+}
+
+fn play_destruction_sounds(
+    time: Res<Time>,
+    mut ev_sim: EventReader<SimEvent>,
+    grid: Res<Grid>,
+    q_camera: Single<(&Projection, &Transform), With<MainCamera>>,
+    sounds: Res<SoundAssets>,
+    effects: Res<AudioChannel<Effects>>,
+    mut since_last: Local<[f32; Destruction::ALL.len()]>,
+) {
+    for since_last in since_last.iter_mut() {
+        *since_last += time.delta_secs();
+    }
+    // How many cells were destroyed each way, and the sum of their positions
+    let mut heard = [(0, Vec2::ZERO); Destruction::ALL.len()];
+    for event in ev_sim.read() {
+        if let SimEvent::Destroyed { x, y, id } = *event {
+            let (count, positions) = &mut heard[Destruction::of(id) as usize];
+            *count += 1;
+            *positions += tiles_to_world(&grid, x, y);
+        }
+    }
+    let (projection, transform) = *q_camera;
+    let Some(view) = camera_view(projection, transform) else {
+        return;
+    };
+
+    for destruction in Destruction::ALL {
+        let (count, positions) = heard[destruction as usize];
+        let since_last = &mut since_last[destruction as usize];
+        if count == 0 || *since_last < DESTRUCTION_INTERVAL {
+            continue;
+        }
+        *since_last = 0.0;
+        let volume = 0.1 + 0.4 * (count as f32 / LOUD_DESTRUCTION).min(1.0);
+        Emitter::at(positions / count as f32, view).play(
+            &effects,
+            destruction.sound(&sounds),
+            volume as f64,
+            thread_rng().gen_range(0.9..1.1),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn master_volume_scales_the_channels() {
+        let mut volumes = Volumes {
+            master: 0.5,
+            effects: 0.4,
+            ..default()
+        };
+        assert_eq!(volumes.output(VolumeKind::Effects), 0.2);
+        volumes.change(VolumeKind::Master, 10.0);
+        assert_eq!(volumes.output(VolumeKind::Effects), 0.4);
+        volumes.muted = true;
+        assert_eq!(volumes.output(VolumeKind::Master), 0.0);
+    }
+
+    #[test]
+    fn destruction_sounds_follow_the_material_and_side() {
+        assert_eq!(Destruction::of(CellId::Wood), Destruction::Crackle);
+        assert_eq!(Destruction::of(CellId::Oil), Destruction::Crackle);
+        assert_eq!(Destruction::of(CellId::Stone), Destruction::Sizzle);
+        assert_eq!(Destruction::of(CellId::Fire), Destruction::Hiss);
+    }
+
+    #[test]
+    fn stems_fade_in_as_the_simulation_gets_busier() {
+        assert_eq!(Stem::Pad.volume(0.0), 1.0);
+        assert_eq!(Stem::Pulse.volume(0.0), 0.0);
+        assert_eq!(Stem::Drums.volume(0.0), 0.0);
+        let busy = 1100.0;
+        assert_eq!(Stem::Pulse.volume(busy), 0.5);
+        assert_eq!(Stem::Drums.volume(busy), 0.0);
+        let ablaze = 20000.0;
+        assert!(Stem::ALL.iter().all(|stem| stem.volume(ablaze) == 1.0));
+    }
+
+    #[test]
+    fn sounds_out_of_view_are_panned_and_fainter() {
+        let view = Rect::new(-100.0, -50.0, 100.0, 50.0);
+        let center = Emitter::at(Vec2::ZERO, view);
+        assert_eq!(center.panning, 0.5);
+        assert_eq!(center.attenuation, 1.0);
+        let right = Emitter::at(Vec2::new(100.0, 0.0), view);
+        assert!(right.panning > 0.8);
+        assert_eq!(right.attenuation, 1.0);
+
+        let near = Emitter::at(Vec2::new(-300.0, 0.0), view);
+        let far = Emitter::at(Vec2::new(-3000.0, 0.0), view);
+        assert!(near.panning < 0.2);
+        assert_eq!(far.panning, near.panning);
+        assert!(far.attenuation < near.attenuation && near.attenuation < 1.0);
+        assert_eq!(far.attenuation, QUIETEST);
     }
 }
@@ -0,0 +1,303 @@
+use crate::cell::CellId;
+use crate::grid::{SimRng, TickTimer, WorldReset};
+use crate::level::{end_level, LevelHud};
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::time::Duration;
+
+/// Seconds between the end of one disaster and the start of the next
+const DISASTER_INTERVAL: f32 = 12.0;
+
+/// Seconds a disaster keeps injecting cells
+const DISASTER_DURATION: f32 = 4.0;
+
+const WAVES: u32 = 8;
+
+/// The cell kind the player has to protect
+const STRUCTURE: CellId = CellId::Timber;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum Disaster {
+    Rain,
+    Meteor,
+    AcidLeak,
+}
+
+impl Disaster {
+    pub const ALL: [Disaster; 3] = [Disaster::Rain, Disaster::Meteor, Disaster::AcidLeak];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Disaster::Rain => "Rain",
+            Disaster::Meteor => "Meteor",
+            Disaster::AcidLeak => "Acid leak",
+        }
+    }
+
+    /// Injects cells from the top edge of the grid for one simulation tick
+    pub fn inject(&self, grid: &mut Grid, origin: usize, rng: &mut impl Rng) {
+        let width = grid.width();
+        match self {
+            Disaster::Rain => {
+                for _ in 0..width / 40 {
+                    grid.spawn(rng.gen_range(0..width), 0, CellId::Water);
+                }
+            }
+            Disaster::Meteor => {
+                // A falling clump of stone trailing burning oil
+                let radius = 4isize;
+                for dx in -radius..=radius {
+                    for dy in 0..=radius {
+                        let (x, y) = (origin as isize + dx, dy);
+                        if dx * dx + (dy - radius).pow(2) > radius * radius
+                            || !grid.in_bounds(x, y)
+                            || rng.gen::<f32>() > 0.3
+                        {
+                            continue;
+                        }
+                        let id = match rng.gen_range(0..3) {
+                            0 => CellId::Stone,
+                            1 => CellId::Oil,
+                            _ => CellId::Fire,
+                        };
+                        grid.spawn(x as usize, y as usize, id);
+                    }
+                }
+            }
+            Disaster::AcidLeak => {
+                for x in origin.saturating_sub(2)..(origin + 3).min(width) {
+                    if rng.gen::<f32>() < 0.5 {
+                        grid.spawn(x, 0, CellId::Acid);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// State of a running challenge
+#[derive(Resource, Debug)]
+pub struct Challenge {
+    pub wave: u32,
+    pub schedule: Timer,
+    pub active: Option<(Disaster, usize, Timer)>,
+    pub initial_structure: usize,
+    pub finished: bool,
+}
+
+impl Challenge {
+    /// Score for the current state of the grid: every surviving structure cell is worth 10 points
+    pub fn score(&self, grid: &Grid) -> usize {
+        count_structure(grid) * 10
+    }
+}
+
+fn count_structure(grid: &Grid) -> usize {
+    grid.iter()
+        .filter(|&(_, _, cell)| cell.is_some_and(|c| c.id == STRUCTURE))
+        .count()
+}
+
+/// Builds a two-storey wooden house on a stone floor for the player to defend
+pub fn build_arena(grid: &mut Grid) {
+    let (width, height) = (grid.width(), grid.height());
+    grid.clear();
+
+    let mut fill = |x0: usize, y0: usize, x1: usize, y1: usize, id: CellId| {
+        for x in x0..x1.min(width) {
+            for y in y0..y1.min(height) {
+                grid.spawn(x, y, id);
+            }
+        }
+    };
+
+    let center = width / 2;
+    let floor = height - 4;
+    fill(0, floor, width, height, CellId::Stone);
+    fill(center - 30, floor - 30, center - 27, floor, STRUCTURE);
+    fill(center + 27, floor - 30, center + 30, floor, STRUCTURE);
+    // Roof resting on the walls with short eaves, and an upper floor spanning between them
+    fill(center - 33, floor - 34, center + 33, floor - 30, STRUCTURE);
+    fill(center - 27, floor - 14, center + 27, floor - 12, STRUCTURE);
+}
+
+/// Starts a challenge on a freshly built arena
+#[derive(Event)]
+pub struct StartChallenge;
+
+#[derive(Component)]
+pub struct ChallengeHud;
+
+pub struct ChallengePlugin;
+
+/// This plugin runs the challenge mode, where timed disasters hit a structure the player has to protect
+impl Plugin for ChallengePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<StartChallenge>()
+            .add_systems(Update, start_challenge.run_if(on_event::<StartChallenge>))
+            .add_systems(Update, cancel_challenge.run_if(on_event::<WorldReset>))
+            .add_systems(
+                Update,
+                (schedule_disasters, update_challenge_hud)
+                    .run_if(in_state(InWorld).and(resource_exists::<Challenge>)),
+            );
+    }
+}
+
+fn start_challenge(
+    mut commands: Commands,
+    mut ev_start_challenge: EventReader<StartChallenge>,
+    mut grid: ResMut<Grid>,
+    q_level_hud: Query<Entity, With<LevelHud>>,
+    q_challenge_hud: Query<Entity, With<ChallengeHud>>,
+    mut ev_world_reset: EventWriter<WorldReset>,
+) {
+    if ev_start_challenge.read().last().is_none() {
+        return;
+    }
+
+    build_arena(&mut grid);
+    end_level(&mut commands, &q_level_hud);
+    end_challenge(&mut commands, &q_challenge_hud);
+    ev_world_reset.write(WorldReset::Challenge);
+    commands.insert_resource(Challenge {
+        wave: 0,
+        schedule: Timer::from_seconds(DISASTER_INTERVAL, TimerMode::Once),
+        active: None,
+        initial_structure: count_structure(&grid),
+        finished: false,
+    });
+    commands.spawn((
+        ChallengeHud,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+    ));
+}
+
+fn schedule_disasters(
+    time: Res<Time>,
+    tick_timer: Res<TickTimer>,
+    mut challenge: ResMut<Challenge>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+) {
+    let Challenge {
+        wave,
+        schedule,
+        active,
+        finished,
+        ..
+    } = &mut *challenge;
+    if *finished {
+        return;
+    }
+
+    if let Some((disaster, origin, timer)) = active {
+        timer.tick(time.delta());
+        if tick_timer.just_finished() {
+            disaster.inject(&mut grid, *origin, &mut **rng);
+        }
+        if timer.finished() {
+            *active = None;
+            schedule.reset();
+        }
+        return;
+    }
+
+    schedule.tick(time.delta());
+    if schedule.finished() {
+        if *wave == WAVES {
+            *finished = true;
+            return;
+        }
+
+        *wave += 1;
+        let disaster = *Disaster::ALL.choose(&mut **rng).unwrap();
+        let origin = rng.gen_range(0..grid.width());
+        *active = Some((
+            disaster,
+            origin,
+            Timer::new(Duration::from_secs_f32(DISASTER_DURATION), TimerMode::Once),
+        ));
+    }
+}
+
+fn update_challenge_hud(
+    grid: Res<Grid>,
+    challenge: Res<Challenge>,
+    mut q_hud: Query<&mut Text, With<ChallengeHud>>,
+) {
+    let surviving = count_structure(&grid);
+    let percent = surviving * 100 / challenge.initial_structure.max(1);
+
+    let status = if challenge.finished {
+        format!(
+            "Challenge over! Final score: {}\nPress Escape for the menu",
+            challenge.score(&grid)
+        )
+    } else if let Some((disaster, _, _)) = &challenge.active {
+        format!("Wave {}/{WAVES}: {}!", challenge.wave, disaster.name())
+    } else {
+        format!(
+            "Wave {}/{WAVES}, next disaster in {:.0}s",
+            challenge.wave,
+            challenge.schedule.remaining_secs().ceil()
+        )
+    };
+
+    for mut hud in &mut q_hud {
+        hud.0 = format!(
+            "{status}\nStructure: {percent}%  Score: {}",
+            challenge.score(&grid)
+        );
+    }
+}
+
+/// Ends the challenge once another world replaces its arena
+fn cancel_challenge(
+    mut commands: Commands,
+    mut ev_world_reset: EventReader<WorldReset>,
+    q_hud: Query<Entity, With<ChallengeHud>>,
+) {
+    if ev_world_reset
+        .read()
+        .last()
+        .is_some_and(|&reset| reset != WorldReset::Challenge)
+    {
+        end_challenge(&mut commands, &q_hud);
+    }
+}
+
+/// Ends a running challenge, removing its HUD
+pub fn end_challenge(commands: &mut Commands, q_hud: &Query<Entity, With<ChallengeHud>>) {
+    for entity in q_hud {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<Challenge>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_house_stands_on_its_own() {
+        let mut grid = Grid::default();
+        build_arena(&mut grid);
+        let before = grid.clone();
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(count_structure(&grid), count_structure(&before));
+        assert_eq!(grid, before);
+    }
+}
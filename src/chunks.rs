@@ -0,0 +1,330 @@
+use crate::grid::{MainCamera, WorldReset, DATA_SIZE};
+use crate::pointer::has_window;
+use crate::sim::{Grid, CHUNK_SIZE};
+use crate::InWorld;
+use bevy::input::mouse::AccumulatedMouseMotion;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+/// Side of a chunk in world units
+const CHUNK_WORLD_SIZE: f32 = CHUNK_SIZE as f32 * DATA_SIZE;
+
+/// Where the grid lies in the unbounded world: the chunk its top left corner is on. Chunk rows
+/// grow downwards like the rows of the grid.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WorldOrigin(pub IVec2);
+
+/// The grid was scrolled by a chunk, so whatever was placed by grid position is out of place
+#[derive(Event, Debug, Clone, Copy)]
+pub struct WorldShifted;
+
+/// Keeps the chunks that scrolled out of the grid, in files under the data directory or in memory
+/// on the web. Each run of the game keeps its own, so several can be open at once.
+#[cfg(not(target_arch = "wasm32"))]
+mod storage {
+    use bevy::math::IVec2;
+    use std::io;
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn dir() -> Option<PathBuf> {
+        static SESSION: OnceLock<String> = OnceLock::new();
+        let session = SESSION.get_or_init(|| {
+            let started = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            format!("{}-{}", std::process::id(), started.as_millis())
+        });
+        dirs::data_local_dir().map(|dir| dir.join("powder-keg").join("chunks").join(session))
+    }
+
+    fn path(chunk: IVec2) -> Option<PathBuf> {
+        dir().map(|dir| dir.join(format!("{}_{}.ron", chunk.x, chunk.y)))
+    }
+
+    pub fn read(chunk: IVec2) -> io::Result<Option<String>> {
+        let Some(path) = path(chunk) else {
+            return Ok(None);
+        };
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn write(chunk: IVec2, contents: Option<&str>) -> io::Result<()> {
+        let Some(path) = path(chunk) else {
+            return Ok(());
+        };
+        match contents {
+            Some(contents) => {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(path, contents)
+            }
+            None => match std::fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    pub fn clear() -> io::Result<()> {
+        let Some(dir) = dir() else {
+            return Ok(());
+        };
+        match std::fs::remove_dir_all(dir) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod storage {
+    use bevy::math::IVec2;
+    use std::collections::HashMap;
+    use std::io;
+    use std::sync::Mutex;
+
+    static CHUNKS: Mutex<Option<HashMap<IVec2, String>>> = Mutex::new(None);
+
+    pub fn read(chunk: IVec2) -> io::Result<Option<String>> {
+        let chunks = CHUNKS.lock().unwrap();
+        Ok(chunks
+            .as_ref()
+            .and_then(|chunks| chunks.get(&chunk).cloned()))
+    }
+
+    pub fn write(chunk: IVec2, contents: Option<&str>) -> io::Result<()> {
+        let mut chunks = CHUNKS.lock().unwrap();
+        let chunks = chunks.get_or_insert_with(HashMap::new);
+        match contents {
+            Some(contents) => chunks.insert(chunk, contents.to_string()),
+            None => chunks.remove(&chunk),
+        };
+        Ok(())
+    }
+
+    pub fn clear() -> io::Result<()> {
+        *CHUNKS.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+fn save_chunk(chunk: IVec2, cells: &Grid) {
+    let empty = cells.iter().all(|(_, _, cell)| cell.is_none());
+    let result = match empty {
+        true => storage::write(chunk, None),
+        false => ron::to_string(cells)
+            .map_err(std::io::Error::other)
+            .and_then(|contents| storage::write(chunk, Some(&contents))),
+    };
+    if let Err(err) = result {
+        warn!("Could not save chunk {chunk}: {err}");
+    }
+}
+
+/// A chunk saved by [`save_chunk`], or an empty one when it was never visited
+fn load_chunk(chunk: IVec2) -> Grid {
+    let loaded = storage::read(chunk).and_then(|contents| {
+        contents
+            .map(|contents| ron::from_str::<Grid>(&contents).map_err(std::io::Error::other))
+            .transpose()
+    });
+    match loaded {
+        Ok(Some(cells)) if (cells.width(), cells.height()) == (CHUNK_SIZE, CHUNK_SIZE) => cells,
+        Ok(None) => Grid::new(CHUNK_SIZE, CHUNK_SIZE),
+        Ok(Some(_)) => {
+            warn!("Chunk {chunk} has the wrong size");
+            Grid::new(CHUNK_SIZE, CHUNK_SIZE)
+        }
+        Err(err) => {
+            warn!("Could not load chunk {chunk}: {err}");
+            Grid::new(CHUNK_SIZE, CHUNK_SIZE)
+        }
+    }
+}
+
+/// Scrolls the grid by one chunk towards `step`: the chunks on the opposite edge are saved and
+/// dropped, and the ones coming in on the `step` edge are loaded
+fn scroll(
+    grid: &mut Grid,
+    origin: &mut WorldOrigin,
+    step: IVec2,
+    mut save: impl FnMut(IVec2, &Grid),
+    mut load: impl FnMut(IVec2) -> Grid,
+) {
+    let chunks = IVec2::new(
+        grid.width().div_ceil(CHUNK_SIZE) as i32,
+        grid.height().div_ceil(CHUNK_SIZE) as i32,
+    );
+    let edge = |step: i32, count: i32| if step > 0 { 0 } else { count - 1 };
+
+    for x in 0..chunks.x {
+        for y in 0..chunks.y {
+            let leaving = (step.x != 0 && x == edge(step.x, chunks.x))
+                || (step.y != 0 && y == edge(step.y, chunks.y));
+            if leaving {
+                let (tx, ty) = (x as usize * CHUNK_SIZE, y as usize * CHUNK_SIZE);
+                let cells = grid.region(tx..tx + CHUNK_SIZE, ty..ty + CHUNK_SIZE);
+                save(origin.0 + IVec2::new(x, y), &cells);
+            }
+        }
+    }
+
+    let offset = -step * CHUNK_SIZE as i32;
+    grid.shift(offset.x as isize, offset.y as isize);
+    origin.0 += step;
+
+    for x in 0..chunks.x {
+        for y in 0..chunks.y {
+            let entering = (step.x != 0 && x == edge(-step.x, chunks.x))
+                || (step.y != 0 && y == edge(-step.y, chunks.y));
+            if entering {
+                let cells = load(origin.0 + IVec2::new(x, y));
+                grid.paste(x as usize * CHUNK_SIZE, y as usize * CHUNK_SIZE, &cells);
+            }
+        }
+    }
+}
+
+pub struct ChunkPlugin;
+
+/// This plugin makes the grid a window onto an unbounded world. Dragging with the right mouse
+/// button pans the view, and once it moves past half a chunk the grid scrolls by a chunk: the
+/// chunks leaving it are saved and the ones entering it are loaded, empty the first time. Only the
+/// grid is simulated. Bodies and creatures don't scroll along and are cleared instead. The saved
+/// chunks belong to the world on the grid and are dropped along with it, or on exit.
+impl Plugin for ChunkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldOrigin>()
+            .add_event::<WorldShifted>()
+            .add_systems(
+                Update,
+                (pan_view.run_if(has_window), stream_chunks)
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(Update, forget_chunks.run_if(on_event::<WorldReset>))
+            .add_systems(Last, clear_chunks.run_if(on_event::<AppExit>));
+    }
+}
+
+fn pan_view(
+    buttons: Res<ButtonInput<MouseButton>>,
+    motion: Res<AccumulatedMouseMotion>,
+    q_camera: Single<(&Projection, &mut Transform), With<MainCamera>>,
+    q_window: Single<&Window, With<PrimaryWindow>>,
+) {
+    if !buttons.pressed(MouseButton::Right) {
+        return;
+    }
+    let (projection, mut transform) = q_camera.into_inner();
+    let Projection::Orthographic(projection) = projection else {
+        return;
+    };
+    let world_per_pixel = projection.area.width() / q_window.width();
+    transform.translation.x -= motion.delta.x * world_per_pixel;
+    transform.translation.y += motion.delta.y * world_per_pixel;
+}
+
+fn stream_chunks(
+    q_camera: Single<(&Projection, &mut Transform), With<MainCamera>>,
+    mut grid: ResMut<Grid>,
+    mut origin: ResMut<WorldOrigin>,
+    mut ev_world_shifted: EventWriter<WorldShifted>,
+) {
+    let (projection, mut transform) = q_camera.into_inner();
+    let Projection::Orthographic(projection) = projection else {
+        return;
+    };
+    // The view pans freely over a grid larger than it, and only scrolls it at the edges
+    let slack = (Vec2::new(grid.width() as f32, grid.height() as f32) * DATA_SIZE
+        - projection.area.size())
+    .max(Vec2::ZERO)
+        / 2.0;
+    let past_half = |offset: f32, slack: f32| {
+        if offset > slack + CHUNK_WORLD_SIZE / 2.0 {
+            1
+        } else if offset < -slack - CHUNK_WORLD_SIZE / 2.0 {
+            -1
+        } else {
+            0
+        }
+    };
+    // World y grows upwards, chunk rows downwards
+    let offset = transform.translation.truncate();
+    let step = IVec2::new(past_half(offset.x, slack.x), -past_half(offset.y, slack.y));
+    if step == IVec2::ZERO {
+        return;
+    }
+    scroll(&mut grid, &mut origin, step, save_chunk, load_chunk);
+    transform.translation.x -= step.x as f32 * CHUNK_WORLD_SIZE;
+    transform.translation.y += step.y as f32 * CHUNK_WORLD_SIZE;
+    ev_world_shifted.write(WorldShifted);
+}
+
+fn clear_chunks() {
+    if let Err(err) = storage::clear() {
+        warn!("Could not clear the saved chunks: {err}");
+    }
+}
+
+fn forget_chunks(
+    mut origin: ResMut<WorldOrigin>,
+    mut q_camera: Single<&mut Transform, With<MainCamera>>,
+) {
+    clear_chunks();
+    *origin = WorldOrigin::default();
+    q_camera.translation.x = 0.0;
+    q_camera.translation.y = 0.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    #[test]
+    fn scrolling_away_and_back_restores_the_grid() {
+        let mut grid = Grid::new(CHUNK_SIZE * 3, CHUNK_SIZE * 2);
+        for x in 0..grid.width() {
+            grid.spawn(x, x % grid.height(), CellId::Stone);
+        }
+        let before = grid.clone();
+        let mut origin = WorldOrigin::default();
+        let saved = RefCell::new(HashMap::new());
+        let mut scroll_by = |grid: &mut Grid, step| {
+            scroll(
+                grid,
+                &mut origin,
+                step,
+                |chunk, cells| {
+                    saved.borrow_mut().insert(chunk, cells.clone());
+                },
+                |chunk| {
+                    saved
+                        .borrow()
+                        .get(&chunk)
+                        .cloned()
+                        .unwrap_or_else(|| Grid::new(CHUNK_SIZE, CHUNK_SIZE))
+                },
+            );
+            origin.0
+        };
+
+        scroll_by(&mut grid, IVec2::new(1, 0));
+        assert_eq!(scroll_by(&mut grid, IVec2::new(0, -1)), IVec2::new(1, -1));
+        assert_eq!(grid.get(0, CHUNK_SIZE), before.get(CHUNK_SIZE, 0));
+
+        scroll_by(&mut grid, IVec2::new(0, 1));
+        assert_eq!(scroll_by(&mut grid, IVec2::new(-1, 0)), IVec2::ZERO);
+        assert_eq!(grid, before);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::sim::{CHUNK_SIZE, GRID_HEIGHT, GRID_WIDTH, MAX_TILES};
+use bevy::prelude::*;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Ticks simulated by `--headless` unless `--ticks` says otherwise
+const HEADLESS_TICKS: u64 = 1000;
+
+pub const USAGE: &str = "\
+Usage: powder-keg [options]
+
+  --window <width>x<height>   Size of the window in pixels
+  --world <width>x<height>    Size of the grid in cells, larger than the screen if need be,
+                              in multiples of 20 so it splits into whole chunks
+  --seed <number>             Seed of the simulation, for runs that can be reproduced
+  --level <file>              Start in a .level.ron file instead of the menu
+  --replay <file>             Play back a replay recorded with --record-replay
+  --record-replay <file>      Record the session into a replay, written on exit
+  --headless                  Simulate without a window and print the cells left at the end,
+                              starting from the level, the replay or a generated world
+  --ticks <number>            Ticks to simulate with --headless, 1000 by default
+  --bench-sim                 Time the simulation on its worst cases
+  --host <port>               Share the world with players joining on the port
+  --join <address>            Join a world shared with --host
+  --spectate <port>           Stream the grid to spectators on the port
+  --watch <url>               Watch a grid streamed with --spectate
+  --help                      Show this message";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CliError {
+    #[error("unknown option {0}")]
+    Unknown(String),
+    #[error("{0} needs a value")]
+    Missing(String),
+    #[error("could not read {value:?} given to {flag}")]
+    Invalid { flag: String, value: String },
+}
+
+/// How the game was launched, read from the command line
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    /// Size of the window in logical pixels
+    pub window: Option<(u32, u32)>,
+    /// Size of the grid in cells
+    pub world: Option<(usize, usize)>,
+    /// Seed of the simulation's random numbers, random when not given
+    pub seed: Option<u64>,
+    pub level: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub record_replay: Option<PathBuf>,
+    pub headless: bool,
+    pub ticks: Option<u64>,
+    pub bench_sim: bool,
+    pub host: Option<u16>,
+    pub join: Option<String>,
+    pub spectate: Option<u16>,
+    pub watch: Option<String>,
+    pub help: bool,
+}
+
+fn parse_size<T: std::str::FromStr + PartialEq + Default>(size: &str) -> Option<(T, T)> {
+    let (width, height) = size.split_once('x')?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+    (size.0 != T::default() && size.1 != T::default()).then_some(size)
+}
+
+/// A size for the grid, which scrolls by whole chunks
+fn parse_world(size: &str) -> Option<(usize, usize)> {
+    parse_size(size).filter(|&(width, height): &(usize, usize)| {
+        width % CHUNK_SIZE == 0
+            && height % CHUNK_SIZE == 0
+            && width
+                .checked_mul(height)
+                .is_some_and(|tiles| tiles <= MAX_TILES)
+    })
+}
+
+impl Options {
+    /// Reads the options out of the arguments, without the name of the program
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or_else(|| CliError::Missing(flag.clone()));
+            match flag.as_str() {
+                "--window" => options.window = Some(read(&flag, value()?, parse_size)?),
+                "--world" => options.world = Some(read(&flag, value()?, parse_world)?),
+                "--seed" => options.seed = Some(read(&flag, value()?, |v| v.parse().ok())?),
+                "--level" => options.level = Some(value()?.into()),
+                "--replay" => options.replay = Some(value()?.into()),
+                "--record-replay" => options.record_replay = Some(value()?.into()),
+                "--headless" => options.headless = true,
+                "--ticks" => options.ticks = Some(read(&flag, value()?, |v| v.parse().ok())?),
+                "--bench-sim" => options.bench_sim = true,
+                "--host" => options.host = Some(read(&flag, value()?, |v| v.parse().ok())?),
+                "--join" => options.join = Some(value()?),
+                "--spectate" => options.spectate = Some(read(&flag, value()?, |v| v.parse().ok())?),
+                "--watch" => options.watch = Some(value()?),
+                "--help" | "-h" => options.help = true,
+                _ => return Err(CliError::Unknown(flag)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Reads the options the game was started with
+    pub fn from_env() -> Result<Self, CliError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    /// Size of the grid, the default one unless `--world` was given
+    pub fn world_size(&self) -> (usize, usize) {
+        self.world.unwrap_or((GRID_WIDTH, GRID_HEIGHT))
+    }
+
+    pub fn headless_ticks(&self) -> u64 {
+        self.ticks.unwrap_or(HEADLESS_TICKS)
+    }
+}
+
+fn read<T>(flag: &str, value: String, parse: impl Fn(&str) -> Option<T>) -> Result<T, CliError> {
+    parse(&value).ok_or_else(|| CliError::Invalid {
+        flag: flag.to_string(),
+        value,
+    })
+}
+
+/// The world a headless run starts from: the replay's, the level's or a generated one
+#[cfg(not(target_arch = "wasm32"))]
+fn headless_world(
+    options: &Options,
+    replay: Option<&crate::replay::Replay>,
+    seed: u64,
+) -> Result<crate::sim::Grid, String> {
+    use crate::sim::Grid;
+    use crate::worldgen::{generate, WorldGenSettings};
+
+    let (width, height) = options.world_size();
+    if let Some(replay) = replay {
+        // Without the game's cell definitions, the grid keeps the built-in spread chances
+        let grid = replay
+            .start((width, height))
+            .map_err(|err| err.to_string())?;
+        replay.check(&grid).map_err(|err| err.to_string())?;
+        return Ok(grid);
+    }
+    let mut grid = Grid::new(width, height);
+    match &options.level {
+        Some(path) => crate::level::read(path)
+            .map_err(|err| err.to_string())?
+            .build(&mut grid),
+        None => generate(&mut grid, seed, &WorldGenSettings::default()),
+    }
+    Ok(grid)
+}
+
+/// Simulates without a window as the options say, and prints the cells of each kind left at the
+/// end
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_headless(options: &Options) -> Result<(), String> {
+    use crate::grid::SimRng;
+    use crate::replay::Replay;
+    use rand::prelude::*;
+
+    let replay = match &options.replay {
+        Some(path) => Some(Replay::load(path).map_err(|err| err.to_string())?),
+        None => None,
+    };
+    let seed = replay
+        .as_ref()
+        .map(|replay| replay.header.seed)
+        .or(options.seed)
+        .unwrap_or_else(|| thread_rng().gen());
+    let mut grid = headless_world(options, replay.as_ref(), seed)?;
+    // A replay plays for as long as it was recorded unless told otherwise
+    let ticks = match (&replay, options.ticks) {
+        (Some(replay), None) => replay.ticks,
+        _ => options.headless_ticks(),
+    };
+    let start = std::time::Instant::now();
+    for tick in 0..ticks {
+        match &replay {
+            Some(replay) => replay.step(&mut grid, tick),
+            None => {
+                grid.step_with(Default::default(), tick, &mut SimRng::for_tick(seed, tick));
+            }
+        }
+    }
+    println!(
+        "Simulated {ticks} ticks with seed {seed} in {:.2}s",
+        start.elapsed().as_secs_f64()
+    );
+    let census = grid.census();
+    for id in crate::cell::CellId::ALL {
+        let count = census.count(id);
+        if count > 0 {
+            println!("{:>16}: {count}", id.data().name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, CliError> {
+        Options::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let options =
+            parse("--world 2040x1020 --seed 7 --level basin.level.ron --headless --ticks 50")
+                .unwrap();
+        assert_eq!(options.world_size(), (2040, 1020));
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.level, Some(PathBuf::from("basin.level.ron")));
+        assert!(options.headless);
+        assert_eq!(options.headless_ticks(), 50);
+        assert_eq!(parse("").unwrap(), Options::default());
+        assert_eq!(
+            parse("--window 1920x1080").unwrap().window,
+            Some((1920, 1080))
+        );
+    }
+
+    #[test]
+    fn bad_options_are_rejected() {
+        assert_eq!(
+            parse("--fast"),
+            Err(CliError::Unknown("--fast".to_string()))
+        );
+        assert_eq!(
+            parse("--seed"),
+            Err(CliError::Missing("--seed".to_string()))
+        );
+        assert!(matches!(
+            parse("--world 2048"),
+            Err(CliError::Invalid { .. })
+        ));
+        assert!(matches!(
+            parse("--world 0x10"),
+            Err(CliError::Invalid { .. })
+        ));
+        // Only whole chunks scroll in and out
+        assert!(matches!(
+            parse("--world 2048x1024"),
+            Err(CliError::Invalid { .. })
+        ));
+        assert!(matches!(
+            parse("--world 100000x100000"),
+            Err(CliError::Invalid { .. })
+        ));
+    }
+}
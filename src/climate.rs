@@ -0,0 +1,137 @@
+use crate::grid::{GridMesh, SimRng, TickSet, TickTimer};
+use crate::settings::Settings;
+use crate::sim::climate::Climate;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+/// Length of a whole day and night, in seconds
+const DAY_LENGTH: f32 = 240.0;
+
+/// How much colder the air is at midnight than at noon
+const NIGHT_CHILL: f32 = 30.0;
+
+/// The light at midnight, multiplying the colors of the cells
+const MIDNIGHT_LIGHT: [f32; 3] = [0.3, 0.35, 0.55];
+
+/// Time of day a new world starts at, in the morning
+const MORNING: f32 = 0.3;
+
+/// The time of day in a world going through days and nights
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct WorldClock {
+    /// How far into the day it is, from 0 at midnight to 1 at the next midnight
+    pub time: f32,
+}
+
+impl Default for WorldClock {
+    fn default() -> Self {
+        Self { time: MORNING }
+    }
+}
+
+impl WorldClock {
+    /// How much daylight there is, from 0 at midnight to 1 at noon
+    pub fn daylight(&self) -> f32 {
+        (1.0 - (self.time * TAU).cos()) / 2.0
+    }
+
+    /// Temperature of the air at this time of day, given how warm it is at noon, in degrees
+    /// Celsius
+    pub fn temperature(&self, noon: f32) -> f32 {
+        noon - NIGHT_CHILL * (1.0 - self.daylight())
+    }
+
+    /// The color the scene is lit with at this time of day
+    pub fn light(&self) -> Color {
+        let daylight = self.daylight();
+        let [r, g, b] = MIDNIGHT_LIGHT.map(|night| night + (1.0 - night) * daylight);
+        Color::linear_rgb(r, g, b)
+    }
+}
+
+pub struct ClimatePlugin;
+
+/// This plugin applies a [`Climate`] as warm as the [`Settings`] say after every tick, so water
+/// evaporates, steam condenses or gathers into clouds that rain, and ice forms or melts over time. When days pass, it dims the scene
+/// at night and cools the air with it, freezing shallow water and slowing evaporation.
+impl Plugin for ClimatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldClock>()
+            .add_systems(
+                Update,
+                (
+                    advance_clock.run_if(days_pass),
+                    apply_climate.after(TickSet),
+                )
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(
+                Update,
+                light_scene.run_if(resource_changed::<Settings>.or(resource_changed::<WorldClock>)),
+            );
+    }
+}
+
+fn days_pass(settings: Res<Settings>) -> bool {
+    settings.day_night
+}
+
+fn advance_clock(time: Res<Time>, mut clock: ResMut<WorldClock>) {
+    clock.time = (clock.time + time.delta_secs() / DAY_LENGTH).fract();
+}
+
+fn apply_climate(
+    tick_timer: Res<TickTimer>,
+    settings: Res<Settings>,
+    clock: Res<WorldClock>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+) {
+    if !tick_timer.just_finished() {
+        return;
+    }
+    let temperature = if settings.day_night {
+        clock.temperature(settings.temperature)
+    } else {
+        settings.temperature
+    };
+    let climate = Climate { temperature };
+    climate.apply(&mut grid, &mut **rng);
+}
+
+fn light_scene(
+    settings: Res<Settings>,
+    clock: Res<WorldClock>,
+    q_grid_mesh: Query<&MeshMaterial2d<ColorMaterial>, With<GridMesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let light = if settings.day_night {
+        clock.light()
+    } else {
+        Color::WHITE
+    };
+    for handle in &q_grid_mesh {
+        if let Some(material) = materials.get_mut(&handle.0) {
+            material.color = light;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::climate::{DEW_POINT, FREEZING};
+
+    #[test]
+    fn nights_are_dark_and_freezing() {
+        let noon = WorldClock { time: 0.5 };
+        let midnight = WorldClock { time: 0.0 };
+        assert!(noon.daylight() > 0.99);
+        assert_eq!(noon.temperature(DEW_POINT), DEW_POINT);
+        assert!(midnight.temperature(DEW_POINT) < FREEZING);
+        assert_eq!(midnight.daylight(), 0.0);
+        assert!(WorldClock::default().temperature(0.0) > midnight.temperature(0.0));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::cell::Material;
+use crate::sim::Grid;
+use bevy::prelude::*;
+
+/// Width and height of a collider chunk in marching squares
+pub const CHUNK_SIZE: usize = 16;
+
+/// A line segment in cell coordinates
+pub type Segment = (Vec2, Vec2);
+
+/// Contour of the settled cells, traced with marching squares and cached per chunk
+///
+/// The sample lattice has a one sample border around the grid that always counts as settled, so
+/// the grid edges act as walls. Sample `(i, j)` sits at the center of cell `(i - 1, j - 1)`.
+#[derive(Resource, Debug, Default)]
+pub struct Collider {
+    chunks_x: usize,
+    chunks_y: usize,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Chunk {
+    mask: Vec<bool>,
+    segments: Vec<Segment>,
+}
+
+/// Whether a cell is part of the collidable terrain: solid cells and powder that has come to rest
+pub fn is_settled(grid: &Grid, x: usize, y: usize) -> bool {
+    grid.get(x, y).is_some_and(|c| match c.material() {
+        Material::Solid | Material::Rigid => true,
+        Material::Powder => grid.is_blocked(x as isize, y as isize + 1),
+        _ => false,
+    })
+}
+
+impl Collider {
+    pub fn new(grid: &Grid) -> Self {
+        let chunks_x = (grid.width() + 1).div_ceil(CHUNK_SIZE);
+        let chunks_y = (grid.height() + 1).div_ceil(CHUNK_SIZE);
+        let mut collider = Self {
+            chunks_x,
+            chunks_y,
+            chunks: vec![Chunk::default(); chunks_x * chunks_y],
+        };
+        for chunk in 0..collider.chunks.len() {
+            collider.retrace(grid, chunk);
+        }
+        collider
+    }
+
+    /// Whether the collider hasn't been built for a grid yet
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Re-traces the chunks whose settled cells may have changed with the given grid positions,
+    /// returning how many were regenerated
+    pub fn update(&mut self, grid: &Grid, changes: &[(usize, usize)]) -> usize {
+        let mut dirty = vec![false; self.chunks.len()];
+        for &(x, y) in changes {
+            // Whether powder is settled depends on the cell below, so the change also affects the
+            // sample above. Samples on a chunk edge are shared with the previous chunk.
+            let (i, j) = (x + 1, y + 1);
+            let chunks = |k: usize, count: usize| {
+                let first = (k - usize::from(k.is_multiple_of(CHUNK_SIZE) && k > 0)) / CHUNK_SIZE;
+                first..=(k / CHUNK_SIZE).min(count - 1)
+            };
+            for cx in chunks(i, self.chunks_x) {
+                for j in [j - 1, j] {
+                    for cy in chunks(j, self.chunks_y) {
+                        dirty[cx * self.chunks_y + cy] = true;
+                    }
+                }
+            }
+        }
+
+        (0..self.chunks.len())
+            .filter(|&chunk| dirty[chunk] && self.retrace(grid, chunk))
+            .count()
+    }
+
+    // Rebuilds a chunk if its samples changed, returning whether they did
+    fn retrace(&mut self, grid: &Grid, chunk: usize) -> bool {
+        let (cx, cy) = (chunk / self.chunks_y, chunk % self.chunks_y);
+        let mask = chunk_mask(grid, cx, cy);
+        let chunk = &mut self.chunks[chunk];
+        if chunk.mask == mask {
+            return false;
+        }
+        chunk.segments = trace(&mask, cx, cy);
+        chunk.mask = mask;
+        true
+    }
+
+    /// Segments of the chunks overlapping the box, given in cell coordinates
+    pub fn segments_near(&self, rect: Rect) -> impl Iterator<Item = Segment> + '_ {
+        // Lattice coordinates are offset by half a cell plus the border sample
+        let to_chunk = |v: f32| ((v + 0.5).max(0.0) as usize) / CHUNK_SIZE;
+        let (x0, x1) = (
+            to_chunk(rect.min.x),
+            to_chunk(rect.max.x).min(self.chunks_x - 1),
+        );
+        let (y0, y1) = (
+            to_chunk(rect.min.y),
+            to_chunk(rect.max.y).min(self.chunks_y - 1),
+        );
+        (x0..=x1)
+            .flat_map(move |cx| (y0..=y1).map(move |cy| cx * self.chunks_y + cy))
+            .flat_map(move |i| self.chunks[i].segments.iter().copied())
+    }
+}
+
+fn sample(grid: &Grid, i: usize, j: usize) -> bool {
+    if i == 0 || j == 0 || i > grid.width() || j > grid.height() {
+        return true;
+    }
+    is_settled(grid, i - 1, j - 1)
+}
+
+// Samples covering the squares of a chunk, including the trailing row and column they share with
+// the next chunk
+fn chunk_mask(grid: &Grid, cx: usize, cy: usize) -> Vec<bool> {
+    let mut mask = Vec::with_capacity((CHUNK_SIZE + 1) * (CHUNK_SIZE + 1));
+    for i in cx * CHUNK_SIZE..=(cx + 1) * CHUNK_SIZE {
+        for j in cy * CHUNK_SIZE..=(cy + 1) * CHUNK_SIZE {
+            let in_lattice = i <= grid.width() + 1 && j <= grid.height() + 1;
+            mask.push(in_lattice && sample(grid, i, j));
+        }
+    }
+    mask
+}
+
+fn trace(mask: &[bool], cx: usize, cy: usize) -> Vec<Segment> {
+    let at = |i: usize, j: usize| mask[i * (CHUNK_SIZE + 1) + j];
+    // Lattice point to cell coordinates
+    let origin = Vec2::new(
+        (cx * CHUNK_SIZE) as f32 - 0.5,
+        (cy * CHUNK_SIZE) as f32 - 0.5,
+    );
+
+    let mut segments = Vec::new();
+    for i in 0..CHUNK_SIZE {
+        for j in 0..CHUNK_SIZE {
+            let corner = origin + Vec2::new(i as f32, j as f32);
+            let top = corner + Vec2::new(0.5, 0.0);
+            let right = corner + Vec2::new(1.0, 0.5);
+            let bottom = corner + Vec2::new(0.5, 1.0);
+            let left = corner + Vec2::new(0.0, 0.5);
+
+            let case = (at(i, j) as u8)
+                | (at(i + 1, j) as u8) << 1
+                | (at(i + 1, j + 1) as u8) << 2
+                | (at(i, j + 1) as u8) << 3;
+            match case {
+                0 | 15 => {}
+                1 | 14 => segments.push((left, top)),
+                2 | 13 => segments.push((top, right)),
+                3 | 12 => segments.push((left, right)),
+                4 | 11 => segments.push((right, bottom)),
+                6 | 9 => segments.push((top, bottom)),
+                7 | 8 => segments.push((left, bottom)),
+                // Saddles are resolved as two separate corners
+                5 => segments.extend([(left, top), (right, bottom)]),
+                10 => segments.extend([(top, right), (left, bottom)]),
+                _ => unreachable!(),
+            }
+        }
+    }
+    segments
+}
+
+/// The point on a segment closest to `point`
+pub fn closest_point((a, b): Segment, point: Vec2) -> Vec2 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+
+    #[test]
+    fn update_matches_a_fresh_trace() {
+        let mut grid = Grid::new(40, 36);
+        for x in 0..40 {
+            grid.spawn(x, 35, CellId::Stone);
+        }
+        grid.spawn(36, 15, CellId::Sand);
+        let mut collider = Collider::new(&grid);
+        grid.take_changes();
+
+        // Cells on and next to chunk edges, and support for the sand, which settles it in the
+        // chunk above
+        for (x, y) in [(15, 15), (16, 16), (31, 20), (0, 0), (39, 34), (36, 16)] {
+            grid.spawn(x, y, CellId::Stone);
+        }
+        let changes = grid.take_changes();
+        let regenerated = collider.update(&grid, &changes);
+
+        assert!(regenerated > 0);
+        assert_eq!(collider.chunks, Collider::new(&grid).chunks);
+    }
+
+    #[test]
+    fn unchanged_grid_regenerates_nothing() {
+        let mut grid = Grid::new(20, 20);
+        grid.spawn(5, 19, CellId::Stone);
+        let mut collider = Collider::new(&grid);
+        assert_eq!(collider.update(&grid, &[]), 0);
+        assert_eq!(collider.update(&grid, &[(5, 19)]), 0);
+    }
+}
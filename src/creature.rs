@@ -0,0 +1,254 @@
+use crate::cell::Material;
+use crate::chunks::WorldShifted;
+use crate::grid::{
+    cursor_tile, tiles_to_world, MainCamera, SimRng, TickTimer, WorldReset, DATA_SIZE,
+};
+use crate::input::Action;
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+/// Ticks a fish survives out of liquid
+const FISH_BREATH: u32 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum CreatureKind {
+    /// Walks along powder and solid surfaces, climbing single-cell steps and pushing sand aside
+    Ant,
+    /// Swims around inside liquids and suffocates outside of them
+    Fish,
+}
+
+impl CreatureKind {
+    fn color(&self) -> Color {
+        match self {
+            CreatureKind::Ant => Color::srgb_u8(200, 40, 40),
+            CreatureKind::Fish => Color::srgb_u8(255, 160, 30),
+        }
+    }
+
+    /// Simulation ticks between moves
+    fn speed(&self) -> u32 {
+        match self {
+            CreatureKind::Ant => 4,
+            CreatureKind::Fish => 3,
+        }
+    }
+}
+
+/// A creature living on the grid, positioned in cell coordinates
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Creature {
+    pub kind: CreatureKind,
+    pub x: usize,
+    pub y: usize,
+    /// -1 for left, 1 for right
+    pub facing: isize,
+    pub cooldown: u32,
+    pub breath: u32,
+}
+
+impl Creature {
+    pub fn new(kind: CreatureKind, x: usize, y: usize) -> Self {
+        Self {
+            kind,
+            x,
+            y,
+            facing: 1,
+            cooldown: 0,
+            breath: FISH_BREATH,
+        }
+    }
+
+    /// Whether the creature touches something that kills it
+    pub fn is_hurt(&self, grid: &Grid) -> bool {
+        let (x, y) = (self.x as isize, self.y as isize);
+        [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .any(|(dx, dy)| grid.cell_at(x + dx, y + dy).is_some_and(|c| c.harms()))
+    }
+
+    /// Advances the creature by one simulation tick, returning `false` once it has died
+    pub fn step(&mut self, grid: &mut Grid, rng: &mut impl Rng) -> bool {
+        if self.is_hurt(grid) || grid.is_blocked(self.x as isize, self.y as isize) {
+            return false;
+        }
+
+        let in_liquid = grid.get(self.x, self.y).is_some_and(|c| c.is_liquid());
+        if self.kind == CreatureKind::Fish {
+            if in_liquid {
+                self.breath = FISH_BREATH;
+            } else {
+                self.breath = self.breath.saturating_sub(1);
+                if self.breath == 0 {
+                    return false;
+                }
+            }
+        }
+
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return true;
+        }
+        self.cooldown = self.kind.speed();
+
+        let (x, y) = (self.x as isize, self.y as isize);
+        let swimming = self.kind == CreatureKind::Fish && in_liquid;
+        if !swimming && !grid.is_blocked(x, y + 1) {
+            self.move_to(grid, x, y + 1);
+            return true;
+        }
+
+        match self.kind {
+            CreatureKind::Ant => self.walk(grid, rng),
+            CreatureKind::Fish if in_liquid => self.swim(grid, rng),
+            // Flop around on dry land
+            CreatureKind::Fish => {
+                if rng.gen::<f32>() < 0.2 {
+                    self.facing = -self.facing;
+                }
+            }
+        }
+        true
+    }
+
+    fn walk(&mut self, grid: &mut Grid, rng: &mut impl Rng) {
+        let (x, y) = (self.x as isize, self.y as isize);
+        let ahead = x + self.facing;
+
+        if rng.gen::<f32>() < 0.02 {
+            self.facing = -self.facing;
+        } else if !grid.is_blocked(ahead, y) {
+            self.move_to(grid, ahead, y);
+        } else if !grid.is_blocked(ahead, y - 1) && !grid.is_blocked(x, y - 1) {
+            // Climb a single-cell step
+            self.move_to(grid, ahead, y - 1);
+        } else if grid
+            .cell_at(ahead, y)
+            .is_some_and(|c| c.material() == Material::Powder)
+            && rng.gen::<f32>() < 0.3
+        {
+            // Dig through loose sand by swapping it behind
+            let sand = grid.get(ahead as usize, y as usize);
+            grid.set(ahead as usize, y as usize, grid.get(self.x, self.y));
+            grid.set(self.x, self.y, sand);
+            self.x = ahead as usize;
+        } else {
+            self.facing = -self.facing;
+        }
+    }
+
+    fn swim(&mut self, grid: &mut Grid, rng: &mut impl Rng) {
+        if rng.gen::<f32>() < 0.1 {
+            self.facing = -self.facing;
+        }
+        let (x, y) = (self.x as isize, self.y as isize);
+        let (nx, ny) = (x + self.facing, y + rng.gen_range(-1..=1));
+        if grid.cell_at(nx, ny).is_some_and(|c| c.is_liquid()) {
+            self.move_to(grid, nx, ny);
+        } else {
+            self.facing = -self.facing;
+        }
+    }
+
+    // Moves into a non-blocked cell, swapping places with any liquid or gas occupying it
+    fn move_to(&mut self, grid: &mut Grid, x: isize, y: isize) {
+        if grid.is_blocked(x, y) {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let displaced = grid.get(x, y);
+        grid.set(x, y, grid.get(self.x, self.y));
+        grid.set(self.x, self.y, displaced);
+        (self.x, self.y) = (x, y);
+    }
+}
+
+pub struct CreaturePlugin;
+
+/// This plugin spawns creatures at the cursor (A for ants, F for fish) and moves them along the grid
+impl Plugin for CreaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Creature>()
+            .add_systems(
+                Update,
+                (spawn_creature, update_creatures, draw_creatures)
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(
+                Update,
+                clear_creatures.run_if(on_event::<WorldReset>.or(on_event::<WorldShifted>)),
+            );
+    }
+}
+
+fn spawn_creature(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    pointer: Res<Pointer>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    grid: Res<Grid>,
+) {
+    let kind = if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::SpawnAnt)
+    {
+        CreatureKind::Ant
+    } else if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::SpawnFish)
+    {
+        CreatureKind::Fish
+    } else {
+        return;
+    };
+
+    let Ok((camera, camera_transform)) = q_camera.single() else {
+        return;
+    };
+    if let Some((x, y)) = cursor_tile(&pointer, camera, camera_transform, &grid) {
+        if !grid.is_blocked(x as isize, y as isize) {
+            commands.spawn((
+                Creature::new(kind, x, y),
+                Sprite::from_color(kind.color(), Vec2::splat(DATA_SIZE)),
+                Transform::from_translation(tiles_to_world(&grid, x, y).extend(1.0)),
+            ));
+        }
+    }
+}
+
+fn update_creatures(
+    mut commands: Commands,
+    tick_timer: Res<TickTimer>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+    mut q_creatures: Query<(Entity, &mut Creature)>,
+) {
+    if !tick_timer.just_finished() {
+        return;
+    }
+
+    for (entity, mut creature) in &mut q_creatures {
+        if !creature.step(&mut grid, &mut **rng) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn draw_creatures(grid: Res<Grid>, mut q_creatures: Query<(&Creature, &mut Transform)>) {
+    for (creature, mut transform) in &mut q_creatures {
+        transform.translation = tiles_to_world(&grid, creature.x, creature.y).extend(1.0);
+    }
+}
+
+fn clear_creatures(mut commands: Commands, q_creatures: Query<Entity, With<Creature>>) {
+    for entity in &q_creatures {
+        commands.entity(entity).despawn();
+    }
+}
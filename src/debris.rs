@@ -0,0 +1,310 @@
+use crate::cell::Material;
+use crate::chunks::WorldShifted;
+use crate::collider::{closest_point, Collider};
+use crate::grid::{cells_to_world, cursor_tile, MainCamera, WorldReset, DATA_SIZE};
+use crate::input::Action;
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+
+/// Acceleration and speeds are in cells per second
+const GRAVITY: f32 = 120.0;
+const MAX_SPEED: f32 = 150.0;
+
+/// Fraction of the normal velocity kept when bouncing off the terrain
+const RESTITUTION: f32 = 0.2;
+
+/// Fraction of the tangential velocity kept per contact
+const FRICTION: f32 = 0.9;
+
+/// Upwards acceleration of a fully submerged body, relative to gravity
+const BUOYANCY: f32 = 2.5;
+
+/// Physics substeps per frame
+const SUBSTEPS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum Shape {
+    Ball { radius: f32 },
+    Box { half_size: Vec2 },
+}
+
+impl Shape {
+    fn half_extents(&self) -> Vec2 {
+        match *self {
+            Shape::Ball { radius } => Vec2::splat(radius),
+            Shape::Box { half_size } => half_size,
+        }
+    }
+
+    fn contains(&self, offset: Vec2) -> bool {
+        match *self {
+            Shape::Ball { radius } => offset.length_squared() < radius * radius,
+            Shape::Box { half_size } => {
+                offset.x.abs() < half_size.x && offset.y.abs() < half_size.y
+            }
+        }
+    }
+
+    // Normal pointing away from the contact point and the penetration depth, if the point is
+    // inside the shape
+    fn penetration(&self, offset: Vec2) -> Option<(Vec2, f32)> {
+        match *self {
+            Shape::Ball { radius } => {
+                let distance = offset.length();
+                (distance < radius && distance > f32::EPSILON)
+                    .then(|| (-offset / distance, radius - distance))
+            }
+            Shape::Box { half_size } => {
+                let overlap = half_size - offset.abs();
+                if overlap.x <= 0.0 || overlap.y <= 0.0 {
+                    None
+                } else if overlap.x < overlap.y {
+                    Some((Vec2::new(-offset.x.signum(), 0.0), overlap.x))
+                } else {
+                    Some((Vec2::new(0.0, -offset.y.signum()), overlap.y))
+                }
+            }
+        }
+    }
+}
+
+/// A rigid piece of debris resting on and pushing around the cells, positioned by its center in
+/// cell coordinates
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Body {
+    pub shape: Shape,
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+impl Body {
+    pub fn new(shape: Shape, position: Vec2) -> Self {
+        Self {
+            shape,
+            position,
+            velocity: Vec2::ZERO,
+        }
+    }
+
+    pub fn aabb(&self) -> Rect {
+        Rect::from_center_half_size(self.position, self.shape.half_extents())
+    }
+
+    // Fraction of the cells bordering the body that hold liquid. The covered cells themselves are
+    // always emptied by displacement, so the surroundings decide whether it floats.
+    fn submerged(&self, grid: &Grid) -> f32 {
+        let aabb = self.aabb().inflate(1.0);
+        let (mut border, mut liquid) = (0, 0);
+        for x in aabb.min.x.floor() as isize..aabb.max.x.ceil() as isize {
+            for y in aabb.min.y.floor() as isize..aabb.max.y.ceil() as isize {
+                let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if self.shape.contains(center - self.position) {
+                    continue;
+                }
+                border += 1;
+                if grid.cell_at(x, y).is_some_and(|c| c.is_liquid()) {
+                    liquid += 1;
+                }
+            }
+        }
+        liquid as f32 / border.max(1) as f32
+    }
+
+    fn for_each_covered(&self, grid: &Grid, mut f: impl FnMut(usize, usize)) {
+        let aabb = self.aabb();
+        let (x0, x1) = (aabb.min.x.floor() as isize, aabb.max.x.ceil() as isize);
+        let (y0, y1) = (aabb.min.y.floor() as isize, aabb.max.y.ceil() as isize);
+        for x in x0.max(0)..x1.min(grid.width() as isize) {
+            for y in y0.max(0)..y1.min(grid.height() as isize) {
+                let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if self.shape.contains(center - self.position) {
+                    f(x as usize, y as usize);
+                }
+            }
+        }
+    }
+
+    /// Integrates the body over `dt` seconds and pushes it out of the terrain contour
+    pub fn step(&mut self, grid: &Grid, collider: &Collider, dt: f32) {
+        let submerged = self.submerged(grid);
+        self.velocity.y += GRAVITY * (1.0 - BUOYANCY * submerged) * dt;
+        self.velocity *= 1.0 - submerged * 2.0 * dt;
+        self.velocity = self.velocity.clamp_length_max(MAX_SPEED);
+        self.position += self.velocity * dt;
+
+        let search = self.aabb().inflate(1.0);
+        for segment in collider.segments_near(search) {
+            let contact = closest_point(segment, self.position);
+            let Some((normal, depth)) = self.shape.penetration(contact - self.position) else {
+                continue;
+            };
+            self.position += normal * depth;
+
+            let normal_speed = self.velocity.dot(normal);
+            if normal_speed < 0.0 {
+                let tangent = self.velocity - normal * normal_speed;
+                self.velocity = tangent * FRICTION - normal * normal_speed * RESTITUTION;
+            }
+        }
+
+        let half = self.shape.half_extents();
+        self.position = self.position.clamp(
+            half,
+            Vec2::new(grid.width() as f32, grid.height() as f32) - half,
+        );
+    }
+
+    /// Moves the loose cells covered by the body to the nearest free spot above it, so liquids are
+    /// displaced and falling powder piles up on top. Returns whether any cell was moved.
+    pub fn displace(&self, grid: &mut Grid) -> bool {
+        let mut covered = Vec::new();
+        self.for_each_covered(grid, |x, y| {
+            if grid
+                .get(x, y)
+                .is_some_and(|c| c.material() == Material::Powder || c.is_liquid())
+            {
+                covered.push((x, y));
+            }
+        });
+
+        for &(x, y) in &covered {
+            if let Some(cell) = grid.get(x, y) {
+                let free = (0..y).rev().find(|&above| {
+                    let center = Vec2::new(x as f32 + 0.5, above as f32 + 0.5);
+                    grid.get(x, above).is_none() && !self.shape.contains(center - self.position)
+                });
+                grid.set(x, y, None);
+                if let Some(above) = free {
+                    grid.set(x, above, Some(cell));
+                }
+            }
+        }
+        !covered.is_empty()
+    }
+}
+
+pub struct DebrisPlugin;
+
+/// This plugin spawns boxes (B) and balls (O) at the cursor that rest on the settled cells
+impl Plugin for DebrisPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Body>()
+            .init_resource::<Collider>()
+            .add_systems(
+                Update,
+                (
+                    spawn_debris,
+                    update_collider,
+                    step_debris,
+                    displace_cells,
+                    draw_debris,
+                )
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(
+                Update,
+                clear_debris.run_if(on_event::<WorldReset>.or(on_event::<WorldShifted>)),
+            );
+    }
+}
+
+fn spawn_debris(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    pointer: Res<Pointer>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    grid: Res<Grid>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let shape = if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::SpawnBox)
+    {
+        Shape::Box {
+            half_size: Vec2::new(5.0, 3.0),
+        }
+    } else if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::SpawnBall)
+    {
+        Shape::Ball { radius: 4.0 }
+    } else {
+        return;
+    };
+
+    let Ok((camera, camera_transform)) = q_camera.single() else {
+        return;
+    };
+    let Some((x, y)) = cursor_tile(&pointer, camera, camera_transform, &grid) else {
+        return;
+    };
+    let body = Body::new(shape, Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+
+    let color = Color::srgb_u8(140, 100, 70);
+    match shape {
+        Shape::Ball { radius } => commands.spawn((
+            body,
+            Mesh2d(meshes.add(Circle::new(radius * DATA_SIZE))),
+            MeshMaterial2d(materials.add(color)),
+            Transform::default(),
+        )),
+        Shape::Box { half_size } => commands.spawn((
+            body,
+            Sprite::from_color(color, half_size * 2.0 * DATA_SIZE),
+            Transform::default(),
+        )),
+    };
+}
+
+fn update_collider(mut grid: ResMut<Grid>, mut collider: ResMut<Collider>) {
+    // Reading the record of changed positions doesn't change any cells
+    let changes = grid.bypass_change_detection().take_changes();
+    if collider.is_empty() {
+        *collider = Collider::new(&grid);
+    } else if !changes.is_empty() {
+        collider.update(&grid, &changes);
+    }
+}
+
+fn step_debris(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    collider: Res<Collider>,
+    mut q_bodies: Query<&mut Body>,
+) {
+    let dt = time.delta_secs().min(1.0 / 30.0) / SUBSTEPS as f32;
+    for mut body in &mut q_bodies {
+        for _ in 0..SUBSTEPS {
+            body.step(&grid, &collider, dt);
+        }
+    }
+}
+
+fn displace_cells(mut grid: ResMut<Grid>, q_bodies: Query<&Body>) {
+    let mut moved = false;
+    for body in &q_bodies {
+        moved |= body.displace(grid.bypass_change_detection());
+    }
+    if moved {
+        grid.set_changed();
+    }
+}
+
+fn draw_debris(grid: Res<Grid>, mut q_bodies: Query<(&Body, &mut Transform)>) {
+    for (body, mut transform) in &mut q_bodies {
+        transform.translation = cells_to_world(&grid, body.position).extend(1.5);
+    }
+}
+
+fn clear_debris(mut commands: Commands, q_bodies: Query<Entity, With<Body>>) {
+    for entity in &q_bodies {
+        commands.entity(entity).despawn();
+    }
+}
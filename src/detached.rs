@@ -0,0 +1,302 @@
+use crate::cell::CellId;
+use crate::grid::{cursor_tile, Brush, MainCamera};
+use crate::input::Action;
+use crate::level::ActiveLevel;
+use crate::loading::UiAssets;
+use crate::pointer::Pointer;
+use crate::registry::CellRegistry;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::stats::SimStats;
+use crate::InWorld;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::window::{WindowRef, WindowResolution};
+
+/// A tool opened in a window of its own, so the main window can show nothing but the simulation
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detached {
+    Palette,
+    Stats,
+    Inspector,
+}
+
+impl Detached {
+    const ALL: [Detached; 3] = [Detached::Palette, Detached::Stats, Detached::Inspector];
+
+    fn action(self) -> Action {
+        match self {
+            Detached::Palette => Action::DetachPalette,
+            Detached::Stats => Action::DetachStats,
+            Detached::Inspector => Action::DetachInspector,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Detached::Palette => "Falling Sand - Materials",
+            Detached::Stats => "Falling Sand - Stats",
+            Detached::Inspector => "Falling Sand - Inspector",
+        }
+    }
+
+    /// Size the window opens at, in logical pixels
+    fn size(self) -> (f32, f32) {
+        match self {
+            Detached::Palette => (520.0, 640.0),
+            Detached::Stats | Detached::Inspector => (320.0, 220.0),
+        }
+    }
+}
+
+/// The camera or UI of a detached window, despawned once the window is closed
+#[derive(Component)]
+struct WindowPart(Entity);
+
+#[derive(Component)]
+struct PaletteButton(CellId);
+
+/// Text kept up to date with what the last tick did
+#[derive(Component)]
+struct StatsText;
+
+/// Text describing the cell under the pointer
+#[derive(Component)]
+struct InspectorText;
+
+pub struct DetachedPlugin;
+
+/// This plugin opens the material palette, the simulation stats and an inspector of the cell under
+/// the pointer in separate windows, each toggled by its key, for streamers and large screens
+impl Plugin for DetachedPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_windows,
+                close_parts,
+                choose_material,
+                update_stats.run_if(resource_changed::<SimStats>),
+                update_inspector,
+            )
+                .run_if(in_state(InWorld)),
+        );
+    }
+}
+
+fn toggle_windows(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    registry: Res<CellRegistry>,
+    ui_assets: Res<UiAssets>,
+    active_level: Option<Res<ActiveLevel>>,
+    q_windows: Query<(Entity, &Detached)>,
+) {
+    for detached in Detached::ALL {
+        if !settings
+            .keybindings
+            .just_pressed(&keyboard_input, detached.action())
+        {
+            continue;
+        }
+        if let Some((window, _)) = q_windows.iter().find(|&(_, &open)| open == detached) {
+            commands.entity(window).despawn();
+            continue;
+        }
+
+        let (width, height) = detached.size();
+        let window = commands
+            .spawn((
+                detached,
+                Window {
+                    title: detached.title().to_string(),
+                    resolution: WindowResolution::new(width, height),
+                    ..default()
+                },
+            ))
+            .id();
+        let camera = commands
+            .spawn((
+                WindowPart(window),
+                Camera2d,
+                Camera {
+                    target: RenderTarget::Window(WindowRef::Entity(window)),
+                    ..default()
+                },
+            ))
+            .id();
+        let mut root = commands.spawn((
+            WindowPart(window),
+            UiTargetCamera(camera),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_wrap: FlexWrap::Wrap,
+                align_content: AlignContent::FlexStart,
+                column_gap: Val::Px(8.0),
+                row_gap: Val::Px(8.0),
+                padding: UiRect::all(Val::Px(12.0)),
+                ..default()
+            },
+        ));
+        match detached {
+            Detached::Palette => {
+                let materials = CellId::ALL
+                    .into_iter()
+                    .filter(|&id| active_level.as_ref().is_none_or(|level| level.allows(id)));
+                root.with_children(|children| {
+                    for id in materials {
+                        children
+                            .spawn((
+                                PaletteButton(id),
+                                Button,
+                                Node {
+                                    width: Val::Px(72.0),
+                                    height: Val::Px(72.0),
+                                    flex_direction: FlexDirection::Column,
+                                    align_items: AlignItems::Center,
+                                    justify_content: JustifyContent::Center,
+                                    row_gap: Val::Px(4.0),
+                                    ..default()
+                                },
+                                BackgroundColor(Color::linear_rgb(0.15, 0.15, 0.15)),
+                            ))
+                            .with_children(|entry| {
+                                entry.spawn((
+                                    Node {
+                                        width: Val::Px(36.0),
+                                        height: Val::Px(36.0),
+                                        ..default()
+                                    },
+                                    ui_assets.icon(id, settings.color_scheme.color(&registry, id)),
+                                ));
+                                entry.spawn((
+                                    Text::new(id.data().name),
+                                    TextFont {
+                                        font_size: 12.0,
+                                        ..default()
+                                    },
+                                ));
+                            });
+                    }
+                });
+            }
+            Detached::Stats => {
+                root.with_child((StatsText, Text::default()));
+            }
+            Detached::Inspector => {
+                root.with_child((InspectorText, Text::default()));
+            }
+        }
+    }
+}
+
+// Windows closed from the title bar are despawned by Bevy, which leaves their cameras and UI
+fn close_parts(
+    mut commands: Commands,
+    q_parts: Query<(Entity, &WindowPart)>,
+    q_windows: Query<(), With<Window>>,
+) {
+    for (entity, part) in &q_parts {
+        if !q_windows.contains(part.0) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn choose_material(
+    q_buttons: Query<(&Interaction, &PaletteButton), Changed<Interaction>>,
+    mut brush: ResMut<Brush>,
+) {
+    for (interaction, button) in &q_buttons {
+        if *interaction == Interaction::Pressed {
+            brush.selected = button.0;
+        }
+    }
+}
+
+fn update_stats(
+    stats: Res<SimStats>,
+    grid: Res<Grid>,
+    mut q_text: Query<&mut Text, With<StatsText>>,
+) {
+    if q_text.is_empty() {
+        return;
+    }
+    let cells = grid.iter().filter(|(_, _, cell)| cell.is_some()).count();
+    let text = format!(
+        "Tick {}\nStep time: {:.2} ms\nCells: {cells}\nMoved: {}\nReactions: {}",
+        stats.ticks,
+        stats.duration.as_secs_f64() * 1000.0,
+        stats.tick.moved,
+        stats.tick.reactions,
+    );
+    for mut stats_text in &mut q_text {
+        stats_text.0.clone_from(&text);
+    }
+}
+
+fn update_inspector(
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    grid: Res<Grid>,
+    mut q_text: Query<&mut Text, With<InspectorText>>,
+) {
+    if q_text.is_empty() {
+        return;
+    }
+    let (camera, camera_transform) = *q_camera;
+    let text = match cursor_tile(&pointer, camera, camera_transform, &grid) {
+        Some((x, y)) => describe(grid.get(x, y), x, y),
+        None => "Point at the grid to inspect a cell".to_string(),
+    };
+    for mut inspector_text in &mut q_text {
+        if inspector_text.0 != text {
+            inspector_text.0.clone_from(&text);
+        }
+    }
+}
+
+/// What the inspector says about a cell
+fn describe(cell: Option<crate::cell::Cell>, x: usize, y: usize) -> String {
+    let Some(cell) = cell else {
+        return format!("({x}, {y})\nEmpty");
+    };
+    let data = cell.id.data();
+    let mut text = format!("({x}, {y})\n{}\n{:?}", data.name, data.material);
+    if data.flammable {
+        text.push_str("\nFlammable");
+    }
+    if let Some(life) = cell.life {
+        text.push_str(&format!("\nLife: {life} ticks"));
+    }
+    if let Some([r, g, b]) = cell.tint {
+        text.push_str(&format!("\nTint: #{r:02x}{g:02x}{b:02x}"));
+    }
+    if cell.aux != 0 {
+        text.push_str(&format!("\nPayload: {}", cell.aux));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::Cell;
+
+    #[test]
+    fn inspector_describes_the_cell() {
+        assert_eq!(describe(None, 3, 4), "(3, 4)\nEmpty");
+        let cell = Cell {
+            id: CellId::Wood,
+            life: Some(12),
+            tint: Some([255, 0, 16]),
+            aux: 7,
+        };
+        assert_eq!(
+            describe(Some(cell), 0, 1),
+            "(0, 1)\nWood\nSolid\nFlammable\nLife: 12 ticks\nTint: #ff0010\nPayload: 7"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::grid::{tiles_to_world, world_to_tiles, MainCamera, DATA_SIZE};
+use crate::input::Action;
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use std::ops::Range;
+
+/// Outline of the rectangle being dragged
+const ZONE_COLOR: Color = Color::srgb(0.5, 0.8, 1.0);
+
+/// The freeze tool, which marks rectangles of the grid as frozen instead of painting
+#[derive(Resource, Reflect, Debug, Default)]
+#[reflect(Resource)]
+pub struct FreezeTool {
+    pub active: bool,
+    /// The corners of the rectangle being dragged, starting with the one the drag started from
+    drag: Option<((usize, usize), (usize, usize))>,
+}
+
+pub fn freeze_tool_active(tool: Res<FreezeTool>) -> bool {
+    tool.active
+}
+
+/// The columns and rows of the rectangle between two corners
+pub fn zone((ax, ay): (usize, usize), (bx, by): (usize, usize)) -> (Range<usize>, Range<usize>) {
+    (ax.min(bx)..ax.max(bx) + 1, ay.min(by)..ay.max(by) + 1)
+}
+
+pub struct FreezePlugin;
+
+/// This plugin adds the freeze tool. While it is on, dragging a rectangle freezes the cells in
+/// it, or thaws them when the drag starts on a frozen tile.
+impl Plugin for FreezePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<FreezeTool>()
+            .init_resource::<FreezeTool>()
+            .add_systems(
+                Update,
+                (toggle_freeze_tool, drag_zone.run_if(freeze_tool_active))
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            );
+    }
+}
+
+fn toggle_freeze_tool(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut tool: ResMut<FreezeTool>,
+    mut grid: ResMut<Grid>,
+) {
+    let bindings = &settings.keybindings;
+    if bindings.just_pressed(&keyboard_input, Action::FreezeTool) {
+        tool.active = !tool.active;
+        tool.drag = None;
+    }
+    if bindings.just_pressed(&keyboard_input, Action::ThawAll) {
+        grid.thaw_all();
+    }
+}
+
+fn drag_zone(
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut tool: ResMut<FreezeTool>,
+    mut grid: ResMut<Grid>,
+    mut gizmos: Gizmos,
+) {
+    let (camera, camera_transform) = *q_camera;
+    let tile = pointer
+        .position
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
+        .and_then(|ray| world_to_tiles(&grid, ray.origin.truncate()));
+
+    if !pointer.painting {
+        // The rectangle is applied when the drag ends
+        if let Some((start, end)) = tool.drag.take() {
+            let (xs, ys) = zone(start, end);
+            let frozen = !grid.is_frozen(start.0, start.1);
+            grid.set_frozen(xs, ys, frozen);
+        }
+        return;
+    }
+
+    let Some(tile) = tile else {
+        return;
+    };
+    let (start, end) = tool.drag.get_or_insert((tile, tile));
+    *end = tile;
+
+    let (xs, ys) = zone(*start, *end);
+    let min = tiles_to_world(&grid, xs.start, ys.end - 1) - DATA_SIZE / 2.0;
+    let max = tiles_to_world(&grid, xs.end - 1, ys.start) + DATA_SIZE / 2.0;
+    gizmos.rect_2d((min + max) / 2.0, max - min, ZONE_COLOR);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zone_covers_both_corners() {
+        assert_eq!(zone((4, 1), (2, 3)), (2..5, 1..4));
+        assert_eq!(zone((2, 2), (2, 2)), (2..3, 2..3));
+    }
+}
@@ -1,53 +1,364 @@
+use crate::accessibility::hatch;
 use crate::cell::{Material, *};
-use crate::GameState;
+use crate::cli::Options;
+use crate::freeze::freeze_tool_active;
+use crate::input::Action;
+use crate::level::ActiveLevel;
+use crate::life::life_shown;
+use crate::net::Lockstep;
+use crate::overlay::{draws_empty, Overlay, SimTrace, ViewMode};
+use crate::pointer::{has_window, Pointer};
+use crate::portal::placing_portals;
+use crate::registry::CellRegistry;
+use crate::rewind::rewinding;
+use crate::ruler::ruler_active;
+use crate::selection::selection_tool_active;
+use crate::settings::Settings;
+use crate::sim::life::LifeLayer;
+use crate::sim::{Grid, SimEvent};
+use crate::stats::SimStats;
+use crate::touch::Palette;
+use crate::view::{tiles_in, view_size};
+use crate::{GameState, InWorld};
 use bevy::asset::RenderAssetUsages;
 use bevy::input::mouse::MouseWheel;
+use bevy::platform::time::Instant;
 use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
 use bevy::render::mesh::{Indices, VertexAttributeValues};
 use bevy::render::render_resource::PrimitiveTopology;
-use bevy::window::PrimaryWindow;
 use line_drawing::Bresenham;
 use rand::prelude::*;
-use std::cmp::max;
 use std::time::Duration;
 
-const DATA_SIZE: f32 = 4.0;
+pub const DATA_SIZE: f32 = 4.0;
 
-const GRID_WIDTH: usize = 320;
+pub const TICK_RATE: f32 = 0.01;
 
-const GRID_HEIGHT: usize = 180;
+/// Color of empty frozen tiles, and the color frozen cells are tinted towards
+const FROZEN_COLOR: [u8; 3] = [40, 60, 90];
+/// Tint of the positions where a Life cell lives
+const LIFE_COLOR: [u8; 3] = [120, 230, 140];
 
-const TICK_RATE: f32 = 0.01;
+/// Share of the color of a facing cell kept by the arrowhead drawn over it
+const MARKER_SHADE: f32 = 0.5;
 
-const BRUSH_SIZES: [isize; 4] = [0, 2, 4, 8];
+/// How much the density changes per key press, as a fraction of the covered tiles
+const DENSITY_STEP: f32 = 0.1;
+const MIN_DENSITY: f32 = 0.1;
 
-#[derive(Resource)]
-pub struct Grid {
-    pub cells: Vec<Vec<Option<Cell>>>,
-    pub timer: Timer,
-    pub brush_size: usize,
+/// Cells emitted per second in flow mode
+const MIN_FLOW_RATE: f32 = 10.0;
+const MAX_FLOW_RATE: f32 = 1280.0;
+
+#[derive(Resource, Reflect, Deref, DerefMut)]
+#[reflect(Resource)]
+pub struct TickTimer(Timer);
+
+/// The system stepping the simulation, so other plugins can hold it back
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TickSet;
+
+/// The system painting with the brush, so other plugins can hold it back
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PaintSet;
+
+/// Random number generator driving the simulation, seeded so runs can be reproduced
+#[derive(Resource, Deref, DerefMut)]
+pub struct SimRng(StdRng);
+
+impl SimRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+
+    /// The generator a tick is stepped with when every tick of a run is seeded from one seed, so
+    /// replays and shared worlds go through the same states wherever they are played
+    pub fn for_tick(seed: u64, tick: u64) -> StdRng {
+        StdRng::seed_from_u64(seed ^ tick.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+}
+
+/// The pattern of tiles the brush covers around the cursor
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BrushShape {
+    #[default]
+    Circle,
+    Square,
+    /// A line one tile wide, perpendicular to the direction of the drag
+    Line,
+    /// Drops scattered within the radius, thicker towards the middle
+    Spray,
+}
+
+impl BrushShape {
+    const ALL: [BrushShape; 4] = [
+        BrushShape::Circle,
+        BrushShape::Square,
+        BrushShape::Line,
+        BrushShape::Spray,
+    ];
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&shape| shape == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+/// Copies of every stroke painted across the middle of the grid
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Symmetry {
+    #[default]
+    None,
+    /// Mirrored left to right
+    MirrorX,
+    /// Mirrored top to bottom
+    MirrorY,
+    /// Rotated a quarter turn at a time
+    Radial,
+}
+
+impl Symmetry {
+    const ALL: [Symmetry; 4] = [
+        Symmetry::None,
+        Symmetry::MirrorX,
+        Symmetry::MirrorY,
+        Symmetry::Radial,
+    ];
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL
+            .iter()
+            .position(|&symmetry| symmetry == self)
+            .unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// The tile with all of its copies on a grid of the given size. Rotated copies of a grid
+    /// that isn't square can land outside of it.
+    pub fn images(
+        self,
+        (x, y): (isize, isize),
+        (width, height): (usize, usize),
+    ) -> Vec<(isize, isize)> {
+        let (width, height) = (width as isize, height as isize);
+        let (mx, my) = (width - 1 - x, height - 1 - y);
+        match self {
+            Symmetry::None => vec![(x, y)],
+            Symmetry::MirrorX => vec![(x, y), (mx, y)],
+            Symmetry::MirrorY => vec![(x, y), (x, my)],
+            Symmetry::Radial => {
+                // Rotate around the center in doubled coordinates so odd sizes stay exact
+                let (dx, dy) = (2 * x - (width - 1), 2 * y - (height - 1));
+                [(dx, dy), (-dy, dx), (-dx, -dy), (dy, -dx)]
+                    .map(|(dx, dy)| {
+                        (
+                            (dx + width - 1).div_euclid(2),
+                            (dy + height - 1).div_euclid(2),
+                        )
+                    })
+                    .to_vec()
+            }
+        }
+    }
+
+    /// The way a cell facing `facing` faces in each of the copies [`Symmetry::images`] gives, in
+    /// the same order
+    pub fn facings(self, facing: Facing) -> Vec<Facing> {
+        match self {
+            Symmetry::None => vec![facing],
+            Symmetry::MirrorX => vec![facing, facing.flip_x()],
+            Symmetry::MirrorY => vec![facing, facing.flip_y()],
+            Symmetry::Radial => {
+                let turned = |turns| (0..turns).fold(facing, |facing, _| facing.clockwise());
+                vec![facing, turned(1), turned(2), turned(3)]
+            }
+        }
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Brush {
+    /// Tiles covered out from the cursor, which alone is covered at 0
+    pub radius: usize,
     pub selected: CellId,
+    pub shape: BrushShape,
+    /// Paint over occupied tiles instead of only filling empty ones
+    pub replace: bool,
+    pub symmetry: Symmetry,
+    /// The fraction of the covered tiles filled each frame
+    pub density: f32,
+    /// Emit a steady stream of `flow_rate` cells per second instead of filling the brush at once
+    pub flow: bool,
+    pub flow_rate: f32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            radius: 2,
+            selected: CellId::Sand,
+            shape: BrushShape::Circle,
+            replace: false,
+            symmetry: Symmetry::None,
+            density: 0.5,
+            flow: false,
+            flow_rate: 160.0,
+        }
+    }
+}
+
+impl Brush {
+    /// The tiles covered by the brush centered on `(cx, cy)`, which may lie outside of the grid.
+    /// `direction` is the way the cursor is being dragged, which lines are drawn across.
+    pub fn stamp(
+        &self,
+        (cx, cy): (isize, isize),
+        direction: Vec2,
+        rng: &mut impl Rng,
+    ) -> Vec<(isize, isize)> {
+        let radius = self.radius as isize;
+        let square = (-radius..=radius).flat_map(|dx| (-radius..=radius).map(move |dy| (dx, dy)));
+        let offsets: Vec<_> = match self.shape {
+            BrushShape::Circle => square
+                .filter(|(dx, dy)| dx.pow(2) + dy.pow(2) <= radius.pow(2))
+                .collect(),
+            BrushShape::Square => square.collect(),
+            BrushShape::Line => {
+                let across = direction.try_normalize().unwrap_or(Vec2::Y).perp();
+                (-radius..=radius)
+                    .map(|t| {
+                        let offset = (across * t as f32).round();
+                        (offset.x as isize, offset.y as isize)
+                    })
+                    .collect()
+            }
+            BrushShape::Spray => (0..=radius * 2)
+                .map(|_| {
+                    let offset = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU))
+                        * rng.gen_range(0.0..=radius as f32);
+                    let offset = offset.round();
+                    (offset.x as isize, offset.y as isize)
+                })
+                .filter(|(dx, dy)| dx.pow(2) + dy.pow(2) <= radius.pow(2))
+                .collect(),
+        };
+        offsets
+            .into_iter()
+            .map(|(dx, dy)| (cx + dx, cy + dy))
+            .collect()
+    }
+
+    /// Steps the density by tenths, between 10% and 100%
+    pub fn change_density(&mut self, steps: f32) {
+        let density = (self.density + steps * DENSITY_STEP).clamp(MIN_DENSITY, 1.0);
+        self.density = (density / DENSITY_STEP).round() * DENSITY_STEP;
+    }
+
+    /// Doubles or halves the flow rate per step
+    pub fn change_flow_rate(&mut self, steps: f32) {
+        self.flow_rate = (self.flow_rate * 2f32.powf(steps)).clamp(MIN_FLOW_RATE, MAX_FLOW_RATE);
+    }
+
+    /// How many of the `covered` tiles to fill this frame. In flow mode the fraction of a cell
+    /// left over is carried to the next frame so the stream stays steady at any frame rate.
+    fn cells_to_fill(&self, covered: usize, delta: f32, carry: &mut f32) -> usize {
+        if self.flow {
+            *carry += self.flow_rate * delta;
+            let count = carry.floor();
+            *carry -= count;
+            (count as usize).min(covered)
+        } else {
+            ((covered as f32 * self.density).round() as usize).clamp(1, covered.max(1))
+        }
+    }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
 pub struct LastCursorPosition(Option<(usize, usize)>);
 
+/// Cells the brush placed during a frame
+#[derive(Event)]
+pub struct Poured {
+    pub id: CellId,
+    pub count: usize,
+}
+
+/// The grid was replaced by another world, so whatever belonged to the old one has to go. Sent by
+/// whatever replaced it, saying what it was replaced with.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldReset {
+    Preset,
+    Generated,
+    Level,
+    Challenge,
+    Tutorial,
+    Imported,
+}
+
 #[derive(Component)]
 pub struct GridMesh;
 
+/// The camera showing the grid in the main window, as opposed to the ones of detached windows
+#[derive(Component)]
+pub struct MainCamera;
+
+/// Shows the size of the brush in a corner of the screen
+#[derive(Component)]
+struct BrushHud;
+
 pub struct GridPlugin;
 
 impl Plugin for GridPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Playing), setup)
-            .add_systems(Update, tick_grid.run_if(in_state(GameState::Playing)))
-            .add_systems(Update, spawn_sand.run_if(in_state(GameState::Playing)))
-            .add_systems(Update, draw_grid.run_if(in_state(GameState::Playing)))
+        app.register_type::<Grid>()
+            .register_type::<TickTimer>()
+            .register_type::<Brush>()
+            .register_type::<BrushShape>()
+            .register_type::<Symmetry>()
+            .register_type::<LastCursorPosition>()
+            .register_type::<Cell>()
+            .register_type::<CellId>()
+            .register_type::<Material>()
+            .add_event::<Poured>()
+            .add_event::<WorldReset>()
+            .add_event::<SimEvent>()
+            .add_systems(OnExit(GameState::Loading), setup)
+            // Painting follows the pointer, which only a window has
+            .configure_sets(Update, PaintSet.run_if(has_window))
+            .add_systems(
+                Update,
+                tick_grid
+                    .in_set(TickSet)
+                    .run_if(in_state(InWorld).and(not(rewinding))),
+            )
             .add_systems(
                 Update,
-                update_brush_size.run_if(in_state(GameState::Playing)),
+                spawn_sand.in_set(PaintSet).run_if(
+                    in_state(InWorld)
+                        .and(not(any_with_component::<Palette>))
+                        .and(not(freeze_tool_active))
+                        .and(not(selection_tool_active))
+                        .and(not(ruler_active))
+                        .and(not(placing_portals))
+                        .and(not(life_shown)),
+                ),
             )
-            .add_systems(Update, select_tile.run_if(in_state(GameState::Playing)));
+            .add_systems(Update, draw_grid.run_if(in_state(InWorld)))
+            .add_systems(
+                Update,
+                (update_brush_size, update_brush_settings).run_if(in_state(InWorld)),
+            )
+            .add_systems(Update, select_tile.run_if(in_state(InWorld)))
+            .add_systems(OnEnter(InWorld), spawn_brush_hud)
+            .add_systems(
+                Update,
+                update_brush_hud
+                    .after(update_brush_size)
+                    .run_if(in_state(InWorld)),
+            );
     }
 }
 
@@ -55,14 +366,35 @@ fn setup(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
+    options: Res<Options>,
 ) {
-    commands.spawn((Camera2d, Msaa::Off));
-    commands.insert_resource(Grid {
-        cells: vec![vec![None; GRID_HEIGHT]; GRID_WIDTH],
-        timer: Timer::new(Duration::from_secs_f32(TICK_RATE), TimerMode::Repeating),
-        brush_size: 1,
-        selected: CellId::Sand,
-    });
+    let (width, height) = options.world_size();
+    // Keep the whole grid in view whatever the size of the window or canvas, or as much of a
+    // large one as fits the default grid size
+    let view = view_size(width, height);
+    commands.spawn((
+        Camera2d,
+        MainCamera,
+        Msaa::Off,
+        Projection::Orthographic(OrthographicProjection {
+            scaling_mode: ScalingMode::AutoMin {
+                min_width: view.x,
+                min_height: view.y,
+            },
+            ..OrthographicProjection::default_2d()
+        }),
+    ));
+    commands.insert_resource(Grid::new(width, height));
+    commands.insert_resource(
+        options
+            .seed
+            .map_or_else(|| SimRng(StdRng::from_entropy()), SimRng::from_seed),
+    );
+    commands.insert_resource(TickTimer(Timer::new(
+        Duration::from_secs_f32(TICK_RATE),
+        TimerMode::Repeating,
+    )));
+    commands.insert_resource(Brush::default());
     commands.insert_resource(LastCursorPosition(None));
 
     let mut mesh = Mesh::new(
@@ -91,274 +423,69 @@ fn setup(
         .insert(Transform::default());
 }
 
-fn tick_grid(time: Res<Time>, mut grid: ResMut<Grid>) {
-    grid.timer.tick(time.delta());
-
-    if grid.timer.just_finished() {
-        let mut new_cells = grid.cells.clone();
-
-        let mut rng = thread_rng();
-        let mut coords: Vec<_> = (0..GRID_WIDTH)
-            .map(|x| (0..GRID_HEIGHT).map(move |y| (x, y)))
-            .flatten()
-            .collect();
-        coords.shuffle(&mut rng);
-
-        for (x, y) in coords {
-            if let Some(mut cell) = grid.cells[x][y] {
-                if let Some(life) = &mut cell.life {
-                    *life -= 1;
-                    if *life == 0 {
-                        new_cells[x][y] = None;
-                        continue;
-                    }
-                }
-
-                if y > 0 {
-                    let above = grid.cells[x][y - 1];
-
-                    // Float
-                    if above.is_some() && above.unwrap().sinks_under(Some(cell)) {
-                        new_cells[x][y] = above;
-                        new_cells[x][y - 1] = Some(cell);
-                        continue;
-                    }
-                }
-
-                if y < GRID_HEIGHT - 1 {
-                    if cell.falls() {
-                        // Fall
-                        if cell.sinks_under(grid.cells[x][y + 1])
-                            || cell.dissolves(grid.cells[x][y + 1])
-                        {
-                            if cell.dissolves(grid.cells[x][y + 1]) {
-                                new_cells[x][y] = None;
-                                new_cells[x][y + 1] = None;
-                            } else {
-                                new_cells[x][y] = grid.cells[x][y + 1];
-                                new_cells[x][y + 1] = Some(cell);
-                            }
-                            continue;
-                        } else {
-                            match grid.cells[x][y + 1] {
-                                // Extinguish fire
-                                Some(c) if c.material() == Material::Fire => {
-                                    new_cells[x][y] = None;
-                                    if !cell.flammable() {
-                                        new_cells[x][y + 1] = Some(cell);
-                                    }
-                                    continue;
-                                }
-                                // Dissolve in acid
-                                Some(c) if c.dissolves(Some(cell)) => {
-                                    new_cells[x][y] = None;
-                                    new_cells[x][y + 1] = None;
-                                }
-                                _ => (),
-                            }
-                        }
-                    }
-
-                    // Slide down slopes
-                    if cell.slides() {
-                        let below_left = x > 0
-                            && (cell.sinks_under(grid.cells[x - 1][y + 1])
-                                || cell.dissolves(grid.cells[x - 1][y + 1]))
-                            && cell.sinks_under(grid.cells[x - 1][y])
-                            && grid.cells[x - 1][y + 1] == new_cells[x - 1][y + 1];
-                        let below_right = x < GRID_WIDTH - 1
-                            && (cell.sinks_under(grid.cells[x + 1][y + 1])
-                                || cell.dissolves(grid.cells[x + 1][y + 1]))
-                            && cell.sinks_under(grid.cells[x + 1][y])
-                            && grid.cells[x + 1][y + 1] == new_cells[x + 1][y + 1];
-
-                        let (below_left, below_right) = if below_left && below_right {
-                            if rng.gen() {
-                                (true, false)
-                            } else {
-                                (false, true)
-                            }
-                        } else {
-                            (below_left, below_right)
-                        };
-
-                        if below_left {
-                            if cell.dissolves(grid.cells[x - 1][y + 1]) {
-                                new_cells[x][y] = None;
-                                new_cells[x - 1][y + 1] = None;
-                            } else {
-                                new_cells[x][y] = grid.cells[x - 1][y + 1];
-                                new_cells[x - 1][y + 1] = Some(cell);
-                            }
-                            continue;
-                        }
-
-                        if below_right {
-                            if cell.dissolves(grid.cells[x + 1][y + 1]) {
-                                new_cells[x][y] = None;
-                                new_cells[x + 1][y + 1] = None;
-                            } else {
-                                new_cells[x][y] = grid.cells[x + 1][y + 1];
-                                new_cells[x + 1][y + 1] = Some(cell);
-                            }
-                            continue;
-                        }
-                    }
-
-                    match cell.material() {
-                        Material::Powder | Material::Solid => (),
-                        Material::Liquid(_) | Material::Acid => {
-                            // Fill gaps
-
-                            let left = x > 0
-                                && (cell.sinks_under(new_cells[x - 1][y])
-                                    || cell.dissolves(new_cells[x - 1][y]))
-                                && (y == 0 || cell.sinks_under(grid.cells[x - 1][y - 1]));
-                            let right = x < GRID_WIDTH - 1
-                                && (cell.sinks_under(new_cells[x + 1][y])
-                                    || cell.dissolves(new_cells[x + 1][y]))
-                                && (y == 0 || cell.sinks_under(grid.cells[x + 1][y - 1]));
-
-                            let (left, right) = if left && right {
-                                if rng.gen() {
-                                    (true, false)
-                                } else {
-                                    (false, true)
-                                }
-                            } else {
-                                (left, right)
-                            };
-
-                            if left {
-                                if cell.dissolves(new_cells[x - 1][y]) {
-                                    new_cells[x][y] = None;
-                                    new_cells[x - 1][y] = None;
-                                } else {
-                                    new_cells[x][y] = new_cells[x - 1][y];
-                                    new_cells[x - 1][y] = Some(cell);
-                                }
-                                continue;
-                            }
-
-                            if right {
-                                if cell.dissolves(new_cells[x + 1][y]) {
-                                    new_cells[x][y] = None;
-                                    new_cells[x + 1][y] = None;
-                                } else {
-                                    new_cells[x][y] = new_cells[x + 1][y];
-                                    new_cells[x + 1][y] = Some(cell);
-                                }
-                                continue;
-                            }
-                        }
-                        Material::Gas => {
-                            // Disperse
-
-                            let dx = rng.gen_range(-1..=1);
-                            let dy = rng.gen_range(-1..=1);
-
-                            let new_x =
-                                (x as isize + dx).clamp(0, GRID_WIDTH as isize - 1) as usize;
-                            let new_y =
-                                (y as isize + dy).clamp(0, GRID_HEIGHT as isize - 1) as usize;
-
-                            if grid.cells[new_x][new_y].is_none()
-                                && new_cells[new_x][new_y].is_none()
-                            {
-                                new_cells[x][y] = None;
-                                new_cells[new_x][new_y] = Some(cell);
-                                continue;
-                            }
-                        }
-                        Material::Fire => {
-                            // Spread flames
-
-                            let flammables: Vec<_> = adjacent(x, y)
-                                .into_iter()
-                                .filter(|&(nx, ny)| {
-                                    grid.cells[nx][ny].is_some()
-                                        && grid.cells[nx][ny].unwrap().flammable()
-                                })
-                                .collect();
-
-                            for (nx, ny) in flammables {
-                                let open: Vec<_> = adjacent(nx, ny)
-                                    .into_iter()
-                                    .filter(|&(ax, ay)| {
-                                        grid.cells[ax][ay].is_none() && new_cells[ax][ay].is_none()
-                                    })
-                                    .collect();
-
-                                if let Some(&(ax, ay)) = open.choose(&mut rng) {
-                                    new_cells[ax][ay] = Some(Cell {
-                                        id: cell.id,
-                                        life: cell.lifespan(),
-                                    });
-                                }
-
-                                let chance = match grid.cells[nx][ny].unwrap().material() {
-                                    Material::Liquid(_) => 0.55,
-                                    _ => 0.1,
-                                };
-
-                                if rng.gen::<f32>() < chance {
-                                    new_cells[nx][ny] = Some(Cell {
-                                        id: cell.id,
-                                        life: cell.lifespan(),
-                                    });
-                                }
-                            }
-
-                            // Rise
-
-                            let dx = rng.gen_range(-1..=1);
-                            let dy = rng.gen_range(-2..=0);
-
-                            let new_x =
-                                (x as isize + dx).clamp(0, GRID_WIDTH as isize - 1) as usize;
-                            let new_y =
-                                (y as isize + dy).clamp(0, GRID_HEIGHT as isize - 1) as usize;
-
-                            new_cells[x][y] = None;
-
-                            match grid.cells[new_x][new_y] {
-                                Some(c) => {
-                                    if c.flammable() {
-                                        new_cells[new_x][new_y] = Some(cell);
-                                    }
-                                }
-                                None => new_cells[new_x][new_y] = Some(cell),
-                            }
-
-                            continue;
-                        }
-                        Material::Wind => todo!(),
-                    }
-                }
-            }
-        }
-
-        grid.cells = new_cells;
+fn tick_grid(
+    time: Res<Time>,
+    mut timer: ResMut<TickTimer>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+    view_mode: Res<ViewMode>,
+    settings: Res<Settings>,
+    mut trace: ResMut<SimTrace>,
+    mut stats: ResMut<SimStats>,
+    mut ev_sim: EventWriter<SimEvent>,
+) {
+    timer.tick(time.delta());
+
+    if timer.just_finished() {
+        let start = Instant::now();
+        let order = grid.scan_order(settings.scan_order, stats.ticks, &mut **rng);
+        let mut tick = if *view_mode == ViewMode::Normal {
+            grid.step_in_order(order, &mut **rng)
+        } else {
+            let order: Vec<_> = order.collect();
+            let before = grid.clone();
+            let tick = grid.step_in_order(order.iter().copied(), &mut **rng);
+            trace.record(before, &grid, &order);
+            tick
+        };
+        ev_sim.write_batch(tick.events.drain(..));
+        stats.tick = tick;
+        stats.duration = start.elapsed();
+        stats.ticks += 1;
     }
 }
 
 fn spawn_sand(
-    buttons: Res<ButtonInput<MouseButton>>,
-    q_window: Single<&Window, With<PrimaryWindow>>,
-    q_camera: Single<(&Camera, &GlobalTransform)>,
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    brush: Res<Brush>,
     mut grid: ResMut<Grid>,
     mut last_cursor_position: ResMut<LastCursorPosition>,
+    mut active_level: Option<ResMut<ActiveLevel>>,
+    mut lockstep: Option<ResMut<Lockstep>>,
+    mut ev_poured: EventWriter<Poured>,
+    mut flow_carry: Local<f32>,
+    mut drag_direction: Local<Vec2>,
 ) -> Result {
-    if buttons.pressed(MouseButton::Left) {
+    if pointer.painting {
         let (camera, camera_transform) = *q_camera;
-        if let Some(position) = q_window
-            .cursor_position()
-            .and_then(|cursor| Some(camera.viewport_to_world(camera_transform, cursor)))
+        if let Some(position) = pointer
+            .position
+            .map(|cursor| camera.viewport_to_world(camera_transform, cursor))
             .map(|ray| ray.map(|ray| ray.origin.truncate()))
         {
-            if let Some((cx, cy)) = world_to_tiles(position?) {
+            if let Some((cx, cy)) = world_to_tiles(&grid, position?) {
+                let mut rng = thread_rng();
                 let mut tiles = Vec::new();
-                let brush_size = BRUSH_SIZES[grid.brush_size];
+
+                if let Some(last) = last_cursor_position.0 {
+                    let delta = Vec2::new(cx as f32 - last.0 as f32, cy as f32 - last.1 as f32);
+                    if delta != Vec2::ZERO {
+                        *drag_direction = delta;
+                    }
+                }
 
                 let cursor_positions = match last_cursor_position.0 {
                     Some(last) => Bresenham::new(
@@ -370,39 +497,77 @@ fn spawn_sand(
                     None => vec![(cx, cy)],
                 };
 
+                // Oriented cells face the way the brush is dragged
+                let facing = Facing::of(*drag_direction)
+                    .filter(|_| brush.selected.oriented())
+                    .unwrap_or_default();
                 for (cx, cy) in cursor_positions {
-                    for x in (cx as isize - brush_size)..=(cx as isize + brush_size) {
-                        for y in (cy as isize - brush_size)..=(cy as isize + brush_size) {
-                            if (x - cx as isize).pow(2) + (y - cy as isize).pow(2)
-                                <= brush_size.pow(2)
-                                && x >= 0
-                                && (x as usize) < GRID_WIDTH
-                                && y >= 0
-                                && (y as usize) < GRID_HEIGHT
-                            {
-                                tiles.push((x as usize, y as usize));
-                            }
-                        }
-                    }
+                    tiles.extend(
+                        brush
+                            .stamp((cx as isize, cy as isize), *drag_direction, &mut rng)
+                            .into_iter()
+                            .flat_map(|tile| {
+                                let size = (grid.width(), grid.height());
+                                let images = brush.symmetry.images(tile, size);
+                                images.into_iter().zip(brush.symmetry.facings(facing))
+                            })
+                            .filter(|&((x, y), _)| grid.in_bounds(x, y))
+                            .map(|((x, y), facing)| (x as usize, y as usize, facing)),
+                    );
                 }
 
-                let mut rng = thread_rng();
                 tiles.shuffle(&mut rng);
 
-                for (x, y) in tiles[..max(tiles.len() / 2, 1)].iter().copied() {
-                    if grid.cells[x][y].is_none() {
-                        grid.cells[x][y] = Some(Cell {
-                            id: grid.selected,
-                            life: grid.selected.data().lifespan,
-                        });
+                // Holding shift paints over occupied tiles for as long as it is held
+                let replace = brush.replace
+                    || keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+                let paint = |grid: &mut Grid, x, y, facing| {
+                    let painted = if replace {
+                        grid.replace(x, y, brush.selected)
+                    } else {
+                        grid.spawn(x, y, brush.selected)
+                    };
+                    if painted {
+                        grid.face(x, y, facing);
+                    }
+                    painted
+                };
+
+                let count = brush.cells_to_fill(tiles.len(), time.delta_secs(), &mut flow_carry);
+                let mut placed = 0;
+                for (x, y, facing) in tiles[..count].iter().copied() {
+                    if let Some(lockstep) = &mut lockstep {
+                        // In a shared world the cells land once every player has simulated up to
+                        // the same tick
+                        lockstep.paint(x, y, brush.selected, replace, facing);
+                        placed += 1;
+                    } else if let Some(level) = &mut active_level {
+                        if !level.can_paint(x, y) {
+                            continue;
+                        }
+                        if paint(&mut grid, x, y, facing) {
+                            placed += 1;
+                            if let Some(budget) = &mut level.budget {
+                                *budget -= 1;
+                            }
+                        }
+                    } else if paint(&mut grid, x, y, facing) {
+                        placed += 1;
                     }
                 }
+                if placed > 0 {
+                    ev_poured.write(Poured {
+                        id: brush.selected,
+                        count: placed,
+                    });
+                }
 
                 last_cursor_position.0 = Some((cx, cy));
             }
         }
     } else {
         last_cursor_position.0 = None;
+        *flow_carry = 0.0;
     }
 
     Ok(())
@@ -411,46 +576,99 @@ fn spawn_sand(
 fn draw_grid(
     mut meshes: ResMut<Assets<Mesh>>,
     grid: Res<Grid>,
+    registry: Res<CellRegistry>,
+    settings: Res<Settings>,
+    view_mode: Res<ViewMode>,
+    trace: Res<SimTrace>,
+    life: Option<Res<LifeLayer>>,
+    q_camera: Single<(&Projection, &Transform), With<MainCamera>>,
     mut grid_mesh: Single<&mut Mesh2d, With<GridMesh>>,
 ) {
     let mut vertices = Vec::new();
     let mut vertex_colors = Vec::new();
     let mut indices = Vec::new();
 
-    for x in 0..GRID_WIDTH {
-        for y in 0..GRID_HEIGHT {
-            if let Some(cell) = grid.cells[x][y] {
-                let position = tiles_to_world(x, y);
-                vertices.extend([
-                    [
-                        position.x - DATA_SIZE / 2.0,
-                        position.y - DATA_SIZE / 2.0,
-                        0.0,
-                    ],
-                    [
-                        position.x + DATA_SIZE / 2.0,
-                        position.y - DATA_SIZE / 2.0,
-                        0.0,
-                    ],
-                    [
-                        position.x + DATA_SIZE / 2.0,
-                        position.y + DATA_SIZE / 2.0,
-                        0.0,
-                    ],
-                    [
-                        position.x - DATA_SIZE / 2.0,
-                        position.y + DATA_SIZE / 2.0,
-                        0.0,
-                    ],
-                ]);
-
-                let [r, g, b] = cell.color();
-                let c = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0];
-                vertex_colors.extend([c, c, c, c]);
-
-                let index = vertices.len() as u32 - 4;
-                indices.extend([index, index + 1, index + 2, index, index + 2, index + 3]);
+    let overlay = Overlay::new(*view_mode, &grid, &trace);
+    let draw_empty = overlay.is_some() && draws_empty(*view_mode);
+
+    // Only the tiles on screen are drawn, though all of them are simulated
+    let (projection, transform) = *q_camera;
+    let Projection::Orthographic(projection) = projection else {
+        return;
+    };
+    let offset = transform.translation.truncate();
+    let seen = Rect::from_corners(projection.area.min + offset, projection.area.max + offset);
+    let (xs, ys) = tiles_in(&grid, seen);
+    let visible = xs
+        .flat_map(|x| ys.clone().map(move |y| (x, y)))
+        .map(|(x, y)| (x, y, grid.get(x, y)));
+
+    for (x, y, cell) in visible {
+        let frozen = overlay.is_none() && grid.is_frozen(x, y);
+        let living = overlay.is_none()
+            && life
+                .as_ref()
+                .is_some_and(|life| x < life.width() && y < life.height() && life.is_alive(x, y));
+        let tint = |color: [u8; 3], with: [u8; 3]| -> [u8; 3] {
+            std::array::from_fn(|i| ((color[i] as u16 * 3 + with[i] as u16) / 4) as u8)
+        };
+        let look = |cell: Cell| -> [u8; 3] {
+            let color = settings.color_scheme.cell_color(&registry, cell);
+            if settings.hatching {
+                hatch(cell.id, x, y, color)
+            } else {
+                color
             }
+        };
+        let color = match (&overlay, cell) {
+            (Some(overlay), Some(_)) => overlay.color(x, y),
+            (Some(overlay), None) if draw_empty => overlay.color(x, y),
+            (None, Some(cell)) if living => tint(look(cell), LIFE_COLOR),
+            (None, Some(cell)) if frozen => tint(look(cell), FROZEN_COLOR),
+            (None, Some(cell)) => look(cell),
+            (None, None) if living => LIFE_COLOR,
+            (None, None) if frozen => FROZEN_COLOR,
+            _ => continue,
+        };
+
+        let position = tiles_to_world(&grid, x, y);
+        vertices.extend([
+            [
+                position.x - DATA_SIZE / 2.0,
+                position.y - DATA_SIZE / 2.0,
+                0.0,
+            ],
+            [
+                position.x + DATA_SIZE / 2.0,
+                position.y - DATA_SIZE / 2.0,
+                0.0,
+            ],
+            [
+                position.x + DATA_SIZE / 2.0,
+                position.y + DATA_SIZE / 2.0,
+                0.0,
+            ],
+            [
+                position.x - DATA_SIZE / 2.0,
+                position.y + DATA_SIZE / 2.0,
+                0.0,
+            ],
+        ]);
+
+        let [r, g, b] = color;
+        let c = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0];
+        vertex_colors.extend([c, c, c, c]);
+
+        let index = vertices.len() as u32 - 4;
+        indices.extend([index, index + 1, index + 2, index, index + 2, index + 3]);
+
+        if let Some(facing) = cell.filter(|_| overlay.is_none()).and_then(|c| c.facing()) {
+            vertices.extend(marker(position, facing).map(|corner| [corner.x, corner.y, 0.0]));
+            let [r, g, b, _] = c;
+            let shade = [r * MARKER_SHADE, g * MARKER_SHADE, b * MARKER_SHADE, 1.0];
+            vertex_colors.extend([shade; 3]);
+            let index = vertices.len() as u32 - 3;
+            indices.extend([index, index + 1, index + 2]);
         }
     }
 
@@ -473,99 +691,280 @@ fn draw_grid(
     }
 }
 
-fn update_brush_size(mut evr_scroll: EventReader<MouseWheel>, mut grid: ResMut<Grid>) {
-    for ev in evr_scroll.read() {
-        if ev.y < 0.0 && grid.brush_size > 0 {
-            grid.brush_size -= 1;
-        } else if ev.y > 0.0 && grid.brush_size < BRUSH_SIZES.len() - 1 {
-            grid.brush_size += 1;
+/// Corners of the arrowhead drawn over a cell centered on `position`, pointing the way it faces
+fn marker(position: Vec2, facing: Facing) -> [Vec2; 3] {
+    let (dx, dy) = facing.offset();
+    // Rows count down the grid but up the world
+    let ahead = Vec2::new(dx as f32, -dy as f32) * DATA_SIZE;
+    let back = position - ahead * 0.15;
+    [
+        position + ahead * 0.35,
+        back + ahead.perp() * 0.25,
+        back - ahead.perp() * 0.25,
+    ]
+}
+
+fn update_brush_size(
+    mut evr_scroll: EventReader<MouseWheel>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    q_gamepads: Query<&Gamepad>,
+    settings: Res<Settings>,
+    mut brush: ResMut<Brush>,
+) {
+    // Ctrl and the wheel zooms instead
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let mut steps: Vec<f32> = evr_scroll.read().filter(|_| !ctrl).map(|ev| ev.y).collect();
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::GrowBrush)
+    {
+        steps.push(1.0);
+    }
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::ShrinkBrush)
+    {
+        steps.push(-1.0);
+    }
+    for gamepad in &q_gamepads {
+        if gamepad.just_pressed(GamepadButton::DPadUp) {
+            steps.push(1.0);
+        }
+        if gamepad.just_pressed(GamepadButton::DPadDown) {
+            steps.push(-1.0);
         }
     }
+
+    for step in steps {
+        if step < 0.0 {
+            brush.radius = brush.radius.saturating_sub(1);
+        } else if step > 0.0 {
+            brush.radius += 1;
+        }
+    }
+    // Also pulls the radius back under a maximum lowered in the settings
+    if brush.radius > settings.max_brush_radius {
+        brush.radius = settings.max_brush_radius;
+    }
+}
+
+fn spawn_brush_hud(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(InWorld),
+        BrushHud,
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+    ));
 }
 
-fn select_tile(keyboard_input: Res<ButtonInput<KeyCode>>, mut grid: ResMut<Grid>) {
-    if keyboard_input.just_pressed(KeyCode::Digit1) {
-        grid.selected = CellId::Sand;
+fn update_brush_hud(brush: Res<Brush>, mut q_hud: Query<&mut Text, With<BrushHud>>) {
+    let text = format!("Brush radius: {}", brush.radius);
+    for mut hud in &mut q_hud {
+        if hud.0 != text {
+            hud.0.clone_from(&text);
+        }
+    }
+}
+
+fn update_brush_settings(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut brush: ResMut<Brush>,
+) {
+    let bindings = &settings.keybindings;
+    if bindings.just_pressed(&keyboard_input, Action::CycleShape) {
+        brush.shape = brush.shape.next();
     }
-    if keyboard_input.just_pressed(KeyCode::Digit2) {
-        grid.selected = CellId::Stone;
+    if bindings.just_pressed(&keyboard_input, Action::CycleSymmetry) {
+        brush.symmetry = brush.symmetry.next();
     }
-    if keyboard_input.just_pressed(KeyCode::Digit3) {
-        grid.selected = CellId::Wood;
+    if bindings.just_pressed(&keyboard_input, Action::ToggleReplace) {
+        brush.replace = !brush.replace;
     }
-    if keyboard_input.just_pressed(KeyCode::Digit4) {
-        grid.selected = CellId::Water;
+    if bindings.just_pressed(&keyboard_input, Action::DenserBrush) {
+        brush.change_density(1.0);
     }
-    if keyboard_input.just_pressed(KeyCode::Digit5) {
-        grid.selected = CellId::Oil;
+    if bindings.just_pressed(&keyboard_input, Action::SparserBrush) {
+        brush.change_density(-1.0);
     }
-    if keyboard_input.just_pressed(KeyCode::Digit6) {
-        grid.selected = CellId::Acid;
+    if bindings.just_pressed(&keyboard_input, Action::ToggleFlow) {
+        brush.flow = !brush.flow;
     }
-    if keyboard_input.just_pressed(KeyCode::Digit7) {
-        grid.selected = CellId::Oxygen;
+    if bindings.just_pressed(&keyboard_input, Action::FasterFlow) {
+        brush.change_flow_rate(1.0);
     }
-    if keyboard_input.just_pressed(KeyCode::Digit8) {
-        grid.selected = CellId::Fire;
+    if bindings.just_pressed(&keyboard_input, Action::SlowerFlow) {
+        brush.change_flow_rate(-1.0);
+    }
+}
+
+fn select_tile(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    q_gamepads: Query<&Gamepad>,
+    settings: Res<Settings>,
+    mut brush: ResMut<Brush>,
+    active_level: Option<Res<ActiveLevel>>,
+) {
+    // The bumpers step through the materials in the order of their default keys
+    let allowed: Vec<_> = Action::materials()
+        .filter(|&id| active_level.as_ref().is_none_or(|level| level.allows(id)))
+        .collect();
+    for gamepad in &q_gamepads {
+        let step = if gamepad.just_pressed(GamepadButton::RightTrigger) {
+            1
+        } else if gamepad.just_pressed(GamepadButton::LeftTrigger) {
+            allowed.len().saturating_sub(1)
+        } else {
+            continue;
+        };
+        let current = allowed.iter().position(|&id| id == brush.selected);
+        if let Some(&id) = allowed.get(current.map_or(0, |i| (i + step) % allowed.len())) {
+            brush.selected = id;
+        }
+    }
+
+    // Ctrl + number keys are reserved for loading presets
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    for id in Action::materials() {
+        if settings
+            .keybindings
+            .just_pressed(&keyboard_input, Action::Select(id))
+            && active_level.as_ref().is_none_or(|level| level.allows(id))
+        {
+            brush.selected = id;
+        }
     }
 }
 
-fn world_to_tiles(position: Vec2) -> Option<(usize, usize)> {
-    let x = (position.x + GRID_WIDTH as f32 * DATA_SIZE / 2.0) / DATA_SIZE;
-    let y = (-position.y + GRID_HEIGHT as f32 * DATA_SIZE / 2.0) / DATA_SIZE;
-    if x >= 0.0 && (x as usize) < GRID_WIDTH && y >= 0.0 && (y as usize) < GRID_HEIGHT {
+/// The grid position under the pointer, if any
+pub fn cursor_tile(
+    pointer: &Pointer,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    grid: &Grid,
+) -> Option<(usize, usize)> {
+    let cursor = pointer.position?;
+    let ray = camera.viewport_to_world(camera_transform, cursor).ok()?;
+    world_to_tiles(grid, ray.origin.truncate())
+}
+
+pub fn world_to_tiles(grid: &Grid, position: Vec2) -> Option<(usize, usize)> {
+    let x = (position.x + grid.width() as f32 * DATA_SIZE / 2.0) / DATA_SIZE;
+    let y = (-position.y + grid.height() as f32 * DATA_SIZE / 2.0) / DATA_SIZE;
+    if x >= 0.0 && (x as usize) < grid.width() && y >= 0.0 && (y as usize) < grid.height() {
         Some((x as usize, y as usize))
     } else {
         None
     }
 }
 
-fn tiles_to_world(x: usize, y: usize) -> Vec2 {
+pub fn tiles_to_world(grid: &Grid, x: usize, y: usize) -> Vec2 {
+    cells_to_world(grid, Vec2::new(x as f32 + 0.5, y as f32 + 0.5))
+}
+
+/// Converts continuous cell coordinates, as used by bodies moving between cells, to world space
+pub fn cells_to_world(grid: &Grid, position: Vec2) -> Vec2 {
     Vec2::new(
-        x as f32 * DATA_SIZE - GRID_WIDTH as f32 * DATA_SIZE / 2.0 + DATA_SIZE / 2.0,
-        -(y as f32 * DATA_SIZE - GRID_HEIGHT as f32 * DATA_SIZE / 2.0 + DATA_SIZE / 2.0),
+        position.x * DATA_SIZE - grid.width() as f32 * DATA_SIZE / 2.0,
+        -(position.y * DATA_SIZE - grid.height() as f32 * DATA_SIZE / 2.0),
     )
 }
 
-fn adjacent(x: usize, y: usize) -> Vec<(usize, usize)> {
-    let mut ids = Vec::new();
-    if x > 0 {
-        ids.push((x - 1, y));
-    }
-    if x < GRID_WIDTH - 1 {
-        ids.push((x + 1, y));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn density_fills_a_fraction_of_the_brush() {
+        let mut brush = Brush::default();
+        let mut carry = 0.0;
+        assert_eq!(brush.cells_to_fill(100, 0.1, &mut carry), 50);
+        brush.change_density(-9.0);
+        assert_eq!(brush.density, MIN_DENSITY);
+        assert_eq!(brush.cells_to_fill(100, 0.1, &mut carry), 10);
+        assert_eq!(brush.cells_to_fill(1, 0.1, &mut carry), 1);
+        brush.change_density(20.0);
+        assert_eq!(brush.cells_to_fill(100, 0.1, &mut carry), 100);
     }
-    if y > 0 {
-        ids.push((x, y - 1));
-    }
-    if y < GRID_HEIGHT - 1 {
-        ids.push((x, y + 1));
-    }
-    ids
-}
-
-fn neighbors(x: usize, y: usize) -> Vec<(usize, usize)> {
-    neighbors_within(x, y, 1)
-}
 
-fn neighbors_within(x: usize, y: usize, n: usize) -> Vec<(usize, usize)> {
-    let mut ids = Vec::new();
-    for dx in -(n as isize)..=n as isize {
-        for dy in -(n as isize)..=n as isize {
-            let nx = x as isize + dx;
-            let ny = y as isize + dy;
+    #[test]
+    fn shapes_cover_their_outline() {
+        let mut brush = Brush {
+            radius: 2,
+            ..default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+
+        brush.shape = BrushShape::Square;
+        assert_eq!(brush.stamp((10, 10), Vec2::X, &mut rng).len(), 25);
+
+        brush.shape = BrushShape::Line;
+        let mut line = brush.stamp((10, 10), Vec2::X, &mut rng);
+        line.sort();
+        assert_eq!(line, [(10, 8), (10, 9), (10, 10), (10, 11), (10, 12)]);
+
+        brush.shape = BrushShape::Spray;
+        assert!(brush
+            .stamp((10, 10), Vec2::X, &mut rng)
+            .iter()
+            .all(|&(x, y)| (x - 10).pow(2) + (y - 10).pow(2) <= 4));
+    }
 
-            if nx < 0 || nx >= GRID_WIDTH as isize || ny < 0 || ny >= GRID_HEIGHT as isize {
-                continue;
-            }
+    #[test]
+    fn symmetry_copies_across_the_middle() {
+        assert_eq!(Symmetry::MirrorX.images((1, 2), (10, 6)), [(1, 2), (8, 2)]);
+        assert_eq!(Symmetry::MirrorY.images((1, 2), (10, 6)), [(1, 2), (1, 3)]);
+        assert_eq!(
+            Symmetry::Radial.images((1, 2), (5, 5)),
+            [(1, 2), (2, 1), (3, 2), (2, 3)]
+        );
+    }
 
-            let nx = nx as usize;
-            let ny = ny as usize;
+    #[test]
+    fn copies_of_facing_cells_face_the_mirrored_way() {
+        assert_eq!(
+            Symmetry::MirrorX.facings(Facing::Right),
+            [Facing::Right, Facing::Left]
+        );
+        assert_eq!(
+            Symmetry::MirrorX.facings(Facing::Up),
+            [Facing::Up, Facing::Up]
+        );
+        // Each copy of a cell facing the middle still faces it
+        assert_eq!(
+            Symmetry::Radial.facings(Facing::Right),
+            [Facing::Right, Facing::Down, Facing::Left, Facing::Up]
+        );
+        assert_eq!(Facing::of(Vec2::new(-3.0, 1.0)), Some(Facing::Left));
+        assert_eq!(Facing::of(Vec2::ZERO), None);
+        // Arrowheads point up the screen for cells facing up the grid
+        let [tip, ..] = marker(Vec2::ZERO, Facing::Up);
+        assert!(tip.y > 0.0 && tip.x == 0.0);
+    }
 
-            if !(nx == 0 && ny == 0) {
-                ids.push((nx, ny));
-            }
-        }
+    #[test]
+    fn flow_is_steady_across_frames() {
+        let brush = Brush {
+            flow: true,
+            flow_rate: 100.0,
+            ..default()
+        };
+        let mut carry = 0.0;
+        let total: usize = (0..100)
+            .map(|_| brush.cells_to_fill(1000, 0.0025, &mut carry))
+            .sum();
+        assert_eq!(total, 25);
     }
-    ids
 }
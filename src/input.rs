@@ -0,0 +1,413 @@
+use crate::cell::CellId;
+use crate::locale::Locale;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Something the player can do from the keyboard, through whichever key they bound to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Select(CellId),
+    GrowBrush,
+    ShrinkBrush,
+    CycleShape,
+    ToggleReplace,
+    CycleSymmetry,
+    DenserBrush,
+    SparserBrush,
+    ToggleFlow,
+    FasterFlow,
+    SlowerFlow,
+    FreezeTool,
+    ThawAll,
+    SelectionTool,
+    CopySelection,
+    RotateSelection,
+    MirrorSelection,
+    PlaceSelection,
+    Ruler,
+    SpawnBox,
+    SpawnBall,
+    BuildPlatform,
+    BuildPiston,
+    DropMeteor,
+    HangRope,
+    HangCloth,
+    ToggleLife,
+    CycleLifeEffects,
+    SpawnAnt,
+    SpawnFish,
+    TogglePlayer,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    MoveDown,
+    Dig,
+    Mute,
+    Record,
+    Rewind,
+    NextLevel,
+    LeaveFinishedLevel,
+    ZoomIn,
+    ZoomOut,
+    CycleView,
+    ToggleFullscreen,
+    DetachPalette,
+    DetachStats,
+    DetachInspector,
+}
+
+/// Every action with the key it starts out bound to, in the order the controls screen lists them
+const DEFAULT_BINDINGS: [(Action, KeyCode); 88] = [
+    (Action::Select(CellId::Sand), KeyCode::Digit1),
+    (Action::Select(CellId::Stone), KeyCode::Digit2),
+    (Action::Select(CellId::Wood), KeyCode::Digit3),
+    (Action::Select(CellId::Water), KeyCode::Digit4),
+    (Action::Select(CellId::Oil), KeyCode::Digit5),
+    (Action::Select(CellId::Acid), KeyCode::Digit6),
+    (Action::Select(CellId::Oxygen), KeyCode::Digit7),
+    (Action::Select(CellId::Fire), KeyCode::Digit8),
+    (Action::Select(CellId::Bedrock), KeyCode::Digit9),
+    (Action::Select(CellId::Metal), KeyCode::Digit0),
+    (Action::Select(CellId::Barrier), KeyCode::KeyG),
+    (Action::Select(CellId::RedDye), KeyCode::KeyH),
+    (Action::Select(CellId::YellowDye), KeyCode::KeyJ),
+    (Action::Select(CellId::BlueDye), KeyCode::KeyI),
+    (Action::Select(CellId::Soap), KeyCode::KeyE),
+    (Action::Select(CellId::Foam), KeyCode::KeyQ),
+    (Action::Select(CellId::Gel), KeyCode::KeyD),
+    (Action::Select(CellId::Mercury), KeyCode::KeyW),
+    (Action::Select(CellId::Hydrogen), KeyCode::F1),
+    (Action::Select(CellId::Steam), KeyCode::F2),
+    (Action::Select(CellId::ToxicGas), KeyCode::F3),
+    (Action::Select(CellId::Ember), KeyCode::F4),
+    (Action::Select(CellId::Ash), KeyCode::ScrollLock),
+    (Action::Select(CellId::AcidGas), KeyCode::F6),
+    (Action::Select(CellId::MetalPowder), KeyCode::F7),
+    (Action::Select(CellId::Thermite), KeyCode::F8),
+    (Action::Select(CellId::MoltenMetal), KeyCode::F10),
+    (Action::Select(CellId::GravityWell), KeyCode::Numpad3),
+    (Action::Select(CellId::Repulsor), KeyCode::Pause),
+    (Action::Select(CellId::Portal), KeyCode::Semicolon),
+    (Action::Select(CellId::ConveyorLeft), KeyCode::Home),
+    (Action::Select(CellId::ConveyorRight), KeyCode::End),
+    (Action::Select(CellId::Heater), KeyCode::Insert),
+    (Action::Select(CellId::Cooler), KeyCode::Delete),
+    (Action::Select(CellId::Ice), KeyCode::Quote),
+    (Action::Select(CellId::Fuse), KeyCode::Slash),
+    (Action::Select(CellId::Firework), KeyCode::Backslash),
+    (Action::Select(CellId::Antimatter), KeyCode::Numpad1),
+    (Action::Select(CellId::Mold), KeyCode::Numpad2),
+    (Action::Select(CellId::Nitroglycerin), KeyCode::NumpadAdd),
+    (Action::Select(CellId::Fan), KeyCode::NumpadSubtract),
+    (Action::GrowBrush, KeyCode::BracketRight),
+    (Action::ShrinkBrush, KeyCode::BracketLeft),
+    (Action::CycleShape, KeyCode::KeyS),
+    (Action::ToggleReplace, KeyCode::KeyR),
+    (Action::CycleSymmetry, KeyCode::KeyY),
+    (Action::DenserBrush, KeyCode::Equal),
+    (Action::SparserBrush, KeyCode::Minus),
+    (Action::ToggleFlow, KeyCode::KeyT),
+    (Action::FasterFlow, KeyCode::Period),
+    (Action::SlowerFlow, KeyCode::Comma),
+    (Action::FreezeTool, KeyCode::KeyZ),
+    (Action::ThawAll, KeyCode::KeyU),
+    (Action::SelectionTool, KeyCode::Space),
+    (Action::CopySelection, KeyCode::KeyC),
+    (Action::RotateSelection, KeyCode::NumpadMultiply),
+    (Action::MirrorSelection, KeyCode::NumpadDivide),
+    (Action::PlaceSelection, KeyCode::NumpadEnter),
+    (Action::Ruler, KeyCode::NumpadDecimal),
+    (Action::SpawnBox, KeyCode::KeyB),
+    (Action::SpawnBall, KeyCode::KeyO),
+    (Action::BuildPlatform, KeyCode::KeyK),
+    (Action::BuildPiston, KeyCode::KeyN),
+    (Action::DropMeteor, KeyCode::Backquote),
+    (Action::HangRope, KeyCode::Numpad7),
+    (Action::HangCloth, KeyCode::Numpad8),
+    (Action::ToggleLife, KeyCode::Numpad9),
+    (Action::CycleLifeEffects, KeyCode::Numpad6),
+    (Action::SpawnAnt, KeyCode::KeyA),
+    (Action::SpawnFish, KeyCode::KeyF),
+    (Action::TogglePlayer, KeyCode::KeyP),
+    (Action::MoveLeft, KeyCode::ArrowLeft),
+    (Action::MoveRight, KeyCode::ArrowRight),
+    (Action::Jump, KeyCode::ArrowUp),
+    (Action::MoveDown, KeyCode::ArrowDown),
+    (Action::Dig, KeyCode::KeyX),
+    (Action::Mute, KeyCode::KeyM),
+    // R already toggles replace mode, so recording sits on F9
+    (Action::Record, KeyCode::F9),
+    (Action::Rewind, KeyCode::Backspace),
+    (Action::NextLevel, KeyCode::KeyL),
+    (Action::LeaveFinishedLevel, KeyCode::Enter),
+    (Action::ZoomIn, KeyCode::PageUp),
+    (Action::ZoomOut, KeyCode::PageDown),
+    (Action::CycleView, KeyCode::KeyV),
+    (Action::ToggleFullscreen, KeyCode::F11),
+    (Action::DetachPalette, KeyCode::Numpad4),
+    (Action::DetachStats, KeyCode::Numpad5),
+    (Action::DetachInspector, KeyCode::Numpad0),
+];
+
+/// Keys the game reads directly instead of through an action, which no action may be bound to:
+/// Escape for the menu, and the modifiers held for Ctrl and Shift shortcuts
+pub const RESERVED_KEYS: [KeyCode; 5] = [
+    KeyCode::Escape,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+];
+
+impl Action {
+    pub const ALL: [Action; DEFAULT_BINDINGS.len()] = {
+        let mut all = [Action::GrowBrush; DEFAULT_BINDINGS.len()];
+        let mut i = 0;
+        while i < all.len() {
+            all[i] = DEFAULT_BINDINGS[i].0;
+            i += 1;
+        }
+        all
+    };
+
+    /// The materials that have a selection key, in the order of their default keys
+    pub fn materials() -> impl Iterator<Item = CellId> {
+        Self::ALL.into_iter().filter_map(|action| match action {
+            Action::Select(id) => Some(id),
+            _ => None,
+        })
+    }
+
+    /// What the action does, in English with `{cell}` for the material it selects
+    fn label(self) -> &'static str {
+        match self {
+            Action::Select(_) => "Select {cell}",
+            Action::GrowBrush => "Grow brush",
+            Action::ShrinkBrush => "Shrink brush",
+            Action::CycleShape => "Change brush shape",
+            Action::ToggleReplace => "Toggle replace mode",
+            Action::CycleSymmetry => "Change symmetry",
+            Action::DenserBrush => "Denser brush",
+            Action::SparserBrush => "Sparser brush",
+            Action::ToggleFlow => "Toggle flow mode",
+            Action::FasterFlow => "Faster flow",
+            Action::SlowerFlow => "Slower flow",
+            Action::FreezeTool => "Toggle freeze tool",
+            Action::ThawAll => "Thaw everything",
+            Action::SelectionTool => "Toggle selection tool",
+            Action::CopySelection => "Lift a copy of the selection",
+            Action::RotateSelection => "Rotate the selection",
+            Action::MirrorSelection => "Mirror the selection",
+            Action::PlaceSelection => "Put the selection down",
+            Action::Ruler => "Toggle the ruler",
+            Action::SpawnBox => "Drop a box",
+            Action::SpawnBall => "Drop a ball",
+            Action::BuildPlatform => "Build a platform",
+            Action::BuildPiston => "Build a piston",
+            Action::DropMeteor => "Drop a meteor",
+            Action::HangRope => "Hang a rope",
+            Action::HangCloth => "Hang a cloth",
+            Action::ToggleLife => "Toggle the Life layer",
+            Action::CycleLifeEffects => "Change what Life does to the grid",
+            Action::SpawnAnt => "Spawn an ant",
+            Action::SpawnFish => "Spawn a fish",
+            Action::TogglePlayer => "Drop in or remove the player",
+            Action::MoveLeft => "Move the player left",
+            Action::MoveRight => "Move the player right",
+            Action::Jump => "Jump",
+            Action::MoveDown => "Move the player down",
+            Action::Dig => "Dig (hold)",
+            Action::Mute => "Mute",
+            Action::Record => "Start or stop recording",
+            Action::Rewind => "Rewind (hold)",
+            Action::NextLevel => "Play the next level",
+            Action::LeaveFinishedLevel => "Leave a finished level",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::CycleView => "Change the view mode",
+            Action::ToggleFullscreen => "Toggle fullscreen",
+            Action::DetachPalette => "Open the materials in a window",
+            Action::DetachStats => "Open the stats in a window",
+            Action::DetachInspector => "Open the cell inspector in a window",
+        }
+    }
+
+    /// What the action does, in the chosen language
+    pub fn describe(self, locale: &Locale) -> String {
+        let label = locale.text(self.label());
+        match self {
+            Action::Select(id) => label.replace("{cell}", locale.text(id.data().name)),
+            _ => label.to_string(),
+        }
+    }
+}
+
+/// What a key is called on screen, like "4" for `Digit4` and "Page Down" for `PageDown`
+pub fn key_name(key: KeyCode) -> String {
+    let symbol = match key {
+        KeyCode::Minus => Some("-"),
+        KeyCode::Equal => Some("="),
+        KeyCode::BracketLeft => Some("["),
+        KeyCode::BracketRight => Some("]"),
+        KeyCode::Backslash => Some("\\"),
+        KeyCode::Semicolon => Some(";"),
+        KeyCode::Quote => Some("'"),
+        KeyCode::Backquote => Some("`"),
+        KeyCode::Comma => Some(","),
+        KeyCode::Period => Some("."),
+        KeyCode::Slash => Some("/"),
+        _ => None,
+    };
+    if let Some(symbol) = symbol {
+        return symbol.to_string();
+    }
+    let debug = format!("{key:?}");
+    let name = ["Key", "Digit"]
+        .into_iter()
+        .find_map(|prefix| debug.strip_prefix(prefix).filter(|rest| rest.len() == 1))
+        .unwrap_or(&debug);
+    let mut words = String::new();
+    for (i, c) in name.char_indices() {
+        if i > 0 && c.is_ascii_uppercase() {
+            words.push(' ');
+        }
+        words.push(c);
+    }
+    words
+}
+
+/// The key bound to each action. Actions missing from a saved file keep their default key. A file
+/// that binds a key twice, or a key the game reads directly, is ignored for the defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "HashMap<Action, KeyCode>", into = "HashMap<Action, KeyCode>")]
+pub struct Keybindings {
+    keys: HashMap<Action, KeyCode>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            keys: DEFAULT_BINDINGS.into_iter().collect(),
+        }
+    }
+}
+
+impl From<HashMap<Action, KeyCode>> for Keybindings {
+    fn from(keys: HashMap<Action, KeyCode>) -> Self {
+        let mut bindings = Self::default();
+        bindings.keys.extend(keys);
+        if bindings.is_valid() {
+            bindings
+        } else {
+            warn!("The saved keybindings bind a key twice or a reserved key, using the defaults");
+            Self::default()
+        }
+    }
+}
+
+impl From<Keybindings> for HashMap<Action, KeyCode> {
+    fn from(bindings: Keybindings) -> Self {
+        bindings.keys
+    }
+}
+
+impl Keybindings {
+    pub fn key(&self, action: Action) -> KeyCode {
+        self.keys[&action]
+    }
+
+    pub fn just_pressed(&self, keyboard_input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        keyboard_input.just_pressed(self.key(action))
+    }
+
+    pub fn pressed(&self, keyboard_input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        keyboard_input.pressed(self.key(action))
+    }
+
+    /// Whether every key does at most one thing
+    fn is_valid(&self) -> bool {
+        let mut keys: Vec<_> = self.keys.values().chain(&RESERVED_KEYS).collect();
+        keys.sort();
+        keys.dedup();
+        keys.len() == self.keys.len() + RESERVED_KEYS.len()
+    }
+
+    /// Binds the key to the action. An action that already used the key takes over the action's
+    /// old key, so no key ever does two things.
+    pub fn rebind(&mut self, action: Action, key: KeyCode) {
+        let old = self.keys.insert(action, key).unwrap();
+        for (&other, bound) in &mut self.keys {
+            if other != action && *bound == key {
+                *bound = old;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::Translations;
+
+    #[test]
+    fn default_keys_are_all_different() {
+        assert!(Keybindings::default().is_valid());
+        assert_eq!(Keybindings::default().keys.len(), Action::ALL.len());
+    }
+
+    #[test]
+    fn actions_are_described_in_the_chosen_language() {
+        let spanish = Translations {
+            code: "es".to_string(),
+            name: "Español".to_string(),
+            strings: HashMap::from([
+                ("Select {cell}".to_string(), "Elegir {cell}".to_string()),
+                ("Sand".to_string(), "Arena".to_string()),
+            ]),
+        };
+        let locale = Locale::new("es", [&spanish]);
+        assert_eq!(
+            Action::Select(CellId::Sand).describe(&locale),
+            "Elegir Arena"
+        );
+        assert_eq!(Action::Jump.describe(&locale), "Jump");
+        assert_eq!(
+            Action::Select(CellId::Sand).describe(&Locale::default()),
+            "Select Sand"
+        );
+    }
+
+    #[test]
+    fn keys_have_readable_names() {
+        assert_eq!(key_name(KeyCode::Digit4), "4");
+        assert_eq!(key_name(KeyCode::KeyP), "P");
+        assert_eq!(key_name(KeyCode::BracketLeft), "[");
+        assert_eq!(key_name(KeyCode::NumpadDecimal), "Numpad Decimal");
+        assert_eq!(key_name(KeyCode::F9), "F9");
+        assert_eq!(key_name(KeyCode::Space), "Space");
+    }
+
+    #[test]
+    fn rebinding_a_used_key_swaps_the_keys() {
+        let mut bindings = Keybindings::default();
+        bindings.rebind(Action::Select(CellId::Sand), KeyCode::Digit2);
+        assert_eq!(bindings.key(Action::Select(CellId::Sand)), KeyCode::Digit2);
+        assert_eq!(bindings.key(Action::Select(CellId::Stone)), KeyCode::Digit1);
+    }
+
+    #[test]
+    fn missing_actions_keep_their_default_key() {
+        let bindings: Keybindings = ron::from_str("{Select(Sand): Tab}").unwrap();
+        assert_eq!(bindings.key(Action::Select(CellId::Sand)), KeyCode::Tab);
+        assert_eq!(bindings.key(Action::SpawnFish), KeyCode::KeyF);
+    }
+
+    #[test]
+    fn keys_bound_twice_fall_back_to_the_defaults() {
+        for saved in ["{Select(Sand): KeyQ}", "{Select(Sand): Escape}"] {
+            let bindings: Keybindings = ron::from_str(saved).unwrap();
+            assert_eq!(bindings, Keybindings::default(), "kept {saved}");
+        }
+    }
+}
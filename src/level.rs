@@ -0,0 +1,492 @@
+use crate::cell::CellId;
+use crate::cli::Options;
+use crate::grid::{Brush, WorldReset};
+use crate::input::Action;
+use crate::loading::LevelAssets;
+use crate::settings::Settings;
+use crate::sim::{Grid, GRID_HEIGHT, GRID_WIDTH};
+use crate::{GameState, InWorld};
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use line_drawing::Bresenham;
+use serde::Deserialize;
+use std::ops::Range;
+use std::path::Path;
+use thiserror::Error;
+
+/// A puzzle level, loaded from `.level.ron` files
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct Level {
+    pub name: String,
+    /// Shapes drawn onto an empty grid when the level starts
+    #[serde(default)]
+    pub terrain: Vec<Terrain>,
+    /// Materials the player may paint with
+    pub materials: Vec<CellId>,
+    /// Number of cells the player may paint in total
+    #[serde(default)]
+    pub brush_budget: Option<u32>,
+    /// Restricts painting to this region
+    #[serde(default)]
+    pub spawn_region: Option<Region>,
+    pub objective: Objective,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x - self.x < self.width && y >= self.y && y - self.y < self.height
+    }
+
+    /// The columns and rows of the region that lie inside the grid
+    pub fn clamp(&self, grid: &Grid) -> (Range<usize>, Range<usize>) {
+        let clamp = |start: usize, len: usize, max: usize| {
+            start.min(max)..start.saturating_add(len).min(max)
+        };
+        (
+            clamp(self.x, self.width, grid.width()),
+            clamp(self.y, self.height, grid.height()),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum Terrain {
+    Fill {
+        id: CellId,
+        region: Region,
+    },
+    Line {
+        id: CellId,
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum Objective {
+    /// At least `count` cells of the given kind inside the region
+    Collect {
+        id: CellId,
+        count: usize,
+        region: Region,
+    },
+    /// No cells of the given kind left anywhere in the grid
+    Clear { id: CellId },
+}
+
+impl Level {
+    pub fn build(&self, grid: &mut Grid) {
+        grid.clear();
+        for terrain in &self.terrain {
+            match *terrain {
+                Terrain::Fill { id, region } => {
+                    let (columns, rows) = region.clamp(grid);
+                    for x in columns {
+                        for y in rows.clone() {
+                            grid.spawn(x, y, id);
+                        }
+                    }
+                }
+                Terrain::Line { id, from, to } => {
+                    for (x, y) in Bresenham::new(
+                        (from.0 as isize, from.1 as isize),
+                        (to.0 as isize, to.1 as isize),
+                    ) {
+                        if grid.in_bounds(x, y) {
+                            grid.spawn(x as usize, y as usize, id);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rejects terrain that couldn't stay in place, like fire that burns out right away, and lines
+    /// reaching outside the grid
+    fn validate(&self) -> Result<(), LevelLoaderError> {
+        for terrain in &self.terrain {
+            let (id, points) = match *terrain {
+                Terrain::Fill { id, .. } => (id, Vec::new()),
+                Terrain::Line { id, from, to } => (id, vec![from, to]),
+            };
+            if id.data().lifespan.is_some() {
+                return Err(LevelLoaderError::Invalid(format!(
+                    "{id:?} can't be used as terrain"
+                )));
+            }
+            if let Some(&(x, y)) = points
+                .iter()
+                .find(|&&(x, y)| x >= GRID_WIDTH || y >= GRID_HEIGHT)
+            {
+                return Err(LevelLoaderError::Invalid(format!(
+                    "line point ({x}, {y}) lies outside the grid"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Objective {
+    /// How far along the objective is, as `(current, target)`
+    pub fn progress(&self, grid: &Grid) -> (usize, usize) {
+        match *self {
+            Objective::Collect { id, count, region } => {
+                let current = grid
+                    .iter()
+                    .filter(|&(x, y, cell)| {
+                        region.contains(x, y) && cell.is_some_and(|c| c.id == id)
+                    })
+                    .count();
+                (current.min(count), count)
+            }
+            Objective::Clear { id } => {
+                let remaining = grid
+                    .iter()
+                    .filter(|&(_, _, cell)| cell.is_some_and(|c| c.id == id))
+                    .count();
+                (usize::from(remaining == 0), 1)
+            }
+        }
+    }
+
+    pub fn is_complete(&self, grid: &Grid) -> bool {
+        let (current, target) = self.progress(grid);
+        current >= target
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Objective::Collect { id, count, .. } => {
+                format!("Guide {count} {id:?} cells into the goal")
+            }
+            Objective::Clear { id } => format!("Get rid of every {id:?} cell"),
+        }
+    }
+}
+
+/// The level currently being played, with the player's remaining brush budget
+#[derive(Resource)]
+pub struct ActiveLevel {
+    pub level: Level,
+    pub budget: Option<u32>,
+}
+
+impl ActiveLevel {
+    pub fn allows(&self, id: CellId) -> bool {
+        self.level.materials.contains(&id)
+    }
+
+    /// Whether the brush may place a cell at the given position
+    pub fn can_paint(&self, x: usize, y: usize) -> bool {
+        self.budget != Some(0)
+            && self
+                .level
+                .spawn_region
+                .is_none_or(|region| region.contains(x, y))
+    }
+}
+
+/// Reads a level from a `.level.ron` file outside the assets, like one given with `--level`
+pub fn read(path: &Path) -> Result<Level, LevelLoaderError> {
+    let level: Level = ron::from_str(&std::fs::read_to_string(path)?)?;
+    level.validate()?;
+    Ok(level)
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+#[derive(Debug, Error)]
+pub enum LevelLoaderError {
+    #[error("could not read level file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse level file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid level: {0}")]
+    Invalid(String),
+}
+
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let level: Level = ron::de::from_bytes(&bytes)?;
+        level.validate()?;
+        Ok(level)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+/// Starts the bundled level with the given index in [`LevelAssets`]
+#[derive(Event)]
+pub struct LoadLevel(pub usize);
+
+#[derive(Component)]
+pub struct LevelHud;
+
+pub struct LevelPlugin;
+
+/// This plugin runs puzzle levels on top of the sandbox
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .add_event::<LoadLevel>()
+            .enable_state_scoped_entities::<GameState>()
+            .add_systems(Update, next_level.run_if(in_state(InWorld)))
+            .add_systems(Update, start_level.run_if(on_event::<LoadLevel>))
+            .add_systems(
+                OnExit(GameState::Loading),
+                start_level_file.run_if(|options: Res<Options>| options.level.is_some()),
+            )
+            .add_systems(
+                Update,
+                (check_objective, update_hud)
+                    .run_if(in_state(InWorld).and(resource_exists::<ActiveLevel>)),
+            )
+            .add_systems(OnEnter(GameState::LevelComplete), show_level_complete)
+            .add_systems(
+                Update,
+                leave_level_complete.run_if(in_state(GameState::LevelComplete)),
+            );
+    }
+}
+
+// Cycles through the bundled levels
+fn next_level(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    level_assets: Res<LevelAssets>,
+    mut next_index: Local<usize>,
+    mut ev_load_level: EventWriter<LoadLevel>,
+) {
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::NextLevel)
+        && !level_assets.levels.is_empty()
+    {
+        ev_load_level.write(LoadLevel(*next_index % level_assets.levels.len()));
+        *next_index += 1;
+    }
+}
+
+fn start_level(
+    mut commands: Commands,
+    mut ev_load_level: EventReader<LoadLevel>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    mut grid: ResMut<Grid>,
+    mut brush: ResMut<Brush>,
+    q_hud: Query<Entity, With<LevelHud>>,
+    mut ev_world_reset: EventWriter<WorldReset>,
+) {
+    let Some(level) = ev_load_level
+        .read()
+        .last()
+        .and_then(|ev| level_assets.levels.get(ev.0))
+        .and_then(|handle| levels.get(handle))
+    else {
+        return;
+    };
+
+    begin(&mut commands, level, &mut grid, &mut brush, &q_hud);
+    ev_world_reset.write(WorldReset::Level);
+}
+
+// Skips the menu for the level given with `--level <file>`
+fn start_level_file(
+    mut commands: Commands,
+    options: Res<Options>,
+    mut grid: ResMut<Grid>,
+    mut brush: ResMut<Brush>,
+    q_hud: Query<Entity, With<LevelHud>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(path) = &options.level else {
+        return;
+    };
+    match read(path) {
+        Ok(level) => {
+            begin(&mut commands, &level, &mut grid, &mut brush, &q_hud);
+            next_state.set(GameState::Playing);
+        }
+        Err(err) => error!("Could not start {}: {err}", path.display()),
+    }
+}
+
+fn begin(
+    commands: &mut Commands,
+    level: &Level,
+    grid: &mut Grid,
+    brush: &mut Brush,
+    q_hud: &Query<Entity, With<LevelHud>>,
+) {
+    level.build(grid);
+    if let Some(&id) = level.materials.first() {
+        brush.selected = id;
+    }
+
+    for entity in q_hud {
+        commands.entity(entity).despawn();
+    }
+    commands.spawn((
+        LevelHud,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+    ));
+    commands.insert_resource(ActiveLevel {
+        level: level.clone(),
+        budget: level.brush_budget,
+    });
+}
+
+fn check_objective(
+    grid: Res<Grid>,
+    active_level: Res<ActiveLevel>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if active_level.level.objective.is_complete(&grid) {
+        next_state.set(GameState::LevelComplete);
+    }
+}
+
+fn update_hud(
+    grid: Res<Grid>,
+    active_level: Res<ActiveLevel>,
+    mut q_hud: Query<&mut Text, With<LevelHud>>,
+) {
+    let objective = &active_level.level.objective;
+    let (current, target) = objective.progress(&grid);
+    let mut text = format!(
+        "{}\n{} ({current}/{target})",
+        active_level.level.name,
+        objective.describe()
+    );
+    if let Some(budget) = active_level.budget {
+        text.push_str(&format!("\nBrush budget: {budget}"));
+    }
+
+    for mut hud in &mut q_hud {
+        hud.0.clone_from(&text);
+    }
+}
+
+fn show_level_complete(mut commands: Commands, active_level: Res<ActiveLevel>) {
+    commands.spawn((
+        StateScoped(GameState::LevelComplete),
+        Text::new(format!(
+            "{} complete!\nPress Enter to keep playing",
+            active_level.level.name
+        )),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            align_self: AlignSelf::Center,
+            justify_self: JustifySelf::Center,
+            ..default()
+        },
+    ));
+}
+
+fn leave_level_complete(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    q_hud: Query<Entity, With<LevelHud>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::LeaveFinishedLevel)
+    {
+        end_level(&mut commands, &q_hud);
+        next_state.set(GameState::Playing);
+    }
+}
+
+/// Returns to free play, removing the level HUD
+pub fn end_level(commands: &mut Commands, q_hud: &Query<Entity, With<LevelHud>>) {
+    for entity in q_hud {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<ActiveLevel>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(terrain: &str) -> Level {
+        ron::from_str(&format!(
+            "(name: \"Test\", terrain: [{terrain}], materials: [Sand], objective: Clear(id: Sand))"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn build_clamps_regions_to_the_grid() {
+        let level = parse(&format!(
+            "Fill(id: Stone, region: (x: 2, y: 1, width: {}, height: {}))",
+            usize::MAX,
+            usize::MAX
+        ));
+        let mut grid = Grid::new(4, 3);
+        level.build(&mut grid);
+        assert_eq!(grid.census().count(CellId::Stone), 4);
+        assert!(grid.get(3, 2).is_some() && grid.get(1, 2).is_none());
+    }
+
+    #[test]
+    fn region_contains_does_not_overflow() {
+        let region = Region {
+            x: 5,
+            y: 5,
+            width: usize::MAX,
+            height: 1,
+        };
+        assert!(region.contains(usize::MAX, 5));
+        assert!(!region.contains(4, 5) && !region.contains(6, 6));
+    }
+
+    #[test]
+    fn validate_rejects_unusable_terrain() {
+        let bad = [
+            "Fill(id: Fire, region: (x: 0, y: 0, width: 4, height: 4))",
+            "Line(id: Wind, from: (0, 0), to: (4, 4))",
+            "Line(id: Stone, from: (0, 0), to: (100000, 4))",
+        ];
+        for terrain in bad {
+            assert!(parse(terrain).validate().is_err(), "accepted {terrain}");
+        }
+        assert!(parse("Line(id: Stone, from: (0, 0), to: (4, 4))")
+            .validate()
+            .is_ok());
+    }
+}
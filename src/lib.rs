@@ -1,13 +1,100 @@
 #![allow(clippy::type_complexity)]
+#![allow(clippy::too_many_arguments)]
 
+mod accessibility;
+mod achievements;
 mod audio;
-mod cell;
+mod challenge;
+mod chunks;
+pub mod cli;
+mod climate;
+mod collider;
+mod creature;
+mod debris;
+mod detached;
+mod freeze;
 mod grid;
+mod input;
+mod level;
+mod life;
 mod loading;
+mod locale;
+mod menu;
+mod meteor;
+#[cfg(feature = "mods")]
+mod mods;
+mod net;
+mod osd;
+mod overlay;
+mod player;
+mod pointer;
+mod portal;
+mod preset;
+#[cfg(not(target_arch = "wasm32"))]
+mod record;
+mod registry;
+#[cfg(not(target_arch = "wasm32"))]
+mod replay;
+mod rewind;
+mod ruler;
+mod rules;
+#[cfg(feature = "scripting")]
+mod script;
+mod selection;
+mod settings;
+mod share;
+mod softbody;
+#[cfg(not(target_arch = "wasm32"))]
+mod spectate;
+mod stats;
+mod structure;
+mod touch;
+mod tutorial;
+mod vfx;
+mod view;
+mod worldgen;
 
+pub use powder_keg_sim as sim;
+pub use powder_keg_sim::cell;
+
+use crate::achievements::AchievementsPlugin;
 use crate::audio::InternalAudioPlugin;
+use crate::challenge::ChallengePlugin;
+use crate::chunks::ChunkPlugin;
+use crate::climate::ClimatePlugin;
+use crate::creature::CreaturePlugin;
+use crate::debris::DebrisPlugin;
+use crate::detached::DetachedPlugin;
+use crate::freeze::FreezePlugin;
 use crate::grid::GridPlugin;
+use crate::level::LevelPlugin;
+use crate::life::LifePlugin;
 use crate::loading::LoadingPlugin;
+use crate::locale::LocalePlugin;
+use crate::menu::MenuPlugin;
+use crate::meteor::MeteorPlugin;
+use crate::net::NetPlugin;
+use crate::osd::OsdPlugin;
+use crate::overlay::OverlayPlugin;
+use crate::player::PlayerPlugin;
+use crate::pointer::PointerPlugin;
+use crate::portal::PortalPlugin;
+use crate::preset::PresetPlugin;
+use crate::registry::CellRegistryPlugin;
+use crate::rewind::RewindPlugin;
+use crate::ruler::RulerPlugin;
+use crate::rules::RulesPlugin;
+use crate::selection::SelectionPlugin;
+use crate::settings::SettingsPlugin;
+use crate::share::SharePlugin;
+use crate::softbody::SoftBodyPlugin;
+use crate::stats::StatsPlugin;
+use crate::structure::StructurePlugin;
+use crate::touch::TouchPlugin;
+use crate::tutorial::TutorialPlugin;
+use crate::vfx::VfxPlugin;
+use crate::view::ViewPlugin;
+use crate::worldgen::WorldGenPlugin;
 
 use bevy::app::App;
 #[cfg(debug_assertions)]
@@ -19,15 +106,91 @@ use bevy::prelude::*;
 enum GameState {
     #[default]
     Loading,
+    Menu,
     Playing,
+    Tutorial,
+    LevelComplete,
+    Settings,
+}
+
+/// Whether a world is on screen and running, in free play or in the tutorial
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+struct InWorld;
+
+impl ComputedStates for InWorld {
+    type SourceStates = GameState;
+
+    fn compute(state: GameState) -> Option<Self> {
+        matches!(state, GameState::Playing | GameState::Tutorial).then_some(InWorld)
+    }
 }
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
+        // Left as it is when the binary inserted the options it was started with
+        app.init_resource::<cli::Options>();
         app.init_state::<GameState>()
-            .add_plugins((LoadingPlugin, InternalAudioPlugin, GridPlugin));
+            .add_computed_state::<InWorld>()
+            .enable_state_scoped_entities::<InWorld>();
+        app.add_plugins((
+            LoadingPlugin,
+            MenuPlugin,
+            InternalAudioPlugin,
+            GridPlugin,
+            CellRegistryPlugin,
+            LevelPlugin,
+            PresetPlugin,
+            WorldGenPlugin,
+            ChallengePlugin,
+            CreaturePlugin,
+            PlayerPlugin,
+            DebrisPlugin,
+            StructurePlugin,
+            OverlayPlugin,
+            (
+                PointerPlugin,
+                TouchPlugin,
+                SettingsPlugin,
+                OsdPlugin,
+                FreezePlugin,
+                SelectionPlugin,
+                RulerPlugin,
+                SharePlugin,
+                RewindPlugin,
+                NetPlugin,
+                ChunkPlugin,
+                ViewPlugin,
+                StatsPlugin,
+                VfxPlugin,
+                (
+                    PortalPlugin,
+                    MeteorPlugin,
+                    SoftBodyPlugin,
+                    LifePlugin,
+                    RulesPlugin,
+                    TutorialPlugin,
+                    AchievementsPlugin,
+                    LocalePlugin,
+                    DetachedPlugin,
+                    ClimatePlugin,
+                ),
+            ),
+        ));
+
+        // Recordings are written to disk, which the web doesn't have
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugins(record::RecordPlugin);
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugins(replay::ReplayPlugin);
+        // Spectators connect over TCP, which the web doesn't have either
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugins(spectate::SpectatePlugin);
+        #[cfg(feature = "scripting")]
+        app.add_plugins(script::ScriptPlugin);
+        #[cfg(feature = "mods")]
+        app.add_plugins(mods::ModPlugin);
 
         #[cfg(debug_assertions)]
         {
@@ -36,5 +199,15 @@ impl Plugin for GamePlugin {
                 LogDiagnosticsPlugin::default(),
             ));
         }
+
+        #[cfg(feature = "inspector")]
+        {
+            app.add_plugins((
+                bevy_inspector_egui::bevy_egui::EguiPlugin {
+                    enable_multipass_for_primary_context: true,
+                },
+                bevy_inspector_egui::quick::WorldInspectorPlugin::new(),
+            ));
+        }
     }
 }
@@ -0,0 +1,135 @@
+use crate::cell::CellId;
+use crate::grid::{cursor_tile, MainCamera, TickTimer};
+use crate::input::Action;
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::life::{LifeEffect, LifeLayer};
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+/// Simulation ticks between Life generations
+const GENERATION_TICKS: u32 = 4;
+
+/// Tiles out from the cursor the brush seeds with Life
+const SEED_RADIUS: usize = 3;
+
+/// What Life births and deaths do to the grid, cycled through in this order
+const EFFECTS: [(LifeEffect, LifeEffect); 4] = [
+    (LifeEffect::Nothing, LifeEffect::Nothing),
+    (LifeEffect::Deposit(CellId::Sand), LifeEffect::Nothing),
+    (LifeEffect::Nothing, LifeEffect::Destroy),
+    (LifeEffect::Deposit(CellId::Sand), LifeEffect::Destroy),
+];
+
+pub fn life_shown(life: Option<Res<LifeLayer>>) -> bool {
+    life.is_some()
+}
+
+pub struct LifePlugin;
+
+/// This plugin toggles a Game of Life layer over the grid, which the brush seeds instead of
+/// painting while it is shown. Births can deposit sand and deaths can clear the grid, as cycled
+/// through with their own key.
+impl Plugin for LifePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_life,
+                cycle_life_effects.run_if(life_shown),
+                seed_life.run_if(life_shown),
+                step_life.run_if(life_shown),
+            )
+                .chain()
+                .run_if(in_state(InWorld)),
+        );
+    }
+}
+
+fn toggle_life(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    grid: Res<Grid>,
+    life: Option<Res<LifeLayer>>,
+) {
+    if !settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::ToggleLife)
+    {
+        return;
+    }
+    match life {
+        Some(_) => commands.remove_resource::<LifeLayer>(),
+        None => commands.insert_resource(LifeLayer::new(grid.width(), grid.height())),
+    }
+}
+
+fn cycle_life_effects(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut life: ResMut<LifeLayer>,
+) {
+    if !settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::CycleLifeEffects)
+    {
+        return;
+    }
+    let index = EFFECTS
+        .iter()
+        .position(|&effects| effects == (life.on_birth, life.on_death))
+        .map_or(0, |index| (index + 1) % EFFECTS.len());
+    (life.on_birth, life.on_death) = EFFECTS[index];
+    info!(
+        "Life births: {:?}, deaths: {:?}",
+        life.on_birth, life.on_death
+    );
+}
+
+fn seed_life(
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    grid: Res<Grid>,
+    mut life: ResMut<LifeLayer>,
+) {
+    if !pointer.painting {
+        return;
+    }
+    let (camera, camera_transform) = *q_camera;
+    let Some((x, y)) = cursor_tile(&pointer, camera, camera_transform, &grid) else {
+        return;
+    };
+    let (width, height) = (life.width(), life.height());
+    let mut rng = thread_rng();
+    for sx in x.saturating_sub(SEED_RADIUS)..(x + SEED_RADIUS + 1).min(width) {
+        for sy in y.saturating_sub(SEED_RADIUS)..(y + SEED_RADIUS + 1).min(height) {
+            if rng.gen_bool(0.5) {
+                life.set_alive(sx, sy, true);
+            }
+        }
+    }
+}
+
+fn step_life(
+    tick_timer: Res<TickTimer>,
+    mut grid: ResMut<Grid>,
+    mut life: ResMut<LifeLayer>,
+    mut ticks: Local<u32>,
+) {
+    // A new world may have a new size
+    if (life.width(), life.height()) != (grid.width(), grid.height()) {
+        let (on_birth, on_death) = (life.on_birth, life.on_death);
+        *life = LifeLayer::new(grid.width(), grid.height());
+        (life.on_birth, life.on_death) = (on_birth, on_death);
+    }
+    if !tick_timer.just_finished() {
+        return;
+    }
+    *ticks += 1;
+    if ticks.is_multiple_of(GENERATION_TICKS) {
+        life.step(&mut grid);
+    }
+}
@@ -1,6 +1,11 @@
+use crate::cell::CellId;
+use crate::level::Level;
+use crate::locale::Translations;
+use crate::registry::CellDefinitions;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
+use bevy_kira_audio::AudioSource;
 
 pub struct LoadingPlugin;
 
@@ -8,7 +13,100 @@ pub struct LoadingPlugin;
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
         app.add_loading_state(
-            LoadingState::new(GameState::Loading).continue_to_state(GameState::Playing),
-        );
+            LoadingState::new(GameState::Loading)
+                .continue_to_state(GameState::Menu)
+                .load_collection::<LevelAssets>()
+                .load_collection::<CellAssets>()
+                .load_collection::<SoundAssets>()
+                .load_collection::<MusicAssets>()
+                .load_collection::<UiAssets>()
+                .load_collection::<LanguageAssets>(),
+        )
+        .add_systems(OnExit(GameState::Loading), use_ui_font);
+    }
+}
+
+/// Puts the UI font in place of Bevy's default font, so every text is drawn with it without
+/// naming it
+fn use_ui_font(ui_assets: Res<UiAssets>, mut fonts: ResMut<Assets<Font>>) {
+    if let Some(font) = fonts.get(&ui_assets.font).cloned() {
+        fonts.insert(AssetId::default(), font);
+    }
+}
+
+// the following asset collections will be loaded during the State `GameState::Loading`
+// when done loading, they will be inserted as resources (see <https://github.com/NiklasEi/bevy_asset_loader>)
+
+#[derive(AssetCollection, Resource)]
+pub struct LevelAssets {
+    #[asset(
+        paths("levels/basin.level.ron", "levels/controlled_burn.level.ron"),
+        collection(typed)
+    )]
+    pub levels: Vec<Handle<Level>>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct CellAssets {
+    #[asset(path = "default.cells.ron")]
+    pub definitions: Handle<CellDefinitions>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct LanguageAssets {
+    #[asset(paths("lang/es.lang.ron", "lang/de.lang.ron"), collection(typed))]
+    pub languages: Vec<Handle<Translations>>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct SoundAssets {
+    #[asset(path = "audio/pour_sand.wav")]
+    pub pour_sand: Handle<AudioSource>,
+    #[asset(path = "audio/pour_water.wav")]
+    pub pour_water: Handle<AudioSource>,
+    #[asset(path = "audio/place_stone.wav")]
+    pub place_stone: Handle<AudioSource>,
+    #[asset(path = "audio/crackle.wav")]
+    pub crackle: Handle<AudioSource>,
+    #[asset(path = "audio/sizzle.wav")]
+    pub sizzle: Handle<AudioSource>,
+    #[asset(path = "audio/hiss.wav")]
+    pub hiss: Handle<AudioSource>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct MusicAssets {
+    #[asset(path = "audio/ambient.wav")]
+    pub ambient: Handle<AudioSource>,
+    #[asset(path = "audio/music_pulse.wav")]
+    pub pulse: Handle<AudioSource>,
+    #[asset(path = "audio/music_drums.wav")]
+    pub drums: Handle<AudioSource>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct UiAssets {
+    #[asset(path = "fonts/FiraMono-Medium.ttf")]
+    pub font: Handle<Font>,
+    /// One icon per cell kind in the order of [`CellId::ALL`], drawn in grays to be tinted with
+    /// the color of the cell
+    #[asset(path = "textures/palette_icons.png")]
+    #[asset(image(sampler(filter = nearest)))]
+    pub palette_icons: Handle<Image>,
+    #[asset(texture_atlas_layout(tile_size_x = 16, tile_size_y = 16, columns = 49, rows = 1))]
+    pub palette_layout: Handle<TextureAtlasLayout>,
+}
+
+impl UiAssets {
+    /// The palette icon of a cell kind, tinted with its color
+    pub fn icon(&self, id: CellId, [r, g, b]: [u8; 3]) -> ImageNode {
+        ImageNode::from_atlas_image(
+            self.palette_icons.clone(),
+            TextureAtlas {
+                layout: self.palette_layout.clone(),
+                index: CellId::ALL.iter().position(|&kind| kind == id).unwrap(),
+            },
+        )
+        .with_color(Color::srgb_u8(r, g, b))
     }
 }
@@ -0,0 +1,210 @@
+use crate::loading::LanguageAssets;
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Code of the language the game is written in, which needs no translations
+pub const ENGLISH: &str = "en";
+
+/// Translations of the game's text into one language, loaded from a `.lang.ron` file. The English
+/// text is the key, so anything left out shows up in English.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct Translations {
+    /// Code the language is chosen by in the settings, like `es`
+    pub code: String,
+    /// Name of the language in the language itself
+    pub name: String,
+    pub strings: HashMap<String, String>,
+}
+
+/// The text of the chosen language
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Locale {
+    strings: HashMap<String, String>,
+    /// Codes and names of the languages there are translations for, English first
+    languages: Vec<(String, String)>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            strings: HashMap::new(),
+            languages: vec![(ENGLISH.to_string(), "English".to_string())],
+        }
+    }
+}
+
+impl Locale {
+    /// The locale for a language out of the translations available, English if there are none
+    /// for it
+    pub fn new<'a>(code: &str, translations: impl IntoIterator<Item = &'a Translations>) -> Self {
+        let mut locale = Self::default();
+        for translations in translations {
+            locale
+                .languages
+                .push((translations.code.clone(), translations.name.clone()));
+            if translations.code == code {
+                locale.strings = translations.strings.clone();
+            }
+        }
+        locale
+    }
+
+    /// Translates a piece of English text, or gives it back untouched if there is no translation
+    pub fn text<'a>(&'a self, english: &'a str) -> &'a str {
+        self.strings.get(english).map_or(english, String::as_str)
+    }
+
+    /// The name of a language, in that language
+    pub fn language_name<'a>(&'a self, code: &'a str) -> &'a str {
+        self.languages
+            .iter()
+            .find(|(known, _)| known == code)
+            .map_or(code, |(_, name)| name.as_str())
+    }
+
+    /// The language after a language in the list, going round to English after the last one
+    pub fn next_language(&self, code: &str) -> String {
+        let index = self.languages.iter().position(|(known, _)| known == code);
+        let next = index.map_or(0, |index| (index + 1) % self.languages.len());
+        self.languages[next].0.clone()
+    }
+}
+
+/// Text that is kept translated into the chosen language, given in English
+#[derive(Component)]
+pub struct Translated(pub &'static str);
+
+#[derive(Default)]
+pub struct TranslationsLoader;
+
+#[derive(Debug, Error)]
+pub enum TranslationsLoaderError {
+    #[error("could not read translations: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse translations: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for TranslationsLoader {
+    type Asset = Translations;
+    type Settings = ();
+    type Error = TranslationsLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["lang.ron"]
+    }
+}
+
+pub struct LocalePlugin;
+
+/// This plugin keeps the [`Locale`] in the language picked in the [`Settings`], out of the
+/// translations in `assets/lang`, and retranslates every [`Translated`] text when it changes
+impl Plugin for LocalePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Translations>()
+            .init_asset_loader::<TranslationsLoader>()
+            .init_resource::<Locale>()
+            // The menu is built as soon as loading is done, in the chosen language
+            .add_systems(OnExit(GameState::Loading), switch_language)
+            .add_systems(
+                Update,
+                (
+                    switch_language.run_if(resource_exists::<LanguageAssets>.and(
+                        resource_changed::<Settings>.or(on_event::<AssetEvent<Translations>>),
+                    )),
+                    translate_texts,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn switch_language(
+    settings: Res<Settings>,
+    language_assets: Res<LanguageAssets>,
+    translations: Res<Assets<Translations>>,
+    mut locale: ResMut<Locale>,
+) {
+    let loaded = language_assets
+        .languages
+        .iter()
+        .filter_map(|handle| translations.get(handle));
+    let switched = Locale::new(&settings.language, loaded);
+    // Leaves the locale alone when nothing changed, so the texts aren't retranslated every time a
+    // setting is
+    if *locale != switched {
+        *locale = switched;
+    }
+}
+
+fn translate_texts(locale: Res<Locale>, mut q_texts: Query<(Ref<Translated>, &mut Text)>) {
+    for (translated, mut text) in &mut q_texts {
+        if !locale.is_changed() && !translated.is_added() {
+            continue;
+        }
+        let translation = locale.text(translated.0);
+        if text.0 != translation {
+            text.0 = translation.to_string();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spanish() -> Translations {
+        Translations {
+            code: "es".to_string(),
+            name: "Español".to_string(),
+            strings: HashMap::from([("Sand".to_string(), "Arena".to_string())]),
+        }
+    }
+
+    #[test]
+    fn missing_translations_fall_back_to_english() {
+        let spanish = spanish();
+        let locale = Locale::new("es", [&spanish]);
+        assert_eq!(locale.text("Sand"), "Arena");
+        assert_eq!(locale.text("Water"), "Water");
+        assert_eq!(Locale::new(ENGLISH, [&spanish]).text("Sand"), "Sand");
+    }
+
+    #[test]
+    fn languages_cycle_back_to_english() {
+        let spanish = spanish();
+        let locale = Locale::new(ENGLISH, [&spanish]);
+        assert_eq!(locale.next_language(ENGLISH), "es");
+        assert_eq!(locale.next_language("es"), ENGLISH);
+        assert_eq!(locale.language_name("es"), "Español");
+        // A language whose translations went missing starts the cycle over
+        assert_eq!(locale.next_language("fr"), ENGLISH);
+    }
+
+    #[test]
+    fn translation_files_parse() {
+        for file in ["es", "de"] {
+            let path = format!("{}/assets/lang/{file}.lang.ron", env!("CARGO_MANIFEST_DIR"));
+            let contents = std::fs::read_to_string(path).unwrap();
+            let translations: Translations = ron::from_str(&contents).unwrap();
+            assert_eq!(translations.code, file);
+        }
+    }
+}
@@ -4,13 +4,51 @@
 use bevy::asset::AssetMetaCheck;
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
+use bevy::window::WindowResolution;
 use bevy::winit::WinitWindows;
 use bevy::DefaultPlugins;
+use powder_keg::cli::{self, Options};
+use powder_keg::sim::{scenario, ScanOrder, GRID_HEIGHT, GRID_WIDTH};
 use powder_keg::GamePlugin;
+use rand::prelude::*;
 use std::io::Cursor;
+use std::time::{Duration, Instant};
 use winit::window::Icon;
 
+/// Ticks simulated per scenario by `--bench-sim`
+const BENCH_TICKS: u32 = 200;
+
 fn main() {
+    let options = match Options::from_env() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    if options.bench_sim {
+        bench_sim();
+        return;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if options.headless {
+        if let Err(err) = cli::run_headless(&options) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let resolution = options
+        .window
+        .map_or_else(WindowResolution::default, |(width, height)| {
+            WindowResolution::new(width as f32, height as f32)
+        });
+
     App::new()
         .insert_resource(ClearColor(Color::linear_rgb(0.0, 0.0, 0.0)))
         .add_plugins(
@@ -18,6 +56,7 @@ fn main() {
                 .set(WindowPlugin {
                     primary_window: Some(Window {
                         title: "Falling Sand".to_string(),
+                        resolution,
                         // Bind to canvas included in `index.html`
                         canvas: Some("#bevy".to_owned()),
                         fit_canvas_to_parent: true,
@@ -32,11 +71,38 @@ fn main() {
                     ..default()
                 }),
         )
+        .insert_resource(options)
         .add_plugins(GamePlugin)
         .add_systems(Startup, set_window_icon)
         .run();
 }
 
+// Steps the worst-case scenarios without opening a window and prints how long a tick takes, with
+// each scan order
+fn bench_sim() {
+    for scan in [ScanOrder::Shuffled, ScanOrder::BottomUp] {
+        println!("{scan:?}");
+        for scenario in scenario::ALL {
+            let mut grid = (scenario.build)(GRID_WIDTH, GRID_HEIGHT);
+            let mut rng = StdRng::seed_from_u64(0);
+            let (mut total, mut slowest) = (Duration::ZERO, Duration::ZERO);
+            for tick in 0..BENCH_TICKS {
+                let start = Instant::now();
+                grid.step_with(scan, tick as u64, &mut rng);
+                let elapsed = start.elapsed();
+                total += elapsed;
+                slowest = slowest.max(elapsed);
+            }
+            println!(
+                "{:>8}: {:.3} ms per tick on average, {:.3} ms at worst",
+                scenario.name,
+                total.as_secs_f64() * 1000.0 / BENCH_TICKS as f64,
+                slowest.as_secs_f64() * 1000.0
+            );
+        }
+    }
+}
+
 // Sets the icon on windows and X11
 fn set_window_icon(
     windows: NonSend<WinitWindows>,
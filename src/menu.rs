@@ -0,0 +1,200 @@
+use crate::challenge::StartChallenge;
+use crate::level::{Level, LoadLevel};
+use crate::loading::LevelAssets;
+use crate::locale::Locale;
+use crate::preset::{LoadPreset, Preset};
+use crate::worldgen::GenerateWorld;
+use crate::{GameState, InWorld};
+use bevy::prelude::*;
+
+pub struct MenuPlugin;
+
+/// This plugin is responsible for the game menu, listing the preset scenes and levels
+/// The menu is only drawn during the State `GameState::Menu` and is removed when that state is exited
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Menu), setup_menu)
+            .add_systems(Update, click_menu_button.run_if(in_state(GameState::Menu)))
+            .add_systems(Update, hover_buttons)
+            .add_systems(Update, open_menu.run_if(in_state(InWorld)));
+    }
+}
+
+/// The colors of a button, which change while it is hovered
+#[derive(Component)]
+pub struct ButtonColors {
+    pub normal: Color,
+    pub hovered: Color,
+}
+
+impl Default for ButtonColors {
+    fn default() -> Self {
+        ButtonColors {
+            normal: Color::linear_rgb(0.15, 0.15, 0.15),
+            hovered: Color::linear_rgb(0.25, 0.25, 0.25),
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum MenuAction {
+    Tutorial,
+    NewWorld,
+    Challenge,
+    Preset(Preset),
+    Level(usize),
+    Settings,
+}
+
+fn setup_menu(
+    mut commands: Commands,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<Level>>,
+    locale: Res<Locale>,
+) {
+    let mut actions = vec![
+        (MenuAction::Tutorial, locale.text("Tutorial").to_string()),
+        (MenuAction::NewWorld, locale.text("New World").to_string()),
+        (MenuAction::Challenge, locale.text("Challenge").to_string()),
+    ];
+    actions.extend(Preset::ALL.into_iter().map(|preset| {
+        (
+            MenuAction::Preset(preset),
+            locale.text(preset.name()).to_string(),
+        )
+    }));
+    actions.extend(
+        level_assets
+            .levels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, handle)| levels.get(handle).map(|level| (i, level)))
+            .map(|(i, level)| {
+                let label = locale
+                    .text("Level: {name}")
+                    .replace("{name}", locale.text(&level.name));
+                (MenuAction::Level(i), label)
+            }),
+    );
+    actions.push((MenuAction::Settings, locale.text("Settings").to_string()));
+
+    commands
+        .spawn((
+            StateScoped(GameState::Menu),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.8)),
+        ))
+        .with_children(|children| {
+            children.spawn((
+                Text::new("Powder Keg"),
+                TextFont {
+                    font_size: 48.0,
+                    ..default()
+                },
+                Node {
+                    margin: UiRect::bottom(Val::Px(24.0)),
+                    ..default()
+                },
+            ));
+
+            for (action, label) in actions {
+                let button_colors = ButtonColors::default();
+                children
+                    .spawn((
+                        Button,
+                        Node {
+                            width: Val::Px(240.0),
+                            height: Val::Px(44.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        BackgroundColor(button_colors.normal),
+                        button_colors,
+                        action,
+                    ))
+                    .with_child((
+                        Text::new(label),
+                        TextFont {
+                            font_size: 24.0,
+                            ..default()
+                        },
+                        TextColor(Color::linear_rgb(0.9, 0.9, 0.9)),
+                    ));
+            }
+        });
+}
+
+fn click_menu_button(
+    mut next_state: ResMut<NextState<GameState>>,
+    interaction_query: Query<(&Interaction, &MenuAction), (Changed<Interaction>, With<Button>)>,
+    mut ev_load_preset: EventWriter<LoadPreset>,
+    mut ev_load_level: EventWriter<LoadLevel>,
+    mut ev_generate_world: EventWriter<GenerateWorld>,
+    mut ev_start_challenge: EventWriter<StartChallenge>,
+) {
+    for (interaction, action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *action {
+            MenuAction::Tutorial => {
+                next_state.set(GameState::Tutorial);
+                continue;
+            }
+            MenuAction::NewWorld => {
+                ev_generate_world.write(GenerateWorld);
+            }
+            MenuAction::Challenge => {
+                ev_start_challenge.write(StartChallenge);
+            }
+            MenuAction::Preset(preset) => {
+                ev_load_preset.write(LoadPreset(preset));
+            }
+            MenuAction::Level(index) => {
+                ev_load_level.write(LoadLevel(index));
+            }
+            MenuAction::Settings => {
+                next_state.set(GameState::Settings);
+                continue;
+            }
+        }
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn hover_buttons(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &ButtonColors),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button_colors) in &mut interaction_query {
+        match *interaction {
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+            Interaction::Pressed => {}
+        }
+    }
+}
+
+fn open_menu(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::Menu);
+    }
+}
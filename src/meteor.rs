@@ -0,0 +1,40 @@
+use crate::grid::{cursor_tile, MainCamera};
+use crate::input::Action;
+use crate::net::Lockstep;
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+
+pub struct MeteorPlugin;
+
+/// This plugin drops a meteor from the top of the grid over the cursor when the meteor key is
+/// pressed. It falls on its own and blasts a crater where it lands.
+impl Plugin for MeteorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, drop_meteor.run_if(in_state(InWorld)));
+    }
+}
+
+fn drop_meteor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut grid: ResMut<Grid>,
+    lockstep: Option<Res<Lockstep>>,
+) {
+    // Shared worlds only carry painted kinds
+    if lockstep.is_some()
+        || !settings
+            .keybindings
+            .just_pressed(&keyboard_input, Action::DropMeteor)
+    {
+        return;
+    }
+    let (camera, camera_transform) = *q_camera;
+    if let Some((x, _)) = cursor_tile(&pointer, camera, camera_transform, &grid) {
+        grid.drop_meteor(x);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::grid::{SimRng, TickTimer};
+use crate::sim::wasm::Mod;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext, LoadedFolder};
+use bevy::prelude::*;
+
+/// Folder the mods are loaded from, relative to the assets folder
+const MODS_PATH: &str = "mods";
+
+/// The bytes of a `.wasm` mod
+#[derive(Asset, TypePath, Debug)]
+pub struct ModBinary(pub Vec<u8>);
+
+#[derive(Default)]
+pub struct ModLoader;
+
+impl AssetLoader for ModLoader {
+    type Asset = ModBinary;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ModBinary(bytes))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wasm"]
+    }
+}
+
+/// The loaded mods, each with the asset it came from
+#[derive(Resource, Default)]
+pub struct Mods {
+    folder: Handle<LoadedFolder>,
+    loaded: Vec<(AssetId<ModBinary>, Mod)>,
+}
+
+pub struct ModPlugin;
+
+/// This plugin runs the mods in `assets/mods` after every simulation tick, loading them again
+/// whenever their files change
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ModBinary>()
+            .init_asset_loader::<ModLoader>()
+            .init_resource::<Mods>()
+            .add_systems(Startup, load_mods)
+            .add_systems(
+                Update,
+                instantiate_mods.run_if(on_event::<AssetEvent<ModBinary>>),
+            )
+            .add_systems(Update, run_mods.run_if(in_state(InWorld)));
+    }
+}
+
+fn load_mods(asset_server: Res<AssetServer>, mut mods: ResMut<Mods>) {
+    mods.folder = asset_server.load_folder(MODS_PATH);
+}
+
+fn instantiate_mods(
+    mut events: EventReader<AssetEvent<ModBinary>>,
+    asset_server: Res<AssetServer>,
+    binaries: Res<Assets<ModBinary>>,
+    mut mods: ResMut<Mods>,
+) {
+    for event in events.read() {
+        let (AssetEvent::Added { id } | AssetEvent::Modified { id }) = *event else {
+            continue;
+        };
+        let Some(ModBinary(bytes)) = binaries.get(id) else {
+            continue;
+        };
+        let path = asset_server
+            .get_path(id)
+            .map_or_else(|| id.to_string(), |path| path.to_string());
+
+        // A mod that fails to load again keeps nothing of its previous version running
+        mods.loaded.retain(|&(loaded, _)| loaded != id);
+        match Mod::load(bytes) {
+            Ok(loaded) => {
+                let kinds: Vec<_> = loaded.kinds().map(|id| id.data().name).collect();
+                info!("Loaded mod {path} for {}", kinds.join(", "));
+                mods.loaded.push((id, loaded));
+            }
+            Err(err) => error!("{path}: {err}"),
+        }
+    }
+}
+
+fn run_mods(
+    tick_timer: Res<TickTimer>,
+    mut mods: ResMut<Mods>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+) {
+    if !tick_timer.just_finished() || mods.loaded.is_empty() {
+        return;
+    }
+
+    // A failing mod would fail again on every tick, so it stays off until its file changes
+    mods.loaded
+        .retain_mut(|(_, loaded)| match loaded.apply(&mut grid, &mut **rng) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("{err}");
+                false
+            }
+        });
+}
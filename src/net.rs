@@ -0,0 +1,806 @@
+use crate::cell::{CellId, Facing};
+use crate::cli::Options;
+use crate::grid::{SimRng, TickSet, TickTimer};
+use crate::rewind::rewinding;
+use crate::share;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+/// Ticks between painting and the paint landing, which hides the round trip to the other players
+const INPUT_DELAY: u64 = 6;
+/// The most tiles one player paints per tick, so every packet fits in a datagram. Larger strokes
+/// land over several ticks.
+const MAX_TILES_PER_TICK: usize = 256;
+/// How often a client asks to join until the host answers
+const HELLO_INTERVAL: Duration = Duration::from_secs(1);
+/// The largest UDP payload
+const MAX_DATAGRAM: usize = 65507;
+/// The most players in one shared world, the host included
+const MAX_PLAYERS: usize = 8;
+/// How long the host waits to hear from a client before dropping it
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tiles painted with one material by one player in one tick
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Stroke {
+    id: CellId,
+    replace: bool,
+    /// The way oriented cells painted with the stroke face
+    facing: Facing,
+    tiles: Vec<(u16, u16)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Message {
+    /// A client asking to join, repeated until it is welcomed
+    Hello,
+    /// The host's answer to [`Message::Hello`], with the world as of `tick`
+    Welcome {
+        player: u8,
+        seed: u64,
+        /// [`Grid::registry_hash`] of the host's world, which the clients' has to match
+        registry: u64,
+        tick: u64,
+        players: Vec<(u8, u64, Option<u64>)>,
+        world: String,
+    },
+    /// Strokes of the players by tick, and the next tick the sender will simulate so the receiver
+    /// knows what it still needs. Packets from the host also list the players, the first tick of
+    /// each and the tick each one who left stopped at.
+    Inputs {
+        tick: u64,
+        players: Vec<(u8, u64, Option<u64>)>,
+        inputs: Vec<(u64, u8, Vec<Stroke>)>,
+    },
+}
+
+impl Message {
+    fn encode(&self) -> io::Result<Vec<u8>> {
+        let text = ron::to_string(self).map_err(io::Error::other)?;
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(text.as_bytes())?;
+        encoder.finish()
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let mut text = String::new();
+        DeflateDecoder::new(bytes)
+            .take(MAX_DATAGRAM as u64 * 64)
+            .read_to_string(&mut text)?;
+        ron::from_str(&text).map_err(io::Error::other)
+    }
+}
+
+/// The state every player of a shared world agrees on: which tick comes next, who takes part from
+/// which tick on, and what each of them paints on each tick. A tick is only simulated once the
+/// strokes of every player for it are in, and its randomness comes from the session seed and the
+/// tick number, so every copy of the grid goes through the same states.
+#[derive(Resource, Debug)]
+pub struct Lockstep {
+    player: u8,
+    seed: u64,
+    tick: u64,
+    /// The players, the first tick they send strokes for and, once they left, the first tick they
+    /// don't
+    players: BTreeMap<u8, (u64, Option<u64>)>,
+    inputs: BTreeMap<u64, BTreeMap<u8, Vec<Stroke>>>,
+    /// Painted here but not yet given a tick
+    local: Vec<Stroke>,
+    /// Whether the strokes of the current tick are on the grid, waiting for it to step
+    applied: bool,
+}
+
+impl Lockstep {
+    fn new(
+        player: u8,
+        seed: u64,
+        tick: u64,
+        players: impl IntoIterator<Item = (u8, u64, Option<u64>)>,
+    ) -> Self {
+        let mut lockstep = Self {
+            player,
+            seed,
+            tick,
+            players: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+            local: Vec::new(),
+            applied: false,
+        };
+        lockstep.sync_players(players);
+        lockstep
+    }
+
+    /// Expects strokes from a player from tick `from` on
+    fn join(&mut self, player: u8, from: u64) {
+        if self.players.insert(player, (from, None)).is_none() && player == self.player {
+            // Nothing was painted before joining, which also lets the first ticks go ahead
+            for tick in from..from + INPUT_DELAY {
+                self.receive(tick, player, Vec::new());
+            }
+        }
+    }
+
+    /// Stops expecting strokes from a player from the first tick they are missing for on. Nobody
+    /// has simulated that tick, since every stroke goes through the host.
+    fn leave(&mut self, player: u8) -> Option<u64> {
+        let (from, _) = *self.players.get(&player)?;
+        let until = (self.tick.max(from)..)
+            .find(|tick| {
+                !self
+                    .inputs
+                    .get(tick)
+                    .is_some_and(|inputs| inputs.contains_key(&player))
+            })
+            .unwrap_or(u64::MAX);
+        self.players.insert(player, (from, Some(until)));
+        Some(until)
+    }
+
+    /// Takes over the players as the host lists them
+    fn sync_players(&mut self, players: impl IntoIterator<Item = (u8, u64, Option<u64>)>) {
+        let players: BTreeMap<_, _> = players
+            .into_iter()
+            .map(|(player, from, until)| (player, (from, until)))
+            .collect();
+        self.players
+            .retain(|player, _| players.contains_key(player));
+        for (&player, &(from, until)) in &players {
+            if self
+                .players
+                .get(&player)
+                .is_none_or(|&(known, _)| known != from)
+            {
+                self.players.remove(&player);
+                self.join(player, from);
+            }
+            if let Some(entry) = self.players.get_mut(&player) {
+                entry.1 = until;
+            }
+        }
+    }
+
+    /// The players as the host lists them to the others
+    fn listed_players(&self) -> Vec<(u8, u64, Option<u64>)> {
+        self.players
+            .iter()
+            .map(|(&player, &(from, until))| (player, from, until))
+            .collect()
+    }
+
+    /// The lowest player number not taken, unless the world is full
+    fn free_player(&self) -> Option<u8> {
+        if self.players.len() >= MAX_PLAYERS {
+            return None;
+        }
+        (0..=u8::MAX).find(|player| !self.players.contains_key(player))
+    }
+
+    /// Queues a tile to be painted on a later tick
+    pub fn paint(&mut self, x: usize, y: usize, id: CellId, replace: bool, facing: Facing) {
+        let tile = (x as u16, y as u16);
+        match self.local.last_mut() {
+            Some(stroke) if (stroke.id, stroke.replace, stroke.facing) == (id, replace, facing) => {
+                stroke.tiles.push(tile)
+            }
+            _ => self.local.push(Stroke {
+                id,
+                replace,
+                facing,
+                tiles: vec![tile],
+            }),
+        }
+    }
+
+    fn receive(&mut self, tick: u64, player: u8, strokes: Vec<Stroke>) {
+        if tick >= self.tick {
+            self.inputs
+                .entry(tick)
+                .or_default()
+                .entry(player)
+                .or_insert(strokes);
+        }
+    }
+
+    fn expected(&self, tick: u64) -> impl Iterator<Item = u8> + '_ {
+        self.players
+            .iter()
+            .filter(move |&(_, &(from, until))| {
+                from <= tick && until.is_none_or(|until| tick < until)
+            })
+            .map(|(&player, _)| player)
+    }
+
+    fn ready(&self) -> bool {
+        let inputs = self.inputs.get(&self.tick);
+        self.expected(self.tick)
+            .all(|player| inputs.is_some_and(|inputs| inputs.contains_key(&player)))
+    }
+
+    /// Paints the strokes of the current tick once every player's are in, and returns the random
+    /// number generator to step it with
+    fn prepare(&mut self, grid: &mut Grid) -> Option<StdRng> {
+        if !self.applied {
+            if !self.ready() {
+                return None;
+            }
+            // The strokes stay around for players who are behind, see [`Lockstep::forget`]
+            for strokes in self
+                .inputs
+                .get(&self.tick)
+                .into_iter()
+                .flat_map(|inputs| inputs.values())
+            {
+                for stroke in strokes {
+                    for &(x, y) in &stroke.tiles {
+                        let (x, y) = (x as usize, y as usize);
+                        if grid.in_bounds(x as isize, y as isize) {
+                            let painted = if stroke.replace {
+                                grid.replace(x, y, stroke.id)
+                            } else {
+                                grid.spawn(x, y, stroke.id)
+                            };
+                            if painted {
+                                grid.face(x, y, stroke.facing);
+                            }
+                        }
+                    }
+                }
+            }
+            self.applied = true;
+        }
+        Some(SimRng::for_tick(self.seed, self.tick))
+    }
+
+    /// Moves on after the current tick was simulated, giving the tiles painted meanwhile to the
+    /// tick [`INPUT_DELAY`] ahead
+    fn finish(&mut self) {
+        self.tick += 1;
+        self.applied = false;
+
+        let mut strokes = Vec::new();
+        let mut budget = MAX_TILES_PER_TICK;
+        while budget > 0 {
+            let Some(stroke) = self.local.first_mut() else {
+                break;
+            };
+            let count = stroke.tiles.len().min(budget);
+            budget -= count;
+            strokes.push(Stroke {
+                id: stroke.id,
+                replace: stroke.replace,
+                facing: stroke.facing,
+                tiles: stroke.tiles.drain(..count).collect(),
+            });
+            if stroke.tiles.is_empty() {
+                self.local.remove(0);
+            }
+        }
+        let tick = self.tick + INPUT_DELAY - 1;
+        if self
+            .players
+            .get(&self.player)
+            .is_some_and(|&(from, _)| from <= tick)
+        {
+            self.receive(tick, self.player, strokes);
+        }
+    }
+
+    /// Drops the strokes of the ticks before `tick` and the current one, and the players who left
+    /// before them
+    fn forget(&mut self, tick: u64) {
+        let tick = tick.min(self.tick);
+        self.inputs = self.inputs.split_off(&tick);
+        self.players
+            .retain(|_, &mut (_, until)| until.is_none_or(|until| until > tick));
+    }
+
+    /// The strokes held for `tick` and later, of one player or of all of them
+    fn outgoing(&self, tick: u64, player: Option<u8>) -> Vec<(u64, u8, Vec<Stroke>)> {
+        self.inputs
+            .range(tick..)
+            .flat_map(|(&tick, inputs)| {
+                inputs
+                    .iter()
+                    .filter(move |&(&from, _)| player.is_none_or(|player| player == from))
+                    .map(move |(&from, strokes)| (tick, from, strokes.clone()))
+            })
+            .collect()
+    }
+}
+
+/// A client as the host sees it
+struct Peer {
+    player: u8,
+    /// The next tick the client simulates
+    acked: u64,
+    /// How long since the client was last heard from
+    silent: Duration,
+}
+
+/// Either end of a shared world. The host hands out player numbers and relays the strokes of every
+/// player to the others.
+enum Role {
+    Host {
+        clients: HashMap<SocketAddr, Peer>,
+    },
+    Client {
+        host: SocketAddr,
+        /// The next tick the host simulates
+        host_tick: u64,
+        since_hello: Duration,
+    },
+}
+
+#[derive(Resource)]
+struct Connection {
+    socket: UdpSocket,
+    role: Role,
+}
+
+fn send(socket: &UdpSocket, to: SocketAddr, message: &Message) {
+    match message.encode() {
+        Ok(bytes) if bytes.len() > MAX_DATAGRAM => {
+            warn!("Dropped a packet of {} bytes", bytes.len())
+        }
+        Ok(bytes) => {
+            if let Err(err) = socket.send_to(&bytes, to) {
+                warn!("Could not send to {to}: {err}");
+            }
+        }
+        Err(err) => warn!("Could not encode a packet: {err}"),
+    }
+}
+
+/// Opens the connection asked for with `--host <port>` or `--join <address>`
+fn connect(options: &Options) -> io::Result<Option<Connection>> {
+    let connection = if let Some(port) = options.host {
+        info!("Hosting a shared world on port {port}");
+        Connection {
+            socket: UdpSocket::bind(("0.0.0.0", port))?,
+            role: Role::Host {
+                clients: HashMap::new(),
+            },
+        }
+    } else if let Some(host) = &options.join {
+        let host = std::net::ToSocketAddrs::to_socket_addrs(host)?
+            .next()
+            .ok_or_else(|| io::Error::other(format!("{host} has no address")))?;
+        info!("Joining the shared world at {host}");
+        Connection {
+            socket: UdpSocket::bind(("0.0.0.0", 0))?,
+            role: Role::Client {
+                host,
+                host_tick: 0,
+                since_hello: HELLO_INTERVAL,
+            },
+        }
+    } else {
+        return Ok(None);
+    };
+    connection.socket.set_nonblocking(true)?;
+    Ok(Some(connection))
+}
+
+fn lockstep_ready(lockstep: Option<Res<Lockstep>>) -> bool {
+    lockstep.is_none_or(|lockstep| lockstep.applied)
+}
+
+pub struct NetPlugin;
+
+/// This plugin shares a world between several instances of the game over UDP, started with
+/// `--host <port>` on one of them and `--join <address>` on the others. Only the strokes of the
+/// brush are exchanged, so anything else that changes the grid, like loading a scene, puts the
+/// players out of sync.
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let connection = match connect(app.world().resource::<Options>()) {
+            Ok(Some(connection)) => connection,
+            Ok(None) => return,
+            Err(err) => {
+                error!("Could not open a shared world: {err}");
+                return;
+            }
+        };
+        if let Role::Host { .. } = connection.role {
+            let seed = app.world().resource::<Options>().seed;
+            let seed = seed.unwrap_or_else(|| thread_rng().gen());
+            app.insert_resource(Lockstep::new(0, seed, 0, [(0, 0, None)]));
+        }
+        app.insert_resource(connection)
+            .configure_sets(Update, TickSet.run_if(lockstep_ready))
+            .add_systems(
+                Update,
+                (receive_packets, prepare_tick)
+                    .chain()
+                    .before(TickSet)
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(
+                Update,
+                (finish_tick.run_if(not(rewinding)), send_packets)
+                    .chain()
+                    .after(TickSet)
+                    .run_if(in_state(InWorld)),
+            );
+    }
+}
+
+fn receive_packets(
+    mut commands: Commands,
+    mut connection: ResMut<Connection>,
+    mut lockstep: Option<ResMut<Lockstep>>,
+    mut grid: ResMut<Grid>,
+) {
+    let mut buffer = vec![0; MAX_DATAGRAM];
+    loop {
+        let (len, from) = match connection.socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!("Could not receive: {err}");
+                break;
+            }
+        };
+        let message = match Message::decode(&buffer[..len]) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("Ignored a packet from {from}: {err}");
+                continue;
+            }
+        };
+
+        let Connection { socket, role } = &mut *connection;
+        match (role, message) {
+            (Role::Host { clients }, Message::Hello) => {
+                let Some(lockstep) = &mut lockstep else {
+                    continue;
+                };
+                let player = match clients.get_mut(&from) {
+                    Some(peer) => {
+                        peer.silent = Duration::ZERO;
+                        peer.player
+                    }
+                    None => {
+                        let Some(player) = lockstep.free_player() else {
+                            warn!("Turned away {from}, the world is full");
+                            continue;
+                        };
+                        // Nobody is further than the input delay ahead of the host, so nobody has
+                        // simulated this tick yet
+                        let first_tick = lockstep.tick + 2 * INPUT_DELAY;
+                        lockstep.join(player, first_tick);
+                        info!("Player {player} joined from {from}");
+                        clients.insert(
+                            from,
+                            Peer {
+                                player,
+                                acked: lockstep.tick,
+                                silent: Duration::ZERO,
+                            },
+                        );
+                        player
+                    }
+                };
+                let world = match share::export(&grid) {
+                    Ok(world) => world,
+                    Err(err) => {
+                        warn!("{err}");
+                        continue;
+                    }
+                };
+                let welcome = Message::Welcome {
+                    player,
+                    seed: lockstep.seed,
+                    registry: grid.registry_hash(),
+                    tick: lockstep.tick,
+                    players: lockstep.listed_players(),
+                    world,
+                };
+                send(socket, from, &welcome);
+            }
+            (
+                Role::Host { clients, .. },
+                Message::Inputs {
+                    tick: client_tick,
+                    inputs,
+                    ..
+                },
+            ) => {
+                let (Some(lockstep), Some(peer)) = (&mut lockstep, clients.get_mut(&from)) else {
+                    continue;
+                };
+                peer.acked = client_tick;
+                peer.silent = Duration::ZERO;
+                // Nobody paints further ahead than this, see the first tick of new players
+                let horizon = lockstep.tick + 2 * INPUT_DELAY;
+                for (tick, _, strokes) in inputs {
+                    if tick <= horizon {
+                        lockstep.receive(tick, peer.player, strokes);
+                    }
+                }
+            }
+            (
+                Role::Client { host, .. },
+                Message::Welcome {
+                    player,
+                    seed,
+                    registry,
+                    tick,
+                    players,
+                    world,
+                },
+            ) if lockstep.is_none() && from == *host => {
+                // The world is replaced below, but this one is tuned like the game's cells
+                if registry != grid.registry_hash() {
+                    warn!(
+                        "Could not join, the host's cells or spread chances differ from this \
+                         game's and the world would not stay the same for both"
+                    );
+                    continue;
+                }
+                // Both sides have to be started with the same `--world` size
+                match share::import(&world, (grid.width(), grid.height())) {
+                    Ok(world) => {
+                        info!("Joined as player {player}");
+                        *grid = world;
+                        commands.insert_resource(Lockstep::new(player, seed, tick, players));
+                    }
+                    Err(err) => warn!("{err}"),
+                }
+            }
+            (
+                Role::Client {
+                    host, host_tick, ..
+                },
+                Message::Inputs {
+                    tick,
+                    players,
+                    inputs,
+                },
+            ) if from == *host => {
+                let Some(lockstep) = &mut lockstep else {
+                    continue;
+                };
+                *host_tick = tick;
+                lockstep.sync_players(players);
+                for (tick, player, strokes) in inputs {
+                    if player != lockstep.player {
+                        lockstep.receive(tick, player, strokes);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn prepare_tick(
+    lockstep: Option<ResMut<Lockstep>>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+) {
+    if let Some(mut lockstep) = lockstep {
+        if let Some(tick_rng) = lockstep.prepare(&mut grid) {
+            **rng = tick_rng;
+        }
+    }
+}
+
+fn finish_tick(timer: Res<TickTimer>, lockstep: Option<ResMut<Lockstep>>) {
+    if let Some(mut lockstep) = lockstep {
+        if lockstep.applied && timer.just_finished() {
+            lockstep.finish();
+        }
+    }
+}
+
+fn send_packets(
+    time: Res<Time>,
+    mut connection: ResMut<Connection>,
+    lockstep: Option<ResMut<Lockstep>>,
+) {
+    let Connection { socket, role } = &mut *connection;
+    match (role, lockstep) {
+        (Role::Host { clients }, Some(mut lockstep)) => {
+            // Players who went quiet would hold up every tick after their last strokes
+            clients.retain(|addr, peer| {
+                peer.silent += time.delta();
+                if peer.silent < PEER_TIMEOUT {
+                    return true;
+                }
+                if let Some(until) = lockstep.leave(peer.player) {
+                    warn!(
+                        "Dropped player {} at {addr}, not heard from since tick {until}",
+                        peer.player
+                    );
+                }
+                false
+            });
+            let players = lockstep.listed_players();
+            for (&addr, peer) in clients.iter() {
+                let inputs = Message::Inputs {
+                    tick: lockstep.tick,
+                    players: players.clone(),
+                    inputs: lockstep.outgoing(peer.acked, None),
+                };
+                send(socket, addr, &inputs);
+            }
+            // Strokes every client has simulated are no longer needed
+            let oldest = clients.values().map(|peer| peer.acked).min();
+            let tick = lockstep.tick;
+            lockstep.forget(oldest.unwrap_or(tick));
+        }
+        (
+            Role::Client {
+                host, host_tick, ..
+            },
+            Some(mut lockstep),
+        ) => {
+            lockstep.forget(*host_tick);
+            let inputs = Message::Inputs {
+                tick: lockstep.tick,
+                players: Vec::new(),
+                inputs: lockstep.outgoing(*host_tick, Some(lockstep.player)),
+            };
+            send(socket, *host, &inputs);
+        }
+        (
+            Role::Client {
+                host, since_hello, ..
+            },
+            None,
+        ) => {
+            *since_hello += time.delta();
+            if *since_hello >= HELLO_INTERVAL {
+                *since_hello = Duration::ZERO;
+                send(socket, *host, &Message::Hello);
+            }
+        }
+        (Role::Host { .. }, None) => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs one tick of the world on every player's copy, as far as the strokes in allow
+    fn step(lockstep: &mut Lockstep, grid: &mut Grid) -> bool {
+        let Some(mut rng) = lockstep.prepare(grid) else {
+            return false;
+        };
+        grid.step(&mut rng);
+        lockstep.finish();
+        true
+    }
+
+    /// Hands each player the strokes of the others
+    fn exchange(players: &mut [Lockstep]) {
+        let packets: Vec<_> = players
+            .iter()
+            .map(|lockstep| lockstep.outgoing(0, Some(lockstep.player)))
+            .collect();
+        for (i, lockstep) in players.iter_mut().enumerate() {
+            for (j, inputs) in packets.iter().enumerate() {
+                if i != j {
+                    for (tick, player, strokes) in inputs.clone() {
+                        lockstep.receive(tick, player, strokes);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn players_stay_in_sync() {
+        let mut players = [
+            Lockstep::new(0, 7, 0, [(0, 0, None), (1, 0, None)]),
+            Lockstep::new(1, 7, 0, [(0, 0, None), (1, 0, None)]),
+        ];
+        let mut grids = [Grid::new(24, 24), Grid::new(24, 24)];
+
+        for tick in 0..60 {
+            if tick < 10 {
+                players[0].paint(4 + tick, 0, CellId::Sand, false, Facing::Right);
+                players[1].paint(20, tick, CellId::Water, false, Facing::Right);
+            }
+            exchange(&mut players);
+            for (lockstep, grid) in players.iter_mut().zip(&mut grids) {
+                assert!(step(lockstep, grid));
+            }
+        }
+        assert_eq!(grids[0], grids[1]);
+        assert_eq!(grids[0].census().count(CellId::Sand), 10);
+    }
+
+    #[test]
+    fn ticks_wait_for_every_player() {
+        let mut host = Lockstep::new(0, 1, 0, [(0, 0, None), (1, 0, None)]);
+        let mut grid = Grid::new(4, 4);
+        assert!(!step(&mut host, &mut grid));
+
+        host.receive(0, 1, Vec::new());
+        assert!(step(&mut host, &mut grid));
+        assert!(!step(&mut host, &mut grid));
+    }
+
+    #[test]
+    fn players_who_left_are_no_longer_waited_for() {
+        let mut host = Lockstep::new(0, 1, 0, [(0, 0, None), (1, 0, None)]);
+        let mut grid = Grid::new(4, 4);
+        host.receive(0, 1, Vec::new());
+        host.receive(1, 1, Vec::new());
+        assert!(step(&mut host, &mut grid));
+
+        // The strokes in still count, the ticks after them go ahead without the player
+        assert_eq!(host.leave(1), Some(2));
+        for _ in 0..10 {
+            assert!(step(&mut host, &mut grid));
+        }
+        host.forget(u64::MAX);
+        assert_eq!(host.listed_players(), [(0, 0, None)]);
+        assert_eq!(host.inputs.keys().next(), Some(&host.tick));
+    }
+
+    #[test]
+    fn full_worlds_turn_players_away() {
+        let mut host = Lockstep::new(0, 1, 0, [(0, 0, None)]);
+        for _ in 1..MAX_PLAYERS {
+            let player = host.free_player().unwrap();
+            host.join(player, 0);
+        }
+        assert_eq!(host.free_player(), None);
+
+        host.leave(3);
+        host.forget(u64::MAX);
+        assert_eq!(host.free_player(), Some(3));
+    }
+
+    #[test]
+    fn large_strokes_spread_over_ticks() {
+        let mut lockstep = Lockstep::new(0, 1, 0, [(0, 0, None)]);
+        for x in 0..MAX_TILES_PER_TICK + 10 {
+            lockstep.paint(x % 100, x / 100, CellId::Stone, false, Facing::Right);
+        }
+        lockstep.finish();
+        lockstep.finish();
+        let tiles = |tick| -> usize {
+            lockstep.inputs[&tick][&0]
+                .iter()
+                .map(|stroke| stroke.tiles.len())
+                .sum()
+        };
+        assert_eq!(tiles(INPUT_DELAY), MAX_TILES_PER_TICK);
+        assert_eq!(tiles(INPUT_DELAY + 1), 10);
+    }
+
+    #[test]
+    fn messages_survive_encoding() {
+        let message = Message::Inputs {
+            tick: 3,
+            players: vec![(0, 0, None), (1, 12, Some(40))],
+            inputs: vec![(
+                8,
+                1,
+                vec![Stroke {
+                    id: CellId::Acid,
+                    replace: true,
+                    facing: Facing::Up,
+                    tiles: vec![(1, 2), (3, 4)],
+                }],
+            )],
+        };
+        let bytes = message.encode().unwrap();
+        assert_eq!(Message::decode(&bytes).unwrap(), message);
+    }
+}
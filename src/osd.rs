@@ -0,0 +1,140 @@
+use crate::cell::CellId;
+use crate::grid::Brush;
+use crate::loading::UiAssets;
+use crate::locale::Locale;
+use crate::registry::CellRegistry;
+use crate::settings::Settings;
+use crate::InWorld;
+use bevy::prelude::*;
+
+/// How long the indicator stays up after the brush changes, in seconds
+const SHOW_TIME: f32 = 1.5;
+/// Side of the material icon, in logical pixels
+const ICON_SIZE: f32 = 18.0;
+
+/// The indicator, hidden again once its timer runs out
+#[derive(Component)]
+struct Osd(Timer);
+
+#[derive(Component)]
+struct OsdIcon;
+
+#[derive(Component)]
+struct OsdLabel;
+
+pub struct OsdPlugin;
+
+/// This plugin briefly shows the selected material and the brush radius in the middle of the
+/// screen whenever either changes, since nothing else tells which key was pressed last.
+impl Plugin for OsdPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(InWorld), spawn_osd)
+            .add_systems(Update, update_osd.run_if(in_state(InWorld)));
+    }
+}
+
+fn spawn_osd(
+    mut commands: Commands,
+    ui_assets: Res<UiAssets>,
+    registry: Res<CellRegistry>,
+    settings: Res<Settings>,
+    brush: Res<Brush>,
+) {
+    // Starts out run down, so the indicator waits for the first change
+    let mut timer = Timer::from_seconds(SHOW_TIME, TimerMode::Once);
+    timer.tick(timer.duration());
+
+    commands
+        .spawn((
+            StateScoped(InWorld),
+            Osd(timer),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Px(48.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            Visibility::Hidden,
+        ))
+        .with_children(|children| {
+            children
+                .spawn((
+                    Node {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.0),
+                        padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.6)),
+                    BorderRadius::all(Val::Px(4.0)),
+                ))
+                .with_children(|row| {
+                    row.spawn((
+                        OsdIcon,
+                        Node {
+                            width: Val::Px(ICON_SIZE),
+                            height: Val::Px(ICON_SIZE),
+                            ..default()
+                        },
+                        ui_assets.icon(
+                            brush.selected,
+                            settings.color_scheme.color(&registry, brush.selected),
+                        ),
+                    ));
+                    row.spawn((
+                        OsdLabel,
+                        Text::default(),
+                        TextFont {
+                            font_size: 18.0,
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn update_osd(
+    time: Res<Time>,
+    brush: Res<Brush>,
+    registry: Res<CellRegistry>,
+    settings: Res<Settings>,
+    ui_assets: Res<UiAssets>,
+    locale: Res<Locale>,
+    mut shown: Local<Option<(CellId, usize)>>,
+    mut q_osd: Query<(&mut Osd, &mut Visibility)>,
+    mut q_icon: Query<&mut ImageNode, With<OsdIcon>>,
+    mut q_label: Query<&mut Text, With<OsdLabel>>,
+) {
+    let current = (brush.selected, brush.radius);
+    // The first frame only records the brush, so entering the game doesn't flash the indicator
+    let changed = shown.is_some_and(|shown| shown != current);
+    *shown = Some(current);
+
+    if changed {
+        for mut icon in &mut q_icon {
+            *icon = ui_assets.icon(
+                brush.selected,
+                settings.color_scheme.color(&registry, brush.selected),
+            );
+        }
+        for mut label in &mut q_label {
+            label.0 = locale
+                .text("{cell}, radius {radius}")
+                .replace("{cell}", locale.text(brush.selected.data().name))
+                .replace("{radius}", &brush.radius.to_string());
+        }
+    }
+
+    for (mut osd, mut visibility) in &mut q_osd {
+        if changed {
+            osd.0.reset();
+        }
+        osd.0.tick(time.delta());
+        *visibility = if osd.0.finished() {
+            Visibility::Hidden
+        } else {
+            Visibility::Visible
+        };
+    }
+}
@@ -0,0 +1,281 @@
+use crate::cell::Cell;
+use crate::input::Action;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+
+/// Width and height of the regions the activity view reports on
+const ACTIVITY_CHUNK: usize = 16;
+
+/// How the grid mesh colors its cells
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum ViewMode {
+    #[default]
+    Normal,
+    /// Temperature, estimated from the fire around each cell until cells carry their own
+    Heat,
+    /// Direction each cell moved in during the last tick
+    Velocity,
+    /// Regions where anything changed during the last tick
+    Activity,
+    /// Position of each cell in the last tick's update order, from dark to bright
+    UpdateOrder,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 5] = [
+        ViewMode::Normal,
+        ViewMode::Heat,
+        ViewMode::Velocity,
+        ViewMode::Activity,
+        ViewMode::UpdateOrder,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ViewMode::Normal => "Normal",
+            ViewMode::Heat => "Heat map",
+            ViewMode::Velocity => "Velocity",
+            ViewMode::Activity => "Chunk activity",
+            ViewMode::UpdateOrder => "Update order",
+        }
+    }
+
+    fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|mode| mode == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// What the last simulation tick did, recorded while a debug view needs it
+#[derive(Resource, Debug, Default)]
+pub struct SimTrace {
+    /// The grid right before and right after the tick, so cells written afterwards by the brush,
+    /// creatures, debris or structures don't show up as simulation activity
+    pub tick: Option<(Grid, Grid)>,
+    /// Index of each position in the update order, column by column
+    pub order: Vec<u32>,
+}
+
+impl SimTrace {
+    pub fn record(&mut self, before: Grid, after: &Grid, order: &[(usize, usize)]) {
+        self.order.resize(order.len(), 0);
+        for (i, &(x, y)) in order.iter().enumerate() {
+            self.order[x * before.height() + y] = i as u32;
+        }
+        self.tick = Some((before, after.clone()));
+    }
+}
+
+/// Colors computed for every position of the grid in one of the debug views
+pub struct Overlay {
+    height: usize,
+    colors: Vec<[u8; 3]>,
+}
+
+impl Overlay {
+    pub fn new(mode: ViewMode, grid: &Grid, trace: &SimTrace) -> Option<Self> {
+        let colors = match mode {
+            ViewMode::Normal => return None,
+            ViewMode::Heat => heat_colors(grid),
+            ViewMode::Velocity => {
+                let (before, after) = trace.tick.as_ref()?;
+                velocity_colors(after, before)
+            }
+            ViewMode::Activity => {
+                let (before, after) = trace.tick.as_ref()?;
+                activity_colors(after, before)
+            }
+            ViewMode::UpdateOrder => {
+                let len = trace.order.len().max(1) as f32;
+                trace
+                    .order
+                    .iter()
+                    .map(|&i| [(i as f32 / len * 255.0) as u8; 3])
+                    .collect()
+            }
+        };
+        (colors.len() == grid.width() * grid.height()).then_some(Self {
+            height: grid.height(),
+            colors,
+        })
+    }
+
+    pub fn color(&self, x: usize, y: usize) -> [u8; 3] {
+        self.colors[x * self.height + y]
+    }
+}
+
+fn cell_heat(cell: Option<Cell>) -> f32 {
+    match cell {
+        Some(cell) if cell.ignites() => match (cell.life, cell.lifespan()) {
+            (Some(life), Some(lifespan)) => 0.5 + 0.5 * life as f32 / lifespan.max as f32,
+            _ => 1.0,
+        },
+        _ => 0.0,
+    }
+}
+
+fn heat_colors(grid: &Grid) -> Vec<[u8; 3]> {
+    let (width, height) = (grid.width(), grid.height());
+    let mut heat: Vec<f32> = grid.iter().map(|(_, _, cell)| cell_heat(cell)).collect();
+
+    // Spread the heat out with a few passes of a 3x3 mean over each cell's neighbours, keeping the
+    // sources at full strength
+    let mut blurred = vec![0.0; heat.len()];
+    for _ in 0..4 {
+        for x in 0..width {
+            let columns = x.saturating_sub(1)..=(x + 1).min(width - 1);
+            for y in 0..height {
+                let rows = y.saturating_sub(1)..=(y + 1).min(height - 1);
+                let i = x * height + y;
+                let mut sum = -heat[i];
+                for nx in columns.clone() {
+                    for ny in rows.clone() {
+                        sum += heat[nx * height + ny];
+                    }
+                }
+                let neighbors = columns.clone().count() * rows.count() - 1;
+                blurred[i] = heat[i].max(sum / neighbors.max(1) as f32);
+            }
+        }
+        std::mem::swap(&mut heat, &mut blurred);
+    }
+
+    heat.into_iter()
+        .map(|t| {
+            // Black through red and yellow to white
+            let t = t.clamp(0.0, 1.0) * 3.0;
+            [
+                (t.min(1.0) * 255.0) as u8,
+                ((t - 1.0).clamp(0.0, 1.0) * 255.0) as u8,
+                ((t - 2.0).clamp(0.0, 1.0) * 255.0) as u8,
+            ]
+        })
+        .collect()
+}
+
+fn velocity_colors(grid: &Grid, previous: &Grid) -> Vec<[u8; 3]> {
+    grid.iter()
+        .map(|(x, y, cell)| {
+            if cell.is_none() || previous.get(x, y) == cell {
+                return [40, 40, 40];
+            }
+
+            // Find where the cell most likely came from, preferring falls over rises
+            let (x, y) = (x as isize, y as isize);
+            let origin = [(0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0), (0, 1)]
+                .into_iter()
+                .find(|&(dx, dy)| previous.cell_at(x + dx, y + dy) == cell);
+            match origin {
+                Some((dx, dy)) => [(128 - dx * 127) as u8, (128 - dy * 127) as u8, 200],
+                // Changed in place, like burning or dissolving
+                None => [255, 255, 255],
+            }
+        })
+        .collect()
+}
+
+fn activity_colors(grid: &Grid, previous: &Grid) -> Vec<[u8; 3]> {
+    let (width, height) = (grid.width(), grid.height());
+    let chunks_y = height.div_ceil(ACTIVITY_CHUNK);
+    let mut awake = vec![false; width.div_ceil(ACTIVITY_CHUNK) * chunks_y];
+    for (x, y, cell) in grid.iter() {
+        if previous.get(x, y) != cell {
+            awake[x / ACTIVITY_CHUNK * chunks_y + y / ACTIVITY_CHUNK] = true;
+        }
+    }
+
+    grid.iter()
+        .map(|(x, y, _)| {
+            let chunk = x / ACTIVITY_CHUNK * chunks_y + y / ACTIVITY_CHUNK;
+            let border = x % ACTIVITY_CHUNK == 0 || y % ACTIVITY_CHUNK == 0;
+            match (awake[chunk], border) {
+                (true, true) => [80, 255, 80],
+                (true, false) => [40, 160, 40],
+                (false, true) => [90, 60, 60],
+                (false, false) => [50, 35, 35],
+            }
+        })
+        .collect()
+}
+
+/// Whether the grid mesh should draw empty positions too, which the region based views need
+pub fn draws_empty(mode: ViewMode) -> bool {
+    mode == ViewMode::Activity
+}
+
+pub struct OverlayPlugin;
+
+/// This plugin cycles through the debug views with V
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ViewMode>()
+            .init_resource::<ViewMode>()
+            .init_resource::<SimTrace>()
+            .add_systems(Update, cycle_view_mode.run_if(in_state(InWorld)));
+    }
+}
+
+fn cycle_view_mode(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut mode: ResMut<ViewMode>,
+    mut trace: ResMut<SimTrace>,
+) {
+    // Ctrl + V pastes a world code instead
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::CycleView)
+        && !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+    {
+        *mode = mode.next();
+        if *mode == ViewMode::Normal {
+            *trace = SimTrace::default();
+        }
+        info!("View mode: {}", mode.name());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+
+    #[test]
+    fn heat_of_a_uniform_fire_is_uniform() {
+        let mut grid = Grid::new(4, 3);
+        for x in 0..4 {
+            for y in 0..3 {
+                grid.set(
+                    x,
+                    y,
+                    Some(Cell {
+                        life: Some(5),
+                        ..Cell::new(CellId::Fire)
+                    }),
+                );
+            }
+        }
+        let colors = heat_colors(&grid);
+        assert!(colors.iter().all(|&c| c == colors[0]), "{colors:?}");
+    }
+
+    #[test]
+    fn trace_ignores_writes_after_the_tick() {
+        let before = Grid::new(2, 2);
+        let mut after = before.clone();
+        after.spawn(0, 1, CellId::Sand);
+        let mut trace = SimTrace::default();
+        trace.record(before, &after, &[(0, 0), (0, 1), (1, 0), (1, 1)]);
+
+        // Painted after the tick
+        let mut grid = after.clone();
+        grid.spawn(1, 1, CellId::Sand);
+        let velocity = Overlay::new(ViewMode::Velocity, &grid, &trace).unwrap();
+        assert_eq!(velocity.color(1, 1), [40, 40, 40]);
+        assert_eq!(velocity.color(0, 1), [255, 255, 255]);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::cell::Material;
+use crate::chunks::WorldShifted;
+use crate::grid::{cells_to_world, cursor_tile, MainCamera, WorldReset, DATA_SIZE};
+use crate::input::{Action, Keybindings};
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+
+/// Width and height of the character in cells
+const PLAYER_SIZE: Vec2 = Vec2::new(3.0, 6.0);
+
+/// Accelerations and speeds below are in cells per second
+const GRAVITY: f32 = 160.0;
+const RUN_SPEED: f32 = 35.0;
+const JUMP_SPEED: f32 = 55.0;
+const SWIM_SPEED: f32 = 20.0;
+const MAX_FALL_SPEED: f32 = 120.0;
+
+const MAX_HEALTH: f32 = 100.0;
+
+/// Health lost per second while touching fire or acid
+const DAMAGE_PER_SECOND: f32 = 60.0;
+
+/// How far in front of the character digging reaches, in cells
+const DIG_REACH: f32 = 2.0;
+
+/// A platformer-style character moving through the grid in continuous cell coordinates
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Player {
+    /// Top-left corner of the character's bounding box
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub health: f32,
+    /// -1 for left, 1 for right
+    pub facing: f32,
+    pub grounded: bool,
+}
+
+/// Movement requested by the keyboard or a gamepad for one frame
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlayerInput {
+    pub axis: Vec2,
+    pub jump: bool,
+    pub dig: bool,
+}
+
+impl Player {
+    pub fn new(position: Vec2) -> Self {
+        Self {
+            position,
+            velocity: Vec2::ZERO,
+            health: MAX_HEALTH,
+            facing: 1.0,
+            grounded: false,
+        }
+    }
+
+    pub fn aabb(&self) -> Rect {
+        Rect::from_corners(self.position, self.position + PLAYER_SIZE)
+    }
+
+    pub fn is_swimming(&self, grid: &Grid) -> bool {
+        grid.overlaps_aabb(self.aabb(), |c| c.is_liquid()).is_some()
+    }
+
+    /// Advances the character by `dt` seconds, returning `false` once it has died
+    pub fn update(&mut self, grid: &mut Grid, input: PlayerInput, dt: f32) -> bool {
+        // Touching counts as overlapping a slightly larger box
+        let touching = self.aabb().inflate(0.5);
+        if grid.overlaps_aabb(touching, |c| c.harms()).is_some() {
+            self.health -= DAMAGE_PER_SECOND * dt;
+        }
+        if self.health <= 0.0 {
+            return false;
+        }
+
+        if input.axis.x != 0.0 {
+            self.facing = input.axis.x.signum();
+        }
+        if input.dig {
+            self.dig(grid, input.axis);
+        }
+
+        self.velocity.x = input.axis.x * RUN_SPEED;
+        if self.is_swimming(grid) {
+            self.velocity.x *= 0.6;
+            self.velocity.y = if input.jump || input.axis.y < 0.0 {
+                -SWIM_SPEED
+            } else if input.axis.y > 0.0 {
+                SWIM_SPEED
+            } else {
+                // Sink slowly when idle
+                SWIM_SPEED * 0.25
+            };
+        } else {
+            if input.jump && self.grounded {
+                self.velocity.y = -JUMP_SPEED;
+            }
+            self.velocity.y = (self.velocity.y + GRAVITY * dt).min(MAX_FALL_SPEED);
+        }
+
+        if self.move_by(grid, Vec2::new(self.velocity.x * dt, 0.0)) {
+            self.velocity.x = 0.0;
+        }
+        self.grounded = false;
+        if self.move_by(grid, Vec2::new(0.0, self.velocity.y * dt)) {
+            self.grounded = self.velocity.y > 0.0;
+            self.velocity.y = 0.0;
+        }
+        true
+    }
+
+    // Moves along a single axis in sub-cell steps so the character can't tunnel through thin walls,
+    // returning whether it was stopped
+    fn move_by(&mut self, grid: &Grid, delta: Vec2) -> bool {
+        let steps = (delta.abs().max_element() / 0.5).ceil().max(1.0);
+        let step = delta / steps;
+        for _ in 0..steps as usize {
+            let aabb = Rect::from_corners(self.position + step, self.position + step + PLAYER_SIZE);
+            if !grid.aabb_blocked(aabb) {
+                self.position += step;
+                continue;
+            }
+
+            // Walk up single-cell steps
+            let climb = Vec2::new(step.x, -1.0);
+            let climbed =
+                Rect::from_corners(self.position + climb, self.position + climb + PLAYER_SIZE);
+            if step.y == 0.0 && self.grounded && !grid.aabb_blocked(climbed) {
+                self.position += climb;
+                continue;
+            }
+            return true;
+        }
+        false
+    }
+
+    // Removes the powder cells in front of the character, or below it while holding down
+    fn dig(&self, grid: &mut Grid, axis: Vec2) {
+        let aabb = self.aabb();
+        let reach = if axis.y > 0.0 {
+            Rect::new(aabb.min.x, aabb.max.y, aabb.max.x, aabb.max.y + DIG_REACH)
+        } else if self.facing > 0.0 {
+            Rect::new(aabb.max.x, aabb.min.y, aabb.max.x + DIG_REACH, aabb.max.y)
+        } else {
+            Rect::new(aabb.min.x - DIG_REACH, aabb.min.y, aabb.min.x, aabb.max.y)
+        };
+        while let Some(hit) = grid.overlaps_aabb(reach, |c| c.material() == Material::Powder) {
+            grid.set(hit.x, hit.y, None);
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PlayerHud;
+
+pub struct PlayerPlugin;
+
+/// This plugin lets P spawn or remove a controllable character, moved with the arrow keys or a gamepad
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Player>()
+            .add_systems(
+                Update,
+                (toggle_player, move_player, draw_player)
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(
+                Update,
+                remove_player.run_if(on_event::<WorldReset>.or(on_event::<WorldShifted>)),
+            );
+    }
+}
+
+fn toggle_player(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    pointer: Res<Pointer>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    grid: Res<Grid>,
+    q_player: Query<Entity, Or<(With<Player>, With<PlayerHud>)>>,
+) {
+    if !settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::TogglePlayer)
+    {
+        return;
+    }
+    if !q_player.is_empty() {
+        for entity in &q_player {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    // Drop in under the cursor, or at the top center of the grid
+    let Ok((camera, camera_transform)) = q_camera.single() else {
+        return;
+    };
+    let (x, y) =
+        cursor_tile(&pointer, camera, camera_transform, &grid).unwrap_or((grid.width() / 2, 0));
+    let position = Vec2::new(x as f32, y as f32) - PLAYER_SIZE / 2.0;
+    let position = position.clamp(
+        Vec2::ZERO,
+        Vec2::new(grid.width() as f32, grid.height() as f32) - PLAYER_SIZE,
+    );
+
+    commands.spawn((
+        Player::new(position),
+        Sprite::from_color(Color::srgb_u8(60, 200, 255), PLAYER_SIZE * DATA_SIZE),
+        Transform::default(),
+    ));
+    commands.spawn((
+        PlayerHud,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            right: Val::Px(12.0),
+            ..default()
+        },
+    ));
+}
+
+fn move_player(
+    mut commands: Commands,
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    q_gamepads: Query<&Gamepad>,
+    mut grid: ResMut<Grid>,
+    mut q_player: Query<(Entity, &mut Player)>,
+    q_hud: Query<Entity, With<PlayerHud>>,
+) {
+    let keys = &settings.keybindings;
+    let mut input = PlayerInput {
+        axis: Vec2::new(
+            axis(keys, &keyboard_input, Action::MoveLeft, Action::MoveRight),
+            axis(keys, &keyboard_input, Action::Jump, Action::MoveDown),
+        ),
+        jump: keys.just_pressed(&keyboard_input, Action::Jump),
+        dig: keys.pressed(&keyboard_input, Action::Dig),
+    };
+    for gamepad in &q_gamepads {
+        let stick = gamepad.left_stick();
+        if stick.length() > 0.2 {
+            // Stick up is positive, grid rows grow downwards
+            input.axis = Vec2::new(stick.x, -stick.y);
+        }
+        input.jump |= gamepad.just_pressed(GamepadButton::South);
+        input.dig |= gamepad.pressed(GamepadButton::West);
+    }
+
+    for (entity, mut player) in &mut q_player {
+        if !player.update(&mut grid, input, time.delta_secs()) {
+            commands.entity(entity).despawn();
+            for hud in &q_hud {
+                commands.entity(hud).despawn();
+            }
+        }
+    }
+}
+
+fn axis(
+    keys: &Keybindings,
+    keyboard_input: &ButtonInput<KeyCode>,
+    negative: Action,
+    positive: Action,
+) -> f32 {
+    keys.pressed(keyboard_input, positive) as i32 as f32
+        - keys.pressed(keyboard_input, negative) as i32 as f32
+}
+
+fn draw_player(
+    grid: Res<Grid>,
+    mut q_player: Query<(&Player, &mut Transform)>,
+    mut q_hud: Query<&mut Text, With<PlayerHud>>,
+) {
+    for (player, mut transform) in &mut q_player {
+        transform.translation = cells_to_world(&grid, player.aabb().center()).extend(2.0);
+        for mut hud in &mut q_hud {
+            hud.0 = format!("Health: {:.0}", player.health.max(0.0));
+        }
+    }
+}
+
+fn remove_player(
+    mut commands: Commands,
+    q_player: Query<Entity, Or<(With<Player>, With<PlayerHud>)>>,
+) {
+    for entity in &q_player {
+        commands.entity(entity).despawn();
+    }
+}
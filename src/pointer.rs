@@ -0,0 +1,170 @@
+use crate::player::Player;
+use crate::InWorld;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::{CursorMoved, PrimaryWindow};
+
+/// Speed of the gamepad cursor with the stick pushed all the way, in logical pixels per second
+const GAMEPAD_CURSOR_SPEED: f32 = 600.0;
+/// Stick deflection below which the stick counts as centered
+const STICK_DEADZONE: f32 = 0.2;
+/// Diameter of the gamepad cursor's marker, in logical pixels
+const MARKER_SIZE: f32 = 12.0;
+
+/// Where the player points and whether they paint, from the mouse, a finger on a touchscreen or the
+/// gamepad's virtual cursor, whichever was used last
+#[derive(Resource, Reflect, Debug, Default)]
+#[reflect(Resource)]
+pub struct Pointer {
+    /// Position in the window, in logical pixels
+    pub position: Option<Vec2>,
+    pub painting: bool,
+    pub source: PointerSource,
+}
+
+#[derive(Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PointerSource {
+    #[default]
+    Mouse,
+    Touch,
+    Gamepad,
+}
+
+/// Marks where the gamepad cursor is
+#[derive(Component)]
+struct PointerMarker;
+
+pub struct PointerPlugin;
+
+/// This plugin gathers the mouse, touches and gamepads into the [`Pointer`] before the game reads
+/// it, and moves a virtual cursor with a gamepad's left stick, or its right stick while a player
+/// character takes the left one. The right trigger paints.
+impl Plugin for PointerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Pointer>()
+            .init_resource::<Pointer>()
+            .add_systems(
+                PreUpdate,
+                update_pointer.after(InputSystem).run_if(has_window),
+            )
+            .add_systems(OnEnter(InWorld), spawn_marker)
+            .add_systems(Update, move_marker.run_if(in_state(InWorld)));
+    }
+}
+
+/// Whether there is a window to point into. Headless runs and tests have none, nor the input
+/// resources that come with one, so the systems reading input are held back by this.
+pub fn has_window(q_window: Query<(), With<PrimaryWindow>>) -> bool {
+    !q_window.is_empty()
+}
+
+fn update_pointer(
+    time: Res<Time>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    q_gamepads: Query<&Gamepad>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_player: Query<(), With<Player>>,
+    mut pointer: ResMut<Pointer>,
+) {
+    let Ok(window) = q_window.single() else {
+        return;
+    };
+
+    let mouse_used = cursor_moved.read().count() > 0 || buttons.get_pressed().next().is_some();
+    if mouse_used {
+        pointer.source = PointerSource::Mouse;
+    }
+    if touches.iter().next().is_some() {
+        pointer.source = PointerSource::Touch;
+    }
+
+    for gamepad in &q_gamepads {
+        let stick = if q_player.is_empty() {
+            gamepad.left_stick()
+        } else {
+            gamepad.right_stick()
+        };
+        let painting = gamepad.pressed(GamepadButton::RightTrigger2);
+        if stick.length() < STICK_DEADZONE && !painting {
+            continue;
+        }
+
+        if pointer.source != PointerSource::Gamepad {
+            pointer.source = PointerSource::Gamepad;
+            pointer.position = pointer.position.or(Some(window.size() / 2.0));
+        }
+        // Stick up is positive, window coordinates grow downwards
+        let moved = Vec2::new(stick.x, -stick.y) * GAMEPAD_CURSOR_SPEED * time.delta_secs();
+        pointer.position = pointer
+            .position
+            .map(|position| (position + moved).clamp(Vec2::ZERO, window.size()));
+        pointer.painting = painting;
+        return;
+    }
+
+    match pointer.source {
+        PointerSource::Mouse => {
+            pointer.position = window.cursor_position();
+            pointer.painting = buttons.pressed(MouseButton::Left);
+        }
+        // Two fingers are a pinch rather than a stroke
+        PointerSource::Touch => {
+            let single = touches.iter().count() == 1;
+            pointer.position = touches.first_pressed_position().filter(|_| single);
+            pointer.painting = single;
+        }
+        PointerSource::Gamepad => {
+            pointer.painting = false;
+        }
+    }
+}
+
+fn spawn_marker(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(InWorld),
+        PointerMarker,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(MARKER_SIZE),
+            height: Val::Px(MARKER_SIZE),
+            border: UiRect::all(Val::Px(2.0)),
+            ..default()
+        },
+        BorderColor(Color::WHITE),
+        BorderRadius::MAX,
+        Visibility::Hidden,
+    ));
+}
+
+fn move_marker(
+    pointer: Res<Pointer>,
+    mut q_marker: Query<(&mut Node, &mut Visibility), With<PointerMarker>>,
+) {
+    for (mut node, mut visibility) in &mut q_marker {
+        match pointer.position {
+            Some(position) if pointer.source == PointerSource::Gamepad => {
+                node.left = Val::Px(position.x - MARKER_SIZE / 2.0);
+                node.top = Val::Px(position.y - MARKER_SIZE / 2.0);
+                *visibility = Visibility::Visible;
+            }
+            _ => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pointing_needs_no_window() {
+        // Without a window there are no mouse buttons or touches to read either
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, PointerPlugin));
+        app.update();
+        let pointer = app.world().resource::<Pointer>();
+        assert!(pointer.position.is_none() && !pointer.painting);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::cell::CellId;
+use crate::grid::{world_to_tiles, Brush, MainCamera, Poured};
+use crate::level::ActiveLevel;
+use crate::net::Lockstep;
+use crate::pointer::Pointer;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+
+pub fn placing_portals(brush: Res<Brush>) -> bool {
+    brush.selected == CellId::Portal
+}
+
+pub struct PortalPlugin;
+
+/// This plugin places portals one click at a time instead of painting them with the brush. Every
+/// second portal placed pairs up with the one before it, and both take the pair's color.
+impl Plugin for PortalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            place_portal.run_if(in_state(InWorld).and(placing_portals)),
+        );
+    }
+}
+
+fn place_portal(
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut grid: ResMut<Grid>,
+    mut active_level: Option<ResMut<ActiveLevel>>,
+    lockstep: Option<Res<Lockstep>>,
+    mut ev_poured: EventWriter<Poured>,
+    mut was_painting: Local<bool>,
+) {
+    let clicked = pointer.painting && !*was_painting;
+    *was_painting = pointer.painting;
+    // Shared worlds only carry painted kinds, not the pairs portals belong to
+    if !clicked || lockstep.is_some() {
+        return;
+    }
+
+    let (camera, camera_transform) = *q_camera;
+    let Some((x, y)) = pointer
+        .position
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
+        .and_then(|ray| world_to_tiles(&grid, ray.origin.truncate()))
+    else {
+        return;
+    };
+    if active_level
+        .as_ref()
+        .is_some_and(|level| !level.can_paint(x, y))
+    {
+        return;
+    }
+    if !grid.place_portal(x, y) {
+        return;
+    }
+    if let Some(budget) = active_level
+        .as_mut()
+        .and_then(|level| level.budget.as_mut())
+    {
+        *budget -= 1;
+    }
+    ev_poured.write(Poured {
+        id: CellId::Portal,
+        count: 1,
+    });
+}
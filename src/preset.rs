@@ -0,0 +1,227 @@
+use crate::cell::{Cell, CellId};
+use crate::grid::{SimRng, WorldReset};
+use crate::level::{end_level, LevelHud};
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+/// Built-in starting scenes, generated procedurally
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum Preset {
+    Empty,
+    Ocean,
+    Volcano,
+    Forest,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::Empty,
+        Preset::Ocean,
+        Preset::Volcano,
+        Preset::Forest,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Empty => "Empty",
+            Preset::Ocean => "Ocean",
+            Preset::Volcano => "Volcano",
+            Preset::Forest => "Forest",
+        }
+    }
+
+    pub fn build(&self, grid: &mut Grid, rng: &mut impl Rng) {
+        grid.clear();
+        match self {
+            Preset::Empty => (),
+            Preset::Ocean => build_ocean(grid, rng),
+            Preset::Volcano => build_volcano(grid, rng),
+            Preset::Forest => build_forest(grid, rng),
+        }
+    }
+}
+
+fn build_ocean(grid: &mut Grid, rng: &mut impl Rng) {
+    let (width, height) = (grid.width(), grid.height());
+    let sea_level = height * 5 / 9;
+
+    for x in 0..width {
+        let dunes = (x as f32 * 0.05).sin() * 4.0 + (x as f32 * 0.013).cos() * 6.0;
+        let floor = height - (24.0 + dunes) as usize - rng.gen_range(0..2);
+        fill_column(grid, x, floor, height, CellId::Sand);
+        fill_column(grid, x, height - 6, height, CellId::Stone);
+        fill_column(grid, x, sea_level, floor, CellId::Water);
+    }
+}
+
+fn build_volcano(grid: &mut Grid, rng: &mut impl Rng) {
+    let (width, height) = (grid.width(), grid.height());
+    let center = width / 2;
+    let peak = height / 3;
+
+    for x in 0..width {
+        fill_column(
+            grid,
+            x,
+            height - 8 - rng.gen_range(0..2),
+            height,
+            CellId::Sand,
+        );
+
+        let distance = x.abs_diff(center);
+        let top = peak + distance * 6 / 5 + rng.gen_range(0..2);
+        if top < height {
+            fill_column(grid, x, top, height, CellId::Stone);
+        }
+
+        // Central vent full of oil, capped with fire
+        if distance <= 3 {
+            fill_column(grid, x, peak, height - 20, CellId::Oil);
+            fill_column(grid, x, peak - 3, peak, CellId::Fire);
+        }
+    }
+}
+
+fn build_forest(grid: &mut Grid, rng: &mut impl Rng) {
+    let (width, height) = (grid.width(), grid.height());
+    let mut ground = Vec::with_capacity(width);
+
+    for x in 0..width {
+        let hills = (x as f32 * 0.03).sin() * 5.0;
+        let top = height - (40.0 + hills) as usize;
+        ground.push(top);
+        fill_column(grid, x, top, top + 3, CellId::Sand);
+        fill_column(grid, x, top + 3, height, CellId::Stone);
+    }
+
+    // Oil pockets in the bedrock
+    for _ in 0..6 {
+        let cx = rng.gen_range(0..width);
+        let cy = rng.gen_range(ground[cx] + 10..height - 4);
+        fill_ellipse(
+            grid,
+            cx,
+            cy,
+            rng.gen_range(4..9),
+            rng.gen_range(2..5),
+            CellId::Oil,
+        );
+    }
+
+    // Trees
+    let mut x = rng.gen_range(4..16);
+    while x < width - 4 {
+        let base = ground[x];
+        let trunk = rng.gen_range(14..26);
+        let top = base.saturating_sub(trunk);
+        for tx in x..x + 2 {
+            fill_column(grid, tx, top, base, CellId::Timber);
+        }
+        fill_ellipse(
+            grid,
+            x,
+            top,
+            rng.gen_range(4..7),
+            rng.gen_range(3..5),
+            CellId::Timber,
+        );
+        x += rng.gen_range(18..34);
+    }
+}
+
+fn fill_column(grid: &mut Grid, x: usize, from: usize, to: usize, id: CellId) {
+    for y in from..to.min(grid.height()) {
+        grid.set(x, y, Some(Cell::new(id)));
+    }
+}
+
+fn fill_ellipse(grid: &mut Grid, cx: usize, cy: usize, rx: usize, ry: usize, id: CellId) {
+    let (rx, ry) = (rx as isize, ry as isize);
+    for dx in -rx..=rx {
+        for dy in -ry..=ry {
+            let (x, y) = (cx as isize + dx, cy as isize + dy);
+            if dx * dx * ry * ry + dy * dy * rx * rx <= rx * rx * ry * ry && grid.in_bounds(x, y) {
+                fill_column(grid, x as usize, y as usize, y as usize + 1, id);
+            }
+        }
+    }
+}
+
+/// Replaces the grid with a freshly generated preset scene
+#[derive(Event)]
+pub struct LoadPreset(pub Preset);
+
+pub struct PresetPlugin;
+
+/// This plugin builds the preset scenes on request, from the menu or via Ctrl + number keys
+impl Plugin for PresetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LoadPreset>()
+            .add_systems(Update, quick_load_preset.run_if(in_state(InWorld)))
+            .add_systems(Update, load_preset.run_if(on_event::<LoadPreset>));
+    }
+}
+
+fn quick_load_preset(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut ev_load_preset: EventWriter<LoadPreset>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    let keys = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+    ];
+    for (key, preset) in keys.into_iter().zip(Preset::ALL) {
+        if keyboard_input.just_pressed(key) {
+            ev_load_preset.write(LoadPreset(preset));
+        }
+    }
+}
+
+fn load_preset(
+    mut commands: Commands,
+    mut ev_load_preset: EventReader<LoadPreset>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+    q_hud: Query<Entity, With<LevelHud>>,
+    mut ev_world_reset: EventWriter<WorldReset>,
+) {
+    if let Some(ev) = ev_load_preset.read().last() {
+        ev.0.build(&mut grid, &mut **rng);
+        end_level(&mut commands, &q_hud);
+        ev_world_reset.write(WorldReset::Preset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trees(grid: &Grid) -> Vec<(usize, usize)> {
+        grid.iter()
+            .filter(|&(_, _, cell)| cell.is_some_and(|c| c.id == CellId::Timber))
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn forest_trees_stay_standing() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut grid = Grid::default();
+        Preset::Forest.build(&mut grid, &mut rng);
+        let before = trees(&grid);
+        assert!(!before.is_empty());
+
+        for _ in 0..60 {
+            grid.step(&mut rng);
+        }
+        assert_eq!(trees(&grid), before);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::cell::CellId;
+use crate::grid::TickTimer;
+use crate::input::{key_name, Action};
+use crate::locale::Locale;
+use crate::registry::CellRegistry;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Simulation ticks between two frames of a recording, 25 frames per second at 100 ticks per
+/// second
+const FRAME_TICKS: u32 = 4;
+/// How long each frame is shown, in hundredths of a second
+const FRAME_DELAY: u16 = 4;
+/// Recordings stop by themselves after this many frames, half a minute, to bound their memory
+const MAX_FRAMES: usize = 750;
+/// Pixels per cell in the recording
+const SCALE: usize = 2;
+/// Color of the empty tiles
+const BACKGROUND: [u8; 3] = [0, 0, 0];
+
+/// The frames captured since recording started, as palette indices: 0 for an empty tile, and
+/// one more than the position in [`CellId::ALL`] for a cell
+#[derive(Resource, Default)]
+pub struct Recording {
+    frames: Option<Vec<Vec<u8>>>,
+    ticks: u32,
+}
+
+impl Recording {
+    pub fn active(&self) -> bool {
+        self.frames.is_some()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RecordError {
+    #[error("could not write the recording: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not encode the recording: {0}")]
+    Gif(#[from] gif::EncodingError),
+}
+
+/// The palette indices of the tiles of a grid, row by row
+fn capture(grid: &Grid) -> Vec<u8> {
+    let mut frame = vec![0; grid.width() * grid.height()];
+    for (x, y, cell) in grid.iter() {
+        if let Some(cell) = cell {
+            let index = CellId::ALL.iter().position(|&id| id == cell.id).unwrap();
+            frame[y * grid.width() + x] = index as u8 + 1;
+        }
+    }
+    frame
+}
+
+/// Writes frames captured from a `width` by `height` grid as a looping GIF, scaled up by
+/// [`SCALE`]
+fn encode(
+    writer: impl Write,
+    frames: &[Vec<u8>],
+    (width, height): (usize, usize),
+    palette: &[[u8; 3]],
+) -> Result<(), RecordError> {
+    let (scaled_width, scaled_height) = (width * SCALE, height * SCALE);
+    let mut encoder = gif::Encoder::new(
+        writer,
+        scaled_width as u16,
+        scaled_height as u16,
+        palette.as_flattened(),
+    )?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    let mut pixels = vec![0; scaled_width * scaled_height];
+    for frame in frames {
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let (x, y) = (i % scaled_width / SCALE, i / scaled_width / SCALE);
+            *pixel = frame[y * width + x];
+        }
+        let mut gif_frame = gif::Frame {
+            width: scaled_width as u16,
+            height: scaled_height as u16,
+            delay: FRAME_DELAY,
+            buffer: pixels.as_slice().into(),
+            ..default()
+        };
+        gif_frame.make_lzw_pre_encoded();
+        encoder.write_lzw_pre_encoded_frame(&gif_frame)?;
+    }
+    Ok(())
+}
+
+/// Where recordings go: the videos directory, or the working directory when there is none
+fn recording_path() -> PathBuf {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    dirs::video_dir()
+        .unwrap_or_default()
+        .join(format!("powder-keg-{stamp}.gif"))
+}
+
+#[derive(Component)]
+struct RecordingHud;
+
+pub struct RecordPlugin;
+
+/// This plugin records the simulation as an animated GIF. The record key, F9 by default since R
+/// toggles replace mode, starts and stops it, and the file is encoded and written on a background
+/// thread once it stops.
+impl Plugin for RecordPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recording>()
+            .add_systems(OnEnter(InWorld), spawn_recording_hud)
+            .add_systems(
+                Update,
+                (toggle_recording, capture_frame, update_recording_hud)
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            );
+    }
+}
+
+fn toggle_recording(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    grid: Res<Grid>,
+    registry: Res<CellRegistry>,
+    mut recording: ResMut<Recording>,
+) {
+    if !settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::Record)
+    {
+        return;
+    }
+
+    match recording.frames.take() {
+        Some(frames) => save(frames, &grid, &registry),
+        None => {
+            info!("Recording started");
+            *recording = Recording {
+                frames: Some(vec![capture(&grid)]),
+                ticks: 0,
+            };
+        }
+    }
+}
+
+fn capture_frame(
+    timer: Res<TickTimer>,
+    grid: Res<Grid>,
+    registry: Res<CellRegistry>,
+    mut recording: ResMut<Recording>,
+) {
+    if !timer.just_finished() || !recording.active() {
+        return;
+    }
+    recording.ticks += 1;
+    if !recording.ticks.is_multiple_of(FRAME_TICKS) {
+        return;
+    }
+
+    let frames = recording.frames.as_mut().unwrap();
+    frames.push(capture(&grid));
+    if frames.len() >= MAX_FRAMES {
+        let frames = recording.frames.take().unwrap();
+        save(frames, &grid, &registry);
+    }
+}
+
+fn save(frames: Vec<Vec<u8>>, grid: &Grid, registry: &CellRegistry) {
+    let size = (grid.width(), grid.height());
+    let palette: Vec<[u8; 3]> = std::iter::once(BACKGROUND)
+        .chain(CellId::ALL.map(|id| registry.color(id)))
+        .collect();
+    let path = recording_path();
+    info!("Recording stopped after {} frames", frames.len());
+
+    IoTaskPool::get()
+        .spawn(async move {
+            let result = std::fs::File::create(&path)
+                .map_err(RecordError::from)
+                .and_then(|file| encode(std::io::BufWriter::new(file), &frames, size, &palette));
+            match result {
+                Ok(()) => info!("Saved the recording to {}", path.display()),
+                Err(err) => warn!("{err}"),
+            }
+        })
+        .detach();
+}
+
+fn spawn_recording_hud(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(InWorld),
+        RecordingHud,
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.2, 0.2)),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            right: Val::Px(12.0),
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+/// Shows the indicator while recording, naming the key that stops it
+fn update_recording_hud(
+    recording: Res<Recording>,
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    mut q_hud: Query<(&mut Visibility, &mut Text), With<RecordingHud>>,
+) {
+    for (mut visibility, mut text) in &mut q_hud {
+        if recording.active() {
+            *visibility = Visibility::Visible;
+            let key = key_name(settings.keybindings.key(Action::Record));
+            let label = locale.text("REC, {key} to stop").replace("{key}", &key);
+            if text.0 != label {
+                text.0 = label;
+            }
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_indexes_the_palette() {
+        let mut grid = Grid::new(3, 2);
+        grid.spawn(2, 1, CellId::Stone);
+        assert_eq!(capture(&grid), vec![0, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn encoded_gifs_hold_every_frame() {
+        let frames = vec![vec![0, 1, 2, 0], vec![1, 1, 0, 0], vec![2, 0, 0, 2]];
+        let palette = [[0, 0, 0], [255, 0, 0], [0, 0, 255]];
+        let mut gif = Vec::new();
+        encode(&mut gif, &frames, (2, 2), &palette).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(gif.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (4, 4));
+        let mut count = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(
+                frame.buffer[..4],
+                [
+                    frames[count][0],
+                    frames[count][0],
+                    frames[count][1],
+                    frames[count][1]
+                ]
+            );
+            count += 1;
+        }
+        assert_eq!(count, frames.len());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::cell::{Cell, CellId};
+use crate::loading::CellAssets;
+use crate::sim::Grid;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use ron::extensions::Extensions;
+use serde::Deserialize;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// How cell kinds look, loaded from `.cells.ron` files so they can be tuned without rebuilding the
+/// game
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct CellDefinitions {
+    /// Definitions by cell kind name. Kinds that are left out keep their built-in look.
+    pub cells: HashMap<String, CellDefinition>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CellDefinition {
+    pub color: (u8, u8, u8),
+    /// Chance each tick of flames spreading to the cell, the built-in one when left out
+    #[serde(default)]
+    pub spread: Option<f32>,
+}
+
+/// The colors each cell kind is drawn with, and the chances of flames spreading to it the grid is
+/// tuned with
+#[derive(Resource, Reflect, Debug, Clone, PartialEq)]
+#[reflect(Resource)]
+pub struct CellRegistry {
+    colors: [[u8; 3]; CellId::ALL.len()],
+    spread: [f32; CellId::ALL.len()],
+}
+
+impl Default for CellRegistry {
+    fn default() -> Self {
+        Self {
+            colors: CellId::ALL.map(|id| id.data().color),
+            spread: CellId::ALL.map(|id| id.data().spread),
+        }
+    }
+}
+
+impl CellRegistry {
+    /// Builds the registry the definitions describe, on top of the built-in cell data
+    pub fn new(definitions: &CellDefinitions) -> Result<Self, CellLoaderError> {
+        let mut registry = Self::default();
+        for (name, definition) in &definitions.cells {
+            let id = CellId::from_name(name)
+                .ok_or_else(|| CellLoaderError::UnknownKind(name.clone()))?;
+            let (r, g, b) = definition.color;
+            registry.colors[index(id)] = [r, g, b];
+            if let Some(spread) = definition.spread {
+                if !(0.0..=1.0).contains(&spread) {
+                    return Err(CellLoaderError::Chance(name.clone(), spread));
+                }
+                registry.spread[index(id)] = spread;
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn color(&self, id: CellId) -> [u8; 3] {
+        self.colors[index(id)]
+    }
+
+    /// Chance each tick of flames spreading to a cell of the kind
+    pub fn spread(&self, id: CellId) -> f32 {
+        self.spread[index(id)]
+    }
+
+    /// The color a cell is drawn with, its tint if it has one
+    pub fn cell_color(&self, cell: Cell) -> [u8; 3] {
+        cell.tint.unwrap_or_else(|| self.color(cell.id))
+    }
+}
+
+fn index(id: CellId) -> usize {
+    CellId::ALL.iter().position(|&kind| kind == id).unwrap()
+}
+
+/// Reads cell definitions, with the fields that may be left out given by their value alone
+fn parse_definitions(bytes: &[u8]) -> Result<CellDefinitions, ron::error::SpannedError> {
+    ron::Options::default()
+        .with_default_extension(Extensions::IMPLICIT_SOME)
+        .from_bytes(bytes)
+}
+
+#[derive(Default)]
+pub struct CellLoader;
+
+#[derive(Debug, Error)]
+pub enum CellLoaderError {
+    #[error("could not read cell definitions: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse cell definitions: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("no cell kind is called {0}")]
+    UnknownKind(String),
+    #[error("{0} has a chance of {1}, which is not between 0 and 1")]
+    Chance(String, f32),
+}
+
+impl AssetLoader for CellLoader {
+    type Asset = CellDefinitions;
+    type Settings = ();
+    type Error = CellLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let definitions = parse_definitions(&bytes)?;
+        CellRegistry::new(&definitions)?;
+        Ok(definitions)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cells.ron"]
+    }
+}
+
+pub struct CellRegistryPlugin;
+
+/// This plugin keeps the [`CellRegistry`] in sync with `assets/default.cells.ron`, rebuilding it
+/// whenever the file changes while the game runs with the `dev` feature, and tunes the grid with
+/// its chances, also when they are changed in the inspector
+impl Plugin for CellRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CellRegistry>()
+            .init_asset::<CellDefinitions>()
+            .init_asset_loader::<CellLoader>()
+            .init_resource::<CellRegistry>()
+            .add_systems(
+                Update,
+                (
+                    rebuild_registry.run_if(resource_exists::<CellAssets>),
+                    tune_grid.run_if(resource_exists::<Grid>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+// Grids loaded or built anew start out with the built-in chances, so this checks every frame
+fn tune_grid(registry: Res<CellRegistry>, mut grid: ResMut<Grid>) {
+    for id in CellId::ALL {
+        let spread = registry.spread(id);
+        if grid.spread_chance(id) != spread {
+            grid.set_spread_chance(id, spread);
+        }
+    }
+}
+
+fn rebuild_registry(
+    mut events: EventReader<AssetEvent<CellDefinitions>>,
+    cell_assets: Res<CellAssets>,
+    definitions: Res<Assets<CellDefinitions>>,
+    mut registry: ResMut<CellRegistry>,
+) {
+    // The definitions finish loading before the collection holding them is inserted, so the first
+    // build happens when the collection shows up
+    let id = cell_assets.definitions.id();
+    let changed = events
+        .read()
+        .any(|event| matches!(*event, AssetEvent::Modified { id: modified } if modified == id));
+    if !changed && !cell_assets.is_added() {
+        return;
+    }
+
+    let Some(definitions) = definitions.get(id) else {
+        return;
+    };
+    match CellRegistry::new(definitions) {
+        Ok(rebuilt) => {
+            if changed {
+                info!("Reloaded cell definitions");
+            }
+            *registry = rebuilt;
+        }
+        Err(err) => error!("{err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(cells: &str) -> CellDefinitions {
+        parse_definitions(format!("(cells: {{{cells}}})").as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn definitions_override_the_built_in_colors() {
+        let registry = CellRegistry::new(&parse("\"sand\": (color: (1, 2, 3))")).unwrap();
+        assert_eq!(registry.color(CellId::Sand), [1, 2, 3]);
+        assert_eq!(registry.color(CellId::Water), CellId::Water.data().color);
+    }
+
+    #[test]
+    fn definitions_tune_the_spread_of_flames() {
+        let registry =
+            CellRegistry::new(&parse("\"Wood\": (color: (1, 2, 3), spread: 0.5)")).unwrap();
+        assert_eq!(registry.spread(CellId::Wood), 0.5);
+        assert_eq!(registry.spread(CellId::Oil), CellId::Oil.data().spread);
+        let result = CellRegistry::new(&parse("\"Oil\": (color: (1, 2, 3), spread: 2.0)"));
+        assert!(matches!(result, Err(CellLoaderError::Chance(name, _)) if name == "Oil"));
+    }
+
+    #[test]
+    fn unknown_kinds_are_rejected() {
+        let result = CellRegistry::new(&parse("\"Lava\": (color: (255, 0, 0))"));
+        assert!(matches!(result, Err(CellLoaderError::UnknownKind(name)) if name == "Lava"));
+    }
+
+    #[test]
+    fn bundled_definitions_cover_every_kind() {
+        let definitions = parse_definitions(include_bytes!("../assets/default.cells.ron")).unwrap();
+        assert!(CellRegistry::new(&definitions).is_ok());
+        assert_eq!(definitions.cells.len(), CellId::ALL.len());
+    }
+}
@@ -0,0 +1,458 @@
+use crate::cell::Cell;
+use crate::cli::Options;
+use crate::grid::{PaintSet, SimRng, TickSet, TickTimer};
+use crate::settings::Settings;
+use crate::share::{self, ShareError};
+use crate::sim::{Grid, ScanOrder};
+use crate::stats::SimStats;
+use crate::{GameState, InWorld};
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// A position of the grid set to a cell, or cleared
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Edit {
+    pub x: u16,
+    pub y: u16,
+    pub cell: Option<Cell>,
+}
+
+/// Widest a thumbnail gets, in pixels
+const THUMBNAIL_WIDTH: usize = 64;
+
+/// A small picture of a world, a pixel for each block of its cells
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub width: u16,
+    pub height: u16,
+    /// The colors of the pixels row by row, three bytes each, base64 encoded
+    pub pixels: String,
+}
+
+impl Thumbnail {
+    /// Pictures a grid in the colors its cells are built with, each pixel the color of the cell at
+    /// the middle of its block
+    pub fn of(grid: &Grid) -> Self {
+        let scale = grid.width().div_ceil(THUMBNAIL_WIDTH).max(1);
+        let (width, height) = (grid.width() / scale, grid.height() / scale);
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let cell = grid.get(x * scale + scale / 2, y * scale + scale / 2);
+                pixels
+                    .extend(cell.map_or([0; 3], |cell| cell.tint.unwrap_or(cell.id.data().color)));
+            }
+        }
+        Self {
+            width: width as u16,
+            height: height as u16,
+            pixels: STANDARD_NO_PAD.encode(pixels),
+        }
+    }
+}
+
+/// What a replay says about how it was recorded, so it can be checked before it plays
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    /// Version of the game that recorded it
+    pub version: String,
+    pub seed: u64,
+    /// [`Grid::registry_hash`] of the world it was recorded in, covering the spread chances the
+    /// game tuned it with
+    pub registry: u64,
+    /// The world when recording started
+    pub thumbnail: Thumbnail,
+}
+
+/// A recorded session: the world it started from and every change made to the grid between two
+/// ticks, by painting or anything else outside the simulation. The randomness of each tick comes
+/// from the seed and the tick number, so stepping the world while making the same changes goes
+/// through the same states.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub scan: ScanOrder,
+    /// Number the first tick had in the session, which the order of some scans depends on
+    pub first_tick: u64,
+    /// The world when recording started, as a world code
+    pub world: String,
+    /// The changes made before each tick, by tick counted from the start, for the ticks with any
+    pub edits: Vec<(u64, Vec<Edit>)>,
+    /// Ticks recorded
+    pub ticks: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("could not access the replay: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the replay: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not write the replay: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("could not read the replay's world: {0}")]
+    World(#[from] ShareError),
+    #[error(
+        "the replay was recorded by version {version} with other cells or spread chances than \
+         this game has, and would not play back the same"
+    )]
+    Registry { version: String },
+}
+
+impl Replay {
+    pub fn new(
+        grid: &Grid,
+        seed: u64,
+        scan: ScanOrder,
+        first_tick: u64,
+    ) -> Result<Self, ShareError> {
+        Ok(Self {
+            header: ReplayHeader {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                seed,
+                registry: grid.registry_hash(),
+                thumbnail: Thumbnail::of(grid),
+            },
+            scan,
+            first_tick,
+            world: share::export(grid)?,
+            edits: Vec::new(),
+            ticks: 0,
+        })
+    }
+
+    /// Reads a replay, which is to be [checked](Self::check) against the grid it plays on
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Checks the replay plays back on `grid` the same as when it was recorded, refusing a grid
+    /// whose cells or spread chances behave differently
+    pub fn check(&self, grid: &Grid) -> Result<(), ReplayError> {
+        let header = &self.header;
+        if header.registry != grid.registry_hash() {
+            return Err(ReplayError::Registry {
+                version: header.version.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        std::fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The world the replay starts from, for a grid of the given size
+    pub fn start(&self, size: (usize, usize)) -> Result<Grid, ReplayError> {
+        Ok(share::import(&self.world, size)?)
+    }
+
+    /// Records the changes made to the grid since it was `before`, to be made before the next
+    /// tick
+    pub fn record(&mut self, before: &Grid, grid: &Grid) {
+        let edits: Vec<Edit> = before
+            .iter()
+            .zip(grid.iter())
+            .filter(|((_, _, was), (_, _, is))| was != is)
+            .map(|(_, (x, y, cell))| Edit {
+                x: x as u16,
+                y: y as u16,
+                cell,
+            })
+            .collect();
+        if edits.is_empty() {
+            return;
+        }
+        match self.edits.last_mut() {
+            Some((tick, pending)) if *tick == self.ticks => pending.extend(edits),
+            _ => self.edits.push((self.ticks, edits)),
+        }
+    }
+
+    /// Makes the changes recorded before a tick
+    pub fn apply(&self, grid: &mut Grid, tick: u64) {
+        let Ok(index) = self.edits.binary_search_by_key(&tick, |&(tick, _)| tick) else {
+            return;
+        };
+        for edit in &self.edits[index].1 {
+            let (x, y) = (edit.x as usize, edit.y as usize);
+            if x < grid.width() && y < grid.height() {
+                grid.set(x, y, edit.cell);
+            }
+        }
+    }
+
+    /// Makes the changes recorded before a tick, then steps it
+    pub fn step(&self, grid: &mut Grid, tick: u64) {
+        self.apply(grid, tick);
+        grid.step_with(
+            self.scan,
+            self.first_tick + tick,
+            &mut SimRng::for_tick(self.header.seed, tick),
+        );
+    }
+}
+
+/// A replay being recorded, with the grid as of the last check for changes
+#[derive(Resource)]
+struct Recorder {
+    path: PathBuf,
+    replay: Option<Replay>,
+    last: Grid,
+}
+
+/// A replay being played back, and the next tick of it
+#[derive(Resource)]
+struct Playback {
+    replay: Replay,
+    tick: u64,
+}
+
+fn playing_replay(playback: Option<Res<Playback>>) -> bool {
+    playback.is_some()
+}
+
+pub struct ReplayPlugin;
+
+/// This plugin records the session into a replay file with `--record-replay <file>`, written when
+/// the game closes, and plays one back with `--replay <file>`. Painting is held back while a
+/// replay plays.
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let options = app.world().resource::<Options>().clone();
+        if let Some(path) = options.record_replay {
+            app.insert_resource(Recorder {
+                path,
+                replay: None,
+                last: Grid::new(0, 0),
+            })
+            .add_systems(
+                Update,
+                (
+                    record_changes.before(TickSet),
+                    count_recorded_tick.after(TickSet),
+                )
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(Last, save_replay.run_if(on_event::<AppExit>));
+        }
+        if let Some(path) = options.replay {
+            match Replay::load(&path) {
+                Ok(replay) => {
+                    app.insert_resource(Playback { replay, tick: 0 })
+                        .configure_sets(Update, PaintSet.run_if(not(playing_replay)))
+                        .add_systems(OnExit(GameState::Loading), start_playback)
+                        .add_systems(
+                            Update,
+                            (
+                                prepare_replayed_tick.before(TickSet),
+                                count_replayed_tick.after(TickSet),
+                            )
+                                .run_if(in_state(InWorld).and(playing_replay)),
+                        );
+                }
+                Err(err) => error!("Could not play {}: {err}", path.display()),
+            }
+        }
+    }
+}
+
+fn record_changes(
+    grid: Res<Grid>,
+    settings: Res<Settings>,
+    stats: Res<SimStats>,
+    options: Res<Options>,
+    mut recorder: ResMut<Recorder>,
+    mut rng: ResMut<SimRng>,
+) {
+    let recorder = &mut *recorder;
+    let replay = match &mut recorder.replay {
+        Some(replay) => {
+            if grid.is_changed() {
+                replay.record(&recorder.last, &grid);
+            }
+            replay
+        }
+        None => {
+            let seed = options.seed.unwrap_or_else(|| thread_rng().gen());
+            match Replay::new(&grid, seed, settings.scan_order, stats.ticks) {
+                Ok(replay) => recorder.replay.insert(replay),
+                Err(err) => {
+                    error!("Could not record a replay: {err}");
+                    return;
+                }
+            }
+        }
+    };
+    if grid.is_changed() {
+        recorder.last = grid.clone();
+    }
+    // Whether or not this frame ends up stepping the grid
+    **rng = SimRng::for_tick(replay.header.seed, replay.ticks);
+}
+
+fn count_recorded_tick(timer: Res<TickTimer>, grid: Res<Grid>, mut recorder: ResMut<Recorder>) {
+    if !timer.just_finished() {
+        return;
+    }
+    if let Some(replay) = &mut recorder.replay {
+        replay.ticks += 1;
+    }
+    recorder.last = grid.clone();
+}
+
+fn save_replay(recorder: Res<Recorder>) {
+    let Some(replay) = &recorder.replay else {
+        return;
+    };
+    match replay.save(&recorder.path) {
+        Ok(()) => info!(
+            "Saved {} ticks of replay to {}",
+            replay.ticks,
+            recorder.path.display()
+        ),
+        Err(err) => error!("{err}"),
+    }
+}
+
+fn start_playback(
+    mut commands: Commands,
+    playback: Res<Playback>,
+    mut grid: ResMut<Grid>,
+    settings: Res<Settings>,
+    mut stats: ResMut<SimStats>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    match playback.replay.start((grid.width(), grid.height())) {
+        Ok(start) => {
+            *grid = start;
+            stats.ticks = playback.replay.first_tick;
+            info!(
+                "Playing a replay recorded by version {} with seed {}",
+                playback.replay.header.version, playback.replay.header.seed
+            );
+            if settings.scan_order != playback.replay.scan {
+                warn!("The replay was recorded with another scan order and will play back differently");
+            }
+            next_state.set(GameState::Playing);
+        }
+        Err(err) => {
+            error!("{err}");
+            commands.remove_resource::<Playback>();
+        }
+    }
+}
+
+fn prepare_replayed_tick(
+    mut commands: Commands,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+    playback: Res<Playback>,
+    mut applied: Local<Option<u64>>,
+) {
+    // Only checked once the game has tuned the grid with the spread chances of its cells
+    if applied.is_none() {
+        if let Err(err) = playback.replay.check(&grid) {
+            error!("{err}");
+            commands.remove_resource::<Playback>();
+            return;
+        }
+    }
+    if *applied != Some(playback.tick) {
+        playback.replay.apply(&mut grid, playback.tick);
+        *applied = Some(playback.tick);
+    }
+    **rng = SimRng::for_tick(playback.replay.header.seed, playback.tick);
+}
+
+fn count_replayed_tick(
+    mut commands: Commands,
+    timer: Res<TickTimer>,
+    mut playback: ResMut<Playback>,
+) {
+    if !timer.just_finished() {
+        return;
+    }
+    playback.tick += 1;
+    if playback.tick >= playback.replay.ticks {
+        info!("The replay is over");
+        commands.remove_resource::<Playback>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+    use crate::preset::Preset;
+
+    #[test]
+    fn replays_go_through_the_recorded_states() {
+        let mut grid = Grid::new(40, 30);
+        Preset::Volcano.build(&mut grid, &mut StdRng::seed_from_u64(1));
+        let mut replay = Replay::new(&grid, 9, ScanOrder::Shuffled, 0).unwrap();
+
+        // Record a session, painting now and then between the ticks
+        let mut last = grid.clone();
+        for tick in 0..60 {
+            if tick % 20 == 5 {
+                for x in 10..20 {
+                    grid.spawn(x, 2, CellId::Water);
+                }
+            }
+            replay.record(&last, &grid);
+            grid.step_with(
+                replay.scan,
+                tick,
+                &mut SimRng::for_tick(replay.header.seed, tick),
+            );
+            replay.ticks += 1;
+            last = grid.clone();
+        }
+        assert_eq!(replay.edits.len(), 3);
+
+        let text = ron::to_string(&replay).unwrap();
+        let replay: Replay = ron::from_str(&text).unwrap();
+        let mut replayed = replay.start((40, 30)).unwrap();
+        for tick in 0..replay.ticks {
+            replay.step(&mut replayed, tick);
+        }
+        assert!(replayed.iter().eq(grid.iter()));
+    }
+
+    #[test]
+    fn replays_from_other_cells_are_refused() {
+        let mut grid = Grid::new(200, 100);
+        grid.spawn(102, 50, CellId::Water);
+        let mut replay = Replay::new(&grid, 9, ScanOrder::Shuffled, 0).unwrap();
+        assert_eq!(replay.header.seed, 9);
+        assert!(replay.check(&grid).is_ok());
+
+        let thumbnail = &replay.header.thumbnail;
+        assert_eq!((thumbnail.width, thumbnail.height), (50, 25));
+        let pixels = STANDARD_NO_PAD.decode(&thumbnail.pixels).unwrap();
+        assert_eq!(pixels.len(), 50 * 25 * 3);
+        let water = (12 * 50 + 25) * 3;
+        assert_eq!(pixels[water..water + 3], CellId::Water.data().color);
+        assert_eq!(pixels[..3], [0; 3]);
+
+        let mut tuned = grid.clone();
+        tuned.set_spread_chance(CellId::Wood, 1.0);
+        assert!(matches!(
+            replay.check(&tuned),
+            Err(ReplayError::Registry { .. })
+        ));
+
+        replay.header.registry ^= 1;
+        assert!(matches!(
+            replay.check(&grid),
+            Err(ReplayError::Registry { .. })
+        ));
+    }
+}
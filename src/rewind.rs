@@ -0,0 +1,196 @@
+use crate::cell::Cell;
+use crate::chunks::WorldShifted;
+use crate::grid::{TickTimer, TICK_RATE};
+use crate::input::Action;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// Simulation ticks between two snapshots
+const SNAPSHOT_TICKS: u32 = 10;
+/// How far back the history reaches, in seconds of simulation
+const HISTORY_SECONDS: f32 = 20.0;
+const CAPACITY: usize = (HISTORY_SECONDS / (SNAPSHOT_TICKS as f32 * TICK_RATE)) as usize;
+
+/// Snapshots of the grid over the last [`HISTORY_SECONDS`], each kept as the cells that differ
+/// from the snapshot after it
+#[derive(Resource, Default)]
+pub struct History {
+    /// The grid as of the newest snapshot
+    last: Option<Grid>,
+    /// For each snapshot, the cells that turn it back into the one before, oldest first
+    undo: VecDeque<Vec<(usize, usize, Option<Cell>)>>,
+    ticks: u32,
+    rewinding: bool,
+}
+
+impl History {
+    fn snapshot(&mut self, grid: &Grid) {
+        if let Some(last) = &self.last {
+            self.undo.push_back(grid.diff(last));
+            if self.undo.len() > CAPACITY {
+                self.undo.pop_front();
+            }
+        }
+        self.last = Some(grid.clone());
+    }
+
+    /// Takes the grid back to the newest snapshot, or to the one before when it is already there.
+    /// Returns false once the history runs out.
+    fn step_back(&mut self, grid: &mut Grid) -> bool {
+        let Some(last) = &mut self.last else {
+            return false;
+        };
+        let changes = grid.diff(last);
+        if changes.is_empty() {
+            let Some(undo) = self.undo.pop_back() else {
+                return false;
+            };
+            for (x, y, cell) in undo {
+                grid.set(x, y, cell);
+                last.set(x, y, cell);
+            }
+        } else {
+            for (x, y, cell) in changes {
+                grid.set(x, y, cell);
+            }
+        }
+        true
+    }
+
+    /// The seconds of simulation that can still be undone
+    fn seconds(&self) -> f32 {
+        self.undo.len() as f32 * SNAPSHOT_TICKS as f32 * TICK_RATE
+    }
+}
+
+pub fn rewinding(history: Res<History>) -> bool {
+    history.rewinding
+}
+
+#[derive(Component)]
+struct RewindHud;
+
+pub struct RewindPlugin;
+
+/// This plugin keeps the last seconds of the simulation. Holding the rewind key pauses it and
+/// scrubs backward through them, and the simulation resumes from wherever the key is let go.
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+            .add_systems(OnEnter(InWorld), spawn_rewind_hud)
+            .add_systems(
+                Update,
+                (rewind, record_history, update_rewind_hud)
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(Update, forget_history.run_if(on_event::<WorldShifted>));
+    }
+}
+
+fn rewind(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut history: ResMut<History>,
+    mut grid: ResMut<Grid>,
+) {
+    let key = settings.keybindings.key(Action::Rewind);
+    history.rewinding = keyboard_input.pressed(key);
+    if history.rewinding {
+        history.step_back(&mut grid);
+        history.ticks = 0;
+    }
+}
+
+fn record_history(timer: Res<TickTimer>, grid: Res<Grid>, mut history: ResMut<History>) {
+    if !timer.just_finished() || history.rewinding {
+        return;
+    }
+    history.ticks += 1;
+    if history.ticks.is_multiple_of(SNAPSHOT_TICKS) || history.last.is_none() {
+        history.snapshot(&grid);
+    }
+}
+
+/// The snapshots are of tiles that aren't there anymore
+fn forget_history(mut history: ResMut<History>) {
+    *history = History::default();
+}
+
+fn spawn_rewind_hud(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(InWorld),
+        RewindHud,
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            width: Val::Percent(100.0),
+            ..default()
+        },
+        Visibility::Hidden,
+    ));
+}
+
+fn update_rewind_hud(
+    history: Res<History>,
+    mut q_hud: Query<(&mut Text, &mut Visibility), With<RewindHud>>,
+) {
+    for (mut text, mut visibility) in &mut q_hud {
+        if history.rewinding {
+            text.0 = format!("Rewinding, {:.1} s left", history.seconds());
+            *visibility = Visibility::Visible;
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+    use rand::prelude::*;
+
+    #[test]
+    fn stepping_back_restores_each_snapshot() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut grid = Grid::new(8, 8);
+        for x in 0..8 {
+            grid.spawn(x, 0, CellId::Sand);
+        }
+        let mut history = History::default();
+        let mut snapshots = Vec::new();
+        for _ in 0..4 {
+            history.snapshot(&grid);
+            snapshots.push(grid.clone());
+            grid.step(&mut rng);
+        }
+
+        for snapshot in snapshots.iter().rev() {
+            assert!(history.step_back(&mut grid));
+            assert_eq!(&grid, snapshot);
+        }
+        assert!(!history.step_back(&mut grid));
+    }
+
+    #[test]
+    fn history_forgets_the_oldest_snapshots() {
+        let mut grid = Grid::new(2, 2);
+        let mut history = History::default();
+        for _ in 0..CAPACITY + 5 {
+            grid.spawn(0, 0, CellId::Stone);
+            history.snapshot(&grid);
+            grid.set(0, 0, None);
+        }
+        assert_eq!(history.undo.len(), CAPACITY);
+    }
+}
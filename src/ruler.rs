@@ -0,0 +1,184 @@
+use crate::grid::{cursor_tile, tiles_to_world, MainCamera, DATA_SIZE};
+use crate::input::Action;
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+
+/// Color of the line measured and the tiles at its ends
+const RULER_COLOR: Color = Color::srgb(1.0, 0.4, 0.8);
+
+/// The ruler, which measures between two tiles clicked while it is on
+#[derive(Resource, Reflect, Debug, Default)]
+#[reflect(Resource)]
+pub struct Ruler {
+    pub active: bool,
+    /// The tile clicked first, measured from
+    start: Option<(usize, usize)>,
+    /// The tile clicked second. Until there is one, the ruler follows the pointer.
+    end: Option<(usize, usize)>,
+}
+
+impl Ruler {
+    /// Starts a new measurement from the tile, or ends the one started
+    fn click(&mut self, tile: (usize, usize)) {
+        match (self.start, self.end) {
+            (Some(_), None) => self.end = Some(tile),
+            _ => (self.start, self.end) = (Some(tile), None),
+        }
+    }
+
+    /// The ends of the measurement, following the pointer on `hovered` until it is ended
+    fn ends(&self, hovered: Option<(usize, usize)>) -> Option<((usize, usize), (usize, usize))> {
+        Some((self.start?, self.end.or(hovered)?))
+    }
+}
+
+pub fn ruler_active(ruler: Res<Ruler>) -> bool {
+    ruler.active
+}
+
+/// What the ruler says about the distance between two tiles
+fn measure((ax, ay): (usize, usize), (bx, by): (usize, usize)) -> String {
+    let (dx, dy) = (bx as isize - ax as isize, by as isize - ay as isize);
+    let length = ((dx * dx + dy * dy) as f32).sqrt();
+    format!("({ax}, {ay}) to ({bx}, {by}): {dx} across, {dy} down, {length:.1} tiles")
+}
+
+/// Shows the position under the pointer, and the measurement while the ruler is on
+#[derive(Component)]
+struct ReadoutHud;
+
+pub struct RulerPlugin;
+
+/// This plugin shows the grid coordinates under the pointer above the brush size, and adds the
+/// ruler. While it is on, clicking two tiles draws a line between them and shows how far apart
+/// they are.
+impl Plugin for RulerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Ruler>()
+            .init_resource::<Ruler>()
+            .add_systems(OnEnter(InWorld), spawn_readout_hud)
+            .add_systems(
+                Update,
+                (
+                    toggle_ruler,
+                    click_ruler.run_if(ruler_active),
+                    update_readout,
+                )
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            );
+    }
+}
+
+fn spawn_readout_hud(mut commands: Commands) {
+    commands.spawn((
+        StateScoped(InWorld),
+        ReadoutHud,
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(32.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+    ));
+}
+
+fn toggle_ruler(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut ruler: ResMut<Ruler>,
+) {
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::Ruler)
+    {
+        *ruler = Ruler {
+            active: !ruler.active,
+            ..default()
+        };
+    }
+}
+
+fn click_ruler(
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    grid: Res<Grid>,
+    mut ruler: ResMut<Ruler>,
+    mut was_painting: Local<bool>,
+) {
+    let clicked = pointer.painting && !*was_painting;
+    *was_painting = pointer.painting;
+    if !clicked {
+        return;
+    }
+    let (camera, camera_transform) = *q_camera;
+    if let Some(tile) = cursor_tile(&pointer, camera, camera_transform, &grid) {
+        ruler.click(tile);
+    }
+}
+
+fn update_readout(
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    grid: Res<Grid>,
+    ruler: Res<Ruler>,
+    mut q_hud: Query<&mut Text, With<ReadoutHud>>,
+    mut gizmos: Gizmos,
+) {
+    let (camera, camera_transform) = *q_camera;
+    let hovered = cursor_tile(&pointer, camera, camera_transform, &grid);
+    let mut text = hovered.map_or_else(String::new, |(x, y)| format!("({x}, {y})"));
+
+    if ruler.active {
+        match ruler.ends(hovered) {
+            Some((start, end)) => {
+                text = format!("Ruler: {}", measure(start, end));
+                let (a, b) = (
+                    tiles_to_world(&grid, start.0, start.1),
+                    tiles_to_world(&grid, end.0, end.1),
+                );
+                gizmos.line_2d(a, b, RULER_COLOR);
+                for end in [a, b] {
+                    gizmos.rect_2d(end, Vec2::splat(DATA_SIZE), RULER_COLOR);
+                }
+            }
+            None => text = "Ruler: click two tiles to measure between them".to_string(),
+        }
+    }
+    for mut hud in &mut q_hud {
+        if hud.0 != text {
+            hud.0.clone_from(&text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ruler_measures_between_two_clicks() {
+        let mut ruler = Ruler::default();
+        assert_eq!(ruler.ends(Some((1, 1))), None);
+        ruler.click((2, 10));
+        // Follows the pointer until the second click
+        assert_eq!(ruler.ends(Some((5, 6))), Some(((2, 10), (5, 6))));
+        ruler.click((14, 5));
+        assert_eq!(ruler.ends(Some((5, 6))), Some(((2, 10), (14, 5))));
+        assert_eq!(
+            measure((2, 10), (14, 5)),
+            "(2, 10) to (14, 5): 12 across, -5 down, 13.0 tiles"
+        );
+        // A third click starts over
+        ruler.click((0, 0));
+        assert_eq!(ruler.ends(None), None);
+    }
+}
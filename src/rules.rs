@@ -0,0 +1,68 @@
+use crate::grid::{SimRng, TickTimer};
+use crate::sim::rules::Rules;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+
+/// What the rules start out as: how to write them, with nothing to apply yet
+const STARTING_RULES: &str = "\
+# One rule per line, `<kind>: <conditions> => <kind> [<chance>%]`, with conditions counting the
+# eight neighbours, like `>=2 Water`, `<=1 Sand` or `=0 Fire`, and Empty for empty positions.
+# Sand: >=3 Water => Stone 2%
+";
+
+/// Cellular automaton rules applied after every simulation tick, written in the text format of
+/// the `rules` module. Editing them in the inspector while the game runs recompiles them.
+#[derive(Resource, Reflect, Debug)]
+#[reflect(Resource)]
+pub struct CustomRules {
+    pub source: String,
+}
+
+impl Default for CustomRules {
+    fn default() -> Self {
+        Self {
+            source: STARTING_RULES.to_string(),
+        }
+    }
+}
+
+/// The last rules that compiled, kept while the source has errors
+#[derive(Resource, Default)]
+struct CompiledRules(Rules);
+
+pub struct RulesPlugin;
+
+/// This plugin applies the [`CustomRules`] after every simulation tick, compiling them again
+/// whenever they change
+impl Plugin for RulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<CustomRules>()
+            .init_resource::<CustomRules>()
+            .init_resource::<CompiledRules>()
+            .add_systems(
+                Update,
+                compile_rules.run_if(resource_changed::<CustomRules>),
+            )
+            .add_systems(Update, apply_rules.run_if(in_state(InWorld)));
+    }
+}
+
+fn compile_rules(rules: Res<CustomRules>, mut compiled: ResMut<CompiledRules>) {
+    match Rules::compile(&rules.source) {
+        Ok(rules) => compiled.0 = rules,
+        Err(err) => error!("{err}"),
+    }
+}
+
+fn apply_rules(
+    tick_timer: Res<TickTimer>,
+    compiled: Res<CompiledRules>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+) {
+    if !tick_timer.just_finished() || compiled.0.is_empty() {
+        return;
+    }
+    compiled.0.apply(&mut grid, &mut **rng);
+}
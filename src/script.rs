@@ -0,0 +1,116 @@
+use crate::grid::{SimRng, TickTimer};
+use crate::sim::script::Scripts;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+
+/// Where the element behaviours are loaded from, relative to the assets folder
+const SCRIPT_PATH: &str = "scripts/elements.rhai";
+
+/// The source of a `.rhai` script
+#[derive(Asset, TypePath, Debug)]
+pub struct ScriptSource(pub String);
+
+#[derive(Default)]
+pub struct ScriptLoader;
+
+impl AssetLoader for ScriptLoader {
+    type Asset = ScriptSource;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source).await?;
+        Ok(ScriptSource(source))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rhai"]
+    }
+}
+
+/// The compiled element behaviours, if the script loaded and hasn't failed since
+#[derive(Resource, Default)]
+pub struct ElementScripts {
+    handle: Handle<ScriptSource>,
+    scripts: Option<Scripts>,
+}
+
+pub struct ScriptPlugin;
+
+/// This plugin runs the element behaviours from `assets/scripts/elements.rhai` after every
+/// simulation tick, compiling the script again whenever the file changes
+impl Plugin for ScriptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ScriptSource>()
+            .init_asset_loader::<ScriptLoader>()
+            .init_resource::<ElementScripts>()
+            .add_systems(Startup, load_scripts)
+            .add_systems(
+                Update,
+                compile_scripts.run_if(on_event::<AssetEvent<ScriptSource>>),
+            )
+            .add_systems(Update, run_scripts.run_if(in_state(InWorld)));
+    }
+}
+
+fn load_scripts(asset_server: Res<AssetServer>, mut scripts: ResMut<ElementScripts>) {
+    scripts.handle = asset_server.load(SCRIPT_PATH);
+}
+
+fn compile_scripts(
+    mut events: EventReader<AssetEvent<ScriptSource>>,
+    sources: Res<Assets<ScriptSource>>,
+    mut scripts: ResMut<ElementScripts>,
+) {
+    for event in events.read() {
+        let (AssetEvent::Added { id } | AssetEvent::Modified { id }) = *event else {
+            continue;
+        };
+        if id != scripts.handle.id() {
+            continue;
+        }
+        let Some(ScriptSource(source)) = sources.get(id) else {
+            continue;
+        };
+        match Scripts::compile(source) {
+            Ok(compiled) => {
+                let kinds: Vec<_> = compiled.kinds().map(|id| id.data().name).collect();
+                info!("Loaded element scripts for {}", kinds.join(", "));
+                scripts.scripts = Some(compiled);
+            }
+            Err(err) => {
+                error!("{err}");
+                scripts.scripts = None;
+            }
+        }
+    }
+}
+
+fn run_scripts(
+    tick_timer: Res<TickTimer>,
+    mut scripts: ResMut<ElementScripts>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+) {
+    if !tick_timer.just_finished() {
+        return;
+    }
+    let Some(compiled) = &scripts.scripts else {
+        return;
+    };
+
+    // A failing script would fail again on every tick, so it stays off until the file changes
+    if let Err(err) = compiled.apply(&mut grid, &mut **rng) {
+        error!("{err}");
+        scripts.scripts = None;
+    }
+}
@@ -0,0 +1,449 @@
+use crate::freeze::zone;
+use crate::grid::{cells_to_world, cursor_tile, MainCamera, DATA_SIZE};
+use crate::input::Action;
+use crate::level::ActiveLevel;
+use crate::net::Lockstep;
+use crate::pointer::Pointer;
+use crate::registry::CellRegistry;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::{GameState, InWorld};
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, VertexAttributeValues};
+use bevy::render::render_resource::PrimitiveTopology;
+use std::ops::Range;
+
+/// Outline of the selected rectangle, and of the cells being moved
+const SELECTION_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+/// Opacity of the cells being moved, drawn over the grid where they would be put down
+const GHOST_ALPHA: f32 = 0.6;
+
+/// What the selection tool is doing, moved on by dragging the pointer and the keys of the tool
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Selection {
+    #[default]
+    Empty,
+    /// A rectangle being dragged out, from the corner the drag started at to the one under the
+    /// pointer
+    Dragging {
+        start: (usize, usize),
+        end: (usize, usize),
+    },
+    /// A rectangle of the grid, which dragging from inside lifts off
+    Selected { xs: Range<usize>, ys: Range<usize> },
+    /// Cells lifted off the grid, moved around, turned and mirrored until they are put down
+    Lifted {
+        cells: Box<Grid>,
+        /// Where the top left corner of the cells would be put down, which may lie outside of the
+        /// grid
+        at: (isize, isize),
+        /// The cells as they were cut and where from, to put them back if the tool is turned off.
+        /// Copies have none.
+        cut: Option<((usize, usize), Box<Grid>)>,
+        /// The cells' tile under the pointer while they are dragged
+        grab: Option<(isize, isize)>,
+    },
+}
+
+impl Selection {
+    /// The pointer started dragging on a tile
+    pub fn press(&mut self, grid: &mut Grid, tile: (usize, usize)) {
+        let (x, y) = (tile.0 as isize, tile.1 as isize);
+        match self {
+            Selection::Selected { xs, ys } if xs.contains(&tile.0) && ys.contains(&tile.1) => {
+                let corner = (xs.start as isize, ys.start as isize);
+                self.lift(grid, true);
+                if let Selection::Lifted { grab, .. } = self {
+                    *grab = Some((x - corner.0, y - corner.1));
+                }
+            }
+            Selection::Lifted {
+                cells, at, grab, ..
+            } if (at.0..at.0 + cells.width() as isize).contains(&x)
+                && (at.1..at.1 + cells.height() as isize).contains(&y) =>
+            {
+                *grab = Some((x - at.0, y - at.1));
+            }
+            // Starting a drag anywhere else puts down what was lifted and selects anew
+            _ => {
+                self.put_down(grid);
+                *self = Selection::Dragging {
+                    start: tile,
+                    end: tile,
+                };
+            }
+        }
+    }
+
+    /// The pointer was dragged onto a tile
+    pub fn drag(&mut self, tile: (usize, usize)) {
+        match self {
+            Selection::Dragging { end, .. } => *end = tile,
+            Selection::Lifted {
+                at,
+                grab: Some(grab),
+                ..
+            } => *at = (tile.0 as isize - grab.0, tile.1 as isize - grab.1),
+            _ => {}
+        }
+    }
+
+    /// The pointer stopped dragging
+    pub fn release(&mut self) {
+        match self {
+            Selection::Dragging { start, end } => {
+                let (xs, ys) = zone(*start, *end);
+                *self = Selection::Selected { xs, ys };
+            }
+            Selection::Lifted { grab, .. } => *grab = None,
+            _ => {}
+        }
+    }
+
+    /// Lifts the cells of the selected rectangle, cutting them out of the grid or copying them
+    pub fn lift(&mut self, grid: &mut Grid, cut: bool) {
+        let Selection::Selected { xs, ys } = self else {
+            return;
+        };
+        let cells = Box::new(grid.region(xs.clone(), ys.clone()));
+        let corner = (xs.start, ys.start);
+        if cut {
+            for (x, y) in xs.clone().flat_map(|x| ys.clone().map(move |y| (x, y))) {
+                grid.set(x, y, None);
+            }
+        }
+        *self = Selection::Lifted {
+            at: (corner.0 as isize, corner.1 as isize),
+            cut: cut.then(|| (corner, cells.clone())),
+            cells,
+            grab: None,
+        };
+    }
+
+    /// Turns the lifted cells a quarter turn clockwise around their middle, lifting the selected
+    /// ones first
+    pub fn rotate(&mut self, grid: &mut Grid) {
+        self.lift(grid, true);
+        if let Selection::Lifted { cells, at, .. } = self {
+            let (width, height) = (cells.width() as isize, cells.height() as isize);
+            **cells = cells.rotated();
+            *at = (at.0 + (width - height) / 2, at.1 + (height - width) / 2);
+        }
+    }
+
+    /// Mirrors the lifted cells left to right, lifting the selected ones first
+    pub fn mirror(&mut self, grid: &mut Grid) {
+        self.lift(grid, true);
+        if let Selection::Lifted { cells, .. } = self {
+            **cells = cells.mirrored();
+        }
+    }
+
+    /// Puts the lifted cells down where they are, over what is there, and selects where they
+    /// landed
+    pub fn put_down(&mut self, grid: &mut Grid) {
+        let Selection::Lifted { cells, at, .. } = self else {
+            return;
+        };
+        grid.paste_over(at.0, at.1, cells);
+        let clamp = |from: isize, len: usize, max: usize| {
+            from.clamp(0, max as isize) as usize
+                ..(from + len as isize).clamp(0, max as isize) as usize
+        };
+        let xs = clamp(at.0, cells.width(), grid.width());
+        let ys = clamp(at.1, cells.height(), grid.height());
+        *self = if xs.is_empty() || ys.is_empty() {
+            Selection::Empty
+        } else {
+            Selection::Selected { xs, ys }
+        };
+    }
+
+    /// Puts cut cells back where they came from, drops copies, and forgets the selection
+    pub fn cancel(&mut self, grid: &mut Grid) {
+        if let Selection::Lifted {
+            cut: Some(((x, y), cells)),
+            ..
+        } = self
+        {
+            grid.paste_over(*x as isize, *y as isize, cells);
+        }
+        *self = Selection::Empty;
+    }
+}
+
+/// The selection tool, which cuts rectangles of the grid out to move, turn and mirror them
+/// before putting them down
+#[derive(Resource, Debug, Default)]
+pub struct SelectionTool {
+    pub active: bool,
+    pub selection: Selection,
+}
+
+pub fn selection_tool_active(tool: Res<SelectionTool>) -> bool {
+    tool.active
+}
+
+/// The lifted cells, drawn see-through over the grid
+#[derive(Component)]
+struct Ghost;
+
+pub struct SelectionPlugin;
+
+/// This plugin adds the selection tool. While it is on, dragging selects a rectangle, and dragging
+/// from inside it cuts the cells out and moves them with the pointer, shown over the grid until
+/// they are put down. The tool's keys lift a copy instead, turn or mirror what is lifted, and put
+/// it down; turning the tool off puts cut cells back. Levels and shared worlds leave it off, since
+/// moving cells around would get past their rules.
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectionTool>()
+            .add_systems(OnExit(GameState::Loading), spawn_ghost)
+            .add_systems(
+                Update,
+                (
+                    toggle_selection_tool,
+                    (use_selection_keys, drag_selection).run_if(selection_tool_active),
+                    draw_selection,
+                )
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            );
+    }
+}
+
+fn spawn_ghost(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let nothing = Grid::new(0, 0);
+    commands.spawn((
+        Ghost,
+        Mesh2d(meshes.add(ghost_mesh(&nothing, Vec2::ZERO, &nothing, |_| [0; 3]))),
+        MeshMaterial2d(materials.add(Color::WHITE.with_alpha(GHOST_ALPHA))),
+        // In front of the grid
+        Transform::from_xyz(0.0, 0.0, 1.0),
+        Visibility::Hidden,
+    ));
+}
+
+fn toggle_selection_tool(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    active_level: Option<Res<ActiveLevel>>,
+    lockstep: Option<Res<Lockstep>>,
+    mut tool: ResMut<SelectionTool>,
+    mut grid: ResMut<Grid>,
+) {
+    if !settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::SelectionTool)
+    {
+        return;
+    }
+    if tool.active {
+        tool.selection.cancel(&mut grid);
+        tool.active = false;
+    } else {
+        tool.active = active_level.is_none() && lockstep.is_none();
+    }
+}
+
+fn use_selection_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut tool: ResMut<SelectionTool>,
+    mut grid: ResMut<Grid>,
+) {
+    // Ctrl + C copies a world code instead
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let bindings = &settings.keybindings;
+    let selection = &mut tool.selection;
+    if bindings.just_pressed(&keyboard_input, Action::CopySelection) {
+        selection.lift(&mut grid, false);
+    }
+    if bindings.just_pressed(&keyboard_input, Action::RotateSelection) {
+        selection.rotate(&mut grid);
+    }
+    if bindings.just_pressed(&keyboard_input, Action::MirrorSelection) {
+        selection.mirror(&mut grid);
+    }
+    if bindings.just_pressed(&keyboard_input, Action::PlaceSelection) {
+        selection.put_down(&mut grid);
+    }
+}
+
+fn drag_selection(
+    pointer: Res<Pointer>,
+    q_camera: Single<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut tool: ResMut<SelectionTool>,
+    mut grid: ResMut<Grid>,
+    mut was_dragging: Local<bool>,
+) {
+    let (camera, camera_transform) = *q_camera;
+    let tile = cursor_tile(&pointer, camera, camera_transform, &grid);
+    let pressed = pointer.painting && !*was_dragging;
+    *was_dragging = pointer.painting;
+
+    if !pointer.painting {
+        if tool.selection != Selection::Empty {
+            tool.bypass_change_detection().selection.release();
+        }
+        return;
+    }
+    let Some(tile) = tile else {
+        return;
+    };
+    if pressed {
+        tool.selection.press(&mut grid, tile);
+    } else {
+        tool.selection.drag(tile);
+    }
+}
+
+fn draw_selection(
+    tool: Res<SelectionTool>,
+    grid: Res<Grid>,
+    registry: Res<CellRegistry>,
+    settings: Res<Settings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    q_ghost: Single<(&mut Mesh2d, &mut Visibility), With<Ghost>>,
+    mut gizmos: Gizmos,
+) {
+    let (mut ghost, mut visibility) = q_ghost.into_inner();
+    let lifted = tool.active && matches!(tool.selection, Selection::Lifted { .. });
+    visibility.set_if_neq(if lifted {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    });
+
+    let outline = |xs: Range<usize>, ys: Range<usize>| {
+        let corner = Vec2::new(xs.start as f32, ys.start as f32);
+        let size = Vec2::new(xs.len() as f32, ys.len() as f32);
+        (corner, size)
+    };
+    let (corner, size) = match &tool.selection {
+        _ if !tool.active => return,
+        Selection::Empty => return,
+        Selection::Dragging { start, end } => {
+            let (xs, ys) = zone(*start, *end);
+            outline(xs, ys)
+        }
+        Selection::Selected { xs, ys } => outline(xs.clone(), ys.clone()),
+        Selection::Lifted { cells, at, .. } => {
+            let corner = Vec2::new(at.0 as f32, at.1 as f32);
+            if tool.is_changed() {
+                ghost.0 = meshes.add(ghost_mesh(cells, corner, &grid, |cell| {
+                    settings.color_scheme.cell_color(&registry, cell)
+                }));
+            }
+            (
+                corner,
+                Vec2::new(cells.width() as f32, cells.height() as f32),
+            )
+        }
+    };
+    let center = cells_to_world(&grid, corner + size / 2.0);
+    gizmos.rect_2d(center, size * DATA_SIZE, SELECTION_COLOR);
+}
+
+/// A mesh of a square for each of the cells, with their top left corner at `corner` on the grid
+fn ghost_mesh(
+    cells: &Grid,
+    corner: Vec2,
+    grid: &Grid,
+    color: impl Fn(crate::cell::Cell) -> [u8; 3],
+) -> Mesh {
+    let mut vertices = Vec::new();
+    let mut vertex_colors = Vec::new();
+    let mut indices = Vec::new();
+    for (x, y, cell) in cells.iter() {
+        let Some(cell) = cell else {
+            continue;
+        };
+        let position = cells_to_world(grid, corner + Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+        let half = DATA_SIZE / 2.0;
+        vertices.extend([
+            [position.x - half, position.y - half, 0.0],
+            [position.x + half, position.y - half, 0.0],
+            [position.x + half, position.y + half, 0.0],
+            [position.x - half, position.y + half, 0.0],
+        ]);
+        let [r, g, b] = color(cell).map(|c| c as f32 / 255.0);
+        vertex_colors.extend([[r, g, b, 1.0]; 4]);
+        let index = vertices.len() as u32 - 4;
+        indices.extend([index, index + 1, index + 2, index, index + 2, index + 3]);
+    }
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::from(vertices),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_COLOR,
+        VertexAttributeValues::from(vertex_colors),
+    );
+    mesh.insert_indices(Indices::U32(indices));
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+
+    #[test]
+    fn selections_are_cut_moved_turned_and_put_down() {
+        let mut grid = Grid::new(8, 8);
+        grid.spawn(1, 1, CellId::Stone);
+        grid.spawn(2, 1, CellId::Sand);
+        grid.spawn(6, 6, CellId::Water);
+
+        let mut selection = Selection::default();
+        selection.press(&mut grid, (1, 1));
+        selection.drag((2, 1));
+        selection.release();
+        assert_eq!(selection, Selection::Selected { xs: 1..3, ys: 1..2 });
+
+        // Dragging from inside cuts the cells out and carries them along
+        selection.press(&mut grid, (2, 1));
+        assert_eq!(grid.get(1, 1), None);
+        selection.drag((5, 4));
+        selection.release();
+        selection.rotate(&mut grid);
+        selection.put_down(&mut grid);
+        assert_eq!(grid.get(4, 4).map(|c| c.id), Some(CellId::Stone));
+        assert_eq!(grid.get(4, 5).map(|c| c.id), Some(CellId::Sand));
+        assert_eq!(selection, Selection::Selected { xs: 4..5, ys: 4..6 });
+
+        // Turning the tool off puts cut cells back, over what moved in meanwhile
+        selection.mirror(&mut grid);
+        assert_eq!(grid.get(4, 4), None);
+        selection.cancel(&mut grid);
+        assert_eq!(grid.get(4, 4).map(|c| c.id), Some(CellId::Stone));
+        assert_eq!(selection, Selection::Empty);
+    }
+
+    #[test]
+    fn copies_leave_the_grid_as_it_was() {
+        let mut grid = Grid::new(4, 4);
+        grid.spawn(0, 0, CellId::Wood);
+        let mut selection = Selection::Selected { xs: 0..1, ys: 0..1 };
+        selection.lift(&mut grid, false);
+        assert_eq!(grid.get(0, 0).map(|c| c.id), Some(CellId::Wood));
+        selection.press(&mut grid, (0, 0));
+        selection.drag((3, 3));
+        selection.put_down(&mut grid);
+        assert_eq!(grid.get(3, 3).map(|c| c.id), Some(CellId::Wood));
+        assert_eq!(grid.get(0, 0).map(|c| c.id), Some(CellId::Wood));
+    }
+}
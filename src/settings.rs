@@ -0,0 +1,605 @@
+use crate::accessibility::ColorScheme;
+use crate::audio::{VolumeKind, Volumes};
+use crate::input::{key_name, Action, Keybindings, RESERVED_KEYS};
+use crate::locale::{Locale, Translated, ENGLISH};
+use crate::menu::ButtonColors;
+use crate::sim::climate::DEW_POINT;
+use crate::sim::ScanOrder;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use thiserror::Error;
+
+/// Everything the player chooses that outlives a session, kept in `settings.ron` in the config
+/// directory, or in local storage on the web
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub keybindings: Keybindings,
+    /// The largest radius the brush can grow to, in tiles
+    pub max_brush_radius: usize,
+    pub volumes: Volumes,
+    /// How each simulation tick visits the grid, only set in the settings file
+    pub scan_order: ScanOrder,
+    pub color_scheme: ColorScheme,
+    /// Whether materials that are easily confused are drawn with a pattern over them
+    pub hatching: bool,
+    /// Code of the language the text is shown in
+    pub language: String,
+    /// Whether the world goes through days and nights, darker and colder at night
+    pub day_night: bool,
+    /// Temperature of the air in degrees Celsius, or at noon when days pass
+    pub temperature: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            keybindings: Keybindings::default(),
+            max_brush_radius: 64,
+            volumes: Volumes::default(),
+            scan_order: ScanOrder::default(),
+            color_scheme: ColorScheme::default(),
+            hatching: false,
+            language: ENGLISH.to_string(),
+            day_night: false,
+            temperature: DEW_POINT,
+        }
+    }
+}
+
+/// Name the settings are stored under
+const FILE: &str = "settings";
+
+/// Degrees the temperature changes by with each press of its buttons
+const TEMPERATURE_STEP: f32 = 5.0;
+/// Coldest and warmest the air can be set to
+const TEMPERATURES: RangeInclusive<f32> = -30.0..=60.0;
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("could not access the settings: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the settings: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not write the settings: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+impl Settings {
+    /// Reads the saved settings, falling back to the defaults when there are none
+    pub fn load() -> Result<Self, SettingsError> {
+        match storage::read(FILE)? {
+            Some(contents) => Ok(ron::from_str(&contents)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        storage::write(FILE, &contents)?;
+        Ok(())
+    }
+}
+
+/// Files of RON kept between sessions, by name
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod storage {
+    use std::io;
+    use std::path::PathBuf;
+
+    fn path(name: &str) -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("powder-keg").join(format!("{name}.ron")))
+    }
+
+    pub fn read(name: &str) -> io::Result<Option<String>> {
+        let Some(path) = path(name) else {
+            return Ok(None);
+        };
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn write(name: &str, contents: &str) -> io::Result<()> {
+        let Some(path) = path(name) else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) mod storage {
+    use std::io;
+
+    fn local_storage() -> io::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| io::Error::other("local storage is unavailable"))
+    }
+
+    pub fn read(name: &str) -> io::Result<Option<String>> {
+        local_storage()?
+            .get_item(&format!("powder-keg-{name}"))
+            .map_err(|err| io::Error::other(format!("{err:?}")))
+    }
+
+    pub fn write(name: &str, contents: &str) -> io::Result<()> {
+        local_storage()?
+            .set_item(&format!("powder-keg-{name}"), contents)
+            .map_err(|err| io::Error::other(format!("{err:?}")))
+    }
+}
+
+/// The action waiting for the next key press to be bound to it
+#[derive(Resource, Default)]
+struct Rebinding(Option<Action>);
+
+#[derive(Component, Clone, Copy)]
+enum SettingsButton {
+    Volume(VolumeKind, f32),
+    Mute,
+    ColorScheme,
+    Hatching,
+    Language,
+    DayNight,
+    Temperature(f32),
+    Bind(Action),
+    Reset,
+    Back,
+}
+
+/// Shows what an action does, in the chosen language
+#[derive(Component)]
+struct ActionLabel(Action);
+
+/// Shows the key bound to an action
+#[derive(Component)]
+struct BindingLabel(Action);
+
+/// Shows a volume as a percentage
+#[derive(Component)]
+struct VolumeLabel(VolumeKind);
+
+#[derive(Component)]
+struct MuteLabel;
+
+#[derive(Component)]
+struct TemperatureLabel;
+
+/// Shows the color scheme, whether hatching is on, the language or whether days pass
+#[derive(Component)]
+enum LookLabel {
+    ColorScheme,
+    Hatching,
+    Language,
+    DayNight,
+}
+
+pub struct SettingsPlugin;
+
+/// This plugin loads the [`Settings`] at startup and saves them whenever they change. It also adds
+/// the settings screen, opened from the menu, where the keys can be rebound.
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = Settings::load().unwrap_or_else(|err| {
+            warn!("{err}, using the default settings");
+            Settings::default()
+        });
+        app.insert_resource(settings)
+            .init_resource::<Rebinding>()
+            .add_systems(OnEnter(GameState::Settings), setup_settings)
+            .add_systems(
+                Update,
+                (
+                    click_settings_button,
+                    press_key,
+                    update_action_labels,
+                    update_binding_labels,
+                    update_volume_labels,
+                    update_look_labels,
+                    update_temperature_label,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Settings)),
+            )
+            .add_systems(
+                Update,
+                save_settings
+                    .run_if(resource_changed::<Settings>.and(not(resource_added::<Settings>))),
+            );
+    }
+}
+
+fn setup_settings(mut commands: Commands, mut rebinding: ResMut<Rebinding>) {
+    rebinding.0 = None;
+
+    commands
+        .spawn((
+            StateScoped(GameState::Settings),
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.8)),
+        ))
+        .with_children(|children| {
+            children.spawn((
+                Translated("Settings"),
+                Text::default(),
+                TextFont {
+                    font_size: 36.0,
+                    ..default()
+                },
+                Node {
+                    margin: UiRect::bottom(Val::Px(16.0)),
+                    ..default()
+                },
+            ));
+
+            children
+                .spawn(Node {
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(8.0),
+                    margin: UiRect::bottom(Val::Px(16.0)),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for kind in VolumeKind::ALL {
+                        row.spawn((
+                            Translated(kind.label()),
+                            Text::default(),
+                            TextFont {
+                                font_size: 18.0,
+                                ..default()
+                            },
+                        ));
+                        spawn_button(row, SettingsButton::Volume(kind, -1.0), 28.0)
+                            .with_child((Text::new("-"), button_font()));
+                        row.spawn((
+                            VolumeLabel(kind),
+                            Text::default(),
+                            TextFont {
+                                font_size: 18.0,
+                                ..default()
+                            },
+                            TextLayout::new_with_justify(JustifyText::Center),
+                            Node {
+                                width: Val::Px(52.0),
+                                ..default()
+                            },
+                        ));
+                        spawn_button(row, SettingsButton::Volume(kind, 1.0), 28.0)
+                            .with_child((Text::new("+"), button_font()));
+                    }
+                    spawn_button(row, SettingsButton::Mute, 100.0).with_child((
+                        MuteLabel,
+                        Text::default(),
+                        button_font(),
+                    ));
+                });
+
+            children
+                .spawn(Node {
+                    column_gap: Val::Px(8.0),
+                    margin: UiRect::bottom(Val::Px(16.0)),
+                    ..default()
+                })
+                .with_children(|row| {
+                    spawn_button(row, SettingsButton::ColorScheme, 176.0).with_child((
+                        LookLabel::ColorScheme,
+                        Text::default(),
+                        button_font(),
+                    ));
+                    spawn_button(row, SettingsButton::Hatching, 176.0).with_child((
+                        LookLabel::Hatching,
+                        Text::default(),
+                        button_font(),
+                    ));
+                    spawn_button(row, SettingsButton::Language, 176.0).with_child((
+                        LookLabel::Language,
+                        Text::default(),
+                        button_font(),
+                    ));
+                    spawn_button(row, SettingsButton::DayNight, 176.0).with_child((
+                        LookLabel::DayNight,
+                        Text::default(),
+                        button_font(),
+                    ));
+                });
+
+            children
+                .spawn(Node {
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(8.0),
+                    margin: UiRect::bottom(Val::Px(16.0)),
+                    ..default()
+                })
+                .with_children(|row| {
+                    row.spawn((
+                        Translated("Air temperature"),
+                        Text::default(),
+                        TextFont {
+                            font_size: 18.0,
+                            ..default()
+                        },
+                    ));
+                    spawn_button(row, SettingsButton::Temperature(-TEMPERATURE_STEP), 28.0)
+                        .with_child((Text::new("-"), button_font()));
+                    row.spawn((
+                        TemperatureLabel,
+                        Text::default(),
+                        TextFont {
+                            font_size: 18.0,
+                            ..default()
+                        },
+                        TextLayout::new_with_justify(JustifyText::Center),
+                        Node {
+                            width: Val::Px(64.0),
+                            ..default()
+                        },
+                    ));
+                    spawn_button(row, SettingsButton::Temperature(TEMPERATURE_STEP), 28.0)
+                        .with_child((Text::new("+"), button_font()));
+                });
+
+            children
+                .spawn(Node {
+                    display: Display::Grid,
+                    grid_template_columns: RepeatedGridTrack::auto(2),
+                    column_gap: Val::Px(32.0),
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                })
+                .with_children(|grid| {
+                    for action in Action::ALL {
+                        grid.spawn(Node {
+                            width: Val::Px(360.0),
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        })
+                        .with_children(|row| {
+                            row.spawn((
+                                ActionLabel(action),
+                                Text::default(),
+                                TextFont {
+                                    font_size: 18.0,
+                                    ..default()
+                                },
+                            ));
+                            spawn_button(row, SettingsButton::Bind(action), 140.0).with_child((
+                                BindingLabel(action),
+                                Text::default(),
+                                button_font(),
+                            ));
+                        });
+                    }
+                });
+
+            children
+                .spawn(Node {
+                    margin: UiRect::top(Val::Px(16.0)),
+                    column_gap: Val::Px(8.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    spawn_button(row, SettingsButton::Reset, 176.0).with_child((
+                        Translated("Reset keys"),
+                        Text::default(),
+                        button_font(),
+                    ));
+                    spawn_button(row, SettingsButton::Back, 176.0).with_child((
+                        Translated("Back"),
+                        Text::default(),
+                        button_font(),
+                    ));
+                });
+        });
+}
+
+fn spawn_button<'a>(
+    parent: &'a mut ChildSpawnerCommands,
+    action: SettingsButton,
+    width: f32,
+) -> EntityCommands<'a> {
+    let button_colors = ButtonColors::default();
+    parent.spawn((
+        Button,
+        Node {
+            width: Val::Px(width),
+            height: Val::Px(28.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(button_colors.normal),
+        button_colors,
+        action,
+    ))
+}
+
+fn button_font() -> (TextFont, TextColor) {
+    (
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        TextColor(Color::linear_rgb(0.9, 0.9, 0.9)),
+    )
+}
+
+fn click_settings_button(
+    q_buttons: Query<(&Interaction, &SettingsButton), Changed<Interaction>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+    locale: Res<Locale>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button) in &q_buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            SettingsButton::Volume(kind, steps) => settings.volumes.change(kind, steps),
+            SettingsButton::Mute => settings.volumes.muted = !settings.volumes.muted,
+            SettingsButton::ColorScheme => settings.color_scheme = settings.color_scheme.next(),
+            SettingsButton::Hatching => settings.hatching = !settings.hatching,
+            SettingsButton::Language => {
+                settings.language = locale.next_language(&settings.language)
+            }
+            SettingsButton::DayNight => settings.day_night = !settings.day_night,
+            SettingsButton::Temperature(degrees) => {
+                settings.temperature = (settings.temperature + degrees)
+                    .clamp(*TEMPERATURES.start(), *TEMPERATURES.end())
+            }
+            SettingsButton::Bind(action) => rebinding.0 = Some(action),
+            SettingsButton::Reset => settings.keybindings = Keybindings::default(),
+            SettingsButton::Back => next_state.set(GameState::Menu),
+        }
+    }
+}
+
+fn press_key(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(&key) = keyboard_input.get_just_pressed().next() else {
+        return;
+    };
+    // Escape cancels a rebinding, or leaves the screen when nothing is being rebound. The other
+    // keys the game reads directly can't be bound either.
+    match rebinding.0.take() {
+        Some(action) if !RESERVED_KEYS.contains(&key) => settings.keybindings.rebind(action, key),
+        Some(_) => {}
+        None if key == KeyCode::Escape => next_state.set(GameState::Menu),
+        None => {}
+    }
+}
+
+fn update_action_labels(locale: Res<Locale>, mut q_labels: Query<(Ref<ActionLabel>, &mut Text)>) {
+    for (label, mut text) in &mut q_labels {
+        if locale.is_changed() || label.is_added() {
+            text.0 = label.0.describe(&locale);
+        }
+    }
+}
+
+fn update_binding_labels(
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    rebinding: Res<Rebinding>,
+    mut q_labels: Query<(&BindingLabel, &mut Text)>,
+) {
+    for (BindingLabel(action), mut text) in &mut q_labels {
+        let label = if rebinding.0 == Some(*action) {
+            locale.text("Press a key").to_string()
+        } else {
+            key_name(settings.keybindings.key(*action))
+        };
+        if text.0 != label {
+            text.0 = label;
+        }
+    }
+}
+
+fn update_volume_labels(
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    mut q_volumes: Query<(&VolumeLabel, &mut Text), Without<MuteLabel>>,
+    mut q_mute: Query<&mut Text, With<MuteLabel>>,
+) {
+    for (VolumeLabel(kind), mut text) in &mut q_volumes {
+        let label = format!("{:.0}%", settings.volumes.get(*kind) * 100.0);
+        if text.0 != label {
+            text.0 = label;
+        }
+    }
+    let label = locale.text(if settings.volumes.muted {
+        "Unmute"
+    } else {
+        "Mute"
+    });
+    for mut text in &mut q_mute {
+        if text.0 != label {
+            text.0 = label.to_string();
+        }
+    }
+}
+
+fn update_temperature_label(
+    settings: Res<Settings>,
+    mut q_temperature: Query<&mut Text, With<TemperatureLabel>>,
+) {
+    let label = format!("{:.0}°C", settings.temperature);
+    for mut text in &mut q_temperature {
+        if text.0 != label {
+            text.0.clone_from(&label);
+        }
+    }
+}
+
+fn update_look_labels(
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    mut q_labels: Query<(&LookLabel, &mut Text)>,
+) {
+    for (label, mut text) in &mut q_labels {
+        let label = match label {
+            LookLabel::ColorScheme => locale.text(settings.color_scheme.label()),
+            LookLabel::Hatching if settings.hatching => locale.text("Hatching on"),
+            LookLabel::Hatching => locale.text("Hatching off"),
+            LookLabel::Language => locale.language_name(&settings.language),
+            LookLabel::DayNight if settings.day_night => locale.text("Day and night on"),
+            LookLabel::DayNight => locale.text("Day and night off"),
+        };
+        if text.0 != label {
+            text.0 = label.to_string();
+        }
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    if let Err(err) = settings.save() {
+        error!("{err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+
+    #[test]
+    fn settings_survive_a_round_trip() {
+        let mut settings = Settings::default();
+        settings
+            .keybindings
+            .rebind(Action::Select(CellId::Water), KeyCode::KeyW);
+        let contents =
+            ron::ser::to_string_pretty(&settings, ron::ser::PrettyConfig::default()).unwrap();
+        assert_eq!(ron::from_str::<Settings>(&contents).unwrap(), settings);
+    }
+
+    #[test]
+    fn an_empty_file_holds_the_defaults() {
+        assert_eq!(
+            ron::from_str::<Settings>("()").unwrap(),
+            Settings::default()
+        );
+    }
+}
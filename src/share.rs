@@ -0,0 +1,202 @@
+use crate::grid::WorldReset;
+use crate::level::{end_level, LevelHud};
+use crate::sim::Grid;
+use crate::InWorld;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bevy::prelude::*;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+use thiserror::Error;
+
+/// Starts every world code, so codes from a later format can be told apart
+const PREFIX: &str = "pk1:";
+/// The most a code may inflate to per tile of the grid, so a small code can't make the game
+/// allocate gigabytes. Around 4 MB for the default grid.
+const MAX_INFLATED_PER_TILE: u64 = 72;
+
+#[derive(Debug, Error)]
+pub enum ShareError {
+    #[error("not a world code")]
+    Prefix,
+    #[error("could not decode the world code: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("could not inflate the world code: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the world: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not write the world: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("the world is {0}x{1}, but the grid is {2}x{3}")]
+    Size(usize, usize, usize, usize),
+}
+
+/// Packs the cells of a grid into a short text: its run-length encoded form, deflated and base64
+/// encoded
+pub fn export(grid: &Grid) -> Result<String, ShareError> {
+    let text = ron::to_string(grid)?;
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(text.as_bytes())?;
+    Ok(format!(
+        "{PREFIX}{}",
+        URL_SAFE_NO_PAD.encode(encoder.finish()?)
+    ))
+}
+
+/// Unpacks a code made by [`export`] for a grid of the given size, surrounding whitespace allowed
+pub fn import(code: &str, (width, height): (usize, usize)) -> Result<Grid, ShareError> {
+    let data = code.trim().strip_prefix(PREFIX).ok_or(ShareError::Prefix)?;
+    let compressed = URL_SAFE_NO_PAD.decode(data)?;
+    let mut text = String::new();
+    DeflateDecoder::new(compressed.as_slice())
+        .take((width * height) as u64 * MAX_INFLATED_PER_TILE)
+        .read_to_string(&mut text)?;
+    let grid: Grid = ron::from_str(&text)?;
+    if (grid.width(), grid.height()) != (width, height) {
+        return Err(ShareError::Size(grid.width(), grid.height(), width, height));
+    }
+    Ok(grid)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod clipboard {
+    pub fn copy(text: &str) -> Result<(), String> {
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(text))
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn paste() -> Result<Option<String>, String> {
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .map(Some)
+            .map_err(|err| err.to_string())
+    }
+}
+
+// The asynchronous clipboard API can't be waited on from a system, so the browser's prompt
+// dialog stands in for it
+#[cfg(target_arch = "wasm32")]
+mod clipboard {
+    fn prompt(message: &str, default: &str) -> Result<Option<String>, String> {
+        web_sys::window()
+            .ok_or_else(|| "there is no window".to_string())?
+            .prompt_with_message_and_default(message, default)
+            .map_err(|err| format!("{err:?}"))
+    }
+
+    pub fn copy(text: &str) -> Result<(), String> {
+        prompt("Copy this world code:", text).map(|_| ())
+    }
+
+    pub fn paste() -> Result<Option<String>, String> {
+        prompt("Paste a world code:", "")
+    }
+}
+
+/// Replaces the grid with one pasted as a world code
+#[derive(Event)]
+pub struct ImportWorld(pub Grid);
+
+pub struct SharePlugin;
+
+/// This plugin copies the grid to the clipboard as a world code with Ctrl + C, and loads the code
+/// on the clipboard with Ctrl + V
+impl Plugin for SharePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ImportWorld>()
+            .add_systems(Update, copy_paste.run_if(in_state(InWorld)))
+            .add_systems(Update, import_world.run_if(on_event::<ImportWorld>));
+    }
+}
+
+fn copy_paste(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    grid: Res<Grid>,
+    mut ev_import_world: EventWriter<ImportWorld>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        match export(&grid).map_err(|err| err.to_string()) {
+            Ok(code) => match clipboard::copy(&code) {
+                Ok(()) => info!("Copied a world code of {} characters", code.len()),
+                Err(err) => warn!("Could not copy the world code: {err}"),
+            },
+            Err(err) => warn!("{err}"),
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        match clipboard::paste() {
+            Ok(Some(code)) => match import(&code, (grid.width(), grid.height())) {
+                Ok(grid) => {
+                    ev_import_world.write(ImportWorld(grid));
+                }
+                Err(err) => warn!("{err}"),
+            },
+            Ok(None) => (),
+            Err(err) => warn!("Could not paste a world code: {err}"),
+        }
+    }
+}
+
+fn import_world(
+    mut commands: Commands,
+    mut ev_import_world: EventReader<ImportWorld>,
+    mut grid: ResMut<Grid>,
+    q_hud: Query<Entity, With<LevelHud>>,
+    mut ev_world_reset: EventWriter<WorldReset>,
+) {
+    if let Some(ev) = ev_import_world.read().last() {
+        *grid = ev.0.clone();
+        end_level(&mut commands, &q_hud);
+        ev_world_reset.write(WorldReset::Imported);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellId;
+    use crate::preset::Preset;
+    use rand::prelude::*;
+
+    #[test]
+    fn codes_round_trip() {
+        let mut grid = Grid::default();
+        Preset::Volcano.build(&mut grid, &mut StdRng::seed_from_u64(1));
+
+        let code = export(&grid).unwrap();
+        let size = (grid.width(), grid.height());
+        assert_eq!(import(&format!(" {code}\n"), size).unwrap(), grid);
+    }
+
+    #[test]
+    fn small_creations_make_short_codes() {
+        let mut grid = Grid::default();
+        for x in 100..140 {
+            grid.spawn(x, 170, CellId::Stone);
+            grid.spawn(x, 169, CellId::Sand);
+        }
+        assert!(export(&grid).unwrap().len() < 200);
+    }
+
+    #[test]
+    fn import_rejects_foreign_codes() {
+        assert!(matches!(import("hello", (4, 4)), Err(ShareError::Prefix)));
+        assert!(matches!(
+            import("pk1:!!", (4, 4)),
+            Err(ShareError::Base64(_))
+        ));
+
+        let code = export(&Grid::new(4, 4)).unwrap();
+        assert!(matches!(
+            import(&code, (8, 4)),
+            Err(ShareError::Size(4, 4, 8, 4))
+        ));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::cell::{Cell, CellId};
+use crate::chunks::WorldShifted;
+use crate::grid::{cursor_tile, MainCamera, TickTimer, WorldReset};
+use crate::input::Action;
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+
+/// Acceleration in cells per tick squared
+const GRAVITY: f32 = 0.15;
+
+/// Fraction of its speed a point keeps from one tick to the next
+const DAMPING: f32 = 0.98;
+
+/// Passes over the links per tick, more keep them closer to their length
+const ITERATIONS: usize = 8;
+
+/// Points in a rope
+const ROPE_LENGTH: usize = 24;
+
+/// Points across and down a piece of cloth
+const CLOTH_SIZE: (usize, usize) = (14, 10);
+
+#[derive(Reflect, Debug, Clone, Copy)]
+struct Point {
+    position: Vec2,
+    /// Where the point was the tick before, which its speed is taken from
+    previous: Vec2,
+    pinned: bool,
+    /// Position of the cell drawn for the point, unless another point took it
+    tile: Option<(usize, usize)>,
+}
+
+/// A rope or a piece of cloth: points tied to their neighbours by links one cell long, swinging
+/// under gravity and drawn into the grid as cells. Positions are in cell coordinates. Cells the
+/// simulation burns or dissolves take their point out, so fire cuts through it.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct SoftBody {
+    pub id: CellId,
+    points: Vec<Point>,
+    links: Vec<(usize, usize)>,
+}
+
+impl SoftBody {
+    /// Lays out the points at the given offsets from a position and draws them into the grid
+    fn build(
+        grid: &mut Grid,
+        x: usize,
+        y: usize,
+        id: CellId,
+        offsets: impl IntoIterator<Item = (Vec2, bool)>,
+        links: Vec<(usize, usize)>,
+    ) -> Self {
+        let origin = Vec2::new(x as f32, y as f32);
+        let mut body = Self {
+            id,
+            points: offsets
+                .into_iter()
+                .map(|(offset, pinned)| Point {
+                    position: origin + offset,
+                    previous: origin + offset,
+                    pinned,
+                    tile: None,
+                })
+                .collect(),
+            links,
+        };
+        body.draw(grid);
+        body
+    }
+
+    /// A rope tied at the position, starting out stretched to the right so it swings down
+    pub fn rope(grid: &mut Grid, x: usize, y: usize) -> Self {
+        Self::build(
+            grid,
+            x,
+            y,
+            CellId::Rope,
+            (0..ROPE_LENGTH).map(|i| (Vec2::new(i as f32, 0.0), i == 0)),
+            (1..ROPE_LENGTH).map(|i| (i - 1, i)).collect(),
+        )
+    }
+
+    /// A sheet of cloth hung by its top corners, with the left one at the position
+    pub fn cloth(grid: &mut Grid, x: usize, y: usize) -> Self {
+        let (width, height) = CLOTH_SIZE;
+        let index = |i: usize, j: usize| j * width + i;
+        let mut links = Vec::new();
+        for j in 0..height {
+            for i in 0..width {
+                if i + 1 < width {
+                    links.push((index(i, j), index(i + 1, j)));
+                }
+                if j + 1 < height {
+                    links.push((index(i, j), index(i, j + 1)));
+                }
+            }
+        }
+        Self::build(
+            grid,
+            x,
+            y,
+            CellId::Cloth,
+            (0..height).flat_map(|j| {
+                (0..width).map(move |i| {
+                    let pinned = j == 0 && (i == 0 || i == width - 1);
+                    (Vec2::new(i as f32, j as f32), pinned)
+                })
+            }),
+            links,
+        )
+    }
+
+    /// Cells of the body in the grid
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.points.iter().filter_map(|point| point.tile)
+    }
+
+    /// Advances the body by one simulation tick, returning `false` once all of its points are
+    /// gone
+    pub fn step(&mut self, grid: &mut Grid) -> bool {
+        // Lift the body out of the grid, dropping the points whose cells were destroyed
+        let mut gone = vec![false; self.points.len()];
+        for (point, gone) in self.points.iter_mut().zip(&mut gone) {
+            let Some((x, y)) = point.tile else {
+                continue;
+            };
+            if grid.get(x, y).is_some_and(|c| c.id == self.id) {
+                grid.set(x, y, None);
+            } else {
+                *gone = true;
+            }
+        }
+        // Links index points, so they follow the points left to their new places
+        let mut kept = 0;
+        let moved_to: Vec<_> = gone
+            .iter()
+            .map(|&gone| {
+                let to = kept;
+                kept += usize::from(!gone);
+                to
+            })
+            .collect();
+        self.links = self
+            .links
+            .iter()
+            .filter(|&&(a, b)| !gone[a] && !gone[b])
+            .map(|&(a, b)| (moved_to[a], moved_to[b]))
+            .collect();
+        let mut gone = gone.into_iter();
+        self.points.retain(|_| !gone.next().unwrap_or(false));
+        if self.points.is_empty() {
+            return false;
+        }
+
+        for point in self.points.iter_mut().filter(|point| !point.pinned) {
+            let velocity = (point.position - point.previous) * DAMPING;
+            point.previous = point.position;
+            point.position += velocity + Vec2::Y * GRAVITY;
+        }
+
+        for _ in 0..ITERATIONS {
+            for &(a, b) in &self.links {
+                let (pa, pb) = (self.points[a], self.points[b]);
+                let delta = pb.position - pa.position;
+                let length = delta.length();
+                if length < f32::EPSILON {
+                    continue;
+                }
+                let correction = delta * (length - 1.0) / length;
+                match (pa.pinned, pb.pinned) {
+                    (false, false) => {
+                        self.points[a].position += correction / 2.0;
+                        self.points[b].position -= correction / 2.0;
+                    }
+                    (true, false) => self.points[b].position -= correction,
+                    (false, true) => self.points[a].position += correction,
+                    (true, true) => (),
+                }
+            }
+        }
+
+        // Points slide along whatever they run into, or stop where they were
+        let max = Vec2::new(grid.width() as f32 - 1.0, grid.height() as f32 - 1.0);
+        for point in self.points.iter_mut().filter(|point| !point.pinned) {
+            let position = point.position.clamp(Vec2::ZERO, max);
+            point.position = [
+                position,
+                Vec2::new(position.x, point.previous.y),
+                Vec2::new(point.previous.x, position.y),
+            ]
+            .into_iter()
+            .find(|&position| grid.get(tile(position).0, tile(position).1).is_none())
+            .unwrap_or(point.previous);
+        }
+
+        self.draw(grid);
+        true
+    }
+
+    fn draw(&mut self, grid: &mut Grid) {
+        for point in &mut self.points {
+            let (x, y) = tile(point.position);
+            point.tile = (x < grid.width() && y < grid.height() && grid.get(x, y).is_none())
+                .then_some((x, y));
+            if point.tile.is_some() {
+                grid.set(x, y, Some(Cell::new(self.id)));
+            }
+        }
+    }
+}
+
+fn tile(position: Vec2) -> (usize, usize) {
+    let position = position.round().max(Vec2::ZERO);
+    (position.x as usize, position.y as usize)
+}
+
+pub struct SoftBodyPlugin;
+
+/// This plugin hangs ropes and cloth from the cursor and swings them every simulation tick
+impl Plugin for SoftBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SoftBody>()
+            .add_systems(
+                Update,
+                (spawn_soft_body, move_soft_bodies)
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(
+                Update,
+                clear_soft_bodies.run_if(on_event::<WorldReset>.or(on_event::<WorldShifted>)),
+            );
+    }
+}
+
+fn spawn_soft_body(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    pointer: Res<Pointer>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut grid: ResMut<Grid>,
+) {
+    let build = if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::HangRope)
+    {
+        SoftBody::rope
+    } else if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::HangCloth)
+    {
+        SoftBody::cloth
+    } else {
+        return;
+    };
+
+    let Ok((camera, camera_transform)) = q_camera.single() else {
+        return;
+    };
+    if let Some((x, y)) = cursor_tile(&pointer, camera, camera_transform, &grid) {
+        let body = build(&mut grid, x, y);
+        if body.cells().next().is_some() {
+            commands.spawn(body);
+        }
+    }
+}
+
+fn move_soft_bodies(
+    mut commands: Commands,
+    tick_timer: Res<TickTimer>,
+    mut grid: ResMut<Grid>,
+    mut q_bodies: Query<(Entity, &mut SoftBody)>,
+) {
+    if !tick_timer.just_finished() {
+        return;
+    }
+
+    for (entity, mut body) in &mut q_bodies {
+        if !body.step(&mut grid) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn clear_soft_bodies(mut commands: Commands, q_bodies: Query<Entity, With<SoftBody>>) {
+    for entity in &q_bodies {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    // Interleaves simulation ticks with soft body moves the way the game does
+    fn run(grid: &mut Grid, body: &mut SoftBody, ticks: usize) -> bool {
+        let mut rng = StdRng::seed_from_u64(5);
+        (0..ticks).all(|_| {
+            grid.step(&mut rng);
+            body.step(grid)
+        })
+    }
+
+    #[test]
+    fn rope_swings_down_and_hangs_below_its_knot() {
+        let mut grid = Grid::new(60, 40);
+        let mut rope = SoftBody::rope(&mut grid, 20, 5);
+        assert_eq!(rope.cells().count(), ROPE_LENGTH);
+
+        assert!(run(&mut grid, &mut rope, 400));
+        let lowest = rope.cells().map(|(_, y)| y).max().unwrap();
+        assert!(lowest > 5 + ROPE_LENGTH / 2);
+        assert!(rope.cells().all(|(x, _)| x.abs_diff(20) < 4));
+        assert_eq!(grid.census().count(CellId::Rope), rope.cells().count());
+    }
+
+    #[test]
+    fn cloth_rests_on_the_ground() {
+        let mut grid = Grid::new(40, 20);
+        for x in 0..40 {
+            grid.spawn(x, 14, CellId::Bedrock);
+        }
+        let mut cloth = SoftBody::cloth(&mut grid, 10, 3);
+        assert_eq!(cloth.cells().count(), CLOTH_SIZE.0 * CLOTH_SIZE.1);
+
+        assert!(run(&mut grid, &mut cloth, 200));
+        assert!(cloth.cells().all(|(_, y)| y < 14));
+        assert!(cloth.cells().any(|(_, y)| y == 13));
+        assert_eq!(grid.census().count(CellId::Bedrock), 40);
+    }
+
+    #[test]
+    fn fire_burns_along_a_rope() {
+        let mut grid = Grid::new(60, 40);
+        let mut rope = SoftBody::rope(&mut grid, 20, 5);
+        run(&mut grid, &mut rope, 200);
+        let (x, y) = rope.cells().max_by_key(|&(_, y)| y).unwrap();
+        grid.set(x, y + 1, Some(Cell::new(CellId::Fire)));
+
+        run(&mut grid, &mut rope, 300);
+        // Pieces cut loose may drop out of reach of the flames
+        assert!(rope.cells().count() < ROPE_LENGTH / 4);
+        assert_eq!(grid.census().count(CellId::Rope), rope.cells().count());
+    }
+}
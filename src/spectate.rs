@@ -0,0 +1,437 @@
+use crate::cell::CellId;
+use crate::cli::Options;
+use crate::grid::{PaintSet, TickSet, TickTimer};
+use crate::registry::CellRegistry;
+use crate::sim::{rle, Grid, MAX_TILES};
+use crate::{GameState, InWorld};
+use bevy::prelude::*;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::sync::Mutex;
+use std::time::Duration;
+use tungstenite::Message;
+
+/// Simulation ticks between two frames sent to the spectators, 20 frames per second
+const FRAME_TICKS: u32 = 5;
+/// Marks a tile that kept its cell since the previous frame
+const UNCHANGED: u8 = u8::MAX;
+/// Frames waiting to be sent to a spectator, a second's worth. Spectators further behind are
+/// dropped.
+const QUEUED_FRAMES: usize = 20;
+/// How long a spectator may take to shake hands or to take a frame before it is dropped
+const SPECTATOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The first byte of every message sent to spectators. Tiles are listed column by column, one
+/// byte each: 0 for an empty tile and one more than the position in [`CellId::ALL`] for a cell.
+/// Frames are run-length encoded as a little-endian `u32` count followed by the byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Kind {
+    /// The colors of the cells in [`CellId::ALL`] order, three bytes each
+    Palette = 0,
+    /// The width and height as little-endian `u16`s, then every tile
+    Keyframe = 1,
+    /// Like a keyframe, but with [`UNCHANGED`] for the tiles that are the same as before
+    Diff = 2,
+}
+
+/// The tiles of a grid as sent to spectators, column by column
+fn capture(grid: &Grid) -> Vec<u8> {
+    grid.iter()
+        .map(|(_, _, cell)| {
+            cell.map_or(0, |cell| {
+                CellId::ALL.iter().position(|&id| id == cell.id).unwrap() as u8 + 1
+            })
+        })
+        .collect()
+}
+
+fn encode_frame(kind: Kind, (width, height): (usize, usize), tiles: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![kind as u8];
+    bytes.extend((width as u16).to_le_bytes());
+    bytes.extend((height as u16).to_le_bytes());
+    for (count, tile) in rle::encode(tiles.iter().copied()) {
+        bytes.extend(count.to_le_bytes());
+        bytes.push(tile);
+    }
+    bytes
+}
+
+/// The tiles of `after`, with [`UNCHANGED`] wherever they match `before`
+fn diff(before: &[u8], after: &[u8]) -> Vec<u8> {
+    before
+        .iter()
+        .zip(after)
+        .map(|(&before, &after)| if before == after { UNCHANGED } else { after })
+        .collect()
+}
+
+/// Applies a keyframe or a diff to the tiles of the previous frames. Returns the size of the grid
+/// they belong to, or `None` for messages that aren't frames or don't make sense.
+fn apply_frame(bytes: &[u8], tiles: &mut Vec<u8>) -> Option<(usize, usize)> {
+    let (&kind, rest) = bytes.split_first()?;
+    if kind != Kind::Keyframe as u8 && kind != Kind::Diff as u8 {
+        return None;
+    }
+    let width = u16::from_le_bytes(rest.get(0..2)?.try_into().ok()?) as usize;
+    let height = u16::from_le_bytes(rest.get(2..4)?.try_into().ok()?) as usize;
+    if width * height > MAX_TILES {
+        return None;
+    }
+    let runs: Vec<(u32, u8)> = rest[4..]
+        .chunks(5)
+        .map(|run| {
+            Some((
+                u32::from_le_bytes(run.get(..4)?.try_into().ok()?),
+                *run.get(4)?,
+            ))
+        })
+        .collect::<Option<_>>()?;
+    let len = runs.iter().map(|&(count, _)| count as u64).sum::<u64>();
+    if len != (width * height) as u64 {
+        return None;
+    }
+
+    if kind == Kind::Keyframe as u8 {
+        *tiles = rle::decode(&runs);
+    } else if tiles.len() == width * height {
+        for (tile, new) in tiles.iter_mut().zip(rle::decode(&runs)) {
+            if new != UNCHANGED {
+                *tile = new;
+            }
+        }
+    } else {
+        return None;
+    }
+    Some((width, height))
+}
+
+/// The port spectators connect to
+#[derive(Resource)]
+struct Spectators(TcpListener);
+
+/// Sends frames to every spectator connected to a port. The frames go through a background thread,
+/// which accepts the connections and greets newcomers with the palette and the current grid.
+#[derive(Resource)]
+struct Broadcast {
+    frames: Sender<Vec<u8>>,
+    ticks: u32,
+}
+
+/// Shakes hands with a spectator, then sends it the greeting and the frames queued for it, on a
+/// thread of its own so a slow spectator only holds up itself. Returns once the spectator is
+/// gone, or was dropped for falling behind.
+fn serve(stream: TcpStream, greeting: [Message; 2], queue: Receiver<Message>) {
+    let addr = stream.peer_addr().map_or_else(
+        |_| "an unknown address".to_string(),
+        |addr| addr.to_string(),
+    );
+    let greeted = stream
+        .set_nonblocking(false)
+        .and_then(|()| stream.set_read_timeout(Some(SPECTATOR_TIMEOUT)))
+        .and_then(|()| stream.set_write_timeout(Some(SPECTATOR_TIMEOUT)))
+        .map_err(|err| err.to_string())
+        .and_then(|()| tungstenite::accept(stream).map_err(|err| err.to_string()))
+        .and_then(|mut socket| {
+            for message in greeting {
+                socket.send(message).map_err(|err| err.to_string())?;
+            }
+            Ok(socket)
+        });
+    let mut socket = match greeted {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("Could not greet the spectator at {addr}: {err}");
+            return;
+        }
+    };
+    info!("Spectator connected from {addr}");
+    for message in queue {
+        if let Err(err) = socket.send(message) {
+            info!("Spectator at {addr} left: {err}");
+            return;
+        }
+    }
+    info!("Dropped the spectator at {addr}, which fell behind");
+}
+
+fn broadcast(
+    listener: TcpListener,
+    palette: Vec<u8>,
+    size: (usize, usize),
+    frames: Receiver<Vec<u8>>,
+) {
+    let mut spectators: Vec<SyncSender<Message>> = Vec::new();
+    let mut tiles = vec![0; size.0 * size.1];
+    loop {
+        while let Ok((stream, _)) = listener.accept() {
+            let greeting = [
+                Message::binary(palette.clone()),
+                Message::binary(encode_frame(Kind::Keyframe, size, &tiles)),
+            ];
+            let (queue, receiver) = mpsc::sync_channel(QUEUED_FRAMES);
+            std::thread::spawn(move || serve(stream, greeting, receiver));
+            spectators.push(queue);
+        }
+
+        let frame = match frames.recv_timeout(Duration::from_millis(50)) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if frame == tiles {
+            continue;
+        }
+        let message = Message::binary(encode_frame(Kind::Diff, size, &diff(&tiles, &frame)));
+        tiles = frame;
+        // Spectators that left or can't keep up are dropped, which ends their thread
+        spectators.retain(|queue| queue.try_send(message.clone()).is_ok());
+    }
+}
+
+/// Frames received from the instance being watched, read on a background thread
+#[derive(Resource)]
+struct Watch {
+    frames: Mutex<Receiver<Vec<u8>>>,
+    tiles: Vec<u8>,
+}
+
+fn watching(watch: Option<Res<Watch>>) -> bool {
+    watch.is_some()
+}
+
+fn receive(url: String, frames: Sender<Vec<u8>>) {
+    let mut socket = match tungstenite::connect(&url) {
+        Ok((socket, _)) => socket,
+        Err(err) => {
+            error!("Could not watch {url}: {err}");
+            return;
+        }
+    };
+    info!("Watching {url}");
+    loop {
+        match socket.read() {
+            Ok(Message::Binary(bytes)) => {
+                if frames.send(bytes.to_vec()).is_err() {
+                    return;
+                }
+            }
+            Ok(_) => (),
+            Err(err) => {
+                warn!("Stopped watching {url}: {err}");
+                return;
+            }
+        }
+    }
+}
+
+/// Opens the port given with `--spectate <port>`
+fn open_port(port: u16) -> io::Result<TcpListener> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    listener.set_nonblocking(true)?;
+    info!("Streaming to spectators on port {port}");
+    Ok(listener)
+}
+
+pub struct SpectatePlugin;
+
+/// This plugin streams the grid over a WebSocket with `--spectate <port>`, for the page in
+/// `build/web/spectate.html` or for another instance started with `--watch ws://<address>:<port>`.
+/// A watching instance shows the streamed grid instead of simulating its own, and can't paint.
+impl Plugin for SpectatePlugin {
+    fn build(&self, app: &mut App) {
+        let options = app.world().resource::<Options>().clone();
+        match options.spectate.map(open_port) {
+            Some(Ok(listener)) => {
+                app.insert_resource(Spectators(listener))
+                    .add_systems(OnExit(GameState::Loading), start_broadcast)
+                    .add_systems(
+                        Update,
+                        send_frame
+                            .after(TickSet)
+                            .run_if(resource_exists::<Broadcast>.and(in_state(InWorld))),
+                    );
+            }
+            Some(Err(err)) => error!("Could not stream to spectators: {err}"),
+            None => (),
+        }
+
+        if let Some(url) = options.watch {
+            let (frames, receiver) = mpsc::channel();
+            std::thread::spawn(move || receive(url, frames));
+            app.insert_resource(Watch {
+                frames: Mutex::new(receiver),
+                tiles: Vec::new(),
+            })
+            .configure_sets(Update, (TickSet, PaintSet).run_if(not(watching)))
+            .add_systems(Update, show_frames.run_if(in_state(InWorld)));
+        }
+    }
+}
+
+fn start_broadcast(
+    mut commands: Commands,
+    spectators: Res<Spectators>,
+    grid: Res<Grid>,
+    registry: Res<CellRegistry>,
+) {
+    let listener = match spectators.0.try_clone() {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Could not stream to spectators: {err}");
+            return;
+        }
+    };
+    let palette: Vec<u8> = std::iter::once(Kind::Palette as u8)
+        .chain(CellId::ALL.iter().flat_map(|&id| registry.color(id)))
+        .collect();
+    let size = (grid.width(), grid.height());
+    let (frames, receiver) = mpsc::channel();
+    std::thread::spawn(move || broadcast(listener, palette, size, receiver));
+    commands.insert_resource(Broadcast { frames, ticks: 0 });
+}
+
+fn send_frame(timer: Res<TickTimer>, grid: Res<Grid>, mut broadcast: ResMut<Broadcast>) {
+    if !timer.just_finished() {
+        return;
+    }
+    broadcast.ticks += 1;
+    if broadcast.ticks.is_multiple_of(FRAME_TICKS) {
+        // The thread only stops once this resource is gone
+        let _ = broadcast.frames.send(capture(&grid));
+    }
+}
+
+fn show_frames(mut watch: ResMut<Watch>, mut grid: ResMut<Grid>) {
+    let watch = &mut *watch;
+    let mut shown = false;
+    for frame in watch.frames.get_mut().unwrap().try_iter() {
+        match apply_frame(&frame, &mut watch.tiles) {
+            Some(size) if size == (grid.width(), grid.height()) => shown = true,
+            Some((width, height)) => warn!("The watched grid is {width}x{height}"),
+            None => (),
+        }
+    }
+    // A frame of another size may have come after the one shown
+    if !shown || watch.tiles.len() != grid.width() * grid.height() {
+        return;
+    }
+
+    let height = grid.height();
+    for (i, &tile) in watch.tiles.iter().enumerate() {
+        let (x, y) = (i / height, i % height);
+        match tile
+            .checked_sub(1)
+            .and_then(|i| CellId::ALL.get(i as usize))
+        {
+            Some(&id) => {
+                grid.replace(x, y, id);
+            }
+            None => grid.set(x, y, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_rebuild_the_grid() {
+        let mut grid = Grid::new(6, 4);
+        grid.spawn(1, 3, CellId::Sand);
+        grid.spawn(4, 0, CellId::Water);
+        let first = capture(&grid);
+        grid.set(1, 3, None);
+        grid.spawn(2, 3, CellId::Stone);
+        let second = capture(&grid);
+
+        let mut tiles = Vec::new();
+        let keyframe = encode_frame(Kind::Keyframe, (6, 4), &first);
+        assert_eq!(apply_frame(&keyframe, &mut tiles), Some((6, 4)));
+        assert_eq!(tiles, first);
+        let diff = encode_frame(Kind::Diff, (6, 4), &diff(&first, &second));
+        assert_eq!(apply_frame(&diff, &mut tiles), Some((6, 4)));
+        assert_eq!(tiles, second);
+    }
+
+    #[test]
+    fn diffs_of_quiet_grids_are_small() {
+        let tiles = vec![0; 320 * 180];
+        let mut changed = tiles.clone();
+        changed[1000] = 3;
+        assert_eq!(
+            encode_frame(Kind::Diff, (320, 180), &diff(&tiles, &changed)).len(),
+            5 + 3 * 5
+        );
+    }
+
+    #[test]
+    fn spectators_get_the_palette_then_frames() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (frames, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            broadcast(listener, vec![Kind::Palette as u8], (2, 2), receiver)
+        });
+
+        let (mut socket, _) = tungstenite::connect(format!("ws://{addr}")).unwrap();
+        let mut read = || socket.read().unwrap().into_data().to_vec();
+        assert_eq!(read(), vec![Kind::Palette as u8]);
+        let mut tiles = Vec::new();
+        assert_eq!(apply_frame(&read(), &mut tiles), Some((2, 2)));
+        assert_eq!(tiles, vec![0; 4]);
+
+        frames.send(vec![0, 1, 0, 2]).unwrap();
+        assert_eq!(apply_frame(&read(), &mut tiles), Some((2, 2)));
+        assert_eq!(tiles, vec![0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn idle_spectators_do_not_hold_up_the_others() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (frames, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            broadcast(listener, vec![Kind::Palette as u8], (2, 2), receiver)
+        });
+
+        // Connects without ever shaking hands
+        let _idle = TcpStream::connect(addr).unwrap();
+        let (mut socket, _) = tungstenite::connect(format!("ws://{addr}")).unwrap();
+        let mut read = || socket.read().unwrap().into_data().to_vec();
+        assert_eq!(read(), vec![Kind::Palette as u8]);
+        let mut tiles = Vec::new();
+        apply_frame(&read(), &mut tiles).unwrap();
+        frames.send(vec![1, 1, 1, 1]).unwrap();
+        assert_eq!(apply_frame(&read(), &mut tiles), Some((2, 2)));
+        assert_eq!(tiles, vec![1; 4]);
+    }
+
+    #[test]
+    fn broken_frames_are_ignored() {
+        let mut tiles = vec![0; 4];
+        assert_eq!(
+            apply_frame(&[Kind::Palette as u8, 1, 2, 3], &mut tiles),
+            None
+        );
+        assert_eq!(apply_frame(&[Kind::Keyframe as u8, 2, 0], &mut tiles), None);
+        let short = encode_frame(Kind::Keyframe, (2, 2), &[0, 1, 2]);
+        assert_eq!(apply_frame(&short, &mut tiles), None);
+        let diff = encode_frame(Kind::Diff, (3, 3), &[UNCHANGED; 9]);
+        assert_eq!(apply_frame(&diff, &mut tiles), None);
+        // Cut off in the middle of a run
+        let mut cut = encode_frame(Kind::Keyframe, (2, 2), &[0; 4]);
+        cut.pop();
+        assert_eq!(apply_frame(&cut, &mut tiles), None);
+        // Claims more tiles than any grid has
+        let mut huge = vec![Kind::Keyframe as u8, 255, 255, 255, 255];
+        huge.extend(u32::MAX.to_le_bytes());
+        huge.push(0);
+        assert_eq!(apply_frame(&huge, &mut tiles), None);
+        assert_eq!(tiles, vec![0; 4]);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::cell::CellId;
+use crate::grid::{Poured, TICK_RATE};
+use crate::sim::{Grid, TickStats};
+use crate::softbody::SoftBody;
+use crate::structure::Structure;
+use crate::GameState;
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const SIM_TICK_TIME: DiagnosticPath = DiagnosticPath::const_new("sim/tick_time");
+pub const SIM_MOVED: DiagnosticPath = DiagnosticPath::const_new("sim/moved");
+pub const SIM_CREATED: DiagnosticPath = DiagnosticPath::const_new("sim/created");
+pub const SIM_DESTROYED: DiagnosticPath = DiagnosticPath::const_new("sim/destroyed");
+pub const SIM_REACTIONS: DiagnosticPath = DiagnosticPath::const_new("sim/reactions");
+
+/// What the last simulation tick did, and how long it took to compute
+#[derive(Resource, Debug, Default)]
+pub struct SimStats {
+    pub tick: TickStats,
+    pub duration: Duration,
+    /// Ticks simulated so far
+    pub ticks: u64,
+}
+
+/// How many moving cells one cell appearing or disappearing counts as in the activity, so fires
+/// and explosions count the most
+const UPHEAVAL: usize = 8;
+
+impl SimStats {
+    /// How busy the last tick was: the cells that moved, and many more for the ones that appeared
+    /// or disappeared
+    pub fn activity(&self) -> usize {
+        self.tick.moved + UPHEAVAL * (self.tick.created.total() + self.tick.destroyed.total())
+    }
+}
+
+/// What happened since the game started, summed up on the menu
+#[derive(Resource, Debug, Default)]
+pub struct SessionStats {
+    /// Cells the brush placed, by kind
+    pub painted: HashMap<CellId, usize>,
+    /// Most fire on the grid at once
+    pub biggest_fire: usize,
+    /// Most simulation ticks a platform, piston, rope or cloth lasted
+    pub longest_contraption: u64,
+}
+
+impl SessionStats {
+    /// Lines of the summary, with the kinds painted most first, or none before anything happened
+    pub fn summary(&self) -> Vec<String> {
+        let mut painted: Vec<_> = self.painted.iter().filter(|(_, &n)| n > 0).collect();
+        if painted.is_empty() && self.biggest_fire == 0 && self.longest_contraption == 0 {
+            return Vec::new();
+        }
+        painted.sort_by(|a, b| {
+            b.1.cmp(a.1)
+                .then_with(|| a.0.data().name.cmp(b.0.data().name))
+        });
+
+        let mut lines = vec![format!(
+            "Cells painted: {}",
+            painted.iter().map(|(_, &n)| n).sum::<usize>()
+        )];
+        lines.extend(
+            painted
+                .iter()
+                .map(|(id, n)| format!("  {}: {n}", id.data().name)),
+        );
+        lines.push(format!("Biggest fire: {} cells", self.biggest_fire));
+        lines.push(format!(
+            "Longest-lived contraption: {:.1}s",
+            self.longest_contraption as f32 * TICK_RATE
+        ));
+        lines
+    }
+}
+
+pub struct StatsPlugin;
+
+/// This plugin keeps the [`SimStats`] the grid writes each tick, and feeds them to Bevy's
+/// diagnostics, which debug builds log. It also sums up the session in [`SessionStats`], shown
+/// when returning to the menu.
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimStats>()
+            .init_resource::<SessionStats>()
+            .register_diagnostic(Diagnostic::new(SIM_TICK_TIME).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(SIM_MOVED))
+            .register_diagnostic(Diagnostic::new(SIM_CREATED))
+            .register_diagnostic(Diagnostic::new(SIM_DESTROYED))
+            .register_diagnostic(Diagnostic::new(SIM_REACTIONS))
+            .add_systems(
+                Update,
+                (measure_stats, measure_session)
+                    .run_if(resource_changed::<SimStats>.and(not(resource_added::<SimStats>))),
+            )
+            .add_systems(Update, count_painted.run_if(on_event::<Poured>))
+            .add_systems(OnEnter(GameState::Menu), show_session_summary);
+    }
+}
+
+fn measure_stats(mut diagnostics: Diagnostics, stats: Res<SimStats>) {
+    diagnostics.add_measurement(&SIM_TICK_TIME, || stats.duration.as_secs_f64() * 1000.0);
+    diagnostics.add_measurement(&SIM_MOVED, || stats.tick.moved as f64);
+    diagnostics.add_measurement(&SIM_CREATED, || stats.tick.created.total() as f64);
+    diagnostics.add_measurement(&SIM_DESTROYED, || stats.tick.destroyed.total() as f64);
+    diagnostics.add_measurement(&SIM_REACTIONS, || stats.tick.reactions as f64);
+}
+
+fn count_painted(mut ev_poured: EventReader<Poured>, mut session: ResMut<SessionStats>) {
+    for poured in ev_poured.read() {
+        *session.painted.entry(poured.id).or_default() += poured.count;
+    }
+}
+
+fn measure_session(
+    grid: Res<Grid>,
+    mut session: ResMut<SessionStats>,
+    q_contraptions: Query<Entity, Or<(With<Structure>, With<SoftBody>)>>,
+    mut ages: Local<HashMap<Entity, u64>>,
+) {
+    let fire = grid.census().count(CellId::Fire);
+    session.biggest_fire = session.biggest_fire.max(fire);
+
+    // Contraptions that were cleared away or burnt up stop aging
+    ages.retain(|&entity, _| q_contraptions.contains(entity));
+    for entity in &q_contraptions {
+        let age = ages.entry(entity).or_default();
+        *age += 1;
+        session.longest_contraption = session.longest_contraption.max(*age);
+    }
+}
+
+fn show_session_summary(mut commands: Commands, session: Res<SessionStats>) {
+    let lines = session.summary();
+    if lines.is_empty() {
+        return;
+    }
+    commands.spawn((
+        StateScoped(GameState::Menu),
+        Text::new(format!("This session\n{}", lines.join("\n"))),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(24.0),
+            left: Val::Px(24.0),
+            padding: UiRect::all(Val::Px(12.0)),
+            ..default()
+        },
+        BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.6)),
+        BorderRadius::all(Val::Px(4.0)),
+        // Above the menu, which is spawned at the same time
+        GlobalZIndex(1),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_lists_the_most_painted_first() {
+        let mut session = SessionStats::default();
+        assert!(session.summary().is_empty());
+
+        session.painted.insert(CellId::Sand, 40);
+        session.painted.insert(CellId::Water, 120);
+        session.biggest_fire = 7;
+        session.longest_contraption = 250;
+        assert_eq!(
+            session.summary(),
+            vec![
+                "Cells painted: 160",
+                "  Water: 120",
+                "  Sand: 40",
+                "Biggest fire: 7 cells",
+                "Longest-lived contraption: 2.5s",
+            ]
+        );
+    }
+}
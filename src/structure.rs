@@ -0,0 +1,315 @@
+use crate::cell::{Cell, CellId, Material};
+use crate::chunks::WorldShifted;
+use crate::grid::{cursor_tile, MainCamera, TickTimer, WorldReset};
+use crate::input::Action;
+use crate::pointer::Pointer;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::InWorld;
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// Longest row of loose cells a structure can shove ahead of itself
+const PUSH_LIMIT: isize = 12;
+
+/// A group of cells that translates back and forth as a unit. Build it from a rigid material, or
+/// the simulation pulls it apart between moves.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Structure {
+    /// Grid positions currently occupied by the structure
+    pub cells: Vec<(usize, usize)>,
+    pub id: CellId,
+    pub direction: IVec2,
+    /// Cells travelled before turning around, unless something blocks the way earlier
+    pub range: u32,
+    /// Cells travelled since the last turn
+    pub travelled: u32,
+    /// Simulation ticks between moves
+    pub period: u32,
+    pub cooldown: u32,
+}
+
+impl Structure {
+    /// Stamps a rectangle of cells into the grid, keeping only the positions that were free
+    pub fn build(
+        grid: &mut Grid,
+        x: usize,
+        y: usize,
+        size: (usize, usize),
+        id: CellId,
+        direction: IVec2,
+        range: u32,
+    ) -> Self {
+        let mut cells = Vec::new();
+        for cx in x..(x + size.0).min(grid.width()) {
+            for cy in y..(y + size.1).min(grid.height()) {
+                if grid.spawn(cx, cy, id) {
+                    cells.push((cx, cy));
+                }
+            }
+        }
+        Self {
+            cells,
+            id,
+            direction,
+            range,
+            travelled: 0,
+            period: 4,
+            cooldown: 0,
+        }
+    }
+
+    /// A horizontal metal platform sliding from side to side, carrying whatever rests on it
+    pub fn platform(grid: &mut Grid, x: usize, y: usize) -> Self {
+        Self::build(grid, x, y, (16, 2), CellId::Metal, IVec2::X, 40)
+    }
+
+    /// A metal column moving up and down, pushing the cells above it
+    pub fn piston(grid: &mut Grid, x: usize, y: usize) -> Self {
+        Self::build(grid, x, y, (6, 4), CellId::Metal, IVec2::NEG_Y, 16)
+    }
+
+    /// Advances the structure by one simulation tick, returning `false` once all of its cells are
+    /// gone
+    pub fn step(&mut self, grid: &mut Grid) -> bool {
+        // Cells destroyed by the simulation, by acid for instance, stop being part of the structure
+        self.cells
+            .retain(|&(x, y)| grid.get(x, y).is_some_and(|c| c.id == self.id));
+        if self.cells.is_empty() {
+            return false;
+        }
+
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return true;
+        }
+        self.cooldown = self.period;
+
+        if self.travelled == self.range || !translate(grid, &mut self.cells, self.direction) {
+            self.direction = -self.direction;
+            self.travelled = 0;
+        } else {
+            self.travelled += 1;
+        }
+        true
+    }
+}
+
+fn is_loose(cell: Cell) -> bool {
+    !matches!(cell.material(), Material::Solid | Material::Rigid)
+}
+
+/// Moves a group of cells by one step in `direction`, shoving loose cells out of the way and
+/// carrying the ones resting on top along horizontal moves. Returns `false` without changing the
+/// grid when something immovable is in the way.
+pub fn translate(grid: &mut Grid, cells: &mut [(usize, usize)], direction: IVec2) -> bool {
+    let (dx, dy) = (direction.x as isize, direction.y as isize);
+    let occupied: HashSet<(isize, isize)> = cells
+        .iter()
+        .map(|&(x, y)| (x as isize, y as isize))
+        .collect();
+    let footprint: HashSet<(isize, isize)> =
+        occupied.iter().map(|&(x, y)| (x + dx, y + dy)).collect();
+
+    // Plan how every occupied cell in front of the structure gets out of the way
+    let mut shoves = Vec::new();
+    let mut claimed = HashSet::new();
+    for &(x, y) in footprint.difference(&occupied) {
+        if grid.is_blocked(x, y) && !grid.cell_at(x, y).is_some_and(is_loose) {
+            return false;
+        }
+        if grid.cell_at(x, y).is_none() {
+            continue;
+        }
+
+        // Shove the whole row of loose cells ahead by one, or squeeze the first one out sideways
+        let row_end = (1..=PUSH_LIMIT)
+            .map(|k| (x + dx * k, y + dy * k))
+            .take_while(|&(px, py)| {
+                grid.in_bounds(px, py) && grid.cell_at(px, py).is_none_or(is_loose)
+            })
+            .find(|&(px, py)| grid.cell_at(px, py).is_none() && !claimed.contains(&(px, py)));
+        let aside = [(dy, dx), (-dy, -dx)]
+            .into_iter()
+            .map(|(sx, sy)| (x + sx, y + sy))
+            .find(|&(px, py)| {
+                grid.in_bounds(px, py)
+                    && grid.cell_at(px, py).is_none()
+                    && !footprint.contains(&(px, py))
+                    && !claimed.contains(&(px, py))
+            });
+
+        match (row_end, aside) {
+            (Some(end), _) => {
+                claimed.insert(end);
+                shoves.push(((x, y), end, (dx, dy)));
+            }
+            (None, Some(to)) => {
+                claimed.insert(to);
+                shoves.push(((x, y), to, (to.0 - x, to.1 - y)));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    for ((x, y), (ex, ey), (sx, sy)) in shoves {
+        // Shift the row from its far end back to the front
+        let (mut px, mut py) = (ex, ey);
+        while (px, py) != (x, y) {
+            let from = grid.cell_at(px - sx, py - sy);
+            grid.set(px as usize, py as usize, from);
+            (px, py) = (px - sx, py - sy);
+        }
+        grid.set(x as usize, y as usize, None);
+    }
+
+    // Loose cells resting on top, ordered front to back so they don't overwrite each other
+    let mut riders: Vec<_> = if dy == 0 {
+        occupied
+            .iter()
+            .map(|&(x, y)| (x, y - 1))
+            .filter(|&(x, y)| {
+                !occupied.contains(&(x, y)) && grid.cell_at(x, y).is_some_and(is_loose)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    riders.sort_by_key(|&(x, _)| -x * dx);
+
+    // Move the structure itself
+    let moved: Vec<_> = cells.iter().map(|&(x, y)| grid.get(x, y)).collect();
+    for &(x, y) in cells.iter() {
+        grid.set(x, y, None);
+    }
+    for ((x, y), cell) in cells.iter_mut().zip(moved) {
+        (*x, *y) = ((*x as isize + dx) as usize, (*y as isize + dy) as usize);
+        grid.set(*x, *y, cell);
+    }
+
+    for (x, y) in riders {
+        let (tx, ty) = (x + dx, y);
+        if grid.in_bounds(tx, ty) && grid.cell_at(tx, ty).is_none() {
+            grid.set(tx as usize, ty as usize, grid.cell_at(x, y));
+            grid.set(x as usize, y as usize, None);
+        }
+    }
+    true
+}
+
+pub struct StructurePlugin;
+
+/// This plugin builds moving metal platforms (K) and pistons (N) at the cursor and moves them every
+/// simulation tick
+impl Plugin for StructurePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Structure>()
+            .add_systems(
+                Update,
+                (spawn_structure, move_structures)
+                    .chain()
+                    .run_if(in_state(InWorld)),
+            )
+            .add_systems(
+                Update,
+                clear_structures.run_if(on_event::<WorldReset>.or(on_event::<WorldShifted>)),
+            );
+    }
+}
+
+fn spawn_structure(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    pointer: Res<Pointer>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut grid: ResMut<Grid>,
+) {
+    let build = if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::BuildPlatform)
+    {
+        Structure::platform
+    } else if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::BuildPiston)
+    {
+        Structure::piston
+    } else {
+        return;
+    };
+
+    let Ok((camera, camera_transform)) = q_camera.single() else {
+        return;
+    };
+    if let Some((x, y)) = cursor_tile(&pointer, camera, camera_transform, &grid) {
+        let structure = build(&mut grid, x, y);
+        if !structure.cells.is_empty() {
+            commands.spawn(structure);
+        }
+    }
+}
+
+fn move_structures(
+    mut commands: Commands,
+    tick_timer: Res<TickTimer>,
+    mut grid: ResMut<Grid>,
+    mut q_structures: Query<(Entity, &mut Structure)>,
+) {
+    if !tick_timer.just_finished() {
+        return;
+    }
+
+    for (entity, mut structure) in &mut q_structures {
+        if !structure.step(&mut grid) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn clear_structures(mut commands: Commands, q_structures: Query<Entity, With<Structure>>) {
+    for entity in &q_structures {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    // Interleaves simulation ticks with structure moves the way the game does
+    fn run(grid: &mut Grid, structure: &mut Structure, ticks: usize) {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..ticks {
+            grid.step(&mut rng);
+            assert!(structure.step(grid));
+        }
+    }
+
+    #[test]
+    fn piston_keeps_its_cells() {
+        let mut grid = Grid::new(20, 30);
+        let mut piston = Structure::piston(&mut grid, 7, 10);
+        assert_eq!(piston.cells.len(), 24);
+
+        run(&mut grid, &mut piston, 300);
+        assert_eq!(piston.cells.len(), 24);
+        assert_eq!(grid.census().count(CellId::Metal), 24);
+    }
+
+    #[test]
+    fn platform_carries_sand_without_losing_any() {
+        let mut grid = Grid::new(60, 20);
+        let mut platform = Structure::platform(&mut grid, 4, 10);
+        for x in 8..12 {
+            grid.spawn(x, 9, CellId::Sand);
+        }
+
+        run(&mut grid, &mut platform, 200);
+        assert_eq!(platform.cells.len(), 32);
+        assert_eq!(grid.census().count(CellId::Metal), 32);
+        assert_eq!(grid.census().count(CellId::Sand), 4);
+    }
+}
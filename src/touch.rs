@@ -0,0 +1,189 @@
+use crate::cell::CellId;
+use crate::grid::{Brush, MainCamera, DATA_SIZE};
+use crate::level::ActiveLevel;
+use crate::loading::UiAssets;
+use crate::pointer::has_window;
+use crate::registry::CellRegistry;
+use crate::settings::Settings;
+use crate::sim::Grid;
+use crate::view::{min_zoom, MAX_ZOOM};
+use crate::InWorld;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use std::time::Duration;
+
+/// How long a finger has to rest in place to open the material palette
+const LONG_PRESS: Duration = Duration::from_millis(500);
+/// How far a finger may drift, in logical pixels, and still count as resting
+const LONG_PRESS_SLOP: f32 = 12.0;
+
+/// The material palette opened by a long press
+#[derive(Component)]
+pub struct Palette;
+
+/// A part of the palette that closes it when tapped, choosing a material if it has one
+#[derive(Component, Clone, Copy)]
+struct PaletteEntry(Option<CellId>);
+
+#[derive(Default)]
+struct LongPress {
+    touch: Option<u64>,
+    held: Duration,
+    opened: bool,
+}
+
+pub struct TouchPlugin;
+
+/// This plugin adds the touchscreen gestures beyond painting with one finger: pinching with two
+/// fingers zooms and pans the view, and a long press opens the material palette
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (pinch_zoom, open_palette, choose_material).run_if(in_state(InWorld).and(has_window)),
+        );
+    }
+}
+
+fn pinch_zoom(
+    touches: Res<Touches>,
+    q_window: Single<&Window, With<PrimaryWindow>>,
+    q_camera: Single<(&mut Projection, &mut Transform), With<MainCamera>>,
+    grid: Res<Grid>,
+) {
+    let mut pressed = touches.iter();
+    let (Some(a), Some(b), None) = (pressed.next(), pressed.next(), pressed.next()) else {
+        return;
+    };
+    let (mut projection, mut transform) = q_camera.into_inner();
+    let Projection::Orthographic(projection) = &mut *projection else {
+        return;
+    };
+
+    let before = a.previous_position().distance(b.previous_position());
+    let after = a.position().distance(b.position());
+    if before > 0.0 && after > 0.0 {
+        projection.scale = (projection.scale * before / after).clamp(MAX_ZOOM, min_zoom(&grid));
+    }
+
+    // Drag the view along with the point between the fingers, without leaving the grid
+    let world_per_pixel = projection.area.width() / q_window.width();
+    let moved = (a.delta() + b.delta()) / 2.0 * world_per_pixel;
+    let slack = (Vec2::new(grid.width() as f32, grid.height() as f32) * DATA_SIZE
+        - projection.area.size())
+    .max(Vec2::ZERO)
+        / 2.0;
+    let target = transform.translation.truncate() + Vec2::new(-moved.x, moved.y);
+    transform.translation = target.clamp(-slack, slack).extend(transform.translation.z);
+}
+
+fn open_palette(
+    mut commands: Commands,
+    time: Res<Time>,
+    touches: Res<Touches>,
+    registry: Res<CellRegistry>,
+    settings: Res<Settings>,
+    ui_assets: Res<UiAssets>,
+    active_level: Option<Res<ActiveLevel>>,
+    q_palette: Query<(), With<Palette>>,
+    mut press: Local<LongPress>,
+) {
+    let mut pressed = touches.iter();
+    let touch = match (pressed.next(), pressed.next()) {
+        (Some(touch), None) if touch.distance().length() < LONG_PRESS_SLOP => touch,
+        _ => {
+            *press = LongPress::default();
+            return;
+        }
+    };
+    if press.touch != Some(touch.id()) {
+        *press = LongPress {
+            touch: Some(touch.id()),
+            ..default()
+        };
+    }
+
+    press.held += time.delta();
+    if press.held < LONG_PRESS || press.opened || !q_palette.is_empty() {
+        return;
+    }
+    press.opened = true;
+
+    let materials = CellId::ALL
+        .into_iter()
+        .filter(|&id| active_level.as_ref().is_none_or(|level| level.allows(id)));
+    commands
+        .spawn((
+            StateScoped(InWorld),
+            Palette,
+            PaletteEntry(None),
+            Button,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_wrap: FlexWrap::Wrap,
+                align_content: AlignContent::Center,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(12.0),
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::linear_rgba(0.0, 0.0, 0.0, 0.6)),
+        ))
+        .with_children(|children| {
+            for id in materials {
+                children
+                    .spawn((
+                        PaletteEntry(Some(id)),
+                        Button,
+                        Node {
+                            width: Val::Px(88.0),
+                            height: Val::Px(88.0),
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::Center,
+                            row_gap: Val::Px(6.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::linear_rgb(0.15, 0.15, 0.15)),
+                    ))
+                    .with_children(|entry| {
+                        entry.spawn((
+                            Node {
+                                width: Val::Px(48.0),
+                                height: Val::Px(48.0),
+                                ..default()
+                            },
+                            ui_assets.icon(id, settings.color_scheme.color(&registry, id)),
+                        ));
+                        entry.spawn((
+                            Text::new(id.data().name),
+                            TextFont {
+                                font_size: 16.0,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn choose_material(
+    mut commands: Commands,
+    q_entries: Query<(&Interaction, &PaletteEntry), Changed<Interaction>>,
+    q_palette: Query<Entity, With<Palette>>,
+    mut brush: ResMut<Brush>,
+) {
+    for (interaction, entry) in &q_entries {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let PaletteEntry(Some(id)) = *entry {
+            brush.selected = id;
+        }
+        for palette in &q_palette {
+            commands.entity(palette).despawn();
+        }
+    }
+}
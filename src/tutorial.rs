@@ -0,0 +1,243 @@
+use crate::cell::CellId;
+use crate::challenge::{end_challenge, ChallengeHud};
+use crate::grid::{Brush, Poured, WorldReset};
+use crate::input::{key_name, Action, Keybindings};
+use crate::level::{end_level, LevelHud};
+use crate::locale::Locale;
+use crate::settings::Settings;
+use crate::sim::{Grid, SimEvent};
+use crate::GameState;
+use bevy::prelude::*;
+
+/// Rows of stone the tutorial world stands on
+const FLOOR: usize = 6;
+
+/// A step of the tutorial, each waiting for the player to do one thing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TutorialStep {
+    SelectWater,
+    PourWater,
+    GrowBrush,
+    PourOil,
+    IgniteOil,
+    Done,
+}
+
+impl TutorialStep {
+    fn prompt(self, keys: &Keybindings, locale: &Locale) -> String {
+        let (text, action) = match self {
+            TutorialStep::SelectWater => (
+                "Press {key} to select water",
+                Some(Action::Select(CellId::Water)),
+            ),
+            TutorialStep::PourWater => ("Hold the left mouse button to pour it", None),
+            TutorialStep::GrowBrush => (
+                "Scroll up or press {key} to enlarge the brush",
+                Some(Action::GrowBrush),
+            ),
+            TutorialStep::PourOil => (
+                "Press {key} and pour some oil",
+                Some(Action::Select(CellId::Oil)),
+            ),
+            TutorialStep::IgniteOil => (
+                "Select fire with {key} and set the oil alight",
+                Some(Action::Select(CellId::Fire)),
+            ),
+            TutorialStep::Done => (
+                "That's the basics! Press Escape for the menu and more to try",
+                None,
+            ),
+        };
+        let text = locale.text(text);
+        match action {
+            Some(action) => text.replace("{key}", &key_name(keys.key(action))),
+            None => text.to_string(),
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            TutorialStep::SelectWater => TutorialStep::PourWater,
+            TutorialStep::PourWater => TutorialStep::GrowBrush,
+            TutorialStep::GrowBrush => TutorialStep::PourOil,
+            TutorialStep::PourOil => TutorialStep::IgniteOil,
+            TutorialStep::IgniteOil | TutorialStep::Done => TutorialStep::Done,
+        }
+    }
+}
+
+/// A running tutorial
+#[derive(Resource, Debug)]
+pub struct Tutorial {
+    pub step: TutorialStep,
+    /// The brush radius when the step began
+    radius: usize,
+}
+
+impl Tutorial {
+    fn new(brush: &Brush) -> Self {
+        Self {
+            step: TutorialStep::SelectWater,
+            radius: brush.radius,
+        }
+    }
+
+    /// Moves on to the next step if what happened during a frame completes the current one
+    fn advance(&mut self, brush: &Brush, poured: &[CellId], ignited: bool) -> bool {
+        let done = match self.step {
+            TutorialStep::SelectWater => brush.selected == CellId::Water,
+            TutorialStep::PourWater => poured.contains(&CellId::Water),
+            TutorialStep::GrowBrush => brush.radius > self.radius,
+            TutorialStep::PourOil => poured.contains(&CellId::Oil),
+            TutorialStep::IgniteOil => ignited,
+            TutorialStep::Done => false,
+        };
+        if done {
+            self.step = self.step.next();
+            self.radius = brush.radius;
+        }
+        done
+    }
+}
+
+#[derive(Component)]
+pub struct TutorialHud;
+
+pub struct TutorialPlugin;
+
+/// This plugin walks new players through the controls one prompt at a time during
+/// `GameState::Tutorial`, moving on once the brush, the cells poured or the simulation show the
+/// prompt was followed. Loading another world turns the tutorial into free play.
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Tutorial), start_tutorial)
+            .add_systems(OnExit(GameState::Tutorial), end_tutorial)
+            .add_systems(
+                Update,
+                (
+                    follow_tutorial,
+                    leave_tutorial.run_if(on_event::<WorldReset>),
+                )
+                    .run_if(in_state(GameState::Tutorial)),
+            );
+    }
+}
+
+/// Starts the tutorial on a bare stone floor
+fn start_tutorial(
+    mut commands: Commands,
+    mut grid: ResMut<Grid>,
+    brush: Res<Brush>,
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    q_level_hud: Query<Entity, With<LevelHud>>,
+    q_challenge_hud: Query<Entity, With<ChallengeHud>>,
+    mut ev_world_reset: EventWriter<WorldReset>,
+) {
+    grid.clear();
+    let (width, height) = (grid.width(), grid.height());
+    for x in 0..width {
+        for y in height.saturating_sub(FLOOR)..height {
+            grid.spawn(x, y, CellId::Stone);
+        }
+    }
+    end_level(&mut commands, &q_level_hud);
+    end_challenge(&mut commands, &q_challenge_hud);
+    ev_world_reset.write(WorldReset::Tutorial);
+
+    let tutorial = Tutorial::new(&brush);
+    commands.spawn((
+        StateScoped(GameState::Tutorial),
+        TutorialHud,
+        Text::new(tutorial.step.prompt(&settings.keybindings, &locale)),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+    ));
+    commands.insert_resource(tutorial);
+}
+
+fn follow_tutorial(
+    mut ev_poured: EventReader<Poured>,
+    mut ev_sim: EventReader<SimEvent>,
+    brush: Res<Brush>,
+    settings: Res<Settings>,
+    locale: Res<Locale>,
+    mut tutorial: ResMut<Tutorial>,
+    mut q_hud: Query<&mut Text, With<TutorialHud>>,
+) {
+    let poured: Vec<CellId> = ev_poured.read().map(|poured| poured.id).collect();
+    let ignited = ev_sim
+        .read()
+        .any(|event| matches!(event, SimEvent::Ignited { .. }));
+    if !tutorial.advance(&brush, &poured, ignited) {
+        return;
+    }
+    for mut hud in &mut q_hud {
+        hud.0 = tutorial.step.prompt(&settings.keybindings, &locale);
+    }
+}
+
+/// Carries on in free play once another world replaces the tutorial's
+fn leave_tutorial(
+    mut ev_world_reset: EventReader<WorldReset>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if ev_world_reset
+        .read()
+        .last()
+        .is_some_and(|&reset| reset != WorldReset::Tutorial)
+    {
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn end_tutorial(mut commands: Commands) {
+    commands.remove_resource::<Tutorial>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_wait_for_what_they_ask() {
+        let mut brush = Brush::default();
+        let mut tutorial = Tutorial::new(&brush);
+
+        // Pouring sand before selecting water does nothing
+        assert!(!tutorial.advance(&brush, &[CellId::Sand], false));
+        brush.selected = CellId::Water;
+        assert!(tutorial.advance(&brush, &[], false));
+        assert!(tutorial.advance(&brush, &[CellId::Water], false));
+
+        assert!(!tutorial.advance(&brush, &[CellId::Water], false));
+        brush.radius += 1;
+        assert!(tutorial.advance(&brush, &[], false));
+
+        assert!(!tutorial.advance(&brush, &[], true));
+        assert!(tutorial.advance(&brush, &[CellId::Oil], false));
+        assert!(tutorial.advance(&brush, &[], true));
+        assert_eq!(tutorial.step, TutorialStep::Done);
+        assert!(!tutorial.advance(&brush, &[CellId::Oil], true));
+    }
+
+    #[test]
+    fn prompts_name_the_bound_keys() {
+        let mut keys = Keybindings::default();
+        keys.rebind(Action::Select(CellId::Water), KeyCode::KeyP);
+        assert_eq!(
+            TutorialStep::SelectWater.prompt(&keys, &Locale::default()),
+            "Press P to select water"
+        );
+    }
+}
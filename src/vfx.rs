@@ -0,0 +1,185 @@
+use crate::cell::CellId;
+use crate::grid::{tiles_to_world, DATA_SIZE};
+use crate::registry::CellRegistry;
+use crate::sim::{Grid, SimEvent};
+use crate::InWorld;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+/// Speeds are in cells per second
+const GRAVITY: f32 = 60.0;
+/// Most particles alive at once, so a collapsing pile doesn't spawn thousands
+const MAX_PARTICLES: usize = 400;
+/// Most simulation events turned into particles per frame, picked at random
+const MAX_EVENTS: usize = 48;
+
+/// A speck flying over the grid for a moment, not part of the simulation
+#[derive(Component)]
+struct Particle {
+    velocity: Vec2,
+    /// Fraction of gravity pulling it down
+    weight: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+/// How the particles of one kind of event start out
+struct Burst {
+    count: usize,
+    color: [u8; 3],
+    /// Range of directions in radians, from the positive x axis
+    angles: (f32, f32),
+    speeds: (f32, f32),
+    weight: f32,
+    lifetime: f32,
+}
+
+impl Burst {
+    fn of(event: SimEvent, grid: &Grid, registry: &CellRegistry) -> Option<Burst> {
+        let burst = match event {
+            SimEvent::Ignited { .. } => Burst {
+                count: 3,
+                color: [255, 196, 64],
+                angles: (0.3, 2.8),
+                speeds: (10.0, 30.0),
+                weight: 0.3,
+                lifetime: 0.4,
+            },
+            SimEvent::Splashed { liquid, .. } => Burst {
+                count: 4,
+                color: registry.color(liquid),
+                angles: (0.5, 2.6),
+                speeds: (15.0, 30.0),
+                weight: 1.0,
+                lifetime: 0.6,
+            },
+            // Dust takes the color of what landed
+            SimEvent::Landed { x, y } => Burst {
+                count: 1,
+                color: grid.get(x, y).map_or(registry.color(CellId::Sand), |cell| {
+                    registry.cell_color(cell)
+                }),
+                angles: (0.0, std::f32::consts::PI),
+                speeds: (2.0, 6.0),
+                weight: -0.05,
+                lifetime: 0.8,
+            },
+            SimEvent::Annihilated { .. } => Burst {
+                count: 6,
+                color: [240, 220, 255],
+                angles: (0.0, std::f32::consts::TAU),
+                speeds: (20.0, 40.0),
+                weight: 0.0,
+                lifetime: 0.25,
+            },
+            SimEvent::Exploded { .. } => Burst {
+                count: 12,
+                color: [255, 140, 48],
+                angles: (0.0, std::f32::consts::TAU),
+                speeds: (30.0, 70.0),
+                weight: 0.5,
+                lifetime: 0.5,
+            },
+            // Splinters of the wall that gave way
+            SimEvent::Burst { wall, .. } => Burst {
+                count: 5,
+                color: registry.color(wall),
+                angles: (0.0, std::f32::consts::TAU),
+                speeds: (25.0, 50.0),
+                weight: 1.0,
+                lifetime: 0.5,
+            },
+            // Only heard, what destroyed the cell shows already
+            SimEvent::Destroyed { .. } => return None,
+        };
+        Some(burst)
+    }
+}
+
+fn position(event: SimEvent) -> (usize, usize) {
+    match event {
+        SimEvent::Ignited { x, y }
+        | SimEvent::Splashed { x, y, .. }
+        | SimEvent::Landed { x, y }
+        | SimEvent::Annihilated { x, y }
+        | SimEvent::Exploded { x, y }
+        | SimEvent::Burst { x, y, .. }
+        | SimEvent::Destroyed { x, y, .. } => (x, y),
+    }
+}
+
+pub struct VfxPlugin;
+
+/// This plugin throws sparks where fire catches, droplets where something falls into a liquid,
+/// dust where powder lands, a flash where antimatter annihilates, embers where something explodes
+/// and splinters where gas bursts a wall, following the events of each simulation tick
+impl Plugin for VfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (spawn_particles, move_particles).run_if(in_state(InWorld)),
+        );
+    }
+}
+
+fn spawn_particles(
+    mut commands: Commands,
+    mut ev_sim: EventReader<SimEvent>,
+    grid: Res<Grid>,
+    registry: Res<CellRegistry>,
+    q_particles: Query<(), With<Particle>>,
+) {
+    let mut rng = thread_rng();
+    let events: Vec<(SimEvent, Burst)> = ev_sim
+        .read()
+        .filter_map(|&event| Some((event, Burst::of(event, &grid, &registry)?)))
+        .collect();
+    let room = MAX_PARTICLES.saturating_sub(q_particles.iter().len());
+
+    let mut spawned = 0;
+    for (event, burst) in events.choose_multiple(&mut rng, MAX_EVENTS) {
+        let (x, y) = position(*event);
+        let [r, g, b] = burst.color;
+        let origin = tiles_to_world(&grid, x, y);
+
+        for _ in 0..burst.count {
+            if spawned == room {
+                return;
+            }
+            spawned += 1;
+            let angle = rng.gen_range(burst.angles.0..burst.angles.1);
+            let speed = rng.gen_range(burst.speeds.0..burst.speeds.1);
+            commands.spawn((
+                StateScoped(InWorld),
+                Particle {
+                    velocity: Vec2::from_angle(angle) * speed,
+                    weight: burst.weight,
+                    age: 0.0,
+                    lifetime: burst.lifetime * rng.gen_range(0.7..1.3),
+                },
+                Sprite::from_color(Color::srgb_u8(r, g, b), Vec2::splat(DATA_SIZE / 2.0)),
+                Transform::from_translation(origin.extend(1.0)),
+            ));
+        }
+    }
+}
+
+fn move_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut particle, mut transform, mut sprite) in &mut q_particles {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        particle.velocity.y -= GRAVITY * particle.weight * dt;
+        transform.translation += (particle.velocity * DATA_SIZE * dt).extend(0.0);
+        sprite
+            .color
+            .set_alpha(1.0 - particle.age / particle.lifetime);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::grid::{MainCamera, DATA_SIZE};
+use crate::input::Action;
+use crate::pointer::has_window;
+use crate::settings::Settings;
+use crate::sim::{Grid, GRID_HEIGHT, GRID_WIDTH};
+use crate::InWorld;
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy::window::{MonitorSelection, PrimaryWindow, WindowMode};
+use std::ops::Range;
+
+/// Closest the camera can zoom in, as a fraction of the default view
+pub const MAX_ZOOM: f32 = 0.25;
+/// How much one wheel notch or key press zooms
+const ZOOM_STEP: f32 = 1.25;
+
+/// The part of the world the camera shows when not zoomed, in world units: the whole grid, up to
+/// the default grid size
+pub fn view_size(width: usize, height: usize) -> Vec2 {
+    Vec2::new(width.min(GRID_WIDTH) as f32, height.min(GRID_HEIGHT) as f32) * DATA_SIZE
+}
+
+/// Most whole screen pixels each cell can take up while the view fits in a window of a size in
+/// physical pixels, or `None` when the window is too small to give each cell even one
+pub fn pixels_per_cell(window: Vec2, view: Vec2) -> Option<u32> {
+    let cells = view / DATA_SIZE;
+    let pixels = (window / cells).min_element().floor();
+    (pixels >= 1.0).then_some(pixels as u32)
+}
+
+/// Furthest the camera can zoom out, where the whole grid fits in the view
+pub fn min_zoom(grid: &Grid) -> f32 {
+    let world = Vec2::new(grid.width() as f32, grid.height() as f32) * DATA_SIZE;
+    (world / view_size(grid.width(), grid.height()))
+        .max_element()
+        .max(1.0)
+}
+
+/// The tiles inside a rectangle of the world, such as the area seen by the camera
+pub fn tiles_in(grid: &Grid, area: Rect) -> (Range<usize>, Range<usize>) {
+    let half = Vec2::new(grid.width() as f32, grid.height() as f32) * DATA_SIZE / 2.0;
+    let to_tiles = |x: f32, offset: f32, len: usize| {
+        (((x + offset) / DATA_SIZE).floor().max(0.0) as usize).min(len)
+    };
+    let xs = to_tiles(area.min.x, half.x, grid.width())
+        ..to_tiles(area.max.x + DATA_SIZE, half.x, grid.width());
+    // Rows grow downwards, against world y
+    let ys = to_tiles(-area.max.y, half.y, grid.height())
+        ..to_tiles(-area.min.y + DATA_SIZE, half.y, grid.height());
+    (xs, ys)
+}
+
+pub struct ViewPlugin;
+
+/// This plugin zooms the camera with Ctrl and the mouse wheel or the zoom keys, from a close up
+/// to the whole grid when it is larger than the screen. As the window is resized or moved to a
+/// screen of another density, it scales the view so each cell covers a whole number of pixels,
+/// leaving black bars around the grid rather than blurring it. F11 toggles fullscreen.
+impl Plugin for ViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, zoom_view.run_if(in_state(InWorld).and(has_window)))
+            .add_systems(Update, (fit_view, toggle_fullscreen).run_if(has_window));
+    }
+}
+
+fn fit_view(
+    q_window: Single<&Window, With<PrimaryWindow>>,
+    grid: Option<Res<Grid>>,
+    mut q_projection: Query<&mut Projection, With<MainCamera>>,
+    mut fitted: Local<Option<(UVec2, f32, (usize, usize))>>,
+) {
+    let Some(grid) = grid else {
+        return;
+    };
+    let window = (
+        q_window.physical_size(),
+        q_window.scale_factor(),
+        (grid.width(), grid.height()),
+    );
+    if *fitted == Some(window) || q_projection.is_empty() {
+        return;
+    }
+    *fitted = Some(window);
+
+    let view = view_size(grid.width(), grid.height());
+    let scaling_mode = match pixels_per_cell(window.0.as_vec2(), view) {
+        // Physical pixels, so cells stay sharp on screens that scale the window up
+        Some(pixels) => ScalingMode::FixedVertical {
+            viewport_height: window.0.y as f32 / pixels as f32 * DATA_SIZE,
+        },
+        None => ScalingMode::AutoMin {
+            min_width: view.x,
+            min_height: view.y,
+        },
+    };
+    for mut projection in &mut q_projection {
+        if let Projection::Orthographic(projection) = &mut *projection {
+            projection.scaling_mode = scaling_mode;
+        }
+    }
+}
+
+fn toggle_fullscreen(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut q_window: Single<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::ToggleFullscreen)
+    {
+        return;
+    }
+    q_window.mode = match q_window.mode {
+        WindowMode::Windowed => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+        _ => WindowMode::Windowed,
+    };
+}
+
+fn zoom_view(
+    mut evr_scroll: EventReader<MouseWheel>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    grid: Res<Grid>,
+    mut q_projection: Single<&mut Projection, With<MainCamera>>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let mut notches: f32 = evr_scroll
+        .read()
+        .filter(|_| ctrl)
+        .map(|ev| ev.y.signum())
+        .sum();
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::ZoomIn)
+    {
+        notches += 1.0;
+    }
+    if settings
+        .keybindings
+        .just_pressed(&keyboard_input, Action::ZoomOut)
+    {
+        notches -= 1.0;
+    }
+    if notches == 0.0 {
+        return;
+    }
+
+    let Projection::Orthographic(projection) = &mut **q_projection else {
+        return;
+    };
+    projection.scale =
+        (projection.scale / ZOOM_STEP.powf(notches)).clamp(MAX_ZOOM, min_zoom(&grid));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_cover_whole_pixels() {
+        let view = view_size(GRID_WIDTH, GRID_HEIGHT);
+        assert_eq!(pixels_per_cell(Vec2::new(1280.0, 720.0), view), Some(4));
+        // A 4K screen and a wider one, letterboxed on the sides
+        assert_eq!(pixels_per_cell(Vec2::new(3840.0, 2160.0), view), Some(12));
+        assert_eq!(pixels_per_cell(Vec2::new(3440.0, 1440.0), view), Some(8));
+        assert_eq!(pixels_per_cell(Vec2::new(1000.0, 720.0), view), Some(3));
+        assert_eq!(pixels_per_cell(Vec2::new(200.0, 100.0), view), None);
+    }
+
+    #[test]
+    fn only_tiles_in_the_area_are_visible() {
+        let grid = Grid::new(100, 50);
+        let whole = Rect::new(-200.0, -100.0, 200.0, 100.0);
+        assert_eq!(tiles_in(&grid, whole), (0..100, 0..50));
+
+        // The top left quarter, plus the tiles along its edges
+        let quarter = Rect::new(-200.0, 0.0, -2.0, 100.0);
+        assert_eq!(tiles_in(&grid, quarter), (0..50, 0..26));
+    }
+}
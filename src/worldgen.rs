@@ -0,0 +1,195 @@
+use crate::cell::CellId;
+use crate::grid::{SimRng, WorldReset};
+use crate::level::{end_level, LevelHud};
+use crate::sim::Grid;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+/// Parameters for generating a new world
+#[derive(Resource, Reflect, Debug, Clone, Copy)]
+#[reflect(Resource)]
+pub struct WorldGenSettings {
+    /// Seed for every new world, or `None` to pick a fresh one each time
+    pub seed: Option<u64>,
+    /// Fraction of the grid height covered by the average terrain surface
+    pub surface: f32,
+    /// Fraction of the grid height below which surface depressions fill with water
+    pub water_level: f32,
+}
+
+impl Default for WorldGenSettings {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            surface: 0.45,
+            water_level: 0.5,
+        }
+    }
+}
+
+/// Smooth 2D value noise in the range `0.0..1.0`
+#[derive(Debug, Clone, Copy)]
+pub struct Noise {
+    seed: u64,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    fn hash(&self, x: i64, y: i64) -> f32 {
+        // splitmix64 over the combined lattice coordinates
+        let mut z = self
+            .seed
+            .wrapping_add((x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            .wrapping_add((y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F));
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn value(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (sx, sy) = (tx * tx * (3.0 - 2.0 * tx), ty * ty * (3.0 - 2.0 * ty));
+        let (ix, iy) = (x0 as i64, y0 as i64);
+
+        let top = lerp(self.hash(ix, iy), self.hash(ix + 1, iy), sx);
+        let bottom = lerp(self.hash(ix, iy + 1), self.hash(ix + 1, iy + 1), sx);
+        lerp(top, bottom, sy)
+    }
+
+    /// Fractal noise summing several octaves of [`Noise::value`]
+    pub fn fbm(&self, x: f32, y: f32, octaves: u32) -> f32 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut norm = 0.0;
+        for _ in 0..octaves {
+            total += self.value(x * frequency, y * frequency) * amplitude;
+            norm += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        total / norm
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Fills the grid with stone layers, caves, a sand surface, water pools and oil pockets
+pub fn generate(grid: &mut Grid, seed: u64, settings: &WorldGenSettings) {
+    let (width, height) = (grid.width(), grid.height());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let terrain = Noise::new(rng.gen());
+    let strata = Noise::new(rng.gen());
+    let caves = Noise::new(rng.gen());
+    let oil = Noise::new(rng.gen());
+
+    grid.clear();
+
+    let water_level = (height as f32 * settings.water_level) as usize;
+    for x in 0..width {
+        let fx = x as f32;
+        let offset = (terrain.fbm(fx * 0.008, 0.5, 4) - 0.5) * height as f32 * 0.9;
+        let surface = ((height as f32 * settings.surface + offset) as usize).min(height - 1);
+        let sand_depth = 2 + (terrain.value(fx * 0.05, 7.5) * 5.0) as usize;
+        // Loose stone rests on bedrock, which is rigid enough to hold up the caves carved into it
+        let bedrock_depth = sand_depth + 6 + (strata.fbm(fx * 0.02, 3.5, 3) * 12.0) as usize;
+
+        for y in surface..height {
+            let depth = y - surface;
+            let fy = y as f32;
+
+            let id = if depth < sand_depth {
+                CellId::Sand
+            } else if depth < bedrock_depth {
+                CellId::Stone
+            } else if depth > bedrock_depth + 2 && caves.fbm(fx * 0.03, fy * 0.05, 3) > 0.62 {
+                continue;
+            } else if depth > bedrock_depth + 4 && oil.fbm(fx * 0.04, fy * 0.04, 2) > 0.68 {
+                CellId::Oil
+            } else if strata.value(fx * 0.01, fy * 0.15) > 0.75 {
+                // Thin seams of loose stone running through the bedrock
+                CellId::Stone
+            } else {
+                CellId::Bedrock
+            };
+            grid.spawn(x, y, id);
+        }
+
+        for y in water_level..surface {
+            grid.spawn(x, y, CellId::Water);
+        }
+    }
+}
+
+/// Generates a new world from the current [`WorldGenSettings`]
+#[derive(Event)]
+pub struct GenerateWorld;
+
+pub struct WorldGenPlugin;
+
+/// This plugin generates procedural worlds when a new world is created from the menu
+impl Plugin for WorldGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WorldGenSettings>()
+            .init_resource::<WorldGenSettings>()
+            .add_event::<GenerateWorld>()
+            .add_systems(Update, generate_world.run_if(on_event::<GenerateWorld>));
+    }
+}
+
+fn generate_world(
+    mut commands: Commands,
+    mut ev_generate_world: EventReader<GenerateWorld>,
+    settings: Res<WorldGenSettings>,
+    mut grid: ResMut<Grid>,
+    mut rng: ResMut<SimRng>,
+    q_hud: Query<Entity, With<LevelHud>>,
+    mut ev_world_reset: EventWriter<WorldReset>,
+) {
+    if ev_generate_world.read().last().is_some() {
+        let seed = settings.seed.unwrap_or_else(|| thread_rng().gen());
+        info!("Generating world with seed {seed}");
+        generate(&mut grid, seed, &settings);
+        *rng = SimRng::from_seed(seed);
+        end_level(&mut commands, &q_hud);
+        ev_world_reset.write(WorldReset::Generated);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_generates_the_same_world() {
+        let settings = WorldGenSettings::default();
+        let (mut a, mut b, mut c) = (Grid::default(), Grid::default(), Grid::default());
+        generate(&mut a, 42, &settings);
+        generate(&mut b, 42, &settings);
+        generate(&mut c, 43, &settings);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn bedrock_lies_below_the_loose_layers() {
+        let mut grid = Grid::default();
+        generate(&mut grid, 7, &WorldGenSettings::default());
+        assert!(grid.census().count(CellId::Bedrock) > 0);
+        for x in 0..grid.width() {
+            let column: Vec<_> = (0..grid.height()).filter_map(|y| grid.get(x, y)).collect();
+            let first_bedrock = column.iter().position(|c| c.id == CellId::Bedrock);
+            let last_sand = column.iter().rposition(|c| c.id == CellId::Sand);
+            if let (Some(bedrock), Some(sand)) = (first_bedrock, last_sand) {
+                assert!(sand < bedrock, "sand below bedrock in column {x}");
+            }
+        }
+    }
+}